@@ -1,7 +1,12 @@
 use crate::config::aws_config::parse_aws_config;
 
 use aws_sdk_ec2 as ec2;
+use aws_sdk_ec2instanceconnect as ec2_instance_connect;
 use aws_sdk_ecs as ecs;
+use aws_sdk_eks as eks;
+use aws_sdk_elasticache as elasticache;
+use aws_sdk_kms as kms;
+use aws_sdk_memorydb as memorydb;
 use aws_sdk_rds as rds;
 use aws_sdk_secretsmanager as secretsmanager;
 use aws_sdk_ssm as ssm;
@@ -9,6 +14,7 @@ use aws_sdk_sts as sts;
 use std::sync::OnceLock;
 
 /// All AWS service clients for a given profile+region.
+#[derive(Clone)]
 pub struct AwsClients {
     pub sts: sts::Client,
     pub ec2: ec2::Client,
@@ -16,6 +22,11 @@ pub struct AwsClients {
     pub rds: rds::Client,
     pub ssm: ssm::Client,
     pub secrets_manager: secretsmanager::Client,
+    pub kms: kms::Client,
+    pub ec2_instance_connect: ec2_instance_connect::Client,
+    pub eks: eks::Client,
+    pub elasticache: elasticache::Client,
+    pub memorydb: memorydb::Client,
 }
 
 /// SSO configuration extracted from an AWS profile.
@@ -123,6 +134,11 @@ pub async fn create_aws_clients(profile: &str, region: &str) -> AwsClients {
         rds: rds::Client::new(&config),
         ssm: ssm::Client::new(&config),
         secrets_manager: secretsmanager::Client::new(&config),
+        kms: kms::Client::new(&config),
+        ec2_instance_connect: ec2_instance_connect::Client::new(&config),
+        eks: eks::Client::new(&config),
+        elasticache: elasticache::Client::new(&config),
+        memorydb: memorydb::Client::new(&config),
     }
 }
 
@@ -1,10 +1,28 @@
 use crate::error::AppError;
-use aws_credential_types::provider::ProvideCredentials;
+use aws_credential_types::provider::{ProvideCredentials, SharedCredentialsProvider};
+use aws_credential_types::Credentials;
 use aws_sigv4::http_request::{
-    sign, SignableBody, SignableRequest, SignatureLocation, SigningSettings,
+    sign, PayloadChecksumKind, SignableBody, SignableRequest, SignatureLocation, SigningSettings,
 };
 use aws_sigv4::sign::v4;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+
+/// How long a generated token is accepted by RDS.
+pub const TOKEN_LIFETIME: Duration = Duration::from_secs(900);
+/// Regenerate tokens this often, leaving 5 minutes of validity as headroom.
+pub const TOKEN_REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+/// Retry delay after a failed refresh (e.g. SSO session expired).
+pub const TOKEN_REFRESH_RETRY: Duration = Duration::from_secs(60);
+/// Resolve cached request-signing credentials again this long before they expire.
+const CREDENTIALS_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+/// How long clients may cache an EKS token. The API server accepts one for
+/// 15 minutes; like `aws eks get-token`, report a minute less.
+pub const EKS_TOKEN_LIFETIME: Duration = Duration::from_secs(14 * 60);
+/// Prefix the EKS authenticator expects in front of the encoded URL.
+const EKS_TOKEN_PREFIX: &str = "k8s-aws-v1.";
 
 /// Generate an RDS IAM authentication token.
 ///
@@ -43,7 +61,7 @@ pub async fn generate_rds_auth_token(
     // Configure signing settings for presigned URL (query string signing)
     let mut signing_settings = SigningSettings::default();
     signing_settings.signature_location = SignatureLocation::QueryParams;
-    signing_settings.expires_in = Some(Duration::from_secs(900)); // 15 minutes
+    signing_settings.expires_in = Some(TOKEN_LIFETIME);
 
     let identity = credentials.into();
     let signing_params = v4::SigningParams::builder()
@@ -82,8 +100,216 @@ pub async fn generate_rds_auth_token(
     Ok(token)
 }
 
+/// Generate a bearer token for an EKS cluster's API server.
+///
+/// The token is a presigned STS `GetCallerIdentity` URL bound to the cluster
+/// by the signed `x-k8s-aws-id` header; the cluster calls it to learn the
+/// caller's IAM identity.
+///
+/// This is equivalent to `aws eks get-token`.
+pub async fn generate_eks_token(
+    config: &aws_config::SdkConfig,
+    cluster_name: &str,
+) -> Result<String, AppError> {
+    let credentials_provider = config
+        .credentials_provider()
+        .ok_or_else(|| AppError::Aws("No credentials provider configured".to_string()))?;
+
+    let credentials = credentials_provider
+        .provide_credentials()
+        .await
+        .map_err(|e| AppError::Aws(format!("Failed to resolve AWS credentials: {}", e)))?;
+
+    let region = config
+        .region()
+        .ok_or_else(|| AppError::Aws("No region configured".to_string()))?
+        .to_string();
+
+    presign_eks_token(credentials, &region, cluster_name, SystemTime::now())
+}
+
+fn presign_eks_token(
+    credentials: Credentials,
+    region: &str,
+    cluster_name: &str,
+    time: SystemTime,
+) -> Result<String, AppError> {
+    let url = format!(
+        "https://sts.{}.amazonaws.com/?Action=GetCallerIdentity&Version=2011-06-15",
+        region
+    );
+
+    // The authenticator only accepts 60 second presigned URLs; it enforces
+    // the 15 minute token lifetime itself
+    let mut signing_settings = SigningSettings::default();
+    signing_settings.signature_location = SignatureLocation::QueryParams;
+    signing_settings.expires_in = Some(Duration::from_secs(60));
+
+    let identity = credentials.into();
+    let signing_params = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(region)
+        .name("sts")
+        .time(time)
+        .settings(signing_settings)
+        .build()
+        .map_err(|e| AppError::Aws(format!("Failed to build signing params: {}", e)))?;
+
+    let signable_request = SignableRequest::new(
+        "GET",
+        &url,
+        std::iter::once(("x-k8s-aws-id", cluster_name)),
+        SignableBody::Bytes(&[]),
+    )
+    .map_err(|e| AppError::Aws(format!("Failed to create signable request: {}", e)))?;
+
+    let signing_params = signing_params.into();
+    let (signing_instructions, _signature) = sign(signable_request, &signing_params)
+        .map_err(|e| AppError::Aws(format!("Failed to sign EKS token request: {}", e)))?
+        .into_parts();
+
+    let mut presigned_url = reqwest::Url::parse(&url)
+        .map_err(|e| AppError::Aws(format!("Failed to parse URL: {}", e)))?;
+
+    for (name, value) in signing_instructions.params() {
+        presigned_url.query_pairs_mut().append_pair(name, value);
+    }
+
+    Ok(format!(
+        "{}{}",
+        EKS_TOKEN_PREFIX,
+        URL_SAFE_NO_PAD.encode(presigned_url.as_str())
+    ))
+}
+
+/// SigV4 request signing for the local HTTP proxy (`serviceType:
+/// "sigv4-http"`) with a profile's credentials. They are cached and resolved
+/// again shortly before they expire, so a long-lived tunnel keeps working for
+/// as long as the SSO session does.
+#[derive(Debug)]
+pub struct Sigv4Signer {
+    provider: SharedCredentialsProvider,
+    region: String,
+    /// Signing name, e.g. `es` or `execute-api`.
+    service: String,
+    cached: Mutex<Option<Credentials>>,
+}
+
+impl Sigv4Signer {
+    pub fn new(config: &aws_config::SdkConfig, service: &str) -> Result<Self, AppError> {
+        let provider = config
+            .credentials_provider()
+            .ok_or_else(|| AppError::Aws("No credentials provider configured".to_string()))?;
+        let region = config
+            .region()
+            .ok_or_else(|| AppError::Aws("No region configured".to_string()))?
+            .to_string();
+        Ok(Self {
+            provider,
+            region,
+            service: service.to_string(),
+            cached: Mutex::default(),
+        })
+    }
+
+    /// Sign a request for `url` (`https://host/path?query`). `headers` are
+    /// covered by the signature and must include `host`. Returns the headers
+    /// to add: `authorization`, `x-amz-date`, `x-amz-content-sha256` and, for
+    /// temporary credentials, `x-amz-security-token`.
+    pub async fn sign(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+    ) -> Result<Vec<(String, String)>, AppError> {
+        let credentials = self.credentials().await?;
+        sign_http_request(
+            credentials,
+            &self.region,
+            &self.service,
+            SystemTime::now(),
+            method,
+            url,
+            headers,
+            body,
+        )
+    }
+
+    async fn credentials(&self) -> Result<Credentials, AppError> {
+        let mut cached = self.cached.lock().await;
+        if let Some(credentials) = cached.as_ref()
+            && !expires_soon(credentials, SystemTime::now())
+        {
+            return Ok(credentials.clone());
+        }
+        let credentials = self
+            .provider
+            .provide_credentials()
+            .await
+            .map_err(|e| AppError::Aws(format!("Failed to resolve AWS credentials: {}", e)))?;
+        *cached = Some(credentials.clone());
+        Ok(credentials)
+    }
+}
+
+fn expires_soon(credentials: &Credentials, now: SystemTime) -> bool {
+    credentials
+        .expiry()
+        .is_some_and(|expiry| expiry <= now + CREDENTIALS_REFRESH_MARGIN)
+}
+
+/// SigV4 header signing with the payload hash included (OpenSearch and API
+/// Gateway both accept `x-amz-content-sha256`).
+#[allow(clippy::too_many_arguments)]
+fn sign_http_request(
+    credentials: Credentials,
+    region: &str,
+    service: &str,
+    time: SystemTime,
+    method: &str,
+    url: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> Result<Vec<(String, String)>, AppError> {
+    let mut signing_settings = SigningSettings::default();
+    signing_settings.payload_checksum_kind = PayloadChecksumKind::XAmzSha256;
+
+    let identity = credentials.into();
+    let signing_params = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(region)
+        .name(service)
+        .time(time)
+        .settings(signing_settings)
+        .build()
+        .map_err(|e| AppError::Aws(format!("Failed to build signing params: {}", e)))?;
+
+    let signable_request = SignableRequest::new(
+        method,
+        url,
+        headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str())),
+        SignableBody::Bytes(body),
+    )
+    .map_err(|e| AppError::Aws(format!("Failed to create signable request: {}", e)))?;
+
+    let signing_params = signing_params.into();
+    let (signing_instructions, _signature) = sign(signable_request, &signing_params)
+        .map_err(|e| AppError::Aws(format!("Failed to sign request: {}", e)))?
+        .into_parts();
+
+    Ok(signing_instructions
+        .headers()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_token_format_basic() {
         // Just verify the URL construction logic (without actual signing)
@@ -95,4 +321,88 @@ mod tests {
         assert!(url.contains("DBUser=db_admin"));
         assert!(url.contains(":5432/"));
     }
+
+    #[test]
+    fn test_sign_http_request_headers() {
+        let credentials = Credentials::new(
+            "AKIDEXAMPLE",
+            "secret",
+            Some("session".to_string()),
+            None,
+            "test",
+        );
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_440_938_160); // 2015-08-30T12:36:00Z
+        let headers = sign_http_request(
+            credentials,
+            "us-east-1",
+            "es",
+            time,
+            "POST",
+            "https://vpc-logs.us-east-1.es.amazonaws.com/_search?q=a",
+            &[(
+                "host".to_string(),
+                "vpc-logs.us-east-1.es.amazonaws.com".to_string(),
+            )],
+            b"{}",
+        )
+        .unwrap();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+                .unwrap_or_default()
+        };
+
+        assert_eq!(header("x-amz-date"), "20150830T123600Z");
+        assert_eq!(header("x-amz-security-token"), "session");
+        // SHA-256 of "{}"
+        assert_eq!(
+            header("x-amz-content-sha256"),
+            "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+        );
+        let authorization = header("authorization");
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/es/aws4_request, "
+        ));
+        assert!(authorization
+            .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-security-token,"));
+    }
+
+    #[test]
+    fn test_presign_eks_token() {
+        let credentials = Credentials::new("AKIDEXAMPLE", "secret", None, None, "test");
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_440_938_160);
+        let token = presign_eks_token(credentials, "eu-central-1", "platform", time).unwrap();
+
+        let encoded = token.strip_prefix("k8s-aws-v1.").unwrap();
+        assert!(!encoded.contains('='));
+        let url = String::from_utf8(URL_SAFE_NO_PAD.decode(encoded).unwrap()).unwrap();
+        assert!(url.starts_with(
+            "https://sts.eu-central-1.amazonaws.com/?Action=GetCallerIdentity&Version=2011-06-15&"
+        ));
+        assert!(url.contains("X-Amz-Expires=60"));
+        assert!(url.contains("X-Amz-SignedHeaders=host%3Bx-k8s-aws-id"));
+        assert!(url.contains("X-Amz-Credential=AKIDEXAMPLE%2F20150830%2Feu-central-1%2Fsts%2Faws4_request"));
+    }
+
+    #[test]
+    fn test_expires_soon() {
+        let now = SystemTime::now();
+        let expiring = |secs| {
+            Credentials::new(
+                "a",
+                "b",
+                None,
+                Some(now + Duration::from_secs(secs)),
+                "test",
+            )
+        };
+        assert!(expires_soon(&expiring(60), now));
+        assert!(!expires_soon(&expiring(3600), now));
+        assert!(!expires_soon(
+            &Credentials::new("a", "b", None, None, "test"),
+            now
+        ));
+    }
 }
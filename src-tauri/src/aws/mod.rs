@@ -1,4 +1,5 @@
 pub mod credentials;
 pub mod iam_auth;
+pub mod instance_connect;
 pub mod operations;
 pub mod sso;
@@ -63,9 +63,56 @@ pub async fn get_connection_credentials(
     username_field: Option<&str>,
     password_field: Option<&str>,
 ) -> Result<DbCredentials, AppError> {
+    let (secret_name, credentials) = get_secret_json(clients, secret_prefix, secret_path).await?;
     let username_key = username_field.unwrap_or("username");
     let password_key = password_field.unwrap_or("password");
+    let username = required_field(&credentials, &secret_name, username_key)?;
+    let password = required_field(&credentials, &secret_name, password_key)?;
 
+    Ok(DbCredentials {
+        username,
+        password,
+        database: database.to_string(),
+        secret_name,
+    })
+}
+
+/// Get a Redis AUTH token, and the ACL user if the secret has one, from
+/// Secrets Manager. Redis treats a bare token as the `default` user, so the
+/// username is optional unless `username_field` names it explicitly.
+pub async fn get_redis_credentials(
+    clients: &AwsClients,
+    secret_prefix: &str,
+    secret_path: Option<&str>,
+    username_field: Option<&str>,
+    password_field: Option<&str>,
+) -> Result<(Option<String>, String), AppError> {
+    let (secret_name, credentials) = get_secret_json(clients, secret_prefix, secret_path).await?;
+    redis_credentials(&credentials, &secret_name, username_field, password_field)
+}
+
+fn redis_credentials(
+    credentials: &serde_json::Value,
+    secret_name: &str,
+    username_field: Option<&str>,
+    password_field: Option<&str>,
+) -> Result<(Option<String>, String), AppError> {
+    let username = match username_field {
+        Some(key) => Some(required_field(credentials, secret_name, key)?),
+        None => get_nested_field(credentials, "username").map(str::to_string),
+    };
+    let password_key = password_field.unwrap_or("password");
+    let password = required_field(credentials, secret_name, password_key)?;
+    Ok((username, password))
+}
+
+/// Fetch a secret by `secret_path`, or the first one matching `secret_prefix`,
+/// and parse its JSON. Returns the secret's name alongside the value.
+async fn get_secret_json(
+    clients: &AwsClients,
+    secret_prefix: &str,
+    secret_path: Option<&str>,
+) -> Result<(String, serde_json::Value), AppError> {
     let secret_name = if let Some(path) = secret_path {
         // Use secret_path directly — skip list_secrets
         path.to_string()
@@ -122,30 +169,22 @@ pub async fn get_connection_credentials(
         ))
     })?;
 
-    let username = get_nested_field(&credentials, username_key)
-        .ok_or_else(|| {
-            AppError::Aws(format!(
-                "Secret '{}' is missing required field: {}",
-                secret_name, username_key
-            ))
-        })?
-        .to_string();
+    Ok((secret_name, credentials))
+}
 
-    let password = get_nested_field(&credentials, password_key)
+fn required_field(
+    credentials: &serde_json::Value,
+    secret_name: &str,
+    key: &str,
+) -> Result<String, AppError> {
+    get_nested_field(credentials, key)
+        .map(str::to_string)
         .ok_or_else(|| {
             AppError::Aws(format!(
                 "Secret '{}' is missing required field: {}",
-                secret_name, password_key
+                secret_name, key
             ))
-        })?
-        .to_string();
-
-    Ok(DbCredentials {
-        username,
-        password,
-        database: database.to_string(),
-        secret_name,
-    })
+        })
 }
 
 /// Find a running bastion instance tagged with the given Name pattern.
@@ -329,6 +368,188 @@ pub async fn get_rds_port(
     }
 }
 
+/// API server of an EKS cluster, as kubeconfig needs it.
+#[derive(Debug, Clone)]
+pub struct EksCluster {
+    /// Endpoint host name, without `https://`.
+    pub endpoint: String,
+    /// Base64-encoded CA bundle (kubeconfig `certificate-authority-data`).
+    pub certificate_authority: String,
+}
+
+/// Look up an active EKS cluster's API endpoint and CA.
+pub async fn describe_eks_cluster(clients: &AwsClients, name: &str) -> Result<EksCluster, AppError> {
+    let response = clients
+        .eks
+        .describe_cluster()
+        .name(name)
+        .send()
+        .await
+        .map_err(|e| AppError::Aws(format!("Failed to describe EKS cluster '{}': {}", name, e)))?;
+
+    let cluster = response
+        .cluster()
+        .ok_or_else(|| AppError::Aws(format!("EKS cluster '{}' not found.", name)))?;
+
+    let status = cluster.status().map(|s| s.as_str()).unwrap_or_default();
+    if status != "ACTIVE" {
+        return Err(AppError::Aws(format!(
+            "EKS cluster '{}' is not active (status: {}).",
+            name, status
+        )));
+    }
+
+    let endpoint = cluster
+        .endpoint()
+        .map(|e| e.strip_prefix("https://").unwrap_or(e).trim_end_matches('/').to_string())
+        .ok_or_else(|| AppError::Aws(format!("EKS cluster '{}' has no API endpoint.", name)))?;
+    let certificate_authority = cluster
+        .certificate_authority()
+        .and_then(|ca| ca.data())
+        .ok_or_else(|| AppError::Aws(format!("EKS cluster '{}' has no certificate authority.", name)))?
+        .to_string();
+
+    Ok(EksCluster {
+        endpoint,
+        certificate_authority,
+    })
+}
+
+/// Where a Redis replication group (or MemoryDB cluster) takes connections.
+#[derive(Debug, Clone)]
+pub struct RedisEndpoint {
+    pub host: String,
+    pub port: u16,
+    /// Cluster mode: `host` is the configuration endpoint and clients are
+    /// redirected to the node serving each slot.
+    pub cluster_mode: bool,
+    /// In-transit encryption: the nodes only speak TLS.
+    pub tls: bool,
+}
+
+/// Default Redis port, for endpoints that don't report one.
+const DEFAULT_REDIS_PORT: u16 = 6379;
+
+/// Look up an ElastiCache replication group's configuration endpoint (cluster
+/// mode) or primary endpoint.
+pub async fn describe_elasticache_endpoint(
+    clients: &AwsClients,
+    replication_group_id: &str,
+) -> Result<RedisEndpoint, AppError> {
+    let response = clients
+        .elasticache
+        .describe_replication_groups()
+        .replication_group_id(replication_group_id)
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::Aws(format!(
+                "Failed to describe replication group '{}': {}",
+                replication_group_id, e
+            ))
+        })?;
+
+    let group = response.replication_groups().first().ok_or_else(|| {
+        AppError::Aws(format!(
+            "Replication group '{}' not found.",
+            replication_group_id
+        ))
+    })?;
+
+    let status = group.status().unwrap_or_default();
+    if !matches!(status, "available" | "modifying" | "snapshotting") {
+        return Err(AppError::Aws(format!(
+            "Replication group '{}' is not available (status: {}).",
+            replication_group_id, status
+        )));
+    }
+
+    let cluster_mode = group.cluster_enabled().unwrap_or(false);
+    let endpoint = if cluster_mode {
+        group.configuration_endpoint()
+    } else {
+        group
+            .node_groups()
+            .first()
+            .and_then(|node_group| node_group.primary_endpoint())
+    };
+    let host = endpoint
+        .and_then(|e| e.address())
+        .ok_or_else(|| {
+            AppError::Aws(format!(
+                "Replication group '{}' has no endpoint.",
+                replication_group_id
+            ))
+        })?
+        .to_string();
+    let port = endpoint
+        .and_then(|e| e.port())
+        .and_then(|p| u16::try_from(p).ok())
+        .unwrap_or(DEFAULT_REDIS_PORT);
+
+    Ok(RedisEndpoint {
+        host,
+        port,
+        cluster_mode,
+        tls: group.transit_encryption_enabled().unwrap_or(false),
+    })
+}
+
+/// Look up a MemoryDB cluster's endpoint. MemoryDB always runs in cluster
+/// mode.
+pub async fn describe_memorydb_endpoint(
+    clients: &AwsClients,
+    cluster_name: &str,
+) -> Result<RedisEndpoint, AppError> {
+    let response = clients
+        .memorydb
+        .describe_clusters()
+        .cluster_name(cluster_name)
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::Aws(format!(
+                "Failed to describe MemoryDB cluster '{}': {}",
+                cluster_name, e
+            ))
+        })?;
+
+    let cluster = response
+        .clusters()
+        .first()
+        .ok_or_else(|| AppError::Aws(format!("MemoryDB cluster '{}' not found.", cluster_name)))?;
+
+    let status = cluster.status().unwrap_or_default();
+    if !matches!(status, "available" | "updating" | "snapshotting") {
+        return Err(AppError::Aws(format!(
+            "MemoryDB cluster '{}' is not available (status: {}).",
+            cluster_name, status
+        )));
+    }
+
+    let endpoint = cluster.cluster_endpoint();
+    let host = endpoint
+        .and_then(|e| e.address())
+        .ok_or_else(|| {
+            AppError::Aws(format!(
+                "MemoryDB cluster '{}' has no endpoint.",
+                cluster_name
+            ))
+        })?
+        .to_string();
+    let port = endpoint
+        .and_then(|e| u16::try_from(e.port()).ok())
+        .filter(|p| *p != 0)
+        .unwrap_or(DEFAULT_REDIS_PORT);
+
+    Ok(RedisEndpoint {
+        host,
+        port,
+        cluster_mode: true,
+        tls: cluster.tls_enabled().unwrap_or(true),
+    })
+}
+
 /// Wait for SSM agent on an instance to become online.
 pub async fn wait_for_ssm_agent_ready(
     clients: &AwsClients,
@@ -429,6 +650,23 @@ pub async fn start_direct_port_forwarding_session(
     Ok(response)
 }
 
+/// Start an interactive SSM shell session on an instance.
+/// No document — the agent opens its default `Standard_Stream` shell.
+pub async fn start_shell_session(
+    clients: &AwsClients,
+    instance_id: &str,
+) -> Result<aws_sdk_ssm::operation::start_session::StartSessionOutput, AppError> {
+    let response = clients
+        .ssm
+        .start_session()
+        .target(instance_id)
+        .send()
+        .await
+        .map_err(|e| AppError::Aws(format!("Failed to start shell session: {}", e)))?;
+
+    Ok(response)
+}
+
 /// Find a running EC2 instance by Name tag pattern. Returns (instance_id, private_ip).
 pub async fn find_ec2_instance(
     clients: &AwsClients,
@@ -528,3 +766,29 @@ pub async fn find_ecs_task_ip(
         service
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redis_secret_without_a_username_uses_the_default_user() {
+        let secret = json!({ "password": "auth-token" });
+        let (username, password) = redis_credentials(&secret, "redis/auth", None, None).unwrap();
+        assert_eq!(username, None);
+        assert_eq!(password, "auth-token");
+    }
+
+    #[test]
+    fn redis_secret_with_an_acl_user() {
+        let secret = json!({ "username": "app", "password": "secret" });
+        let (username, password) = redis_credentials(&secret, "redis/acl", None, None).unwrap();
+        assert_eq!(username.as_deref(), Some("app"));
+        assert_eq!(password, "secret");
+
+        // An explicitly configured username field has to be there
+        let err = redis_credentials(&secret, "redis/acl", Some("auth.user"), None).unwrap_err();
+        assert!(err.to_string().contains("auth.user"));
+    }
+}
@@ -1,33 +1,64 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use dialoguer::{theme::ColorfulTheme, Select};
-use connection_app_lib::aws::credentials::create_aws_clients;
+use connection_app_lib::aws::credentials::{build_aws_config, create_aws_clients};
+use connection_app_lib::aws::iam_auth;
 use connection_app_lib::aws::operations;
-use connection_app_lib::aws::sso::{ensure_sso_session, CliSsoHandler};
+use connection_app_lib::aws::sso::{ensure_sso_session, CliSsoHandler, SsoEventHandler};
 use connection_app_lib::config::aws_config::read_aws_profile_names;
+use connection_app_lib::config::db_clients::{client_data_sources, export_to_client, DbClient};
+use connection_app_lib::config::preferences::update_preferences;
+use connection_app_lib::config::manifest::{
+    load_manifest, open_manifest, ManifestEntry, ManifestTunnelResult,
+};
+#[cfg(unix)]
+use connection_app_lib::config::manifest::{record_manifest_tunnels, take_manifest_tunnels};
+use connection_app_lib::config::saved::{
+    find_saved_connection, load_saved_connections, touch_saved_connection,
+};
+use connection_app_lib::error::AppError;
+use connection_app_lib::history::{self, HistoryEntry};
 use connection_app_lib::config::projects::{
     get_default_port_for_engine, get_local_port, get_profiles_for_project, load_project_configs,
     ProjectConfig,
 };
-use connection_app_lib::tunnel::native::{start_native_port_forwarding, start_multiplexed_port_forwarding};
+use connection_app_lib::tunnel::encryption::KmsSessionContext;
+use connection_app_lib::aws::credentials::AwsClients;
+#[cfg(unix)]
+use connection_app_lib::daemon::{client::DaemonClient, DaemonEvent};
+use connection_app_lib::tunnel::manager::{
+    parse_forward_target, ActiveConnectionInfo, CliTunnelHandler, ConnectionInfo,
+    TunnelEventHandler, TunnelManager,
+};
+use connection_app_lib::tunnel::export::{
+    export_connection, service_name, ConnectionExport, ExportFormat,
+};
+use connection_app_lib::tunnel::native::start_stream_port_forwarding;
+use connection_app_lib::tunnel::protocol::{TerminalSize, STREAM_DATA_PAYLOAD_SIZE};
+use connection_app_lib::tunnel::shell::run_shell_session;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[allow(unused_imports)]
 use connection_app_lib::aws::operations::{
     find_bastion_instance, find_ec2_instance, find_ecs_task_ip,
-    start_direct_port_forwarding_session, start_session,
+    start_direct_port_forwarding_session, start_session, start_shell_session,
 };
 use tokio_util::sync::CancellationToken;
 
+const NO_DAEMON: &str =
+    "No background daemon is running. Start one with `connection-app-cli daemon` to manage tunnels across tools.";
+
 #[derive(Parser)]
 #[command(name = "connection-app", about = "ConnectionApp — Secure tunneling via AWS SSM")]
 #[command(version)]
 struct Cli {
     /// Project name (skip interactive selection)
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     project: Option<String>,
 
     /// AWS profile name (skip interactive selection)
-    #[arg(long)]
+    #[arg(long, global = true)]
     profile: Option<String>,
 
     /// Local port override
@@ -38,10 +69,34 @@ struct Cli {
     #[arg(long)]
     debug: bool,
 
+    /// How to report the tunnel on stdout when connecting
+    #[arg(long, value_enum, global = true, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Print connection strings once connected (comma-separated: libpq, jdbc,
+    /// sqlalchemy, pgpass, pg-service, mysql-cnf, env)
+    #[arg(long, global = true, value_delimiter = ',', value_parser = parse_export_format)]
+    print: Vec<ExportFormat>,
+
+    /// Also write the --print formats as 0600 files, removed on disconnect
+    #[arg(long, global = true, requires = "print")]
+    write_exports: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+/// Output of the connect flow. Progress always goes to stderr.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Connection box for humans
+    Text,
+    /// One `ready` object, then NDJSON lifecycle events
+    Json,
+    /// Shell `TUNNEL_*=...` assignments for `eval`
+    Env,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// List configured projects
@@ -51,6 +106,127 @@ enum Commands {
     /// List AWS profiles
     #[command(name = "profiles")]
     Profiles,
+
+    /// Open an interactive shell on the project's bastion (or direct EC2 target)
+    #[command(name = "shell")]
+    Shell,
+
+    /// Forward one connection over stdin/stdout (for ssh ProxyCommand)
+    #[command(name = "stdio")]
+    Stdio,
+
+    /// Forward a local port to any host:port through a project's bastion
+    #[command(name = "forward")]
+    Forward {
+        /// Project whose region, SSO profile and bastion to use
+        #[arg(long)]
+        via: String,
+
+        /// Remote target as host:port (hostname or IPv4 address)
+        #[arg(long)]
+        to: String,
+
+        /// Local port (default: the remote port, or the next free one)
+        #[arg(long)]
+        local_port: Option<String>,
+    },
+
+    /// Bring up every tunnel in a manifest (TOML); Ctrl+C tears them all down
+    #[command(name = "up")]
+    Up {
+        /// Manifest of [[tunnel]] entries (project, profile, database, local_port)
+        file: PathBuf,
+    },
+
+    /// Disconnect the daemon tunnels brought up from a manifest
+    #[command(name = "down")]
+    Down {
+        /// Manifest passed to `up`
+        file: PathBuf,
+    },
+
+    /// Run the background daemon that owns tunnels for the GUI and CLI
+    #[command(name = "daemon")]
+    Daemon,
+
+    /// Show active tunnels (owned by the background daemon)
+    #[command(name = "status")]
+    Status {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Disconnect a daemon tunnel by ID, or all of them with --all
+    #[command(name = "disconnect")]
+    Disconnect {
+        /// Connection ID (see `status`)
+        #[arg(required_unless_present = "all")]
+        id: Option<String>,
+
+        /// Disconnect every tunnel
+        #[arg(long, conflicts_with = "id")]
+        all: bool,
+    },
+
+    /// Show connection history (filter with --project)
+    #[command(name = "history")]
+    History {
+        /// Only entries at or after this time: RFC 3339, YYYY-MM-DD, or a
+        /// relative age such as 30m, 12h, 7d, 2w
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Maximum number of entries (most recent)
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print an EKS token as an ExecCredential (kubeconfig exec plugin)
+    #[command(name = "eks-token")]
+    EksToken {
+        /// EKS cluster name
+        #[arg(long)]
+        cluster: String,
+
+        /// Cluster region
+        #[arg(long)]
+        region: String,
+    },
+
+    /// Saved connections (bookmarks from the desktop app)
+    #[command(name = "saved", subcommand)]
+    Saved(SavedCommands),
+}
+
+#[derive(Subcommand)]
+enum SavedCommands {
+    /// List saved connections
+    #[command(name = "list")]
+    List {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Connect using a saved connection (by name or ID)
+    #[command(name = "connect")]
+    Connect { name: String },
+
+    /// Write saved connections into a database client (dbeaver, datagrip or
+    /// pgadmin); re-running updates the entries instead of duplicating them
+    #[command(name = "export")]
+    Export {
+        #[arg(value_parser = parse_db_client)]
+        client: DbClient,
+        /// Client config file (DataGrip: the project's .idea/dataSources.xml)
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
@@ -65,6 +241,7 @@ async fn main() {
 
     // Migrate legacy ~/.rds-ssm-connect/ → ~/.connection-app/
     connection_app_lib::config::projects::migrate_legacy_config();
+    connection_app_lib::config::saved::migrate_plugin_store();
 
     if let Some(command) = &cli.command {
         match command {
@@ -74,6 +251,87 @@ async fn main() {
             Commands::Profiles => {
                 run_list_profiles().await;
             }
+            Commands::Shell => {
+                if let Err(e) = run_shell(&cli).await {
+                    eprintln!("\n  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::Stdio => {
+                let code = match run_stdio(&cli).await {
+                    Ok(()) => 0,
+                    Err(e) => {
+                        eprintln!("  \u{274C} {}", e);
+                        1
+                    }
+                };
+                // tokio's stdin reader can't be cancelled and would hold up
+                // runtime shutdown until ssh closes the pipe.
+                std::process::exit(code);
+            }
+            Commands::Forward { via, to, local_port } => {
+                if let Err(e) = run_forward(&cli, via, to, local_port.as_deref()).await {
+                    eprintln!("\n  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::Up { file } => {
+                if let Err(e) = run_up(&cli, file).await {
+                    eprintln!("\n  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::Down { file } => {
+                if let Err(e) = run_down(file).await {
+                    eprintln!("  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::Daemon => {
+                if let Err(e) = run_daemon().await {
+                    eprintln!("\n  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::Status { json } => {
+                if let Err(e) = run_status(*json).await {
+                    eprintln!("  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::Disconnect { id, all } => {
+                if let Err(e) = run_disconnect(id.as_deref(), *all).await {
+                    eprintln!("  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::History { since, limit, json } => {
+                if let Err(e) = run_history(&cli, since.as_deref(), *limit, *json).await {
+                    eprintln!("  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::EksToken { cluster, region } => {
+                if let Err(e) = run_eks_token(&cli, cluster, region).await {
+                    eprintln!("  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::Saved(SavedCommands::List { json }) => {
+                run_list_saved(*json).await;
+            }
+            Commands::Saved(SavedCommands::Connect { name }) => {
+                if let Err(e) = run_saved_connect(&cli, name).await {
+                    eprintln!("\n  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Commands::Saved(SavedCommands::Export { client, path }) => {
+                if let Err(e) = run_saved_export(*client, path.as_deref()).await {
+                    eprintln!("\n  \u{274C} {}", e);
+                    std::process::exit(1);
+                }
+            }
         }
         return;
     }
@@ -128,7 +386,695 @@ async fn run_list_profiles() {
     println!();
 }
 
+/// Active tunnels as reported by the background daemon.
+async fn run_status(json: bool) -> Result<(), String> {
+    let mut connections = daemon_connections().await?;
+
+    if json {
+        let out = serde_json::to_string_pretty(&connections).map_err(|e| e.to_string())?;
+        println!("{}", out);
+        return Ok(());
+    }
+
+    if connections.is_empty() {
+        eprintln!("No active tunnels.");
+        return Ok(());
+    }
+
+    let now = chrono::Utc::now();
+    connections.sort_by(|a, b| a.connected_at.cmp(&b.connected_at));
+    let rows: Vec<Vec<String>> = connections
+        .iter()
+        .map(|c| {
+            let uptime = chrono::DateTime::parse_from_rfc3339(&c.connected_at)
+                .map(|t| format_duration((now - t.with_timezone(&chrono::Utc)).num_seconds()))
+                .unwrap_or_else(|_| "-".to_string());
+            vec![
+                c.id.clone(),
+                c.project_key.clone(),
+                c.profile.clone(),
+                c.local_port.clone(),
+                connection_target(&c.connection_info),
+                uptime,
+            ]
+        })
+        .collect();
+    print_table(&["ID", "PROJECT", "PROFILE", "PORT", "TARGET", "UPTIME"], &rows);
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn daemon_connections() -> Result<Vec<ActiveConnectionInfo>, String> {
+    match DaemonClient::connect_if_running().await {
+        Some(client) => client.list().await.map_err(|e| e.to_string()),
+        None => Err(NO_DAEMON.to_string()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn daemon_connections() -> Result<Vec<ActiveConnectionInfo>, String> {
+    Err(NO_DAEMON.to_string())
+}
+
+#[cfg(unix)]
+async fn run_disconnect(id: Option<&str>, all: bool) -> Result<(), String> {
+    let client = DaemonClient::connect_if_running()
+        .await
+        .ok_or_else(|| NO_DAEMON.to_string())?;
+    match id {
+        Some(id) if !all => {
+            let known = client.list().await.map_err(|e| e.to_string())?;
+            if !known.iter().any(|c| c.id == id) {
+                return Err(format!("No active tunnel with ID '{}'", id));
+            }
+            client.disconnect(id).await.map_err(|e| e.to_string())?;
+            eprintln!("  \u{1F44B} Disconnected {}.", id);
+        }
+        _ => {
+            client.disconnect_all().await.map_err(|e| e.to_string())?;
+            eprintln!("  \u{1F44B} Disconnected all tunnels.");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn run_disconnect(_id: Option<&str>, _all: bool) -> Result<(), String> {
+    Err(NO_DAEMON.to_string())
+}
+
+/// Token for the kubeconfig written by an EKS tunnel. kubectl runs this with
+/// stdout captured, so only the credential goes there.
+async fn run_eks_token(cli: &Cli, cluster: &str, region: &str) -> Result<(), String> {
+    let profile = cli.profile.as_deref().ok_or("--profile is required")?;
+    let config = build_aws_config(profile, region).await;
+    let token = iam_auth::generate_eks_token(&config, cluster)
+        .await
+        .map_err(|e| format!("Failed to generate EKS token: {}", e))?;
+    let expires = chrono::Utc::now()
+        + chrono::Duration::from_std(iam_auth::EKS_TOKEN_LIFETIME).map_err(|e| e.to_string())?;
+    println!("{}", exec_credential(&token, expires));
+    Ok(())
+}
+
+/// `client.authentication.k8s.io/v1beta1` ExecCredential carrying `token`.
+fn exec_credential(token: &str, expires: chrono::DateTime<chrono::Utc>) -> serde_json::Value {
+    serde_json::json!({
+        "kind": "ExecCredential",
+        "apiVersion": "client.authentication.k8s.io/v1beta1",
+        "spec": {},
+        "status": {
+            "expirationTimestamp": expires.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            "token": token,
+        },
+    })
+}
+
+/// Connection history from `~/.connection-app/history.jsonl`.
+async fn run_history(
+    cli: &Cli,
+    since: Option<&str>,
+    limit: usize,
+    json: bool,
+) -> Result<(), String> {
+    let since = since
+        .map(|s| parse_since(s, chrono::Utc::now()))
+        .transpose()?;
+
+    // The file holds at most 10k entries; filter first, then keep the newest `limit`
+    let mut entries: Vec<HistoryEntry> = history::read_history(usize::MAX)
+        .await
+        .into_iter()
+        .filter(|e| cli.project.as_ref().is_none_or(|p| &e.project_key == p))
+        .filter(|e| {
+            since.is_none_or(|since| {
+                chrono::DateTime::parse_from_rfc3339(&e.timestamp)
+                    .is_ok_and(|t| t >= since)
+            })
+        })
+        .collect();
+    let start = entries.len().saturating_sub(limit);
+    entries.drain(..start);
+
+    if json {
+        let out = serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?;
+        println!("{}", out);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        eprintln!("No history entries.");
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = entries
+        .iter()
+        .map(|e| {
+            let time = chrono::DateTime::parse_from_rfc3339(&e.timestamp)
+                .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|_| e.timestamp.clone());
+            vec![
+                time,
+                e.event_type.clone(),
+                e.project_key.clone(),
+                e.profile.clone(),
+                e.connection_id.clone(),
+                e.details.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    print_table(&["TIME", "EVENT", "PROJECT", "PROFILE", "CONNECTION", "DETAILS"], &rows);
+    Ok(())
+}
+
+async fn run_list_saved(json: bool) {
+    let saved = match load_saved_connections().await {
+        Ok(saved) => saved,
+        Err(e) => {
+            eprintln!("Failed to load saved connections: {}", e);
+            return;
+        }
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&saved) {
+            Ok(out) => println!("{}", out),
+            Err(e) => eprintln!("Failed to serialize saved connections: {}", e),
+        }
+        return;
+    }
+
+    if saved.is_empty() {
+        eprintln!("No saved connections.");
+        eprintln!("Save connections from the desktop app (stored in ~/.connection-app/saved-connections.json).");
+        return;
+    }
+
+    let rows: Vec<Vec<String>> = saved
+        .iter()
+        .map(|c| {
+            vec![
+                c.name.clone(),
+                c.project_key.clone(),
+                c.profile.clone(),
+                c.database.clone().unwrap_or_default(),
+                c.group.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    print_table(&["NAME", "PROJECT", "PROFILE", "DATABASE", "GROUP"], &rows);
+}
+
+fn parse_db_client(s: &str) -> Result<DbClient, String> {
+    s.parse().map_err(|e: AppError| e.to_string())
+}
+
+async fn run_saved_export(client: DbClient, path: Option<&Path>) -> Result<(), String> {
+    let saved = load_saved_connections().await.map_err(|e| e.to_string())?;
+    let configs = load_project_configs()
+        .await
+        .map_err(|e| format!("Failed to load project configs: {}", e))?;
+    let sources = update_preferences(|prefs| client_data_sources(&saved, &configs, prefs)).await;
+    if sources.is_empty() {
+        return Err("No saved database connections to export".to_string());
+    }
+
+    let result = export_to_client(client, path, &sources)
+        .await
+        .map_err(|e| e.to_string())?;
+    eprintln!(
+        "  \u{2705} {}: {} added, {} updated in {}",
+        client.name(),
+        result.added,
+        result.updated,
+        result.path
+    );
+    Ok(())
+}
+
+async fn run_saved_connect(cli: &Cli, name: &str) -> Result<(), String> {
+    let saved = load_saved_connections().await.map_err(|e| e.to_string())?;
+    let connection = find_saved_connection(&saved, name)
+        .ok_or_else(|| format!("Saved connection '{}' not found", name))?;
+    let _ = touch_saved_connection(&connection.id).await;
+
+    open_tunnel(
+        &connection.project_key,
+        &connection.name,
+        &connection.profile,
+        cli.port.as_deref(),
+        Destination::Project {
+            database: connection.database.as_deref(),
+        },
+        cli,
+    )
+    .await
+}
+
 async fn run_connect(cli: Cli) -> Result<(), String> {
+    let (project_key, project_config, profile) = select_project_and_profile(&cli).await?;
+
+    // Select database if multiple are configured
+    let selected_database = match project_config.databases {
+        Some(ref databases) if project_config.connection_type != "service" => {
+            if databases.len() > 1 {
+                let selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Select database")
+                    .items(databases)
+                    .default(0)
+                    .interact()
+                    .map_err(|e| format!("Database selection failed: {}", e))?;
+                Some(databases[selection].clone())
+            } else {
+                databases.first().cloned()
+            }
+        }
+        _ => None,
+    };
+
+    open_tunnel(
+        &project_key,
+        &project_config.name,
+        &profile,
+        cli.port.as_deref(),
+        Destination::Project {
+            database: selected_database.as_deref(),
+        },
+        cli,
+    )
+    .await
+}
+
+/// Ad-hoc forward to any host:port through the `via` project's bastion.
+async fn run_forward(
+    cli: &Cli,
+    via: &str,
+    to: &str,
+    local_port: Option<&str>,
+) -> Result<(), String> {
+    let (host, port) = parse_forward_target(to).map_err(|e| e.to_string())?;
+
+    let configs = load_project_configs()
+        .await
+        .map_err(|e| format!("Failed to load project configs: {}", e))?;
+    let (project_key, project_config) = find_project(&configs, via)
+        .ok_or_else(|| format!("Project '{}' not found", via))?;
+
+    let all_profiles = read_aws_profile_names().await;
+    let matching_profiles = get_profiles_for_project(&all_profiles, &project_config, &configs);
+    if matching_profiles.is_empty() {
+        return Err(format!(
+            "No matching AWS profiles found for project '{}'",
+            project_key
+        ));
+    }
+    let profile = select_profile(cli, &matching_profiles)?;
+
+    open_tunnel(
+        &project_key,
+        &format!("{}:{} through {}", host, port, project_config.name),
+        &profile,
+        local_port.or(cli.port.as_deref()),
+        Destination::Forward { host: &host, port },
+        cli,
+    )
+    .await
+}
+
+/// What a tunnel opened through a project reaches.
+#[derive(Clone, Copy)]
+enum Destination<'a> {
+    /// The project's own RDS or service target.
+    Project { database: Option<&'a str> },
+    /// Any host:port through the project's bastion.
+    Forward { host: &'a str, port: u16 },
+}
+
+/// Open a tunnel and keep it in the foreground until Ctrl+C — or hand it to
+/// the background daemon if one is running.
+async fn open_tunnel(
+    project_key: &str,
+    display_name: &str,
+    profile: &str,
+    local_port: Option<&str>,
+    destination: Destination<'_>,
+    cli: &Cli,
+) -> Result<(), String> {
+    let output = cli.output;
+    eprintln!(
+        "\n  \u{1F680} Connecting to {} via profile {}...\n",
+        display_name, profile
+    );
+
+    #[cfg(unix)]
+    if let Some(client) = DaemonClient::connect_if_running().await {
+        return run_daemon_connect(&client, project_key, profile, local_port, destination, cli)
+            .await;
+    }
+
+    // Same engine as the GUI: port fallback, auto-reconnect, health checks.
+    let events = Arc::new(ConnectEventHandler::new(output));
+    let manager = TunnelManager::new(events.clone());
+    let (connection_id, info) = match destination {
+        Destination::Project { database } => {
+            manager.connect(project_key, profile, local_port, database, &[]).await
+        }
+        Destination::Forward { host, port } => {
+            manager.forward(project_key, profile, host, port, local_port, &[]).await
+        }
+    }
+    .map_err(|e| e.to_string())?;
+
+    let exports = connection_exports(cli, &connection_id, &info, project_key, profile);
+    report_ready(output, &connection_id, &info, &exports);
+
+    // Scripts get the password from stdout; no interactive commands.
+    let password = info.password.clone().filter(|_| output == OutputFormat::Text);
+    if password.is_some() {
+        eprintln!("  Commands: [p] show password  [c] copy password  [Ctrl+C] disconnect\n");
+    } else {
+        eprintln!("  Press Ctrl+C to disconnect.\n");
+    }
+
+    let Some(closed) = manager.closed_token(&connection_id).await else {
+        return events.take_error().map_or(Ok(()), Err);
+    };
+
+    // Spawn interactive command reader if password is available
+    if let Some(pw) = password {
+        if let Ok(mut current) = events.password.lock() {
+            *current = pw;
+        }
+        let current = events.password.clone();
+        let cancel_reader = closed.clone();
+        tokio::task::spawn_blocking(move || {
+            run_command_reader(
+                std::io::stdin().lock(),
+                std::io::stderr(),
+                &current,
+                &cancel_reader,
+            );
+        });
+    }
+
+    tokio::select! {
+        _ = closed.cancelled() => {}
+        _ = tokio::signal::ctrl_c() => {
+            eprintln!("\n  \u{1F6D1} Disconnecting...");
+            manager.disconnect(&connection_id).await.map_err(|e| e.to_string())?;
+            // Let the tunnel task finish its cleanup (history, key files).
+            closed.cancelled().await;
+        }
+    }
+
+    if let Some(e) = events.take_error() {
+        return Err(e);
+    }
+    eprintln!("  \u{1F44B} Disconnected.\n");
+
+    Ok(())
+}
+
+/// Open the tunnel in the background daemon. It keeps running after this
+/// command exits.
+#[cfg(unix)]
+async fn run_daemon_connect(
+    client: &DaemonClient,
+    project_key: &str,
+    profile: &str,
+    local_port: Option<&str>,
+    destination: Destination<'_>,
+    cli: &Cli,
+) -> Result<(), String> {
+    let output = cli.output;
+    let handler = ConnectEventHandler::new(output);
+
+    // Show progress and SSO prompts from the daemon while connecting
+    let mut events = client.subscribe().await.map_err(|e| e.to_string())?;
+    let connect = async {
+        match destination {
+            Destination::Project { database } => {
+                client.connect_tunnel(project_key, profile, local_port, database).await
+            }
+            Destination::Forward { host, port } => {
+                client.forward_tunnel(project_key, profile, host, port, local_port).await
+            }
+        }
+    };
+    tokio::pin!(connect);
+    let result = loop {
+        tokio::select! {
+            result = &mut connect => break result,
+            Ok(event) = events.recv() => event.dispatch(&handler),
+        }
+    };
+    let (connection_id, info) = result.map_err(|e| e.to_string())?;
+
+    let exports = connection_exports(cli, &connection_id, &info, project_key, profile);
+    report_ready(output, &connection_id, &info, &exports);
+    eprintln!(
+        "  Tunnel {} is running in the background daemon.\n",
+        connection_id
+    );
+    if output != OutputFormat::Json {
+        return Ok(());
+    }
+
+    // Keep streaming this tunnel's lifecycle events until it closes.
+    eprintln!("  Following its events; Ctrl+C stops following and leaves the tunnel running.\n");
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) if event.connection_id() == Some(connection_id.as_str()) => {
+                    event.dispatch(&handler);
+                    if matches!(event, DaemonEvent::Disconnected { .. }) {
+                        break;
+                    }
+                }
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// Open every tunnel in a manifest. In the background daemon they keep
+/// running after this command exits; otherwise they stay up until Ctrl+C.
+async fn run_up(cli: &Cli, file: &Path) -> Result<(), String> {
+    let manifest = load_manifest(file).await.map_err(|e| e.to_string())?;
+    eprintln!(
+        "\n  \u{1F680} Bringing up {} tunnels from {}...\n",
+        manifest.tunnels.len(),
+        file.display()
+    );
+
+    // One SSO login per profile up front, instead of one browser tab per tunnel
+    let mut profiles: Vec<&str> = manifest.tunnels.iter().map(|t| t.profile.as_str()).collect();
+    profiles.sort();
+    profiles.dedup();
+    for profile in profiles {
+        ensure_sso_session(profile, &CliSsoHandler, None)
+            .await
+            .map_err(|e| format!("SSO login failed for {}: {}", profile, e))?;
+    }
+
+    #[cfg(unix)]
+    if let Some(client) = DaemonClient::connect_if_running().await {
+        let results =
+            open_manifest(&manifest, |entry| open_manifest_entry_in_daemon(&client, entry)).await;
+        report_manifest(cli.output, &results);
+        if !results.iter().any(|r| r.error.is_none()) {
+            return Err("No tunnels could be opened".to_string());
+        }
+        let opened: Vec<String> = results
+            .iter()
+            .filter_map(|r| r.connection_id.clone())
+            .collect();
+        record_manifest_tunnels(file, &opened)
+            .await
+            .map_err(|e| format!("Failed to record tunnels for `down`: {}", e))?;
+        eprintln!(
+            "  Tunnels are running in the background daemon. Stop them with `connection-app-cli down {}`.\n",
+            file.display()
+        );
+        return Ok(());
+    }
+
+    let manager = TunnelManager::new(Arc::new(ConnectEventHandler::new(cli.output)));
+    let results = open_manifest(&manifest, |entry| open_manifest_entry(&manager, entry)).await;
+    report_manifest(cli.output, &results);
+    if !results.iter().any(|r| r.error.is_none()) {
+        return Err("No tunnels could be opened".to_string());
+    }
+    eprintln!("  Press Ctrl+C to disconnect all tunnels.\n");
+
+    let mut closed = Vec::new();
+    for id in results.iter().filter_map(|r| r.connection_id.as_deref()) {
+        if let Some(token) = manager.closed_token(id).await {
+            closed.push(token);
+        }
+    }
+    let all_closed = async {
+        for token in &closed {
+            token.cancelled().await;
+        }
+    };
+
+    tokio::select! {
+        _ = all_closed => {}
+        _ = tokio::signal::ctrl_c() => {
+            eprintln!("\n  \u{1F6D1} Disconnecting...");
+            manager.disconnect_all().await.map_err(|e| e.to_string())?;
+            for token in &closed {
+                token.cancelled().await;
+            }
+        }
+    }
+    eprintln!("  \u{1F44B} Disconnected.\n");
+
+    Ok(())
+}
+
+/// Open one manifest entry in the in-process manager.
+async fn open_manifest_entry(
+    manager: &TunnelManager,
+    entry: ManifestEntry,
+) -> Result<(String, ConnectionInfo), AppError> {
+    let local_port = entry.local_port.map(|p| p.to_string());
+    match entry.to.as_deref() {
+        Some(to) => {
+            let (host, port) = parse_forward_target(to)?;
+            manager
+                .forward(&entry.project, &entry.profile, &host, port, local_port.as_deref(), &[])
+                .await
+        }
+        None => {
+            manager
+                .connect(
+                    &entry.project,
+                    &entry.profile,
+                    local_port.as_deref(),
+                    entry.database.as_deref(),
+                    &[],
+                )
+                .await
+        }
+    }
+}
+
+/// Open one manifest entry in the background daemon.
+#[cfg(unix)]
+async fn open_manifest_entry_in_daemon(
+    client: &DaemonClient,
+    entry: ManifestEntry,
+) -> Result<(String, ConnectionInfo), AppError> {
+    let local_port = entry.local_port.map(|p| p.to_string());
+    match entry.to.as_deref() {
+        Some(to) => {
+            let (host, port) = parse_forward_target(to)?;
+            client
+                .forward_tunnel(&entry.project, &entry.profile, &host, port, local_port.as_deref())
+                .await
+        }
+        None => {
+            client
+                .connect_tunnel(
+                    &entry.project,
+                    &entry.profile,
+                    local_port.as_deref(),
+                    entry.database.as_deref(),
+                )
+                .await
+        }
+    }
+}
+
+/// Aggregated readiness: a table (or one JSON object) and a ready count.
+fn report_manifest(output: OutputFormat, results: &[ManifestTunnelResult]) {
+    let ready = results.iter().filter(|r| r.error.is_none()).count();
+    if output == OutputFormat::Json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "event": "ready",
+                "ready": ready,
+                "total": results.len(),
+                "tunnels": results,
+            })
+        );
+        return;
+    }
+
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .map(|r| {
+            let (port, target) = r
+                .connection_info
+                .as_ref()
+                .map(|info| (info.port.clone(), connection_target(info)))
+                .unwrap_or_default();
+            let status = match r.error {
+                Some(ref e) => format!("failed: {}", e),
+                None => "ready".to_string(),
+            };
+            vec![r.name.clone(), r.project.clone(), r.profile.clone(), port, target, status]
+        })
+        .collect();
+    print_table(&["NAME", "PROJECT", "PROFILE", "PORT", "TARGET", "STATUS"], &rows);
+    eprintln!("\n  {}/{} tunnels ready.\n", ready, results.len());
+}
+
+/// Disconnect the daemon tunnels that `up` opened from a manifest.
+#[cfg(unix)]
+async fn run_down(file: &Path) -> Result<(), String> {
+    let client = DaemonClient::connect_if_running()
+        .await
+        .ok_or_else(|| NO_DAEMON.to_string())?;
+
+    let opened = take_manifest_tunnels(file).await;
+    let active = client.list().await.map_err(|e| e.to_string())?;
+    let matching: Vec<&ActiveConnectionInfo> =
+        active.iter().filter(|c| opened.contains(&c.id)).collect();
+    if matching.is_empty() {
+        eprintln!("No active tunnels were brought up from {}.", file.display());
+        return Ok(());
+    }
+    for connection in &matching {
+        client.disconnect(&connection.id).await.map_err(|e| e.to_string())?;
+    }
+    eprintln!("  \u{1F44B} Disconnected {} tunnels.", matching.len());
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn run_down(_file: &Path) -> Result<(), String> {
+    Err(NO_DAEMON.to_string())
+}
+
+/// Run the background daemon in the foreground until Ctrl+C / SIGTERM.
+#[cfg(unix)]
+async fn run_daemon() -> Result<(), String> {
+    eprintln!(
+        "  \u{1F680} Starting daemon on {}",
+        connection_app_lib::daemon::socket_path().display()
+    );
+    connection_app_lib::daemon::server::run()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+async fn run_daemon() -> Result<(), String> {
+    Err("The daemon is only supported on macOS and Linux".to_string())
+}
+
+/// Resolve the project and AWS profile from flags, prompting where needed.
+async fn select_project_and_profile(
+    cli: &Cli,
+) -> Result<(String, ProjectConfig, String), String> {
     // Load project configs
     let configs = load_project_configs()
         .await
@@ -147,7 +1093,7 @@ async fn run_connect(cli: Cli) -> Result<(), String> {
     }
 
     // Select project
-    let (project_key, project_config) = select_project(&cli, &configs, &all_profiles)?;
+    let (project_key, project_config) = select_project(cli, &configs, &all_profiles)?;
 
     // Get matching profiles for this project
     let matching_profiles =
@@ -161,26 +1107,21 @@ async fn run_connect(cli: Cli) -> Result<(), String> {
     }
 
     // Select profile
-    let profile = select_profile(&cli, &matching_profiles)?;
-
-    // Determine local port
-    let local_port = cli
-        .port
-        .unwrap_or_else(|| get_local_port(&profile, &project_config));
+    let profile = select_profile(cli, &matching_profiles)?;
 
-    eprintln!(
-        "\n  \u{1F680} Connecting to {} via profile {}...\n",
-        project_config.name, profile
-    );
+    Ok((project_key, project_config, profile))
+}
 
+/// SSO pre-flight, client creation and credential check.
+async fn authenticate(profile: &str, project_config: &ProjectConfig) -> Result<AwsClients, String> {
     // SSO pre-flight
     let sso_handler = CliSsoHandler;
-    ensure_sso_session(&profile, &sso_handler, None)
+    ensure_sso_session(profile, &sso_handler, None)
         .await
         .map_err(|e| format!("SSO login failed: {}", e))?;
 
     // Create AWS clients
-    let clients = create_aws_clients(&profile, &project_config.region).await;
+    let clients = create_aws_clients(profile, &project_config.region).await;
 
     // Check credentials
     eprintln!("  \u{1F511} Checking credentials...");
@@ -192,55 +1133,14 @@ async fn run_connect(cli: Cli) -> Result<(), String> {
         ));
     }
 
-    let connection_type = if project_config.connection_type.is_empty() {
-        "rds"
-    } else {
-        project_config.connection_type.as_str()
-    };
-
-    if connection_type == "service" {
-        run_service_connect(&clients, &project_config, &local_port).await
-    } else {
-        // Select database if multiple are configured
-        let selected_database = if let Some(ref databases) = project_config.databases {
-            if databases.len() > 1 {
-                let selection = Select::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Select database")
-                    .items(databases)
-                    .default(0)
-                    .interact()
-                    .map_err(|e| format!("Database selection failed: {}", e))?;
-                Some(databases[selection].clone())
-            } else if databases.len() == 1 {
-                Some(databases[0].clone())
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
-        run_rds_connect(&clients, &profile, &project_config, &local_port, selected_database.as_deref()).await
-    }
+    Ok(clients)
 }
 
-async fn run_rds_connect(
-    clients: &connection_app_lib::aws::credentials::AwsClients,
-    profile: &str,
+/// Look up the project's RDS endpoint and port.
+async fn find_rds_endpoint(
+    clients: &AwsClients,
     project_config: &ProjectConfig,
-    local_port: &str,
-    selected_database: Option<&str>,
-) -> Result<(), String> {
-    let effective_db = project_config.effective_database(selected_database);
-
-    // Find bastion instance
-    eprintln!("  \u{1F50D} Finding bastion instance...");
-    let instance_id = find_bastion_instance(clients, project_config.bastion_pattern(), None)
-        .await
-        .map_err(|e| format!("Failed to find bastion: {}", e))?;
-
-    // Get RDS endpoint
-    eprintln!("  \u{1F4E1} Getting RDS endpoint...");
+) -> Result<(String, String), String> {
     let rds_endpoint = operations::get_rds_endpoint(
         clients,
         &project_config.rds_type,
@@ -250,108 +1150,32 @@ async fn run_rds_connect(
     .map_err(|e| format!("Failed to get RDS endpoint: {}", e))?
     .ok_or_else(|| "No matching RDS endpoint found.".to_string())?;
 
-    // Get RDS port
     let fallback_port = get_default_port_for_engine(project_config);
     let rds_port = operations::get_rds_port(
         clients,
         &project_config.rds_type,
         &project_config.rds_pattern,
-        &fallback_port,
-    )
-    .await
-    .map_err(|e| format!("Failed to get RDS port: {}", e))?;
-
-    // Determine auth type (default to "secrets")
-    let auth_type = if project_config.auth_type.is_empty() {
-        "secrets"
-    } else {
-        project_config.auth_type.as_str()
-    };
-
-    let (username, password) = if auth_type == "iam" {
-        eprintln!("  \u{1F511} Generating IAM auth token...");
-        let iam_username = project_config
-            .iam_username
-            .as_deref()
-            .ok_or("iamUsername is required when authType is \"iam\"")?;
-
-        let rds_port_num: u16 = rds_port
-            .parse()
-            .map_err(|_| format!("Invalid RDS port number: {}", rds_port))?;
-
-        let sdk_config =
-            connection_app_lib::aws::credentials::build_aws_config(profile, &project_config.region)
-                .await;
-        let token = connection_app_lib::aws::iam_auth::generate_rds_auth_token(
-            &sdk_config,
-            &rds_endpoint,
-            rds_port_num,
-            iam_username,
-        )
-        .await
-        .map_err(|e| format!("Failed to generate IAM auth token: {}", e))?;
-
-        (iam_username.to_string(), token)
-    } else {
-        // "secrets" auth type
-        eprintln!("  \u{1F4E6} Getting database credentials...");
-        let db_creds = operations::get_connection_credentials(
-            clients,
-            &project_config.secret_prefix,
-            effective_db,
-            project_config.secret_path.as_deref(),
-            project_config.secret_username_field.as_deref(),
-            project_config.secret_password_field.as_deref(),
-        )
-        .await
-        .map_err(|e| format!("Failed to get credentials: {}", e))?;
-        (db_creds.username, db_creds.password)
-    };
-
-    // Start SSM session
-    eprintln!("  \u{1F6E0}\u{FE0F}  Starting SSM session...");
-    let session_response = start_session(
-        clients,
-        &instance_id,
-        &rds_endpoint,
-        &rds_port,
-        local_port,
+        &fallback_port,
     )
     .await
-    .map_err(|e| format!("Failed to start SSM session: {}", e))?;
-
-    let (stream_url, token_value) = extract_session_info(&session_response)?;
-
-    let port_num: u16 = local_port
-        .parse()
-        .map_err(|_| format!("Invalid port: {}", local_port))?;
-
-    // Display connection info with dynamic-width box
-    let masked_password = mask_password(&password);
-    let rows = vec![
-        ("Host",     "localhost".to_string()),
-        ("Port",     local_port.to_string()),
-        ("Username", username.clone()),
-        ("Password", masked_password),
-        ("Database", effective_db.to_string()),
-        ("Endpoint", rds_endpoint.clone()),
-    ];
-    print_info_box(&rows);
+    .map_err(|e| format!("Failed to get RDS port: {}", e))?;
 
-    // Copy password to clipboard
-    if try_copy_to_clipboard(&password) {
-        eprintln!("  \u{1F4CB} Password copied to clipboard\n");
-    }
+    Ok((rds_endpoint, rds_port))
+}
 
-    let multiplexed = project_config.multiplexed.unwrap_or(false);
-    run_tunnel(stream_url, token_value, port_num, Some(password), multiplexed).await
+/// Resolved service target and its port forwarding session.
+struct ServiceSession {
+    /// Instance the SSM session runs on (target or bastion).
+    ssm_target: String,
+    response: aws_sdk_ssm::operation::start_session::StartSessionOutput,
 }
 
-async fn run_service_connect(
-    clients: &connection_app_lib::aws::credentials::AwsClients,
+/// Resolve a service project's target and start its port forwarding session.
+async fn start_service_session(
+    clients: &AwsClients,
     project_config: &ProjectConfig,
     local_port: &str,
-) -> Result<(), String> {
+) -> Result<ServiceSession, String> {
     let target_type = project_config
         .target_type
         .as_deref()
@@ -360,13 +1184,8 @@ async fn run_service_connect(
         .remote_port
         .map(|p| p.to_string())
         .unwrap_or_else(|| "5900".to_string());
-    let service_type = project_config
-        .service_type
-        .as_deref()
-        .unwrap_or("custom")
-        .to_uppercase();
 
-    let session_response = match target_type {
+    let session = match target_type {
         "ec2-direct" => {
             let pattern = project_config
                 .target_pattern
@@ -377,9 +1196,14 @@ async fn run_service_connect(
                 .await
                 .map_err(|e| format!("Failed to find EC2 instance: {}", e))?;
             eprintln!("  \u{1F6E0}\u{FE0F}  Starting direct SSM session to {}...", instance_id);
-            start_direct_port_forwarding_session(clients, &instance_id, &remote_port, local_port)
-                .await
-                .map_err(|e| format!("Failed to start SSM session: {}", e))?
+            let response =
+                start_direct_port_forwarding_session(clients, &instance_id, &remote_port, local_port)
+                    .await
+                    .map_err(|e| format!("Failed to start SSM session: {}", e))?;
+            ServiceSession {
+                ssm_target: instance_id,
+                response,
+            }
         }
         "ec2-bastion" => {
             let pattern = project_config
@@ -394,9 +1218,13 @@ async fn run_service_connect(
                 .await
                 .map_err(|e| format!("Failed to find EC2 instance: {}", e))?;
             eprintln!("  \u{1F6E0}\u{FE0F}  Starting SSM session via bastion...");
-            start_session(clients, &bastion_id, &private_ip, &remote_port, local_port)
+            let response = start_session(clients, &bastion_id, &private_ip, &remote_port, local_port)
                 .await
-                .map_err(|e| format!("Failed to start SSM session: {}", e))?
+                .map_err(|e| format!("Failed to start SSM session: {}", e))?;
+            ServiceSession {
+                ssm_target: bastion_id,
+                response,
+            }
         }
         "ecs-bastion" => {
             let cluster = project_config
@@ -415,49 +1243,187 @@ async fn run_service_connect(
                 .await
                 .map_err(|e| format!("Failed to find ECS task: {}", e))?;
             eprintln!("  \u{1F6E0}\u{FE0F}  Starting SSM session via bastion to ECS task {}...", task_ip);
-            start_session(clients, &bastion_id, &task_ip, &remote_port, local_port)
+            let response = start_session(clients, &bastion_id, &task_ip, &remote_port, local_port)
                 .await
-                .map_err(|e| format!("Failed to start SSM session: {}", e))?
+                .map_err(|e| format!("Failed to start SSM session: {}", e))?;
+            ServiceSession {
+                ssm_target: bastion_id,
+                response,
+            }
         }
         _ => return Err(format!("Unknown target type: {}", target_type)),
     };
 
-    let (stream_url, token_value) = extract_session_info(&session_response)?;
+    Ok(session)
+}
+
+async fn run_shell(cli: &Cli) -> Result<(), String> {
+    let (_, project_config, profile) = select_project_and_profile(cli).await?;
+
+    eprintln!(
+        "\n  \u{1F680} Opening shell on {} via profile {}...\n",
+        project_config.name, profile
+    );
 
-    let port_num: u16 = local_port
-        .parse()
-        .map_err(|_| format!("Invalid port: {}", local_port))?;
-
-    let mut rows = vec![
-        ("Host",    "localhost".to_string()),
-        ("Port",    local_port.to_string()),
-        ("Service", service_type.clone()),
-        ("Target",  target_type.to_string()),
-    ];
-
-    // Show SSH command for SSH service type
-    if service_type == "SSH" {
-        let ssh_user = project_config
-            .ssh_username
+    let clients = authenticate(&profile, &project_config).await?;
+
+    // Service projects targeting an instance directly have no bastion; shell
+    // into the instance itself.
+    let direct_target = project_config.connection_type == "service"
+        && project_config.target_type.as_deref().unwrap_or("ec2-direct") == "ec2-direct";
+    let instance_id = if direct_target {
+        let pattern = project_config
+            .target_pattern
             .as_deref()
-            .filter(|s| !s.is_empty())
-            .unwrap_or("ec2-user");
-        let mut cmd = format!(
-            "ssh -p {} {}@localhost -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null",
-            local_port, ssh_user
-        );
-        if let Some(ref key_path) = project_config.ssh_key_path
-            && !key_path.is_empty()
-        {
-            cmd.push_str(&format!(" -i {}", key_path));
+            .ok_or("targetPattern is required for ec2-direct")?;
+        eprintln!("  \u{1F50D} Finding EC2 instance...");
+        find_ec2_instance(&clients, pattern)
+            .await
+            .map_err(|e| format!("Failed to find EC2 instance: {}", e))?
+            .0
+    } else {
+        eprintln!("  \u{1F50D} Finding bastion instance...");
+        find_bastion_instance(&clients, project_config.bastion_pattern(), None)
+            .await
+            .map_err(|e| format!("Failed to find bastion: {}", e))?
+    };
+
+    eprintln!("  \u{1F6E0}\u{FE0F}  Starting shell session on {}...", instance_id);
+    let session_response = start_shell_session(&clients, &instance_id)
+        .await
+        .map_err(|e| format!("Failed to start shell session: {}", e))?;
+
+    let (stream_url, token_value) = extract_session_info(&session_response)?;
+    let kms = kms_context(&clients, &session_response, &instance_id);
+
+    eprintln!("\n  \u{2705} Connected to {}. Type 'exit' to end the session.\n", instance_id);
+
+    // std::io::stdin blocks in a plain thread: tokio's stdin would keep the
+    // runtime from shutting down while a read is pending.
+    let (input_tx, input_rx) = tokio::sync::mpsc::channel(64);
+    std::thread::spawn(move || {
+        use std::io::Read;
+        let mut stdin = std::io::stdin();
+        let mut buf = [0u8; STREAM_DATA_PAYLOAD_SIZE];
+        loop {
+            match stdin.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if input_tx.blocking_send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    // Poll the terminal size — there is no portable resize signal.
+    let cancel = CancellationToken::new();
+    let (size_tx, size_rx) = tokio::sync::mpsc::channel(4);
+    let cancel_sizes = cancel.clone();
+    tokio::spawn(async move {
+        let mut last = None;
+        let mut interval = tokio::time::interval(std::time::Duration::from_millis(500));
+        loop {
+            tokio::select! {
+                _ = cancel_sizes.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+            let Ok((cols, rows)) = crossterm::terminal::size() else {
+                continue;
+            };
+            let size = TerminalSize {
+                cols: cols as u32,
+                rows: rows as u32,
+            };
+            if last != Some(size) {
+                last = Some(size);
+                if size_tx.send(size).await.is_err() {
+                    break;
+                }
+            }
         }
-        rows.push(("SSH Cmd", cmd));
+    });
+
+    crossterm::terminal::enable_raw_mode()
+        .map_err(|e| format!("Failed to switch terminal to raw mode: {}", e))?;
+    let result = run_shell_session(
+        stream_url,
+        token_value,
+        Some(kms),
+        input_rx,
+        tokio::io::stdout(),
+        size_rx,
+        cancel.clone(),
+    )
+    .await;
+    let _ = crossterm::terminal::disable_raw_mode();
+    cancel.cancel();
+    result?;
+
+    eprintln!("\n  \u{1F44B} Disconnected.\n");
+
+    Ok(())
+}
+
+/// Forward a single connection over stdin/stdout, for use as an ssh
+/// `ProxyCommand`. stdout carries tunnel data only — progress goes to stderr.
+async fn run_stdio(cli: &Cli) -> Result<(), String> {
+    let (_, project_config, profile) = select_project_and_profile(cli).await?;
+    if project_config.connection_type == "eks" {
+        return Err(
+            "stdio is not available for eks projects; connect and use the kubeconfig".to_string(),
+        );
+    }
+    // TLS and cluster redirects are handled by the Redis proxy on a local port
+    if project_config.connection_type == "redis" {
+        return Err(
+            "stdio is not available for redis projects; connect and use the local port".to_string(),
+        );
     }
+    let clients = authenticate(&profile, &project_config).await?;
 
-    print_info_box(&rows);
+    let local_port = cli
+        .port
+        .clone()
+        .unwrap_or_else(|| get_local_port(&profile, &project_config));
+
+    let (ssm_target, session_response) = if project_config.connection_type == "service" {
+        let session = start_service_session(&clients, &project_config, &local_port).await?;
+        (session.ssm_target, session.response)
+    } else {
+        eprintln!("  \u{1F50D} Finding bastion instance...");
+        let instance_id = find_bastion_instance(&clients, project_config.bastion_pattern(), None)
+            .await
+            .map_err(|e| format!("Failed to find bastion: {}", e))?;
+        eprintln!("  \u{1F4E1} Getting RDS endpoint...");
+        let (rds_endpoint, rds_port) = find_rds_endpoint(&clients, &project_config).await?;
+        eprintln!("  \u{1F6E0}\u{FE0F}  Starting SSM session...");
+        let response = start_session(&clients, &instance_id, &rds_endpoint, &rds_port, &local_port)
+            .await
+            .map_err(|e| format!("Failed to start SSM session: {}", e))?;
+        (instance_id, response)
+    };
+
+    let (stream_url, token_value) = extract_session_info(&session_response)?;
+    let kms = kms_context(&clients, &session_response, &ssm_target);
+
+    let cancel = CancellationToken::new();
+    let cancel_signal = cancel.clone();
+    tokio::spawn(async move {
+        tokio::signal::ctrl_c().await.ok();
+        cancel_signal.cancel();
+    });
 
-    let multiplexed = project_config.multiplexed.unwrap_or(false);
-    run_tunnel(stream_url, token_value, port_num, None, multiplexed).await
+    start_stream_port_forwarding(
+        stream_url,
+        token_value,
+        Some(kms),
+        tokio::io::stdin(),
+        tokio::io::stdout(),
+        cancel,
+    )
+    .await
 }
 
 fn extract_session_info(
@@ -474,66 +1440,408 @@ fn extract_session_info(
     Ok((stream_url, token_value))
 }
 
-fn print_info_box(rows: &[(&str, String)]) {
-    let lines: Vec<String> = rows
+/// KMS context for the data channel handshake, used only if the session
+/// document requires encryption.
+fn kms_context(
+    clients: &connection_app_lib::aws::credentials::AwsClients,
+    response: &aws_sdk_ssm::operation::start_session::StartSessionOutput,
+    ssm_target: &str,
+) -> KmsSessionContext {
+    KmsSessionContext {
+        kms: clients.kms.clone(),
+        session_id: response.session_id().unwrap_or_default().to_string(),
+        target_id: ssm_target.to_string(),
+    }
+}
+
+/// Terminal output for the connect flow: progress on stderr like
+/// [`CliTunnelHandler`], plus NDJSON lifecycle events on stdout with
+/// `--output json`.
+struct ConnectEventHandler {
+    inner: CliTunnelHandler,
+    output: OutputFormat,
+    /// Current password for the `[p]`/`[c]` commands; replaced when an IAM
+    /// auth token is regenerated.
+    password: Arc<std::sync::Mutex<String>>,
+}
+
+impl ConnectEventHandler {
+    fn new(output: OutputFormat) -> Self {
+        Self {
+            inner: CliTunnelHandler::default(),
+            output,
+            password: Arc::default(),
+        }
+    }
+
+    fn take_error(&self) -> Option<String> {
+        self.inner.take_error()
+    }
+
+    fn emit(&self, event: serde_json::Value) {
+        if self.output == OutputFormat::Json {
+            println!("{}", event);
+        }
+    }
+}
+
+impl TunnelEventHandler for ConnectEventHandler {
+    fn on_status(&self, message: &str, connection_id: Option<&str>) {
+        self.inner.on_status(message, connection_id);
+    }
+
+    fn on_notification(&self, title: &str, body: &str) {
+        self.inner.on_notification(title, body);
+    }
+
+    fn on_health(&self, connection_id: &str, status: &str, last_check_ms: u64) {
+        self.inner.on_health(connection_id, status, last_check_ms);
+    }
+
+    fn on_error(&self, connection_id: &str, message: &str) {
+        self.inner.on_error(connection_id, message);
+        self.emit(serde_json::json!({
+            "event": "error",
+            "connectionId": connection_id,
+            "message": message,
+        }));
+    }
+
+    fn on_disconnected(&self, connection_id: &str, reason: &str) {
+        self.emit(serde_json::json!({
+            "event": "disconnected",
+            "connectionId": connection_id,
+            "reason": reason,
+        }));
+    }
+
+    fn on_reconnecting(&self, connection_id: &str, attempt: u32, max_attempts: u32) {
+        self.emit(serde_json::json!({
+            "event": "reconnecting",
+            "connectionId": connection_id,
+            "attempt": attempt,
+            "maxAttempts": max_attempts,
+        }));
+    }
+
+    fn on_reconnected(&self, connection_id: &str) {
+        self.emit(serde_json::json!({
+            "event": "reconnected",
+            "connectionId": connection_id,
+        }));
+    }
+
+    fn on_credentials_refreshed(&self, connection_id: &str, info: &ConnectionInfo) {
+        if let (Some(password), Ok(mut current)) = (&info.password, self.password.lock()) {
+            current.clone_from(password);
+        }
+        if self.output == OutputFormat::Json {
+            self.emit(serde_json::json!({
+                "event": "credentials-refreshed",
+                "connectionId": connection_id,
+                "password": info.password,
+            }));
+        } else {
+            eprintln!("  \u{1F511} IAM auth token refreshed");
+        }
+    }
+
+    fn sso_handler(&self) -> &dyn SsoEventHandler {
+        self.inner.sso_handler()
+    }
+}
+
+fn parse_export_format(s: &str) -> Result<ExportFormat, String> {
+    s.parse().map_err(|e: AppError| e.to_string())
+}
+
+/// Render the `--print` formats for a ready tunnel. A format that doesn't
+/// apply (e.g. pgpass for MySQL) is reported and skipped.
+fn connection_exports(
+    cli: &Cli,
+    connection_id: &str,
+    info: &ConnectionInfo,
+    project_key: &str,
+    profile: &str,
+) -> Vec<ConnectionExport> {
+    let service = service_name(project_key, profile);
+    cli.print
         .iter()
-        .map(|(label, value)| format!("  {:<10}{}", format!("{}:", label), value))
+        .filter_map(|&format| {
+            export_connection(connection_id, info, &service, &[format], cli.write_exports)
+                .map_err(|e| eprintln!("  \u{26A0}\u{FE0F}  {}: {}", format.name(), e))
+                .ok()
+        })
+        .flatten()
+        .collect()
+}
+
+/// Announce a ready tunnel in the requested output format.
+fn report_ready(
+    output: OutputFormat,
+    connection_id: &str,
+    info: &ConnectionInfo,
+    exports: &[ConnectionExport],
+) {
+    let event = ready_event(connection_id, info, exports);
+    match output {
+        OutputFormat::Text => print_connection_info(info),
+        OutputFormat::Json => {
+            println!("{}", event);
+            return;
+        }
+        OutputFormat::Env => {
+            // Exports are included as assignments, so stdout stays `eval`-able
+            for line in env_assignments(&event) {
+                println!("{}", line);
+            }
+            return;
+        }
+    }
+    for export in exports {
+        println!("# {}", export.format.name());
+        if let Some(ref path) = export.path {
+            println!("# written to {}", path);
+        }
+        println!("{}", export.content);
+    }
+}
+
+/// `{"event": "ready", "connectionId": ..., <ConnectionInfo fields>}`, plus
+/// `"exports"` when `--print` was given.
+fn ready_event(
+    connection_id: &str,
+    info: &ConnectionInfo,
+    exports: &[ConnectionExport],
+) -> serde_json::Value {
+    let mut event = serde_json::json!({
+        "event": "ready",
+        "connectionId": connection_id,
+    });
+    if let (Some(event), Ok(serde_json::Value::Object(fields))) =
+        (event.as_object_mut(), serde_json::to_value(info))
+    {
+        event.extend(fields);
+    }
+    if !exports.is_empty()
+        && let (Some(event), Ok(exports)) =
+            (event.as_object_mut(), serde_json::to_value(exports))
+    {
+        event.insert("exports".to_string(), exports);
+    }
+    event
+}
+
+/// `TUNNEL_SOME_FIELD='value'` for every scalar field of the ready event
+/// except `event`, and `TUNNEL_EXPORT_<FORMAT>='content'` (plus `_PATH` when
+/// written to disk) for every `--print` export.
+fn env_assignments(event: &serde_json::Value) -> Vec<String> {
+    let Some(fields) = event.as_object() else {
+        return Vec::new();
+    };
+    let mut lines: Vec<String> = fields
+        .iter()
+        .filter(|(key, value)| key.as_str() != "event" && !value.is_array())
+        .map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            format!("{}={}", env_name(key), shell_quote(&value))
+        })
         .collect();
-    let max_line_len = lines.iter().map(|l| l.len()).max().unwrap_or(0);
-    let box_width = max_line_len + 2;
 
-    eprintln!("\n  \u{2705} Connected!\n");
-    eprintln!("  \u{250C}{}\u{2510}", "\u{2500}".repeat(box_width));
-    for line in &lines {
-        eprintln!("  \u{2502}{:<box_width$}\u{2502}", line, box_width = box_width);
+    let exports = event["exports"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for export in exports {
+        let (Some(format), Some(content)) = (export["format"].as_str(), export["content"].as_str())
+        else {
+            continue;
+        };
+        let name = format!(
+            "TUNNEL_EXPORT_{}",
+            format.replace('-', "_").to_ascii_uppercase()
+        );
+        lines.push(format!("{}={}", name, shell_quote(content)));
+        if let Some(path) = export["path"].as_str() {
+            lines.push(format!("{}_PATH={}", name, shell_quote(path)));
+        }
     }
-    eprintln!("  \u{2514}{}\u{2518}\n", "\u{2500}".repeat(box_width));
+    lines
 }
 
-async fn run_tunnel(
-    stream_url: String,
-    token_value: String,
-    port_num: u16,
-    password: Option<String>,
-    multiplexed: bool,
-) -> Result<(), String> {
-    if password.is_some() {
-        eprintln!("  Commands: [p] show password  [c] copy password  [Ctrl+C] disconnect\n");
+/// `connectionId` -> `TUNNEL_CONNECTION_ID`
+fn env_name(key: &str) -> String {
+    let mut name = String::from("TUNNEL_");
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            name.push('_');
+        }
+        name.push(c.to_ascii_uppercase());
+    }
+    name
+}
+
+/// Single-quote for POSIX shells.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Print the connection box and copy the database password, if any.
+fn print_connection_info(info: &ConnectionInfo) {
+    let mut rows = vec![("Host", info.host.clone()), ("Port", info.port.clone())];
+    if info.connection_type == "service" {
+        rows.push((
+            "Service",
+            info.service_type.as_deref().unwrap_or("custom").to_uppercase(),
+        ));
+        if info.service_type.as_deref() == Some("socks") {
+            rows.push(("Proxy", format!("socks5h://{}:{}", info.host, info.port)));
+        } else if matches!(info.service_type.as_deref(), Some("http" | "sigv4-http")) {
+            rows.push(("URL", format!("http://{}:{}/", info.host, info.port)));
+        } else {
+            rows.push(("Target", info.target_type.clone().unwrap_or_default()));
+        }
+        if let Some(ref cmd) = info.ssh_command {
+            rows.push(("SSH Cmd", cmd.clone()));
+        }
+    } else if info.connection_type == "eks" {
+        rows.push(("Endpoint", info.remote_host.clone().unwrap_or_default()));
+        if let Some(ref path) = info.kubeconfig {
+            rows.push(("Config", path.clone()));
+            rows.push(("Usage", format!("export KUBECONFIG={}", shell_quote(path))));
+        }
+    } else if info.connection_type == "redis" {
+        if let Some(ref username) = info.username {
+            rows.push(("Username", username.clone()));
+            rows.push((
+                "Password",
+                mask_password(info.password.as_deref().unwrap_or_default()),
+            ));
+        }
+        rows.push(("Endpoint", info.remote_host.clone().unwrap_or_default()));
     } else {
-        eprintln!("  Press Ctrl+C to disconnect.\n");
+        rows.push(("Username", info.username.clone().unwrap_or_default()));
+        let password = if info.auth_proxy {
+            "not needed (auth proxy)".to_string()
+        } else {
+            mask_password(info.password.as_deref().unwrap_or_default())
+        };
+        rows.push(("Password", password));
+        rows.push(("Database", info.database.clone().unwrap_or_default()));
+        rows.push(("Endpoint", info.rds_endpoint.clone().unwrap_or_default()));
     }
+    print_info_box(&rows);
 
-    let cancel = CancellationToken::new();
-    let cancel_signal = cancel.clone();
+    // Copy password to clipboard
+    if let Some(ref pw) = info.password
+        && try_copy_to_clipboard(pw)
+    {
+        eprintln!("  \u{1F4CB} Password copied to clipboard\n");
+    }
+}
 
-    tokio::spawn(async move {
-        tokio::signal::ctrl_c().await.ok();
-        eprintln!("\n  \u{1F6D1} Disconnecting...");
-        cancel_signal.cancel();
-    });
+/// Where the tunnel leads, for the status table.
+fn connection_target(info: &ConnectionInfo) -> String {
+    info.rds_endpoint
+        .clone()
+        .or_else(|| info.remote_host.clone())
+        .or_else(|| info.instance_id.clone())
+        .unwrap_or_default()
+}
 
-    // Spawn interactive command reader if password is available
-    if let Some(pw) = password {
-        let cancel_reader = cancel.clone();
-        tokio::task::spawn_blocking(move || {
-            run_command_reader(
-                std::io::stdin().lock(),
-                std::io::stderr(),
-                &pw,
-                &cancel_reader,
-            );
-        });
+/// Print rows as left-aligned columns under a header.
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    println!("{}", format_table_row(headers, &widths));
+    for row in rows {
+        let cells: Vec<&str> = row.iter().map(String::as_str).collect();
+        println!("{}", format_table_row(&cells, &widths));
     }
+}
+
+fn format_table_row(cells: &[&str], widths: &[usize]) -> String {
+    cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+        .collect::<Vec<_>>()
+        .join("  ")
+        .trim_end()
+        .to_string()
+}
 
-    if multiplexed {
-        start_multiplexed_port_forwarding(stream_url, token_value, port_num, cancel, None).await?;
+/// Compact duration like `45s`, `12m 03s`, `3h 07m`, `2d 05h`.
+fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (days, hours, mins, secs) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    if days > 0 {
+        format!("{}d {:02}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {:02}m", hours, mins)
+    } else if mins > 0 {
+        format!("{}m {:02}s", mins, secs)
     } else {
-        start_native_port_forwarding(stream_url, token_value, port_num, cancel, None).await?;
+        format!("{}s", secs)
     }
+}
 
-    eprintln!("  \u{1F44B} Disconnected.\n");
+/// Parse `--since`: a relative age (`30m`, `12h`, `7d`, `2w`), an RFC 3339
+/// timestamp, or a date (`YYYY-MM-DD`, midnight UTC).
+fn parse_since(
+    value: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    let value = value.trim();
+    if let Some(unit) = value.chars().last()
+        && let Ok(amount) = value[..value.len() - unit.len_utf8()].parse::<i64>()
+    {
+        let age = match unit {
+            'm' => Some(chrono::Duration::minutes(amount)),
+            'h' => Some(chrono::Duration::hours(amount)),
+            'd' => Some(chrono::Duration::days(amount)),
+            'w' => Some(chrono::Duration::weeks(amount)),
+            _ => None,
+        };
+        if let Some(age) = age {
+            return Ok(now - age);
+        }
+    }
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(t.with_timezone(&chrono::Utc));
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        && let Some(midnight) = date.and_hms_opt(0, 0, 0)
+    {
+        return Ok(midnight.and_utc());
+    }
+    Err(format!(
+        "Invalid --since '{}'. Use e.g. 30m, 12h, 7d, 2w, 2024-05-01 or an RFC 3339 timestamp",
+        value
+    ))
+}
 
-    Ok(())
+fn print_info_box(rows: &[(&str, String)]) {
+    let lines: Vec<String> = rows
+        .iter()
+        .map(|(label, value)| format!("  {:<10}{}", format!("{}:", label), value))
+        .collect();
+    let max_line_len = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+    let box_width = max_line_len + 2;
+
+    eprintln!("\n  \u{2705} Connected!\n");
+    eprintln!("  \u{250C}{}\u{2510}", "\u{2500}".repeat(box_width));
+    for line in &lines {
+        eprintln!("  \u{2502}{:<box_width$}\u{2502}", line, box_width = box_width);
+    }
+    eprintln!("  \u{2514}{}\u{2518}\n", "\u{2500}".repeat(box_width));
 }
 
 /// Read user commands from `input` and respond on `out` until EOF, read
@@ -541,7 +1849,7 @@ async fn run_tunnel(
 fn run_command_reader<R: std::io::BufRead, W: std::io::Write>(
     mut input: R,
     mut out: W,
-    pw: &str,
+    pw: &std::sync::Mutex<String>,
     cancel: &CancellationToken,
 ) {
     loop {
@@ -556,12 +1864,14 @@ fn run_command_reader<R: std::io::BufRead, W: std::io::Write>(
             Ok(_) => {}
         }
         let cmd = line.trim().to_lowercase();
+        // Re-read each time: IAM auth tokens are refreshed while the tunnel runs
+        let pw = pw.lock().map(|pw| pw.clone()).unwrap_or_default();
         match cmd.as_str() {
             "p" | "password" | "show" => {
                 let _ = writeln!(out, "\n  \u{1F513} Password: {}\n", pw);
             }
             "c" | "copy" => {
-                if try_copy_to_clipboard(pw) {
+                if try_copy_to_clipboard(&pw) {
                     let _ = writeln!(out, "\n  \u{1F4CB} Password copied to clipboard\n");
                 } else {
                     let _ = writeln!(out, "\n  \u{26A0}\u{FE0F}  Failed to copy to clipboard\n");
@@ -585,17 +1895,8 @@ fn select_project(
     all_profiles: &[String],
 ) -> Result<(String, ProjectConfig), String> {
     if let Some(ref project_name) = cli.project {
-        // Direct project selection by key
-        if let Some(config) = configs.get(project_name) {
-            return Ok((project_name.clone(), config.clone()));
-        }
-        // Try matching by name (case-insensitive)
-        for (key, config) in configs {
-            if config.name.to_lowercase() == project_name.to_lowercase() {
-                return Ok((key.clone(), config.clone()));
-            }
-        }
-        return Err(format!("Project '{}' not found", project_name));
+        return find_project(configs, project_name)
+            .ok_or_else(|| format!("Project '{}' not found", project_name));
     }
 
     // Filter to projects that have at least one matching profile
@@ -640,6 +1941,20 @@ fn select_project(
     Ok((key, config.clone()))
 }
 
+/// Look up a project by key, or by name (case-insensitive).
+fn find_project(
+    configs: &HashMap<String, ProjectConfig>,
+    project_name: &str,
+) -> Option<(String, ProjectConfig)> {
+    if let Some(config) = configs.get(project_name) {
+        return Some((project_name.to_string(), config.clone()));
+    }
+    configs
+        .iter()
+        .find(|(_, config)| config.name.to_lowercase() == project_name.to_lowercase())
+        .map(|(key, config)| (key.clone(), config.clone()))
+}
+
 fn select_profile(cli: &Cli, matching_profiles: &[String]) -> Result<String, String> {
     if let Some(ref profile) = cli.profile {
         if matching_profiles.contains(profile) {
@@ -742,7 +2057,8 @@ mod tests {
         std::thread::spawn(move || {
             let input = std::io::Cursor::new(b"p\n".to_vec());
             let mut out = Vec::new();
-            run_command_reader(input, &mut out, "s3cret", &cancel);
+            let pw = std::sync::Mutex::new("s3cret".to_string());
+            run_command_reader(input, &mut out, &pw, &cancel);
             let _ = done_tx.send(out);
         });
         let out = done_rx
@@ -751,4 +2067,143 @@ mod tests {
         let text = String::from_utf8_lossy(&out);
         assert!(text.contains("s3cret"), "expected password output, got: {text}");
     }
+
+    #[test]
+    fn env_output_quotes_ready_event_fields() {
+        let info = ConnectionInfo {
+            host: "localhost".to_string(),
+            port: "5433".to_string(),
+            connection_type: "rds".to_string(),
+            username: Some("app".to_string()),
+            password: Some("it's $ecret".to_string()),
+            database: None,
+            rds_endpoint: None,
+            instance_id: None,
+            service_type: None,
+            remote_host: None,
+            target_type: None,
+            engine: None,
+            ssh_command: None,
+            kubeconfig: None,
+            auth_proxy: false,
+        };
+        let exports = [ConnectionExport {
+            format: ExportFormat::Libpq,
+            content: "postgresql://app@localhost:5433".to_string(),
+            path: None,
+        }];
+        let event = ready_event("conn_1", &info, &exports);
+        assert_eq!(event["event"], "ready");
+        assert_eq!(event["port"], "5433");
+        assert_eq!(event["exports"][0]["format"], "libpq");
+
+        let mut lines = env_assignments(&event);
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                "TUNNEL_CONNECTION_ID='conn_1'",
+                "TUNNEL_CONNECTION_TYPE='rds'",
+                "TUNNEL_EXPORT_LIBPQ='postgresql://app@localhost:5433'",
+                "TUNNEL_HOST='localhost'",
+                "TUNNEL_PASSWORD='it'\\''s $ecret'",
+                "TUNNEL_PORT='5433'",
+                "TUNNEL_USERNAME='app'",
+            ]
+        );
+    }
+
+    #[test]
+    fn env_output_assigns_printed_exports() {
+        let info = ConnectionInfo {
+            host: "localhost".to_string(),
+            port: "5433".to_string(),
+            connection_type: "rds".to_string(),
+            username: Some("app".to_string()),
+            password: Some("s3cret".to_string()),
+            database: Some("main".to_string()),
+            rds_endpoint: None,
+            instance_id: None,
+            service_type: None,
+            remote_host: None,
+            target_type: None,
+            engine: None,
+            ssh_command: None,
+            kubeconfig: None,
+            auth_proxy: false,
+        };
+        let exports = [
+            ConnectionExport {
+                format: ExportFormat::PgService,
+                content: "[conn_1]\nhost=localhost\nport=5433\n".to_string(),
+                path: Some("/home/me/.connection-app/exports/conn_1/pg_service.conf".to_string()),
+            },
+            ConnectionExport {
+                format: ExportFormat::Env,
+                content: "export PGHOST='localhost'\nexport PGPASSWORD='s3cret'\n".to_string(),
+                path: None,
+            },
+        ];
+        let lines = env_assignments(&ready_event("conn_1", &info, &exports));
+
+        // Every line is an assignment: no comments or raw file contents
+        assert!(lines.iter().all(|l| l.starts_with("TUNNEL_")), "{lines:?}");
+        let exported: Vec<&str> = lines
+            .iter()
+            .map(String::as_str)
+            .filter(|l| l.starts_with("TUNNEL_EXPORT_"))
+            .collect();
+        assert_eq!(
+            exported,
+            vec![
+                "TUNNEL_EXPORT_PG_SERVICE='[conn_1]\nhost=localhost\nport=5433\n'",
+                "TUNNEL_EXPORT_PG_SERVICE_PATH='/home/me/.connection-app/exports/conn_1/pg_service.conf'",
+                "TUNNEL_EXPORT_ENV='export PGHOST='\\''localhost'\\''\nexport PGPASSWORD='\\''s3cret'\\''\n'",
+            ]
+        );
+    }
+
+    #[test]
+    fn format_duration_picks_two_largest_units() {
+        assert_eq!(format_duration(-5), "0s");
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(12 * 60 + 3), "12m 03s");
+        assert_eq!(format_duration(3 * 3600 + 7 * 60 + 59), "3h 07m");
+        assert_eq!(format_duration(2 * 86_400 + 5 * 3600), "2d 05h");
+    }
+
+    #[test]
+    fn parse_since_accepts_relative_and_absolute_times() {
+        let now = chrono::DateTime::parse_from_rfc3339("2024-05-10T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let at = |s: &str| parse_since(s, now).unwrap().to_rfc3339();
+
+        assert_eq!(at("30m"), "2024-05-10T11:30:00+00:00");
+        assert_eq!(at("12h"), "2024-05-10T00:00:00+00:00");
+        assert_eq!(at("7d"), "2024-05-03T12:00:00+00:00");
+        assert_eq!(at("2w"), "2024-04-26T12:00:00+00:00");
+        assert_eq!(at("2024-05-01"), "2024-05-01T00:00:00+00:00");
+        assert_eq!(at("2024-05-01T08:30:00+02:00"), "2024-05-01T06:30:00+00:00");
+        assert!(parse_since("yesterday", now).is_err());
+        assert!(parse_since("5y", now).is_err());
+    }
+
+    #[test]
+    fn exec_credential_format() {
+        let expires = chrono::DateTime::parse_from_rfc3339("2024-05-01T12:14:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let credential = exec_credential("k8s-aws-v1.abc", expires);
+        assert_eq!(credential["kind"], "ExecCredential");
+        assert_eq!(
+            credential["apiVersion"],
+            "client.authentication.k8s.io/v1beta1"
+        );
+        assert_eq!(credential["status"]["token"], "k8s-aws-v1.abc");
+        assert_eq!(
+            credential["status"]["expirationTimestamp"],
+            "2024-05-01T12:14:00Z"
+        );
+    }
 }
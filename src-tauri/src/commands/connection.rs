@@ -1,11 +1,22 @@
 use crate::aws::sso;
 use crate::error::AppError;
-use crate::tunnel::manager::{ActiveConnectionInfo, ConnectionInfo, TunnelManager};
+#[cfg(unix)]
+use crate::daemon::client::DaemonClient;
+use crate::config::manifest::{load_manifest, open_manifest, ManifestTunnelResult, TunnelManifest};
+use crate::tunnel::export::{self, ConnectionExport, ExportFormat};
+use crate::tunnel::manager::{
+    parse_forward_target, ActiveConnectionInfo, ConnectionInfo, TunnelManager,
+};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::sync::Arc;
 use tauri::{AppHandle, State};
 use tokio::sync::Mutex;
 
+/// How often the GUI looks for a (re)started background daemon.
+#[cfg(unix)]
+const DAEMON_RETRY_SECS: u64 = 5;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ConnectResult {
     #[serde(rename = "connectionId")]
@@ -24,24 +35,18 @@ pub async fn connect(
     database: Option<String>,
     saved_connection_id: Option<String>,
 ) -> Result<ConnectResult, AppError> {
-    let manager = tunnel_manager.lock().await;
-
-    // Get currently used ports
-    let used_ports = manager.get_used_ports().await;
-
-    let (connection_id, connection_info) = manager
-        .connect(
-            &project_key,
-            &profile,
-            local_port.as_deref(),
-            database.as_deref(),
-            &used_ports,
-        )
-        .await?;
+    let (connection_id, connection_info) = open_connection(
+        &tunnel_manager,
+        &project_key,
+        &profile,
+        local_port.as_deref(),
+        database.as_deref(),
+    )
+    .await?;
 
     // Update last used time if this is from a saved connection
     if let Some(saved_id) = saved_connection_id {
-        let _ = super::saved::update_saved_connection_last_used_inner(&app_handle, &saved_id);
+        let _ = crate::config::saved::touch_saved_connection(&saved_id).await;
     }
 
     // Refresh tray menu to show the new active connection
@@ -53,19 +58,129 @@ pub async fn connect(
     })
 }
 
+/// Forward a local port to an arbitrary host:port through a project's bastion.
+#[tauri::command]
+pub async fn forward(
+    app_handle: AppHandle,
+    tunnel_manager: State<'_, Arc<Mutex<TunnelManager>>>,
+    project_key: String,
+    profile: String,
+    remote_host: String,
+    remote_port: u16,
+    local_port: Option<String>,
+) -> Result<ConnectResult, AppError> {
+    let (connection_id, connection_info) = open_forward(
+        &tunnel_manager,
+        &project_key,
+        &profile,
+        &remote_host,
+        remote_port,
+        local_port.as_deref(),
+    )
+    .await?;
+
+    crate::tray::refresh_tray(&app_handle);
+
+    Ok(ConnectResult {
+        connection_id,
+        connection_info,
+    })
+}
+
+/// Ask for a tunnel manifest to open as a workspace. Returns its path.
+#[tauri::command]
+pub async fn pick_workspace_file(app_handle: AppHandle) -> Result<String, AppError> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app_handle
+        .dialog()
+        .file()
+        .set_title("Select a tunnel manifest")
+        .add_filter("TOML", &["toml"])
+        .pick_file(move |file_path| {
+            let _ = tx.send(file_path);
+        });
+
+    let file_path = rx
+        .await
+        .map_err(|_| AppError::General("Dialog channel closed".to_string()))?
+        .ok_or_else(|| AppError::General("File selection cancelled".to_string()))?;
+
+    let path = file_path
+        .as_path()
+        .ok_or_else(|| AppError::General("Invalid file path from dialog".to_string()))?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Read a tunnel manifest so the UI can show it as a workspace.
+#[tauri::command]
+pub async fn load_workspace(path: String) -> Result<TunnelManifest, AppError> {
+    load_manifest(Path::new(&path)).await
+}
+
+/// Open every tunnel in a workspace (tunnel manifest) concurrently.
+#[tauri::command]
+pub async fn open_workspace(
+    app_handle: AppHandle,
+    tunnel_manager: State<'_, Arc<Mutex<TunnelManager>>>,
+    path: String,
+) -> Result<Vec<ManifestTunnelResult>, AppError> {
+    let manifest = load_manifest(Path::new(&path)).await?;
+
+    // One SSO login per profile up front, instead of one per tunnel
+    let handler = sso::TauriSsoHandler {
+        app_handle: app_handle.clone(),
+    };
+    let mut profiles: Vec<&str> = manifest.tunnels.iter().map(|t| t.profile.as_str()).collect();
+    profiles.sort();
+    profiles.dedup();
+    for profile in profiles {
+        sso::ensure_sso_session(profile, &handler, None).await?;
+    }
+
+    let tunnel_manager: &Mutex<TunnelManager> = &tunnel_manager;
+    let results = open_manifest(&manifest, |entry| async move {
+        let local_port = entry.local_port.map(|p| p.to_string());
+        match entry.to.as_deref() {
+            Some(to) => {
+                let (host, port) = parse_forward_target(to)?;
+                open_forward(
+                    tunnel_manager,
+                    &entry.project,
+                    &entry.profile,
+                    &host,
+                    port,
+                    local_port.as_deref(),
+                )
+                .await
+            }
+            None => {
+                open_connection(
+                    tunnel_manager,
+                    &entry.project,
+                    &entry.profile,
+                    local_port.as_deref(),
+                    entry.database.as_deref(),
+                )
+                .await
+            }
+        }
+    })
+    .await;
+
+    crate::tray::refresh_tray(&app_handle);
+
+    Ok(results)
+}
+
 #[tauri::command]
 pub async fn disconnect(
     app_handle: AppHandle,
     tunnel_manager: State<'_, Arc<Mutex<TunnelManager>>>,
     connection_id: Option<String>,
 ) -> Result<(), AppError> {
-    let manager = tunnel_manager.lock().await;
-
-    if let Some(id) = connection_id {
-        manager.disconnect(&id).await?;
-    } else {
-        manager.disconnect_all().await?;
-    }
+    disconnect_connection(&tunnel_manager, connection_id.as_deref()).await?;
 
     // Refresh tray menu after disconnecting
     crate::tray::refresh_tray(&app_handle);
@@ -78,8 +193,7 @@ pub async fn disconnect_all(
     app_handle: AppHandle,
     tunnel_manager: State<'_, Arc<Mutex<TunnelManager>>>,
 ) -> Result<(), AppError> {
-    let manager = tunnel_manager.lock().await;
-    manager.disconnect_all().await?;
+    disconnect_connection(&tunnel_manager, None).await?;
 
     // Refresh tray menu after disconnecting all
     crate::tray::refresh_tray(&app_handle);
@@ -87,18 +201,44 @@ pub async fn disconnect_all(
     Ok(())
 }
 
+/// Connection strings / client config fragments for an active connection,
+/// optionally written to 0600 files that are removed on disconnect.
+#[tauri::command]
+pub async fn export_connection(
+    tunnel_manager: State<'_, Arc<Mutex<TunnelManager>>>,
+    connection_id: String,
+    formats: Vec<ExportFormat>,
+    write: bool,
+) -> Result<Vec<ConnectionExport>, AppError> {
+    let connections = list_connections(&tunnel_manager).await?;
+    let connection = connections
+        .iter()
+        .find(|c| c.id == connection_id)
+        .ok_or_else(|| AppError::General(format!("No active connection {}", connection_id)))?;
+    export::export_connection(
+        &connection.id,
+        &connection.connection_info,
+        &export::service_name(&connection.project_key, &connection.profile),
+        &formats,
+        write,
+    )
+}
+
 #[tauri::command]
 pub async fn get_active_connections_list(
     tunnel_manager: State<'_, Arc<Mutex<TunnelManager>>>,
 ) -> Result<Vec<ActiveConnectionInfo>, AppError> {
-    let manager = tunnel_manager.lock().await;
-    Ok(manager.get_active_connections().await)
+    list_connections(&tunnel_manager).await
 }
 
 #[tauri::command]
 pub async fn get_used_ports(
     tunnel_manager: State<'_, Arc<Mutex<TunnelManager>>>,
 ) -> Result<Vec<String>, AppError> {
+    #[cfg(unix)]
+    if let Some(client) = DaemonClient::connect_if_running().await {
+        return client.used_ports().await;
+    }
     let manager = tunnel_manager.lock().await;
     Ok(manager.get_used_ports().await)
 }
@@ -113,3 +253,105 @@ pub async fn sso_login(
     };
     sso::ensure_sso_session(&profile, &handler, None).await
 }
+
+/// Open a tunnel in the background daemon if one is running, otherwise in
+/// the in-process manager.
+async fn open_connection(
+    tunnel_manager: &Mutex<TunnelManager>,
+    project_key: &str,
+    profile: &str,
+    local_port: Option<&str>,
+    database: Option<&str>,
+) -> Result<(String, ConnectionInfo), AppError> {
+    #[cfg(unix)]
+    if let Some(client) = DaemonClient::connect_if_running().await {
+        return client
+            .connect_tunnel(project_key, profile, local_port, database)
+            .await;
+    }
+    // A handle, so concurrent connects (workspaces) don't queue on the lock;
+    // ports are kept apart by the manager's own reservations
+    let manager = tunnel_manager.lock().await.clone();
+
+    // Get currently used ports
+    let used_ports = manager.get_used_ports().await;
+
+    manager
+        .connect(project_key, profile, local_port, database, &used_ports)
+        .await
+}
+
+/// Open an ad-hoc forward in the background daemon if one is running,
+/// otherwise in the in-process manager.
+async fn open_forward(
+    tunnel_manager: &Mutex<TunnelManager>,
+    project_key: &str,
+    profile: &str,
+    remote_host: &str,
+    remote_port: u16,
+    local_port: Option<&str>,
+) -> Result<(String, ConnectionInfo), AppError> {
+    #[cfg(unix)]
+    if let Some(client) = DaemonClient::connect_if_running().await {
+        return client
+            .forward_tunnel(project_key, profile, remote_host, remote_port, local_port)
+            .await;
+    }
+    let manager = tunnel_manager.lock().await.clone();
+    let used_ports = manager.get_used_ports().await;
+    manager
+        .forward(project_key, profile, remote_host, remote_port, local_port, &used_ports)
+        .await
+}
+
+/// Active connections from the background daemon if one is running,
+/// otherwise from the in-process manager.
+pub(crate) async fn list_connections(
+    tunnel_manager: &Mutex<TunnelManager>,
+) -> Result<Vec<ActiveConnectionInfo>, AppError> {
+    #[cfg(unix)]
+    if let Some(client) = DaemonClient::connect_if_running().await {
+        return client.list().await;
+    }
+    let manager = tunnel_manager.lock().await;
+    Ok(manager.get_active_connections().await)
+}
+
+/// Disconnect one connection (or all when `connection_id` is `None`), via
+/// the background daemon if one is running.
+pub(crate) async fn disconnect_connection(
+    tunnel_manager: &Mutex<TunnelManager>,
+    connection_id: Option<&str>,
+) -> Result<(), AppError> {
+    #[cfg(unix)]
+    if let Some(client) = DaemonClient::connect_if_running().await {
+        return match connection_id {
+            Some(id) => client.disconnect(id).await,
+            None => client.disconnect_all().await,
+        };
+    }
+    let manager = tunnel_manager.lock().await;
+    match connection_id {
+        Some(id) => manager.disconnect(id).await,
+        None => manager.disconnect_all().await,
+    }
+}
+
+/// Re-emit background daemon events as Tauri events for the lifetime of the
+/// app, re-attaching whenever the daemon (re)starts.
+#[cfg(unix)]
+pub async fn relay_daemon_events(app_handle: AppHandle) {
+    let handler = crate::tunnel::manager::TauriTunnelHandler::new(app_handle.clone());
+    loop {
+        if let Some(client) = DaemonClient::connect_if_running().await
+            && let Ok(mut events) = client.into_events().await
+        {
+            crate::tray::refresh_tray(&app_handle);
+            while let Some(event) = events.next().await {
+                event.dispatch(&handler);
+            }
+            crate::tray::refresh_tray(&app_handle);
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(DAEMON_RETRY_SECS)).await;
+    }
+}
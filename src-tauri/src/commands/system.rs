@@ -377,10 +377,25 @@ pub async fn get_current_version() -> Result<String, AppError> {
     Ok(CURRENT_VERSION.to_string())
 }
 
+/// HTTPS links, and plain HTTP only to a local tunnel (`serviceType: "http"`).
+fn is_openable_url(url: &str) -> bool {
+    if url.starts_with("https://") {
+        return true;
+    }
+    let Some(rest) = url.strip_prefix("http://") else {
+        return false;
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit_once(':').map_or(authority, |(host, _)| host);
+    host == "localhost" || host.parse::<std::net::Ipv4Addr>().is_ok_and(|ip| ip.is_loopback())
+}
+
 #[tauri::command]
 pub async fn open_url(app_handle: AppHandle, url: String) -> Result<(), AppError> {
-    if !url.starts_with("https://") {
-        return Err(AppError::General("Only HTTPS URLs are allowed".to_string()));
+    if !is_openable_url(&url) {
+        return Err(AppError::General(
+            "Only HTTPS URLs and local tunnels are allowed".to_string(),
+        ));
     }
     use tauri_plugin_opener::OpenerExt;
     app_handle
@@ -534,10 +549,9 @@ pub async fn export_projects_file(app_handle: AppHandle) -> Result<String, AppEr
 
 #[tauri::command]
 pub async fn export_saved_connections(app_handle: AppHandle) -> Result<String, AppError> {
-    use crate::commands::saved::{load_saved_connections, SavedConnection};
     use tauri_plugin_dialog::DialogExt;
 
-    let connections: Vec<SavedConnection> = load_saved_connections(app_handle.clone()).await?;
+    let connections = crate::config::saved::load_saved_connections().await?;
 
     let (tx, rx) = tokio::sync::oneshot::channel();
     app_handle
@@ -570,9 +584,8 @@ pub async fn export_saved_connections(app_handle: AppHandle) -> Result<String, A
 
 #[tauri::command]
 pub async fn import_saved_connections(app_handle: AppHandle) -> Result<usize, AppError> {
-    use crate::commands::saved::SavedConnection;
+    use crate::config::saved::SavedConnection;
     use tauri_plugin_dialog::DialogExt;
-    use tauri_plugin_store::StoreExt;
 
     let (tx, rx) = tokio::sync::oneshot::channel();
     app_handle
@@ -605,36 +618,8 @@ pub async fn import_saved_connections(app_handle: AppHandle) -> Result<usize, Ap
         return Ok(0);
     }
 
-    // Load existing connections from store
-    let store = app_handle
-        .store("connections.json")
-        .map_err(|e| AppError::General(format!("Failed to open store: {}", e)))?;
-
-    let mut existing: Vec<SavedConnection> = store
-        .get("savedConnections")
-        .and_then(|v| serde_json::from_value(v).ok())
-        .unwrap_or_default();
-
-    // Merge: skip duplicates (same project_key + profile)
-    let mut added = 0usize;
-    for conn in imported {
-        let is_duplicate = existing
-            .iter()
-            .any(|e| e.project_key == conn.project_key && e.profile == conn.profile);
-        if !is_duplicate {
-            existing.push(conn);
-            added += 1;
-        }
-    }
-
-    store.set(
-        "savedConnections",
-        serde_json::to_value(&existing)
-            .map_err(|e| AppError::General(format!("Serialization error: {}", e)))?,
-    );
-    store
-        .save()
-        .map_err(|e| AppError::General(format!("Failed to save store: {}", e)))?;
+    let added = crate::config::saved::import_saved_connections(imported).await?;
+    crate::tray::refresh_tray(&app_handle);
 
     Ok(added)
 }
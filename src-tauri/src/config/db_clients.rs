@@ -0,0 +1,550 @@
+//! Saved connections written into desktop database clients: DBeaver's
+//! `data-sources.json`, DataGrip's `dataSources.xml` and a pgAdmin
+//! `servers.json` (import with *Tools > Import/Export Servers* or
+//! `setup.py load-servers`).
+//!
+//! Every entry carries a stable ID derived from project/profile/database, so
+//! re-running the export updates the entries it wrote earlier (e.g. after an
+//! `envPortMapping` change) instead of adding duplicates. Entries the user
+//! created by hand are left alone. Passwords are never written.
+
+use crate::config::preferences::{assign_loopback_alias, Preferences};
+use crate::config::projects::{get_local_port, ProjectConfig};
+use crate::config::saved::SavedConnection;
+use crate::error::AppError;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Prefix of the DBeaver connection keys and pgAdmin comments we own.
+const TAG: &str = "connection-app";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DbClient {
+    Dbeaver,
+    Datagrip,
+    Pgadmin,
+}
+
+impl DbClient {
+    pub fn name(self) -> &'static str {
+        match self {
+            DbClient::Dbeaver => "dbeaver",
+            DbClient::Datagrip => "datagrip",
+            DbClient::Pgadmin => "pgadmin",
+        }
+    }
+
+    /// Where the client's config lives when no path is given. DataGrip keeps
+    /// data sources per project (`.idea/dataSources.xml`), so it has none.
+    pub fn default_path(self) -> Option<PathBuf> {
+        match self {
+            DbClient::Dbeaver => {
+                let data_dir = if cfg!(target_os = "macos") {
+                    dirs::home_dir().map(|home| home.join("Library"))
+                } else {
+                    dirs::data_dir()
+                }?;
+                Some(
+                    data_dir
+                        .join("DBeaverData")
+                        .join("workspace6")
+                        .join("General")
+                        .join(".dbeaver")
+                        .join("data-sources.json"),
+                )
+            }
+            DbClient::Datagrip => None,
+            DbClient::Pgadmin => dirs::home_dir().map(|home| {
+                home.join(".connection-app").join("pgadmin-servers.json")
+            }),
+        }
+    }
+}
+
+impl FromStr for DbClient {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [DbClient::Dbeaver, DbClient::Datagrip, DbClient::Pgadmin]
+            .into_iter()
+            .find(|c| c.name() == s)
+            .ok_or_else(|| {
+                AppError::General(format!(
+                    "Unknown database client '{}' (expected dbeaver, datagrip or pgadmin)",
+                    s
+                ))
+            })
+    }
+}
+
+/// One saved connection as a GUI client sees it: `<host>:<port>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientDataSource {
+    /// Stable across exports; see [`data_source_id`].
+    pub id: String,
+    pub name: String,
+    /// Project display name, used as the folder / group.
+    pub group: String,
+    pub mysql: bool,
+    /// `localhost`, or the profile's loopback alias with `loopbackAlias`.
+    pub host: String,
+    pub port: String,
+    pub database: String,
+    /// Only known up front for IAM auth; secrets are fetched at connect time.
+    pub username: Option<String>,
+}
+
+impl ClientDataSource {
+    fn jdbc_url(&self) -> String {
+        let scheme = if self.mysql { "mysql" } else { "postgresql" };
+        format!("jdbc:{}://{}:{}/{}", scheme, self.host, self.port, self.database)
+    }
+}
+
+/// Outcome of exporting to one client.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DbClientExport {
+    pub client: DbClient,
+    pub path: String,
+    pub added: usize,
+    pub updated: usize,
+}
+
+/// UUID (v5-style) for a project/profile/database, so every client gets the
+/// same ID for the same tunnel on every export.
+pub fn data_source_id(project_key: &str, profile: &str, database: &str) -> String {
+    let digest = Sha1::digest(format!("{}/{}/{}/{}", TAG, project_key, profile, database));
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_sha1_bytes(bytes).into_uuid().to_string()
+}
+
+/// Database entries for every saved connection. Service projects (Redis,
+/// SSH, RDP, ...) and connections whose project no longer exists are skipped.
+/// Loopback aliases are assigned in `prefs` as a connect would; the caller
+/// saves them.
+pub fn client_data_sources(
+    saved: &[SavedConnection],
+    configs: &HashMap<String, ProjectConfig>,
+    prefs: &mut Preferences,
+) -> Vec<ClientDataSource> {
+    saved
+        .iter()
+        .filter_map(|connection| {
+            let config = configs.get(&connection.project_key)?;
+            if matches!(config.connection_type.as_str(), "service" | "eks" | "redis") {
+                return None;
+            }
+            let database = config
+                .effective_database(connection.database.as_deref())
+                .to_string();
+            let alias = if config.loopback_alias == Some(true) {
+                assign_loopback_alias(prefs, &connection.project_key, &connection.profile)
+            } else {
+                None
+            };
+            Some(ClientDataSource {
+                id: data_source_id(&connection.project_key, &connection.profile, &database),
+                name: connection.name.clone(),
+                group: config.name.clone(),
+                mysql: config.engine.as_deref() == Some("mysql"),
+                host: alias.map_or_else(|| "localhost".to_string(), |a| a.to_string()),
+                port: get_local_port(&connection.profile, config),
+                database,
+                username: config
+                    .iam_username
+                    .clone()
+                    .filter(|_| config.auth_type == "iam"),
+            })
+        })
+        .collect()
+}
+
+/// Add or update `sources` in the client's config file at `path` (or its
+/// default location). The previous file is kept as `<file>.bak`.
+pub async fn export_to_client(
+    client: DbClient,
+    path: Option<&Path>,
+    sources: &[ClientDataSource],
+) -> Result<DbClientExport, AppError> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let path = client.default_path().ok_or_else(|| {
+                AppError::Config(format!(
+                    "{} has no default config location; pass the path to its dataSources.xml",
+                    client.name()
+                ))
+            })?;
+            // A missing DBeaver workspace means DBeaver isn't installed (or
+            // uses a custom workspace) — don't create one out of nowhere.
+            if client == DbClient::Dbeaver
+                && !path.parent().is_some_and(|dir| dir.exists())
+            {
+                return Err(AppError::Config(format!(
+                    "DBeaver workspace not found at {}; pass the path to data-sources.json",
+                    path.display()
+                )));
+            }
+            path
+        }
+    };
+
+    let existing = match tokio::fs::read_to_string(&path).await {
+        Ok(data) => Some(data),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(AppError::Config(format!(
+                "Failed to read {}: {}",
+                path.display(),
+                e
+            )));
+        }
+    };
+
+    let (content, added, updated) = match client {
+        DbClient::Dbeaver => update_dbeaver(existing.as_deref(), sources)?,
+        DbClient::Datagrip => update_datagrip(existing.as_deref(), sources)?,
+        DbClient::Pgadmin => update_pgadmin(existing.as_deref(), sources)?,
+    };
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    if existing.is_some() {
+        tokio::fs::copy(&path, path.with_extension(backup_extension(&path))).await?;
+    }
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, content).await?;
+    tokio::fs::rename(&tmp, &path).await?;
+
+    Ok(DbClientExport {
+        client,
+        path: path.display().to_string(),
+        added,
+        updated,
+    })
+}
+
+/// `data-sources.json` -> `json.bak`
+fn backup_extension(path: &Path) -> String {
+    match path.extension() {
+        Some(ext) => format!("{}.bak", ext.to_string_lossy()),
+        None => "bak".to_string(),
+    }
+}
+
+fn parse_json_object(existing: Option<&str>, file: &str) -> Result<Map<String, Value>, AppError> {
+    match existing.map(serde_json::from_str::<Value>) {
+        None => Ok(Map::new()),
+        Some(Ok(Value::Object(root))) => Ok(root),
+        Some(Ok(_)) => Err(AppError::Config(format!("{} is not a JSON object", file))),
+        Some(Err(e)) => Err(AppError::Config(format!("Invalid {}: {}", file, e))),
+    }
+}
+
+/// Object member `key`, created empty if missing.
+fn object_entry<'a>(
+    parent: &'a mut Map<String, Value>,
+    key: &str,
+    file: &str,
+) -> Result<&'a mut Map<String, Value>, AppError> {
+    parent
+        .entry(key)
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| AppError::Config(format!("{}: \"{}\" is not an object", file, key)))
+}
+
+/// Merge `fields` into `target`, keeping anything else the user set there.
+fn merge(target: &mut Value, fields: Value) {
+    match (target, fields) {
+        (Value::Object(target), Value::Object(fields)) => {
+            for (key, value) in fields {
+                merge(target.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (target, fields) => *target = fields,
+    }
+}
+
+/// Connections are keyed `connection-app-<id>`; existing ones are merged so
+/// settings changed in DBeaver (colour, connection type, ...) survive.
+fn update_dbeaver(
+    existing: Option<&str>,
+    sources: &[ClientDataSource],
+) -> Result<(String, usize, usize), AppError> {
+    const FILE: &str = "data-sources.json";
+    let mut root = parse_json_object(existing, FILE)?;
+    let (mut added, mut updated) = (0, 0);
+
+    let folders = object_entry(&mut root, "folders", FILE)?;
+    for source in sources {
+        folders.entry(source.group.clone()).or_insert_with(|| json!({}));
+    }
+
+    let connections = object_entry(&mut root, "connections", FILE)?;
+    for source in sources {
+        let (provider, driver) = if source.mysql {
+            ("mysql", "mysql8")
+        } else {
+            ("postgresql", "postgres-jdbc")
+        };
+        let mut configuration = json!({
+            "host": source.host,
+            "port": source.port,
+            "database": source.database,
+            "url": source.jdbc_url(),
+            "auth-model": "native",
+        });
+        if let Some(ref username) = source.username {
+            configuration["user"] = json!(username);
+        }
+        let fields = json!({
+            "provider": provider,
+            "driver": driver,
+            "name": source.name,
+            "folder": source.group,
+            "save-password": false,
+            "configuration": configuration,
+        });
+
+        let key = format!("{}-{}", TAG, source.id);
+        match connections.get_mut(&key) {
+            Some(entry) => {
+                merge(entry, fields);
+                updated += 1;
+            }
+            None => {
+                connections.insert(key, fields);
+                added += 1;
+            }
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&Value::Object(root))?;
+    Ok((format!("{}\n", json), added, updated))
+}
+
+/// Servers are matched on a `connection-app:<id>` comment; new ones get the
+/// next free numeric key. pgAdmin has no MySQL support, so those are skipped.
+fn update_pgadmin(
+    existing: Option<&str>,
+    sources: &[ClientDataSource],
+) -> Result<(String, usize, usize), AppError> {
+    const FILE: &str = "servers.json";
+    let mut root = parse_json_object(existing, FILE)?;
+    let servers = object_entry(&mut root, "Servers", FILE)?;
+    let (mut added, mut updated) = (0, 0);
+
+    for source in sources.iter().filter(|s| !s.mysql) {
+        let comment = format!("{}:{}", TAG, source.id);
+        let fields = json!({
+            "Name": source.name,
+            "Group": source.group,
+            "Host": source.host,
+            "Port": source.port.parse::<u16>().unwrap_or(5432),
+            "MaintenanceDB": source.database,
+            "Username": source.username.clone().unwrap_or_default(),
+            "SSLMode": "prefer",
+            "Comment": comment,
+        });
+
+        let existing_key = servers
+            .iter()
+            .find(|(_, server)| server.get("Comment").and_then(Value::as_str) == Some(comment.as_str()))
+            .map(|(key, _)| key.clone());
+        match existing_key.and_then(|key| servers.get_mut(&key)) {
+            Some(server) => {
+                merge(server, fields);
+                updated += 1;
+            }
+            None => {
+                let next = servers
+                    .keys()
+                    .filter_map(|key| key.parse::<u64>().ok())
+                    .max()
+                    .unwrap_or(0)
+                    + 1;
+                servers.insert(next.to_string(), fields);
+                added += 1;
+            }
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&Value::Object(root))?;
+    Ok((format!("{}\n", json), added, updated))
+}
+
+const DATAGRIP_SKELETON: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<project version=\"4\">\n  \
+<component name=\"DataSourceManagerImpl\" format=\"xml\" multifile-model=\"true\">\n  \
+</component>\n\
+</project>\n";
+
+/// `<data-source>` elements are matched on their `uuid`. The file is edited
+/// as text so everything else in it is preserved byte for byte.
+fn update_datagrip(
+    existing: Option<&str>,
+    sources: &[ClientDataSource],
+) -> Result<(String, usize, usize), AppError> {
+    let mut content = existing.unwrap_or(DATAGRIP_SKELETON).to_string();
+    let (mut added, mut updated) = (0, 0);
+
+    for source in sources {
+        let element = datagrip_data_source(source);
+        let pattern = format!(
+            r#"(?s)[ \t]*<data-source\b[^>]*\buuid="{}"[^>]*>.*?</data-source>\n?"#,
+            regex::escape(&source.id)
+        );
+        let re = Regex::new(&pattern)
+            .map_err(|e| AppError::General(format!("Invalid pattern: {}", e)))?;
+        if let Some(found) = re.find(&content) {
+            content.replace_range(found.range(), &element);
+            updated += 1;
+            continue;
+        }
+
+        let manager = content
+            .find(r#"name="DataSourceManagerImpl""#)
+            .ok_or_else(|| {
+                AppError::Config(
+                    "dataSources.xml has no DataSourceManagerImpl component".to_string(),
+                )
+            })?;
+        let close = content[manager..]
+            .find("</component>")
+            .map(|offset| manager + offset)
+            .ok_or_else(|| AppError::Config("dataSources.xml is not well-formed".to_string()))?;
+        // Insert at the start of the closing tag's line to keep its indentation
+        let line_start = content[..close].rfind('\n').map_or(close, |i| i + 1);
+        let insert_at = if content[line_start..close].trim().is_empty() {
+            line_start
+        } else {
+            close
+        };
+        content.insert_str(insert_at, &element);
+        added += 1;
+    }
+
+    Ok((content, added, updated))
+}
+
+fn datagrip_data_source(source: &ClientDataSource) -> String {
+    let (driver_ref, jdbc_driver) = if source.mysql {
+        ("mysql.8", "com.mysql.cj.jdbc.Driver")
+    } else {
+        ("postgresql", "org.postgresql.Driver")
+    };
+    format!(
+        "    <data-source source=\"LOCAL\" name=\"{}\" group=\"{}\" uuid=\"{}\">\n      \
+         <driver-ref>{}</driver-ref>\n      \
+         <synchronize>true</synchronize>\n      \
+         <jdbc-driver>{}</jdbc-driver>\n      \
+         <jdbc-url>{}</jdbc-url>\n      \
+         <working-dir>$ProjectFileDir$</working-dir>\n    \
+         </data-source>\n",
+        xml_escape(&source.name),
+        xml_escape(&source.group),
+        source.id,
+        driver_ref,
+        jdbc_driver,
+        xml_escape(&source.jdbc_url()),
+    )
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(port: &str) -> ClientDataSource {
+        ClientDataSource {
+            id: data_source_id("app", "app-dev", "main"),
+            name: "App <dev>".to_string(),
+            group: "App".to_string(),
+            mysql: false,
+            host: "localhost".to_string(),
+            port: port.to_string(),
+            database: "main".to_string(),
+            username: None,
+        }
+    }
+
+    #[test]
+    fn test_data_source_id_is_stable() {
+        let id = data_source_id("app", "app-dev", "main");
+        assert_eq!(id, data_source_id("app", "app-dev", "main"));
+        assert_ne!(id, data_source_id("app", "app-prod", "main"));
+        assert_eq!(uuid::Uuid::parse_str(&id).unwrap().get_version_num(), 5);
+    }
+
+    #[test]
+    fn test_dbeaver_reexport_updates_in_place() {
+        let user_entry = r#"{"connections": {"postgres-jdbc-1": {"name": "mine"}}}"#;
+        let (first, added, _) = update_dbeaver(Some(user_entry), &[source("5432")]).unwrap();
+        assert_eq!(added, 1);
+
+        // Simulate a setting changed in DBeaver, then a port mapping change
+        let mut edited: Value = serde_json::from_str(&first).unwrap();
+        let key = format!("connection-app-{}", source("5432").id);
+        edited["connections"][&key]["type"] = json!("prod");
+        let (second, added, updated) =
+            update_dbeaver(Some(&edited.to_string()), &[source("5440")]).unwrap();
+        assert_eq!((added, updated), (0, 1));
+
+        let root: Value = serde_json::from_str(&second).unwrap();
+        let connections = root["connections"].as_object().unwrap();
+        assert_eq!(connections.len(), 2);
+        assert_eq!(connections["postgres-jdbc-1"]["name"], "mine");
+        assert_eq!(connections[&key]["type"], "prod");
+        assert_eq!(connections[&key]["configuration"]["port"], "5440");
+        assert_eq!(
+            connections[&key]["configuration"]["url"],
+            "jdbc:postgresql://localhost:5440/main"
+        );
+        assert!(root["folders"]["App"].is_object());
+    }
+
+    #[test]
+    fn test_pgadmin_reexport_updates_in_place() {
+        let user_entry = r#"{"Servers": {"1": {"Name": "mine", "Port": 5432}}}"#;
+        let (first, added, _) = update_pgadmin(Some(user_entry), &[source("5432")]).unwrap();
+        assert_eq!(added, 1);
+        let (second, added, updated) = update_pgadmin(Some(&first), &[source("5440")]).unwrap();
+        assert_eq!((added, updated), (0, 1));
+
+        let root: Value = serde_json::from_str(&second).unwrap();
+        let servers = root["Servers"].as_object().unwrap();
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers["1"]["Name"], "mine");
+        assert_eq!(servers["2"]["Port"], 5440);
+        assert_eq!(servers["2"]["MaintenanceDB"], "main");
+    }
+
+    #[test]
+    fn test_datagrip_reexport_updates_in_place() {
+        let (first, added, _) = update_datagrip(None, &[source("5432")]).unwrap();
+        assert_eq!(added, 1);
+        assert!(first.contains("name=\"App &lt;dev&gt;\""));
+
+        let (second, added, updated) = update_datagrip(Some(&first), &[source("5440")]).unwrap();
+        assert_eq!((added, updated), (0, 1));
+        assert_eq!(second.matches("<data-source ").count(), 1);
+        assert!(second.contains("<jdbc-url>jdbc:postgresql://localhost:5440/main</jdbc-url>"));
+        assert!(second.trim_end().ends_with("</component>\n</project>"));
+    }
+}
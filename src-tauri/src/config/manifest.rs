@@ -0,0 +1,380 @@
+use crate::error::AppError;
+use crate::tunnel::manager::{parse_forward_target, ConnectionInfo};
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+/// A set of tunnels brought up together (`connection-app-cli up tunnels.toml`,
+/// or a "workspace" in the desktop app).
+///
+/// ```toml
+/// [[tunnel]]
+/// name = "primary"
+/// project = "my-app"
+/// profile = "my-app-dev"
+/// database = "app"
+/// local_port = 5432
+///
+/// [[tunnel]]
+/// name = "redis"
+/// project = "my-app"
+/// profile = "my-app-dev"
+/// to = "10.0.3.17:6379"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TunnelManifest {
+    #[serde(rename = "tunnel", default)]
+    pub tunnels: Vec<ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestEntry {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub project: String,
+    pub profile: String,
+    #[serde(default)]
+    pub database: Option<String>,
+    /// Defaults to the project's port for the profile (see `get_local_port`).
+    #[serde(default)]
+    pub local_port: Option<u16>,
+    /// Ad-hoc `host:port` through the project's bastion instead of the
+    /// project's own target.
+    #[serde(default)]
+    pub to: Option<String>,
+}
+
+impl ManifestEntry {
+    /// `name`, or `project/profile` when unnamed.
+    pub fn label(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{}/{}", self.project, self.profile))
+    }
+}
+
+/// Outcome of opening one manifest entry.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestTunnelResult {
+    pub name: String,
+    pub project: String,
+    pub profile: String,
+    #[serde(rename = "connectionId", skip_serializing_if = "Option::is_none")]
+    pub connection_id: Option<String>,
+    #[serde(rename = "connectionInfo", skip_serializing_if = "Option::is_none")]
+    pub connection_info: Option<ConnectionInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ManifestTunnelResult {
+    pub fn new(
+        entry: &ManifestEntry,
+        result: Result<(String, ConnectionInfo), AppError>,
+    ) -> Self {
+        let (connection_id, connection_info, error) = match result {
+            Ok((id, info)) => (Some(id), Some(info), None),
+            Err(e) => (None, None, Some(e.to_string())),
+        };
+        Self {
+            name: entry.label(),
+            project: entry.project.clone(),
+            profile: entry.profile.clone(),
+            connection_id,
+            connection_info,
+            error,
+        }
+    }
+}
+
+pub async fn load_manifest(path: &Path) -> Result<TunnelManifest, AppError> {
+    let data = tokio::fs::read_to_string(path).await.map_err(|e| {
+        AppError::Config(format!("Failed to read {}: {}", path.display(), e))
+    })?;
+    parse_manifest(&data)
+}
+
+pub fn parse_manifest(data: &str) -> Result<TunnelManifest, AppError> {
+    let manifest: TunnelManifest = toml::from_str(data)
+        .map_err(|e| AppError::Config(format!("Invalid tunnel manifest: {}", e)))?;
+    validate_manifest(&manifest)?;
+    Ok(manifest)
+}
+
+fn validate_manifest(manifest: &TunnelManifest) -> Result<(), AppError> {
+    if manifest.tunnels.is_empty() {
+        return Err(AppError::Config(
+            "Tunnel manifest has no [[tunnel]] entries".to_string(),
+        ));
+    }
+
+    let mut ports = HashSet::new();
+    for entry in &manifest.tunnels {
+        let label = entry.label();
+        if entry.project.is_empty() || entry.profile.is_empty() {
+            return Err(AppError::Config(format!(
+                "Tunnel '{}': project and profile are required",
+                label
+            )));
+        }
+        if let Some(ref to) = entry.to {
+            if entry.database.is_some() {
+                return Err(AppError::Config(format!(
+                    "Tunnel '{}': database cannot be combined with to",
+                    label
+                )));
+            }
+            parse_forward_target(to)
+                .map_err(|e| AppError::Config(format!("Tunnel '{}': {}", label, e)))?;
+        }
+        if let Some(port) = entry.local_port
+            && !ports.insert(port)
+        {
+            return Err(AppError::Config(format!(
+                "Tunnel '{}': local_port {} is used by another tunnel",
+                label, port
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Open every entry concurrently with `open`. Entries with a pinned
+/// `local_port` go first, so entries left on their default port step around
+/// them. Results are in manifest order.
+pub async fn open_manifest<F, Fut>(
+    manifest: &TunnelManifest,
+    open: F,
+) -> Vec<ManifestTunnelResult>
+where
+    F: Fn(ManifestEntry) -> Fut,
+    Fut: Future<Output = Result<(String, ConnectionInfo), AppError>>,
+{
+    let mut results: Vec<Option<ManifestTunnelResult>> = vec![None; manifest.tunnels.len()];
+    for pinned in [true, false] {
+        let wave: Vec<usize> = (0..manifest.tunnels.len())
+            .filter(|&i| manifest.tunnels[i].local_port.is_some() == pinned)
+            .collect();
+        let opened = join_all(wave.iter().map(|&i| open(manifest.tunnels[i].clone()))).await;
+        for (i, result) in wave.into_iter().zip(opened) {
+            results[i] = Some(ManifestTunnelResult::new(&manifest.tunnels[i], result));
+        }
+    }
+    results.into_iter().flatten().collect()
+}
+
+/// Daemon tunnels that `up` opened from one manifest, so `down` closes those
+/// and leaves other tunnels to the same projects alone.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct ManifestState {
+    manifest: PathBuf,
+    #[serde(rename = "connectionIds")]
+    connection_ids: Vec<String>,
+}
+
+fn manifest_states_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".connection-app")
+        .join("manifests")
+}
+
+/// One state file per manifest, named after a hash of its canonical path.
+fn manifest_state_path(dir: &Path, manifest: &Path) -> PathBuf {
+    let manifest = std::fs::canonicalize(manifest).unwrap_or_else(|_| manifest.to_path_buf());
+    let digest = Sha256::digest(manifest.to_string_lossy().as_bytes());
+    let name: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    dir.join(format!("{}.json", name))
+}
+
+/// Remember the connection IDs `up` opened in the daemon for `manifest`,
+/// alongside any from an earlier `up` that hasn't been brought down.
+pub async fn record_manifest_tunnels(
+    manifest: &Path,
+    connection_ids: &[String],
+) -> Result<(), AppError> {
+    record_tunnels_in(&manifest_states_dir(), manifest, connection_ids).await
+}
+
+/// The connection IDs recorded for `manifest`, forgetting them.
+pub async fn take_manifest_tunnels(manifest: &Path) -> Vec<String> {
+    take_tunnels_in(&manifest_states_dir(), manifest).await
+}
+
+async fn record_tunnels_in(
+    dir: &Path,
+    manifest: &Path,
+    connection_ids: &[String],
+) -> Result<(), AppError> {
+    let path = manifest_state_path(dir, manifest);
+    let mut state = read_state(&path).await.unwrap_or_default();
+    state.manifest = manifest.to_path_buf();
+    for id in connection_ids {
+        if !state.connection_ids.contains(id) {
+            state.connection_ids.push(id.clone());
+        }
+    }
+
+    tokio::fs::create_dir_all(dir).await?;
+    // Write then rename, so a crash never leaves a truncated file behind
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, format!("{}\n", serde_json::to_string_pretty(&state)?)).await?;
+    tokio::fs::rename(&tmp, &path).await?;
+    Ok(())
+}
+
+async fn take_tunnels_in(dir: &Path, manifest: &Path) -> Vec<String> {
+    let path = manifest_state_path(dir, manifest);
+    let Some(state) = read_state(&path).await else {
+        return Vec::new();
+    };
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        log::warn!("Failed to remove {}: {}", path.display(), e);
+    }
+    state.connection_ids
+}
+
+async fn read_state(path: &Path) -> Option<ManifestState> {
+    let data = tokio::fs::read_to_string(path).await.ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = parse_manifest(
+            r#"
+            [[tunnel]]
+            name = "primary"
+            project = "app"
+            profile = "app-dev"
+            database = "main"
+            local_port = 5432
+
+            [[tunnel]]
+            project = "app"
+            profile = "app-dev"
+            to = "10.0.3.17:6379"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.tunnels.len(), 2);
+        assert_eq!(manifest.tunnels[0].label(), "primary");
+        assert_eq!(manifest.tunnels[0].local_port, Some(5432));
+        assert_eq!(manifest.tunnels[1].label(), "app/app-dev");
+        assert_eq!(manifest.tunnels[1].to.as_deref(), Some("10.0.3.17:6379"));
+    }
+
+    #[test]
+    fn test_rejects_invalid_manifests() {
+        for (data, expected) in [
+            ("", "no [[tunnel]] entries"),
+            (
+                "[[tunnel]]\nproject = \"app\"\nprofile = \"dev\"\nlocal_port = 1\n\
+                 [[tunnel]]\nproject = \"app\"\nprofile = \"prod\"\nlocal_port = 1\n",
+                "used by another tunnel",
+            ),
+            (
+                "[[tunnel]]\nproject = \"app\"\nprofile = \"dev\"\nto = \"bad host:80\"\n",
+                "Invalid remote host",
+            ),
+            (
+                "[[tunnel]]\nproject = \"app\"\nprofile = \"dev\"\nto = \"db:5432\"\ndatabase = \"x\"\n",
+                "cannot be combined",
+            ),
+            (
+                "[[tunnel]]\nproject = \"app\"\nprofile = \"dev\"\nport = 1\n",
+                "Invalid tunnel manifest",
+            ),
+        ] {
+            let err = parse_manifest(data).unwrap_err().to_string();
+            assert!(err.contains(expected), "{expected}: {err}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_manifest_opens_pinned_ports_first() {
+        let manifest = parse_manifest(
+            "[[tunnel]]\nname = \"a\"\nproject = \"app\"\nprofile = \"dev\"\n\
+             [[tunnel]]\nname = \"b\"\nproject = \"app\"\nprofile = \"dev\"\nlocal_port = 5432\n\
+             [[tunnel]]\nname = \"c\"\nproject = \"missing\"\nprofile = \"dev\"\n",
+        )
+        .unwrap();
+
+        let order = Mutex::new(Vec::new());
+        let results = open_manifest(&manifest, |entry| {
+            order.lock().unwrap().push(entry.label());
+            let result = if entry.project == "missing" {
+                Err(AppError::Config("Unknown project: missing".to_string()))
+            } else {
+                Ok((format!("conn_{}", entry.label()), test_info()))
+            };
+            async move { result }
+        })
+        .await;
+
+        assert_eq!(*order.lock().unwrap(), vec!["b", "a", "c"]);
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert_eq!(results[0].connection_id.as_deref(), Some("conn_a"));
+        assert!(results[2].error.as_deref().unwrap().contains("Unknown project"));
+    }
+
+    #[tokio::test]
+    async fn test_manifest_state_records_only_opened_tunnels() {
+        let dir = std::env::temp_dir().join(format!(
+            "connection-app-manifests-{}",
+            &uuid::Uuid::new_v4().to_string()[..8]
+        ));
+        let manifest = Path::new("/projects/app/tunnels.toml");
+        let other = Path::new("/projects/other/tunnels.toml");
+
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        record_tunnels_in(&dir, manifest, &ids(&["conn_a"]))
+            .await
+            .unwrap();
+        record_tunnels_in(&dir, manifest, &ids(&["conn_a", "conn_b"]))
+            .await
+            .unwrap();
+        record_tunnels_in(&dir, other, &ids(&["conn_c"]))
+            .await
+            .unwrap();
+
+        let taken = take_tunnels_in(&dir, manifest).await;
+        assert_eq!(taken, vec!["conn_a", "conn_b"]);
+        // Taken once: a second `down` has nothing left to close
+        assert!(take_tunnels_in(&dir, manifest).await.is_empty());
+        assert_eq!(take_tunnels_in(&dir, other).await, vec!["conn_c"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn test_info() -> ConnectionInfo {
+        ConnectionInfo {
+            host: "localhost".to_string(),
+            port: "5432".to_string(),
+            connection_type: "rds".to_string(),
+            username: None,
+            password: None,
+            database: None,
+            rds_endpoint: None,
+            instance_id: None,
+            service_type: None,
+            remote_host: None,
+            target_type: None,
+            engine: None,
+            ssh_command: None,
+            kubeconfig: None,
+            auth_proxy: false,
+        }
+    }
+}
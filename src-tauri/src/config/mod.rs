@@ -1,4 +1,7 @@
 pub mod aws_config;
+pub mod db_clients;
+pub mod manifest;
 pub mod preferences;
 pub mod projects;
+pub mod saved;
 pub mod validation;
@@ -1,14 +1,26 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct Preferences {
     /// Map of "project_key:profile" → preferred bastion instance ID
     #[serde(default)]
     pub bastion_preferences: HashMap<String, String>,
+    /// Map of "project_key:profile" → loopback address (127.0.0.N) for
+    /// projects with `loopbackAlias`, kept so each keeps its own address
+    #[serde(default)]
+    pub loopback_aliases: HashMap<String, String>,
 }
 
+/// Held for every read-modify-write of the preferences file, so tunnels
+/// connecting at the same time in this process don't overwrite each other's
+/// changes. Other processes (the GUI, the daemon and the CLI all update the
+/// file) are kept out by [`lock_preferences_file`].
+static PREFERENCES_LOCK: Mutex<()> = Mutex::const_new(());
+
 fn preferences_path() -> PathBuf {
     dirs::home_dir()
         .unwrap_or_else(|| PathBuf::from("."))
@@ -17,17 +29,69 @@ fn preferences_path() -> PathBuf {
 }
 
 pub async fn load_preferences() -> Preferences {
-    let path = preferences_path();
+    load_from(&preferences_path()).await
+}
 
-    match tokio::fs::read_to_string(&path).await {
+/// Load the preferences, apply `update` and save them if anything changed,
+/// all under [`PREFERENCES_LOCK`] and the preferences lock file.
+pub async fn update_preferences<T>(update: impl FnOnce(&mut Preferences) -> T) -> T {
+    update_at(&preferences_path(), update).await
+}
+
+async fn update_at<T>(path: &Path, update: impl FnOnce(&mut Preferences) -> T) -> T {
+    let _guard = PREFERENCES_LOCK.lock().await;
+    let _file_lock = lock_preferences_file(path).await;
+    let mut prefs = load_from(path).await;
+    let before = prefs.clone();
+    let result = update(&mut prefs);
+    if prefs != before {
+        save_to(path, &prefs).await;
+    }
+    result
+}
+
+/// Take an exclusive advisory lock on `preferences.json.lock`, released when
+/// the returned file is dropped. The lock can't be on `preferences.json`
+/// itself, which [`save_to`] replaces. If the lock file can't be opened the
+/// update goes ahead unlocked.
+async fn lock_preferences_file(path: &Path) -> Option<std::fs::File> {
+    let lock_path = path.with_extension("json.lock");
+    let locked = tokio::task::spawn_blocking(move || {
+        if let Some(parent) = lock_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+        file.lock()?;
+        Ok::<_, std::io::Error>(file)
+    })
+    .await;
+    match locked {
+        Ok(Ok(file)) => Some(file),
+        Ok(Err(e)) => {
+            log::warn!("Failed to lock preferences: {}", e);
+            None
+        }
+        Err(e) => {
+            log::warn!("Failed to lock preferences: {}", e);
+            None
+        }
+    }
+}
+
+async fn load_from(path: &Path) -> Preferences {
+    match tokio::fs::read_to_string(path).await {
         Ok(data) => serde_json::from_str(&data).unwrap_or_default(),
         Err(_) => Preferences::default(),
     }
 }
 
-pub async fn save_preferences(prefs: &Preferences) {
-    let path = preferences_path();
-
+/// Write to a temporary file and rename it over the old one, so readers
+/// (including other processes) never see a partly written file.
+async fn save_to(path: &Path, prefs: &Preferences) {
     if let Some(parent) = path.parent()
         && let Err(e) = tokio::fs::create_dir_all(parent).await
     {
@@ -35,15 +99,21 @@ pub async fn save_preferences(prefs: &Preferences) {
         return;
     }
 
-    match serde_json::to_string_pretty(prefs) {
-        Ok(json) => {
-            if let Err(e) = tokio::fs::write(&path, format!("{}\n", json)).await {
-                log::warn!("Failed to write preferences: {}", e);
-            }
-        }
+    let json = match serde_json::to_string_pretty(prefs) {
+        Ok(json) => json,
         Err(e) => {
             log::warn!("Failed to serialize preferences: {}", e);
+            return;
         }
+    };
+    let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    if let Err(e) = tokio::fs::write(&tmp, format!("{}\n", json)).await {
+        log::warn!("Failed to write preferences: {}", e);
+        return;
+    }
+    if let Err(e) = tokio::fs::rename(&tmp, path).await {
+        log::warn!("Failed to write preferences: {}", e);
+        let _ = tokio::fs::remove_file(&tmp).await;
     }
 }
 
@@ -67,3 +137,118 @@ pub fn set_preferred_bastion(
         .bastion_preferences
         .insert(key, instance_id.to_string());
 }
+
+/// The loopback address assigned to `project_key:profile`, assigning the
+/// lowest free 127.0.0.N (N ≥ 2) on first use. `None` once all are taken.
+pub fn assign_loopback_alias(
+    prefs: &mut Preferences,
+    project_key: &str,
+    profile: &str,
+) -> Option<Ipv4Addr> {
+    let key = format!("{}:{}", project_key, profile);
+    if let Some(address) = prefs
+        .loopback_aliases
+        .get(&key)
+        .and_then(|address| address.parse::<Ipv4Addr>().ok())
+    {
+        return Some(address);
+    }
+    let taken: Vec<Ipv4Addr> = prefs
+        .loopback_aliases
+        .values()
+        .filter_map(|address| address.parse().ok())
+        .collect();
+    let address = (2..=254u8)
+        .map(|n| Ipv4Addr::new(127, 0, 0, n))
+        .find(|address| !taken.contains(address))?;
+    prefs.loopback_aliases.insert(key, address.to_string());
+    Some(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_aliases_are_stable_and_distinct() {
+        let mut prefs = Preferences::default();
+        let first = assign_loopback_alias(&mut prefs, "billing", "acme-prod");
+        let second = assign_loopback_alias(&mut prefs, "billing", "acme-dev");
+        assert_eq!(first, Some(Ipv4Addr::new(127, 0, 0, 2)));
+        assert_eq!(second, Some(Ipv4Addr::new(127, 0, 0, 3)));
+        assert_eq!(
+            assign_loopback_alias(&mut prefs, "billing", "acme-prod"),
+            first
+        );
+
+        // A freed address is reused
+        prefs.loopback_aliases.remove("billing:acme-prod");
+        assert_eq!(
+            assign_loopback_alias(&mut prefs, "orders", "acme-prod"),
+            first
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_updates_are_not_lost() {
+        let dir = std::env::temp_dir().join(format!(
+            "connection-app-prefs-{}",
+            &uuid::Uuid::new_v4().to_string()[..8]
+        ));
+        let path = dir.join("preferences.json");
+
+        let updates = (0..16).map(|i| {
+            let path = path.clone();
+            tokio::spawn(async move {
+                update_at(&path, |prefs| {
+                    assign_loopback_alias(prefs, "billing", &format!("profile-{}", i))
+                })
+                .await
+            })
+        });
+        let mut assigned: Vec<Ipv4Addr> = futures_util::future::join_all(updates)
+            .await
+            .into_iter()
+            .map(|result| result.unwrap().unwrap())
+            .collect();
+        assigned.sort();
+        assigned.dedup();
+        assert_eq!(assigned.len(), 16);
+        assert_eq!(load_from(&path).await.loopback_aliases.len(), 16);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn updates_wait_for_other_holders_of_the_lock_file() {
+        let dir = std::env::temp_dir().join(format!(
+            "connection-app-prefs-{}",
+            &uuid::Uuid::new_v4().to_string()[..8]
+        ));
+        let path = dir.join("preferences.json");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Another process mid-update
+        let other = std::fs::File::create(dir.join("preferences.json.lock")).unwrap();
+        other.lock().unwrap();
+        let update = update_at(&path, |prefs| {
+            assign_loopback_alias(prefs, "billing", "acme-prod")
+        });
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(200), update)
+                .await
+                .is_err()
+        );
+        assert!(!path.exists());
+
+        drop(other);
+        let assigned = update_at(&path, |prefs| {
+            assign_loopback_alias(prefs, "billing", "acme-prod")
+        })
+        .await;
+        assert_eq!(assigned, Some(Ipv4Addr::new(127, 0, 0, 2)));
+        assert_eq!(load_from(&path).await.loopback_aliases.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
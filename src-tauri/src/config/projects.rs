@@ -36,7 +36,7 @@ pub struct ProjectConfig {
     #[serde(rename = "bastionPattern", default)]
     pub bastion_pattern: Option<String>,
 
-    // Connection type: "rds" (default) or "service"
+    // Connection type: "rds" (default), "service", "eks" or "redis"
     #[serde(rename = "connectionType", default = "default_connection_type")]
     pub connection_type: String,
 
@@ -53,6 +53,38 @@ pub struct ProjectConfig {
     pub ecs_cluster: Option<String>,
     #[serde(rename = "ecsService", default)]
     pub ecs_service: Option<String>,
+    /// serviceType "socks": destinations the proxy may reach, as CIDRs or
+    /// domain suffixes. Unset allows any destination.
+    #[serde(rename = "socksAllow", default, skip_serializing_if = "Option::is_none")]
+    pub socks_allow: Option<Vec<String>>,
+    /// serviceType "http"/"sigv4-http": the site's host name, sent as `Host`
+    /// and TLS SNI
+    #[serde(rename = "httpHost", default, skip_serializing_if = "Option::is_none")]
+    pub http_host: Option<String>,
+    /// serviceType "http"/"sigv4-http": speak HTTPS to the site (default:
+    /// remotePort 443)
+    #[serde(rename = "httpTls", default, skip_serializing_if = "Option::is_none")]
+    pub http_tls: Option<bool>,
+    /// serviceType "http": accept the site's certificate without checking it
+    /// against the system roots (default: false), for private CAs
+    #[serde(rename = "httpTlsSkipVerify", default, skip_serializing_if = "Option::is_none")]
+    pub http_tls_skip_verify: Option<bool>,
+    /// serviceType "sigv4-http": the SigV4 signing name (`es`, `execute-api`, ...)
+    #[serde(rename = "sigv4Service", default, skip_serializing_if = "Option::is_none")]
+    pub sigv4_service: Option<String>,
+
+    // EKS-specific fields (used when connectionType == "eks")
+    /// Cluster name; its private API endpoint and CA come from DescribeCluster
+    #[serde(rename = "eksCluster", default, skip_serializing_if = "Option::is_none")]
+    pub eks_cluster: Option<String>,
+
+    // Redis-specific fields (used when connectionType == "redis")
+    /// ElastiCache replication group ID or MemoryDB cluster name
+    #[serde(rename = "redisCluster", default, skip_serializing_if = "Option::is_none")]
+    pub redis_cluster: Option<String>,
+    /// "elasticache" (default) or "memorydb"
+    #[serde(rename = "redisService", default, skip_serializing_if = "Option::is_none")]
+    pub redis_service: Option<String>,
 
     // Custom secret path fields
     /// Direct secret ARN or name (bypasses prefix-based search)
@@ -77,11 +109,35 @@ pub struct ProjectConfig {
     #[serde(default)]
     pub multiplexed: Option<bool>,
 
+    /// Answer the database login locally and authenticate upstream with the
+    /// secret / IAM token, so clients connect without a password
+    #[serde(rename = "authProxy", default)]
+    pub auth_proxy: Option<bool>,
+    /// Profile suffixes (matched like envPortMapping keys) whose tunnels get
+    /// the read-only guard; needs authProxy
+    #[serde(rename = "readOnlyEnvs", default, skip_serializing_if = "Option::is_none")]
+    pub read_only_envs: Option<Vec<String>>,
+    /// Bind each profile's tunnel to its own loopback address (127.0.0.N)
+    /// instead of 127.0.0.1, so tunnels can share a port
+    #[serde(rename = "loopbackAlias", default)]
+    pub loopback_alias: Option<bool>,
+
     // SSH-specific fields (used when serviceType == "ssh")
     #[serde(rename = "sshUsername", default)]
     pub ssh_username: Option<String>,
     #[serde(rename = "sshKeyPath", default)]
     pub ssh_key_path: Option<String>,
+    /// "file" (default) uses `sshKeyPath`; "instance-connect" pushes an
+    /// ephemeral key with EC2 Instance Connect for each connection.
+    #[serde(rename = "sshKeyMode", default)]
+    pub ssh_key_mode: Option<String>,
+
+    // Launch hooks: command templates run when a tunnel is ready / closes
+    /// e.g. `psql {{uri}}` or `open vnc://localhost:{{port}}`
+    #[serde(rename = "onConnect", default)]
+    pub on_connect: Option<String>,
+    #[serde(rename = "onDisconnect", default)]
+    pub on_disconnect: Option<String>,
 }
 
 pub const DEFAULT_BASTION_PATTERN: &str = "*bastion*";
@@ -263,6 +319,15 @@ pub fn get_local_port(profile: &str, project_config: &ProjectConfig) -> String {
     }
 }
 
+/// Whether `profile` matches one of the project's `readOnlyEnvs` suffixes.
+pub fn is_read_only_env(profile: &str, project_config: &ProjectConfig) -> bool {
+    project_config
+        .read_only_envs
+        .iter()
+        .flatten()
+        .any(|suffix| !suffix.is_empty() && profile.ends_with(suffix.as_str()))
+}
+
 /// Get the default RDS port for the project's database engine.
 pub fn get_default_port_for_engine(project_config: &ProjectConfig) -> String {
     match project_config.engine.as_deref() {
@@ -296,14 +361,28 @@ mod tests {
             target_pattern: None,
             ecs_cluster: None,
             ecs_service: None,
+            socks_allow: None,
+            http_host: None,
+            http_tls: None,
+            http_tls_skip_verify: None,
+            sigv4_service: None,
+            eks_cluster: None,
+            redis_cluster: None,
+            redis_service: None,
             secret_path: None,
             secret_username_field: None,
             secret_password_field: None,
             auth_type: "secrets".to_string(),
             iam_username: None,
             multiplexed: None,
+            auth_proxy: None,
+            read_only_envs: None,
+            loopback_alias: None,
             ssh_username: None,
             ssh_key_path: None,
+            ssh_key_mode: None,
+            on_connect: None,
+            on_disconnect: None,
         }
     }
 
@@ -369,4 +448,16 @@ mod tests {
         assert_eq!(get_local_port("covered-dev", &config), "5432");
         assert_eq!(get_local_port("covered", &config), "5460");
     }
+
+    #[test]
+    fn read_only_envs_match_profile_suffixes() {
+        let mut config = test_config(None);
+        assert!(!is_read_only_env("acme-prod", &config));
+
+        config.read_only_envs = Some(vec!["prod".to_string()]);
+        assert!(is_read_only_env("acme-prod", &config));
+        assert!(is_read_only_env("prod", &config));
+        assert!(!is_read_only_env("acme-dev", &config));
+        assert!(!is_read_only_env("prod-acme", &config));
+    }
 }
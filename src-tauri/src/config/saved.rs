@@ -0,0 +1,411 @@
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Bundle identifier from `tauri.conf.json`; the store plugin kept its files
+/// in the app data directory named after it.
+const APP_IDENTIFIER: &str = "com.connection-app.desktop";
+const PLUGIN_STORE_FILE: &str = "connections.json";
+const SAVED_CONNECTIONS_KEY: &str = "savedConnections";
+
+/// Serializes read-modify-write cycles within this process.
+static WRITE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedConnection {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "projectKey")]
+    pub project_key: String,
+    pub profile: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+fn saved_connections_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".connection-app")
+        .join("saved-connections.json")
+}
+
+/// Path of the old Tauri plugin store (`<app data dir>/<identifier>/connections.json`).
+fn plugin_store_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(APP_IDENTIFIER)
+        .join(PLUGIN_STORE_FILE)
+}
+
+/// Milliseconds since the epoch, as stored in `lastUsedAt`.
+fn now_millis() -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let duration = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}", duration.as_secs() * 1000)
+}
+
+/// Copy saved connections out of the GUI's plugin store into
+/// `~/.connection-app/saved-connections.json` if that file doesn't exist yet.
+/// The plugin store is left in place.
+pub fn migrate_plugin_store() {
+    let path = saved_connections_path();
+    let store_path = plugin_store_path();
+    if path.exists() || !store_path.exists() {
+        return;
+    }
+
+    let connections = match std::fs::read_to_string(&store_path) {
+        Ok(data) => parse_plugin_store(&data),
+        Err(e) => {
+            log::warn!("Failed to read {}: {}", store_path.display(), e);
+            return;
+        }
+    };
+    let json = match serde_json::to_string_pretty(&connections) {
+        Ok(json) => json,
+        Err(e) => {
+            log::warn!("Failed to serialize saved connections: {}", e);
+            return;
+        }
+    };
+    if let Some(parent) = path.parent()
+        && let Err(e) = std::fs::create_dir_all(parent)
+    {
+        log::warn!("Failed to create {}: {}", parent.display(), e);
+        return;
+    }
+    if let Err(e) = std::fs::write(&path, format!("{}\n", json)) {
+        log::warn!("Failed to write {}: {}", path.display(), e);
+        return;
+    }
+    log::info!(
+        "Migrated {} saved connections from {}",
+        connections.len(),
+        store_path.display()
+    );
+}
+
+fn parse_plugin_store(data: &str) -> Vec<SavedConnection> {
+    serde_json::from_str::<serde_json::Value>(data)
+        .ok()
+        .and_then(|mut store| store.get_mut(SAVED_CONNECTIONS_KEY).map(|v| v.take()))
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+pub async fn load_saved_connections() -> Result<Vec<SavedConnection>, AppError> {
+    let path = saved_connections_path();
+
+    match tokio::fs::read_to_string(&path).await {
+        Ok(data) => serde_json::from_str(&data).map_err(|e| {
+            AppError::Config(format!("Invalid saved connections file: {}", e))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(AppError::Config(format!(
+            "Failed to read saved connections: {}",
+            e
+        ))),
+    }
+}
+
+/// Write via a temp file + rename so the GUI and CLI never see a partial file.
+async fn save_all(connections: &[SavedConnection]) -> Result<(), AppError> {
+    let path = saved_connections_path();
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await.map_err(|e| {
+            AppError::Config(format!("Failed to create config directory: {}", e))
+        })?;
+    }
+
+    let json = serde_json::to_string_pretty(connections)?;
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, format!("{}\n", json))
+        .await
+        .map_err(|e| AppError::Config(format!("Failed to write saved connections: {}", e)))?;
+    tokio::fs::rename(&tmp, &path)
+        .await
+        .map_err(|e| AppError::Config(format!("Failed to write saved connections: {}", e)))?;
+
+    Ok(())
+}
+
+/// Load, apply `f`, and save (only if `f` succeeds).
+async fn update<T>(
+    f: impl FnOnce(&mut Vec<SavedConnection>) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let _guard = WRITE_LOCK.lock().await;
+    let mut connections = load_saved_connections().await?;
+    let result = f(&mut connections)?;
+    save_all(&connections).await?;
+    Ok(result)
+}
+
+fn find_mut<'a>(
+    connections: &'a mut [SavedConnection],
+    id: &str,
+) -> Result<&'a mut SavedConnection, AppError> {
+    connections
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or_else(|| AppError::General(format!("Connection not found: {}", id)))
+}
+
+/// Save a connection, or rename the existing one for the same
+/// project/profile/database.
+pub async fn save_connection(
+    name: String,
+    project_key: String,
+    profile: String,
+    database: Option<String>,
+) -> Result<SavedConnection, AppError> {
+    update(|connections| Ok(upsert(connections, name, project_key, profile, database))).await
+}
+
+fn upsert(
+    connections: &mut Vec<SavedConnection>,
+    name: String,
+    project_key: String,
+    profile: String,
+    database: Option<String>,
+) -> SavedConnection {
+    if let Some(existing) = connections
+        .iter_mut()
+        .find(|c| c.project_key == project_key && c.profile == profile && c.database == database)
+    {
+        existing.name = name;
+        existing.last_used_at = Some(now_millis());
+        return existing.clone();
+    }
+
+    let new_connection = SavedConnection {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        project_key,
+        profile,
+        database,
+        last_used_at: Some(now_millis()),
+        group: None,
+    };
+    connections.push(new_connection.clone());
+    new_connection
+}
+
+pub async fn delete_saved_connection(id: &str) -> Result<(), AppError> {
+    update(|connections| {
+        connections.retain(|c| c.id != id);
+        Ok(())
+    })
+    .await
+}
+
+pub async fn rename_saved_connection(id: &str, name: String) -> Result<SavedConnection, AppError> {
+    update(|connections| {
+        let conn = find_mut(connections, id)?;
+        conn.name = name;
+        Ok(conn.clone())
+    })
+    .await
+}
+
+/// Reorder to match `ids`; connections not listed keep their relative order at the end.
+pub async fn reorder_saved_connections(ids: &[String]) -> Result<(), AppError> {
+    update(|connections| {
+        reorder(connections, ids);
+        Ok(())
+    })
+    .await
+}
+
+fn reorder(connections: &mut Vec<SavedConnection>, ids: &[String]) {
+    let mut reordered: Vec<SavedConnection> = Vec::with_capacity(connections.len());
+    for id in ids {
+        if let Some(pos) = connections.iter().position(|c| c.id == *id) {
+            reordered.push(connections.remove(pos));
+        }
+    }
+    reordered.append(connections);
+    *connections = reordered;
+}
+
+/// Record that a saved connection was just used. Unknown IDs are ignored.
+pub async fn touch_saved_connection(id: &str) -> Result<(), AppError> {
+    update(|connections| {
+        if let Some(conn) = connections.iter_mut().find(|c| c.id == id) {
+            conn.last_used_at = Some(now_millis());
+        }
+        Ok(())
+    })
+    .await
+}
+
+pub async fn move_connection_to_group(
+    id: &str,
+    group: Option<String>,
+) -> Result<SavedConnection, AppError> {
+    update(|connections| {
+        let conn = find_mut(connections, id)?;
+        // Normalize empty strings to None
+        conn.group = group.filter(|g| !g.trim().is_empty());
+        Ok(conn.clone())
+    })
+    .await
+}
+
+pub async fn rename_connection_group(
+    old_name: &str,
+    new_name: &str,
+) -> Result<Vec<SavedConnection>, AppError> {
+    let new_name = new_name.trim().to_string();
+    if new_name.is_empty() {
+        return Err(AppError::General("Group name cannot be empty".to_string()));
+    }
+    update(|connections| {
+        for conn in connections.iter_mut() {
+            if conn.group.as_deref() == Some(old_name) {
+                conn.group = Some(new_name.clone());
+            }
+        }
+        Ok(connections.clone())
+    })
+    .await
+}
+
+/// Ungroup every connection in `group_name` (the connections are kept).
+pub async fn delete_connection_group(group_name: &str) -> Result<Vec<SavedConnection>, AppError> {
+    update(|connections| {
+        for conn in connections.iter_mut() {
+            if conn.group.as_deref() == Some(group_name) {
+                conn.group = None;
+            }
+        }
+        Ok(connections.clone())
+    })
+    .await
+}
+
+/// Merge imported connections, skipping duplicates (same project + profile).
+/// Returns how many were added.
+pub async fn import_saved_connections(imported: Vec<SavedConnection>) -> Result<usize, AppError> {
+    update(|connections| Ok(merge(connections, imported))).await
+}
+
+fn merge(existing: &mut Vec<SavedConnection>, imported: Vec<SavedConnection>) -> usize {
+    let mut added = 0usize;
+    for conn in imported {
+        let is_duplicate = existing
+            .iter()
+            .any(|e| e.project_key == conn.project_key && e.profile == conn.profile);
+        if !is_duplicate {
+            existing.push(conn);
+            added += 1;
+        }
+    }
+    added
+}
+
+/// Find a saved connection by ID or (case-insensitive) name.
+pub fn find_saved_connection<'a>(
+    connections: &'a [SavedConnection],
+    name_or_id: &str,
+) -> Option<&'a SavedConnection> {
+    connections
+        .iter()
+        .find(|c| c.id == name_or_id)
+        .or_else(|| {
+            connections
+                .iter()
+                .find(|c| c.name.eq_ignore_ascii_case(name_or_id))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conn(id: &str, project_key: &str, profile: &str) -> SavedConnection {
+        SavedConnection {
+            id: id.to_string(),
+            name: id.to_uppercase(),
+            project_key: project_key.to_string(),
+            profile: profile.to_string(),
+            database: None,
+            last_used_at: None,
+            group: None,
+        }
+    }
+
+    fn ids(connections: &[SavedConnection]) -> Vec<&str> {
+        connections.iter().map(|c| c.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_plugin_store() {
+        let data = r#"{
+            "savedConnections": [
+                {"id": "a1", "name": "Prod DB", "projectKey": "myapp", "profile": "prod",
+                 "database": "orders", "lastUsedAt": "1700000000000", "group": "Prod"},
+                {"id": "b2", "name": "Dev VNC", "projectKey": "vnc", "profile": "dev",
+                 "lastUsedAt": null}
+            ]
+        }"#;
+        let connections = parse_plugin_store(data);
+        assert_eq!(connections.len(), 2);
+        assert_eq!(connections[0].database.as_deref(), Some("orders"));
+        assert_eq!(connections[1].group, None);
+
+        assert_eq!(find_saved_connection(&connections, "prod db").unwrap().id, "a1");
+        assert_eq!(find_saved_connection(&connections, "b2").unwrap().name, "Dev VNC");
+        assert!(find_saved_connection(&connections, "missing").is_none());
+    }
+
+    #[test]
+    fn test_parse_empty_or_invalid_plugin_store() {
+        assert!(parse_plugin_store("{}").is_empty());
+        assert!(parse_plugin_store("not json").is_empty());
+    }
+
+    #[test]
+    fn test_upsert_renames_existing_connection() {
+        let mut connections = vec![conn("a", "app", "dev")];
+        let saved = upsert(&mut connections, "Renamed".into(), "app".into(), "dev".into(), None);
+        assert_eq!(saved.id, "a");
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].name, "Renamed");
+        assert!(connections[0].last_used_at.is_some());
+
+        let other_db = upsert(
+            &mut connections,
+            "Orders".into(),
+            "app".into(),
+            "dev".into(),
+            Some("orders".into()),
+        );
+        assert_ne!(other_db.id, "a");
+        assert_eq!(connections.len(), 2);
+    }
+
+    #[test]
+    fn test_reorder_keeps_unlisted_connections_at_end() {
+        let mut connections = vec![conn("a", "p", "1"), conn("b", "p", "2"), conn("c", "p", "3")];
+        reorder(&mut connections, &["c".to_string(), "x".to_string(), "a".to_string()]);
+        assert_eq!(ids(&connections), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_merge_skips_duplicates() {
+        let mut existing = vec![conn("a", "app", "dev")];
+        let added = merge(
+            &mut existing,
+            vec![conn("dup", "app", "dev"), conn("b", "app", "prod")],
+        );
+        assert_eq!(added, 1);
+        assert_eq!(ids(&existing), ["a", "b"]);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::config::projects::ProjectConfig;
+use crate::tunnel::hooks;
+use crate::tunnel::socks::SocksAllowList;
 use regex::Regex;
 use std::sync::LazyLock;
 
@@ -13,13 +15,27 @@ static SSH_USERNAME_PATTERN: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9._-]+$").unwrap());
 static SSH_KEY_PATH_PATTERN: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9._/~-]+$").unwrap());
+static HTTP_HOST_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9]([a-zA-Z0-9.-]{0,251}[a-zA-Z0-9])?$").unwrap());
+/// EKS cluster names: up to 100 alphanumerics, hyphens and underscores
+static EKS_CLUSTER_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9_-]{0,99}$").unwrap());
+/// ElastiCache replication group IDs and MemoryDB cluster names: a letter,
+/// then up to 39 alphanumerics and hyphens
+static REDIS_CLUSTER_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-zA-Z][a-zA-Z0-9-]{0,39}$").unwrap());
+static SIGV4_SERVICE_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-z0-9-]{1,64}$").unwrap());
 
 const VALID_RDS_TYPES: &[&str] = &["cluster", "instance"];
 const VALID_ENGINES: &[&str] = &["postgres", "mysql"];
-const VALID_CONNECTION_TYPES: &[&str] = &["rds", "service"];
-const VALID_SERVICE_TYPES: &[&str] = &["vnc", "rdp", "custom", "ssh"];
+const VALID_CONNECTION_TYPES: &[&str] = &["rds", "service", "eks", "redis"];
+const VALID_SERVICE_TYPES: &[&str] =
+    &["vnc", "rdp", "custom", "ssh", "socks", "http", "sigv4-http"];
 const VALID_TARGET_TYPES: &[&str] = &["ec2-direct", "ec2-bastion", "ecs-bastion"];
 const VALID_AUTH_TYPES: &[&str] = &["secrets", "iam"];
+const VALID_REDIS_SERVICES: &[&str] = &["elasticache", "memorydb"];
+const VALID_SSH_KEY_MODES: &[&str] = &["file", "instance-connect"];
 /// Pattern for secret path/ARN (allows colons for ARN format, plus alphanumeric, dots, hyphens, slashes, !)
 static SECRET_PATH_PATTERN: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9._!/:+-]+$").unwrap());
@@ -170,39 +186,6 @@ pub fn validate_project_config(config: &ProjectConfig) -> ValidationResult {
                 }
             }
         }
-
-        // Validate secretPath (if provided, must be non-empty and safe)
-        if let Some(ref secret_path) = config.secret_path {
-            if secret_path.is_empty() {
-                errors.push("secretPath must be non-empty if provided".to_string());
-            } else if !SECRET_PATH_PATTERN.is_match(secret_path) {
-                errors.push(
-                    "secretPath contains invalid characters (only alphanumeric, dots, underscores, hyphens, slashes, colons, +, and ! allowed)".to_string(),
-                );
-            }
-        }
-
-        // Validate secretUsernameField (if provided, must be non-empty)
-        if let Some(ref field) = config.secret_username_field {
-            if field.is_empty() {
-                errors.push("secretUsernameField must be non-empty if provided".to_string());
-            } else if !FIELD_NAME_PATTERN.is_match(field) {
-                errors.push(
-                    "secretUsernameField contains invalid characters (only alphanumeric, dots, and underscores allowed)".to_string(),
-                );
-            }
-        }
-
-        // Validate secretPasswordField (if provided, must be non-empty)
-        if let Some(ref field) = config.secret_password_field {
-            if field.is_empty() {
-                errors.push("secretPasswordField must be non-empty if provided".to_string());
-            } else if !FIELD_NAME_PATTERN.is_match(field) {
-                errors.push(
-                    "secretPasswordField contains invalid characters (only alphanumeric, dots, and underscores allowed)".to_string(),
-                );
-            }
-        }
     } else if connection_type == "service" {
         // Validate serviceType
         match config.service_type.as_deref() {
@@ -219,9 +202,15 @@ pub fn validate_project_config(config: &ProjectConfig) -> ValidationResult {
             }
         }
 
+        // A SOCKS proxy picks its destinations per request through the bastion
+        let is_socks = config.service_type.as_deref() == Some("socks");
+        // Without a targetType an HTTP site is reached by its httpHost
+        let is_http = matches!(config.service_type.as_deref(), Some("http" | "sigv4-http"));
+
         // Validate remotePort
         match config.remote_port {
             Some(port) if port > 0 => {}
+            _ if is_socks => {}
             _ => {
                 errors.push("Missing required field: remotePort (must be > 0)".to_string());
             }
@@ -238,6 +227,7 @@ pub fn validate_project_config(config: &ProjectConfig) -> ValidationResult {
                 }
                 Some(tt)
             }
+            _ if is_socks || is_http => None,
             _ => {
                 errors.push("Missing required field: targetType".to_string());
                 None
@@ -303,11 +293,147 @@ pub fn validate_project_config(config: &ProjectConfig) -> ValidationResult {
                     );
                 }
 
+            // sshKeyMode is optional; Instance Connect needs an EC2 target
+            if let Some(ref mode) = config.ssh_key_mode
+                && !mode.is_empty()
+            {
+                if !VALID_SSH_KEY_MODES.contains(&mode.as_str()) {
+                    errors.push(format!(
+                        "sshKeyMode must be one of: {}",
+                        VALID_SSH_KEY_MODES.join(", ")
+                    ));
+                } else if mode == "instance-connect"
+                    && config.target_type.as_deref() == Some("ecs-bastion")
+                {
+                    errors.push(
+                        "sshKeyMode \"instance-connect\" requires an EC2 target (ec2-direct or ec2-bastion)".to_string(),
+                    );
+                }
+            }
+
             // remotePort should default to 22 for SSH; warn if not set
             if config.remote_port.is_none() {
                 errors.push("Missing required field: remotePort (use 22 for SSH)".to_string());
             }
         }
+
+        // The HTTP proxy needs the site's name for Host and SNI
+        if is_http {
+            match config.http_host.as_deref() {
+                Some(host) if !host.is_empty() => {
+                    if !HTTP_HOST_PATTERN.is_match(host) {
+                        errors.push(
+                            "httpHost must be a host name (only alphanumeric, dots, and hyphens allowed)".to_string(),
+                        );
+                    }
+                }
+                _ => {
+                    errors.push(format!(
+                        "Missing required field: httpHost (required when serviceType is \"{}\")",
+                        config.service_type.as_deref().unwrap_or_default()
+                    ));
+                }
+            }
+        }
+
+        // Requests are signed for one AWS service
+        if config.service_type.as_deref() == Some("sigv4-http") {
+            match config.sigv4_service.as_deref() {
+                Some(service) if !service.is_empty() => {
+                    if !SIGV4_SERVICE_PATTERN.is_match(service) {
+                        errors.push(
+                            "sigv4Service must be an AWS signing name such as es or execute-api (only lowercase letters, digits, and hyphens allowed)".to_string(),
+                        );
+                    }
+                }
+                _ => {
+                    errors.push(
+                        "Missing required field: sigv4Service (required when serviceType is \"sigv4-http\")"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+    } else if connection_type == "eks" {
+        // The API endpoint and CA are looked up by cluster name
+        match config.eks_cluster.as_deref() {
+            Some(cluster) if !cluster.is_empty() => {
+                if !EKS_CLUSTER_PATTERN.is_match(cluster) {
+                    errors.push(
+                        "eksCluster must be an EKS cluster name (only alphanumeric, hyphens, and underscores allowed)".to_string(),
+                    );
+                }
+            }
+            _ => {
+                errors.push("Missing required field: eksCluster".to_string());
+            }
+        }
+    } else if connection_type == "redis" {
+        // The endpoint is looked up by replication group / cluster name
+        match config.redis_cluster.as_deref() {
+            Some(cluster) if !cluster.is_empty() => {
+                if !REDIS_CLUSTER_PATTERN.is_match(cluster) {
+                    errors.push(
+                        "redisCluster must be a replication group or cluster name (a letter, then up to 39 alphanumerics and hyphens)".to_string(),
+                    );
+                }
+            }
+            _ => {
+                errors.push("Missing required field: redisCluster".to_string());
+            }
+        }
+
+        if let Some(ref service) = config.redis_service
+            && !VALID_REDIS_SERVICES.contains(&service.as_str())
+        {
+            errors.push(format!(
+                "redisService must be one of: {}",
+                VALID_REDIS_SERVICES.join(", ")
+            ));
+        }
+
+        // secretPrefix is optional (no AUTH token without it) but must be safe
+        if !config.secret_prefix.is_empty() && !SHELL_SAFE_PATTERN.is_match(&config.secret_prefix) {
+            errors.push(
+                "secretPrefix contains invalid characters (only alphanumeric, dots, underscores, hyphens, slashes, and ! allowed)".to_string(),
+            );
+        }
+    }
+
+    // Secret lookup fields (rds, and the AUTH token for redis)
+    if matches!(connection_type, "rds" | "redis") {
+        // Validate secretPath (if provided, must be non-empty and safe)
+        if let Some(ref secret_path) = config.secret_path {
+            if secret_path.is_empty() {
+                errors.push("secretPath must be non-empty if provided".to_string());
+            } else if !SECRET_PATH_PATTERN.is_match(secret_path) {
+                errors.push(
+                    "secretPath contains invalid characters (only alphanumeric, dots, underscores, hyphens, slashes, colons, +, and ! allowed)".to_string(),
+                );
+            }
+        }
+
+        // Validate secretUsernameField (if provided, must be non-empty)
+        if let Some(ref field) = config.secret_username_field {
+            if field.is_empty() {
+                errors.push("secretUsernameField must be non-empty if provided".to_string());
+            } else if !FIELD_NAME_PATTERN.is_match(field) {
+                errors.push(
+                    "secretUsernameField contains invalid characters (only alphanumeric, dots, and underscores allowed)".to_string(),
+                );
+            }
+        }
+
+        // Validate secretPasswordField (if provided, must be non-empty)
+        if let Some(ref field) = config.secret_password_field {
+            if field.is_empty() {
+                errors.push("secretPasswordField must be non-empty if provided".to_string());
+            } else if !FIELD_NAME_PATTERN.is_match(field) {
+                errors.push(
+                    "secretPasswordField contains invalid characters (only alphanumeric, dots, and underscores allowed)".to_string(),
+                );
+            }
+        }
     }
 
     // envPortMapping is required (checked as non-empty in JS; we check the field exists via struct type)
@@ -326,6 +452,79 @@ pub fn validate_project_config(config: &ProjectConfig) -> ValidationResult {
         }
     }
 
+    // The auth proxy speaks the database protocol
+    if config.auth_proxy == Some(true) && connection_type != "rds" {
+        errors.push("authProxy is only available for rds connections".to_string());
+    }
+
+    // The allow list restricts the SOCKS proxy's destinations
+    if let Some(ref entries) = config.socks_allow {
+        if connection_type != "service" || config.service_type.as_deref() != Some("socks") {
+            errors.push("socksAllow is only available for serviceType socks".to_string());
+        } else if let Err(e) = SocksAllowList::parse(entries) {
+            errors.push(format!("socksAllow: {}", e));
+        }
+    }
+
+    // Host rewriting and TLS origination belong to the HTTP proxy
+    let is_http_service = connection_type == "service"
+        && matches!(config.service_type.as_deref(), Some("http" | "sigv4-http"));
+    if (config.http_host.is_some() || config.http_tls.is_some()) && !is_http_service {
+        errors.push(
+            "httpHost and httpTls are only available for serviceType http or sigv4-http"
+                .to_string(),
+        );
+    }
+    if config.http_tls_skip_verify.is_some()
+        && (connection_type != "service" || config.service_type.as_deref() != Some("http"))
+    {
+        errors.push("httpTlsSkipVerify is only available for serviceType http".to_string());
+    }
+    if config.sigv4_service.is_some()
+        && (connection_type != "service" || config.service_type.as_deref() != Some("sigv4-http"))
+    {
+        errors.push("sigv4Service is only available for serviceType sigv4-http".to_string());
+    }
+
+    // Only EKS connections look up a cluster
+    if config.eks_cluster.is_some() && connection_type != "eks" {
+        errors.push("eksCluster is only available for eks connections".to_string());
+    }
+
+    // Only Redis connections look up a replication group
+    if (config.redis_cluster.is_some() || config.redis_service.is_some())
+        && connection_type != "redis"
+    {
+        errors.push(
+            "redisCluster and redisService are only available for redis connections".to_string(),
+        );
+    }
+
+    // The read-only guard runs inside the auth proxy
+    if let Some(ref suffixes) = config.read_only_envs
+        && !suffixes.is_empty()
+    {
+        if config.auth_proxy != Some(true) {
+            errors.push("readOnlyEnvs requires authProxy".to_string());
+        }
+        if suffixes.iter().any(|suffix| suffix.is_empty()) {
+            errors.push("readOnlyEnvs entries must not be empty".to_string());
+        }
+    }
+
+    // Validate launch hooks (run without a shell; reject anything shell-like)
+    for (field, template) in [
+        ("onConnect", &config.on_connect),
+        ("onDisconnect", &config.on_disconnect),
+    ] {
+        if let Some(template) = template
+            && !template.is_empty()
+            && let Err(e) = hooks::validate_template(template)
+        {
+            errors.push(format!("{} {}", field, e));
+        }
+    }
+
     // Validate region
     if !config.region.is_empty() && !REGION_PATTERN.is_match(&config.region) {
         errors.push(format!("Invalid region format: {}", config.region));
@@ -385,14 +584,28 @@ mod tests {
             target_pattern: None,
             ecs_cluster: None,
             ecs_service: None,
+            socks_allow: None,
+            http_host: None,
+            http_tls: None,
+            http_tls_skip_verify: None,
+            sigv4_service: None,
+            eks_cluster: None,
+            redis_cluster: None,
+            redis_service: None,
             secret_path: None,
             secret_username_field: None,
             secret_password_field: None,
             auth_type: "secrets".to_string(),
             iam_username: None,
             multiplexed: None,
+            auth_proxy: None,
+            read_only_envs: None,
+            loopback_alias: None,
             ssh_username: None,
             ssh_key_path: None,
+            ssh_key_mode: None,
+            on_connect: None,
+            on_disconnect: None,
         }
     }
 
@@ -520,14 +733,28 @@ mod tests {
             target_pattern: Some("*vnc-server*".to_string()),
             ecs_cluster: None,
             ecs_service: None,
+            socks_allow: None,
+            http_host: None,
+            http_tls: None,
+            http_tls_skip_verify: None,
+            sigv4_service: None,
+            eks_cluster: None,
+            redis_cluster: None,
+            redis_service: None,
             secret_path: None,
             secret_username_field: None,
             secret_password_field: None,
             auth_type: "secrets".to_string(),
             iam_username: None,
             multiplexed: None,
+            auth_proxy: None,
+            read_only_envs: None,
+            loopback_alias: None,
             ssh_username: None,
             ssh_key_path: None,
+            ssh_key_mode: None,
+            on_connect: None,
+            on_disconnect: None,
         }
     }
 
@@ -579,6 +806,172 @@ mod tests {
         assert!(result.errors.iter().any(|e| e.contains("ecsCluster")));
     }
 
+    #[test]
+    fn test_service_socks() {
+        let mut config = valid_service_config_ec2();
+        config.service_type = Some("socks".to_string());
+        config.remote_port = None;
+        config.target_type = None;
+        config.target_pattern = None;
+        config.socks_allow = Some(vec!["10.0.0.0/8".to_string(), "*.internal".to_string()]);
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.socks_allow = Some(vec!["10.0.0.0/33".to_string()]);
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("socksAllow")));
+
+        let mut config = valid_service_config_ec2();
+        config.socks_allow = Some(vec!["10.0.0.0/8".to_string()]);
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("socksAllow")));
+    }
+
+    #[test]
+    fn test_service_http() {
+        let mut config = valid_service_config_ec2();
+        config.service_type = Some("http".to_string());
+        config.remote_port = Some(443);
+        config.http_host = Some("grafana.internal.example.com".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.http_host = None;
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("httpHost")));
+
+        config.http_host = Some("grafana.internal/admin".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("httpHost")));
+
+        config.http_host = Some("internal-alb-123.us-east-1.elb.amazonaws.com".to_string());
+        config.target_type = None;
+        config.target_pattern = None;
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.http_tls_skip_verify = Some(true);
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        let mut config = valid_service_config_ec2();
+        config.http_tls = Some(true);
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("httpTls")));
+
+        let mut config = valid_service_config_ec2();
+        config.http_tls_skip_verify = Some(true);
+        let result = validate_project_config(&config);
+        assert!(result
+            .errors
+            .iter()
+            .any(|e| e.contains("httpTlsSkipVerify")));
+    }
+
+    #[test]
+    fn test_service_sigv4_http() {
+        let mut config = valid_service_config_ec2();
+        config.service_type = Some("sigv4-http".to_string());
+        config.remote_port = Some(443);
+        config.target_type = None;
+        config.target_pattern = None;
+        config.http_host = Some("vpc-logs-abc123.us-east-1.es.amazonaws.com".to_string());
+        config.sigv4_service = Some("es".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.sigv4_service = None;
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("sigv4Service")));
+
+        config.sigv4_service = Some("Execute API".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("sigv4Service")));
+
+        config.sigv4_service = Some("es".to_string());
+        config.http_tls_skip_verify = Some(true);
+        let result = validate_project_config(&config);
+        assert!(result
+            .errors
+            .iter()
+            .any(|e| e.contains("httpTlsSkipVerify")));
+
+        config.http_tls_skip_verify = None;
+        config.sigv4_service = Some("execute-api".to_string());
+        config.http_host = None;
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("httpHost")));
+
+        let mut config = valid_service_config_ec2();
+        config.service_type = Some("http".to_string());
+        config.http_host = Some("grafana.internal.example.com".to_string());
+        config.sigv4_service = Some("es".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("sigv4Service")));
+    }
+
+    #[test]
+    fn test_eks_cluster() {
+        let mut config = valid_service_config_ec2();
+        config.connection_type = "eks".to_string();
+        config.service_type = None;
+        config.remote_port = None;
+        config.target_type = None;
+        config.target_pattern = None;
+        config.eks_cluster = Some("platform-prod".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.eks_cluster = None;
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("eksCluster")));
+
+        config.eks_cluster = Some("-platform".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("eksCluster")));
+
+        let mut config = valid_service_config_ec2();
+        config.eks_cluster = Some("platform-prod".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("eksCluster")));
+    }
+
+    #[test]
+    fn test_redis_cluster() {
+        let mut config = valid_service_config_ec2();
+        config.connection_type = "redis".to_string();
+        config.service_type = None;
+        config.remote_port = None;
+        config.target_type = None;
+        config.target_pattern = None;
+        config.redis_cluster = Some("sessions-prod".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.redis_service = Some("memorydb".to_string());
+        config.secret_path = Some("redis/sessions-prod".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.redis_service = Some("valkey".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("redisService")));
+
+        config.redis_service = None;
+        config.redis_cluster = Some("1sessions".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("redisCluster")));
+
+        config.redis_cluster = None;
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("redisCluster")));
+
+        let mut config = valid_service_config_ec2();
+        config.redis_cluster = Some("sessions-prod".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("redisCluster")));
+    }
+
     #[test]
     fn test_databases_valid() {
         let mut config = valid_config();
@@ -781,14 +1174,28 @@ mod tests {
             target_pattern: Some("*my-server*".to_string()),
             ecs_cluster: None,
             ecs_service: None,
+            socks_allow: None,
+            http_host: None,
+            http_tls: None,
+            http_tls_skip_verify: None,
+            sigv4_service: None,
+            eks_cluster: None,
+            redis_cluster: None,
+            redis_service: None,
             secret_path: None,
             secret_username_field: None,
             secret_password_field: None,
             auth_type: "secrets".to_string(),
             iam_username: None,
             multiplexed: None,
+            auth_proxy: None,
+            read_only_envs: None,
+            loopback_alias: None,
             ssh_username: Some("ubuntu".to_string()),
             ssh_key_path: Some("~/.ssh/id_rsa".to_string()),
+            ssh_key_mode: None,
+            on_connect: None,
+            on_disconnect: None,
         }
     }
 
@@ -833,4 +1240,76 @@ mod tests {
             assert!(result.valid, "expected valid for path: {}", path);
         }
     }
+
+    #[test]
+    fn test_ssh_instance_connect_key_mode() {
+        let mut config = valid_ssh_config();
+        config.ssh_key_path = None;
+        config.ssh_key_mode = Some("instance-connect".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.target_type = Some("ecs-bastion".to_string());
+        config.ecs_cluster = Some("cluster".to_string());
+        config.ecs_service = Some("service".to_string());
+        let result = validate_project_config(&config);
+        assert!(!result.valid);
+        assert!(result.errors.iter().any(|e| e.contains("sshKeyMode")));
+    }
+
+    #[test]
+    fn test_ssh_invalid_key_mode() {
+        let mut config = valid_ssh_config();
+        config.ssh_key_mode = Some("agent".to_string());
+        let result = validate_project_config(&config);
+        assert!(!result.valid);
+        assert!(result.errors.iter().any(|e| e.contains("sshKeyMode")));
+    }
+
+    #[test]
+    fn test_launch_hooks() {
+        let mut config = valid_config();
+        config.on_connect = Some("psql {{uri}}".to_string());
+        config.on_disconnect = Some(String::new());
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.on_disconnect = Some("notify-send closed && rm -rf ~".to_string());
+        let result = validate_project_config(&config);
+        assert!(!result.valid);
+        assert!(result.errors.iter().any(|e| e.starts_with("onDisconnect")));
+    }
+
+    #[test]
+    fn test_auth_proxy() {
+        let mut config = valid_config();
+        config.auth_proxy = Some(true);
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.engine = Some("mysql".to_string());
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        let mut service = valid_service_config_ec2();
+        service.auth_proxy = Some(true);
+        let result = validate_project_config(&service);
+        assert!(result.errors.iter().any(|e| e.contains("only available for rds")));
+    }
+
+    #[test]
+    fn test_read_only_envs() {
+        let mut config = valid_config();
+        config.read_only_envs = Some(vec!["prod".to_string()]);
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("requires authProxy")));
+
+        config.auth_proxy = Some(true);
+        let result = validate_project_config(&config);
+        assert!(result.valid, "errors: {:?}", result.errors);
+
+        config.read_only_envs = Some(vec![String::new()]);
+        let result = validate_project_config(&config);
+        assert!(result.errors.iter().any(|e| e.contains("must not be empty")));
+    }
 }
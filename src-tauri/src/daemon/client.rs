@@ -0,0 +1,216 @@
+use super::{
+    socket_path, ConnectParams, ConnectResult, DaemonEvent, DisconnectParams, ForwardParams,
+    RpcNotification, RpcRequest, RpcResponse, JSONRPC_VERSION,
+};
+use crate::error::AppError;
+use crate::tunnel::manager::{ActiveConnectionInfo, ConnectionInfo};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::net::UnixStream;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, oneshot, Mutex};
+use tokio_util::sync::CancellationToken;
+
+type Pending = Arc<std::sync::Mutex<HashMap<u64, oneshot::Sender<RpcResponse>>>>;
+
+/// Connection to the background daemon's control socket.
+pub struct DaemonClient {
+    writer: Mutex<OwnedWriteHalf>,
+    pending: Pending,
+    next_id: AtomicU64,
+    events: broadcast::Sender<DaemonEvent>,
+    /// Cancelled once the daemon hangs up.
+    closed: CancellationToken,
+}
+
+impl DaemonClient {
+    /// Connect to the daemon on the default socket, or `None` if it isn't running.
+    pub async fn connect_if_running() -> Option<Self> {
+        Self::connect(&socket_path()).await.ok()
+    }
+
+    pub async fn connect(path: &Path) -> Result<Self, AppError> {
+        let stream = UnixStream::connect(path).await?;
+        let (read, write) = stream.into_split();
+        let pending: Pending = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let (events, _) = broadcast::channel(256);
+        let closed = CancellationToken::new();
+
+        let reader_pending = pending.clone();
+        let reader_events = events.clone();
+        let reader_closed = closed.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let message: serde_json::Value = match serde_json::from_str(&line) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        log::warn!("Invalid message from daemon: {}", e);
+                        continue;
+                    }
+                };
+                if message.get("id").is_some_and(|id| !id.is_null()) {
+                    if let Ok(response) = serde_json::from_value::<RpcResponse>(message)
+                        && let Some(id) = response.id
+                        && let Some(tx) = reader_pending.lock().ok().and_then(|mut p| p.remove(&id))
+                    {
+                        let _ = tx.send(response);
+                    }
+                } else if let Ok(notification) = serde_json::from_value::<RpcNotification>(message) {
+                    let _ = reader_events.send(notification.params);
+                }
+            }
+            // Daemon went away — fail every outstanding call
+            if let Ok(mut pending) = reader_pending.lock() {
+                pending.clear();
+            }
+            reader_closed.cancel();
+        });
+
+        Ok(Self {
+            writer: Mutex::new(write),
+            pending,
+            next_id: AtomicU64::new(1),
+            events,
+            closed,
+        })
+    }
+
+    /// Send a request and wait for its response.
+    pub async fn call<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, AppError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(id, tx);
+        }
+
+        let mut line = serde_json::to_string(&RpcRequest {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            method: method.to_string(),
+            params,
+        })?;
+        line.push('\n');
+        self.writer.lock().await.write_all(line.as_bytes()).await?;
+
+        let response = rx
+            .await
+            .map_err(|_| AppError::General("Daemon closed the connection".to_string()))?;
+        if let Some(error) = response.error {
+            return Err(AppError::General(error.message));
+        }
+        Ok(serde_json::from_value(
+            response.result.unwrap_or(serde_json::Value::Null),
+        )?)
+    }
+
+    /// Start receiving tunnel and SSO events from the daemon.
+    pub async fn subscribe(&self) -> Result<broadcast::Receiver<DaemonEvent>, AppError> {
+        // Subscribe locally first so nothing sent right after the ack is missed
+        let events = self.events.subscribe();
+        self.call::<bool>("subscribe", serde_json::Value::Null).await?;
+        Ok(events)
+    }
+
+    /// Subscribe and hand the connection over to the returned stream. The
+    /// daemon ends a subscription when its client disconnects, so the client
+    /// has to outlive the receiver.
+    pub async fn into_events(self) -> Result<DaemonEvents, AppError> {
+        let events = self.subscribe().await?;
+        Ok(DaemonEvents {
+            client: self,
+            events,
+        })
+    }
+
+    pub async fn connect_tunnel(
+        &self,
+        project_key: &str,
+        profile: &str,
+        local_port: Option<&str>,
+        database: Option<&str>,
+    ) -> Result<(String, ConnectionInfo), AppError> {
+        let params = ConnectParams {
+            project_key: project_key.to_string(),
+            profile: profile.to_string(),
+            local_port: local_port.map(str::to_string),
+            database: database.map(str::to_string),
+        };
+        let result: ConnectResult = self.call("connect", serde_json::to_value(params)?).await?;
+        Ok((result.connection_id, result.connection_info))
+    }
+
+    pub async fn forward_tunnel(
+        &self,
+        project_key: &str,
+        profile: &str,
+        remote_host: &str,
+        remote_port: u16,
+        local_port: Option<&str>,
+    ) -> Result<(String, ConnectionInfo), AppError> {
+        let params = ForwardParams {
+            project_key: project_key.to_string(),
+            profile: profile.to_string(),
+            remote_host: remote_host.to_string(),
+            remote_port,
+            local_port: local_port.map(str::to_string),
+        };
+        let result: ConnectResult = self.call("forward", serde_json::to_value(params)?).await?;
+        Ok((result.connection_id, result.connection_info))
+    }
+
+    pub async fn disconnect(&self, connection_id: &str) -> Result<(), AppError> {
+        let params = DisconnectParams {
+            connection_id: connection_id.to_string(),
+        };
+        self.call::<serde_json::Value>("disconnect", serde_json::to_value(params)?)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn disconnect_all(&self) -> Result<(), AppError> {
+        self.call::<serde_json::Value>("disconnectAll", serde_json::Value::Null)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn list(&self) -> Result<Vec<ActiveConnectionInfo>, AppError> {
+        self.call("list", serde_json::Value::Null).await
+    }
+
+    pub async fn used_ports(&self) -> Result<Vec<String>, AppError> {
+        self.call("usedPorts", serde_json::Value::Null).await
+    }
+}
+
+/// Events from a subscribed daemon connection, see [`DaemonClient::into_events`].
+pub struct DaemonEvents {
+    client: DaemonClient,
+    events: broadcast::Receiver<DaemonEvent>,
+}
+
+impl DaemonEvents {
+    /// The next event, or `None` once the daemon hangs up.
+    pub async fn next(&mut self) -> Option<DaemonEvent> {
+        loop {
+            tokio::select! {
+                // Deliver anything already received before reporting the hang-up
+                biased;
+                event = self.events.recv() => match event {
+                    Ok(event) => return Some(event),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = self.client.closed.cancelled() => return None,
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Background daemon that owns all tunnels.
+//!
+//! The daemon runs one [`TunnelManager`](crate::tunnel::manager::TunnelManager)
+//! and exposes it over a JSON-RPC 2.0 control API on a Unix domain socket
+//! (`~/.connection-app/daemon.sock`), one JSON message per line. The GUI and
+//! the CLI act as clients, so tunnels outlive the process that opened them and
+//! every tool sees the same set of connections and used ports.
+//!
+//! Methods: `connect`, `forward`, `disconnect`, `disconnectAll`, `list`,
+//! `usedPorts`, `subscribe`. After `subscribe`, the daemon pushes `event` notifications
+//! carrying a [`DaemonEvent`].
+
+pub mod client;
+pub mod server;
+
+use crate::aws::sso::SsoEventHandler;
+use crate::tunnel::manager::{ConnectionInfo, TunnelEventHandler};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+// JSON-RPC 2.0 error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Implementation-defined: the operation itself failed (AWS, tunnel, config).
+pub const SERVER_ERROR: i64 = -32000;
+
+/// Path of the daemon's control socket.
+pub fn socket_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".connection-app")
+        .join("daemon.sock")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    pub id: u64,
+    pub method: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    /// `None` (serialized as `null`) when the request could not be parsed.
+    pub id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Server-to-client notification (no `id`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: DaemonEvent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConnectParams {
+    #[serde(rename = "projectKey")]
+    pub project_key: String,
+    pub profile: String,
+    #[serde(rename = "localPort", default)]
+    pub local_port: Option<String>,
+    #[serde(default)]
+    pub database: Option<String>,
+}
+
+/// Ad-hoc `remoteHost:remotePort` through a project's bastion.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ForwardParams {
+    #[serde(rename = "projectKey")]
+    pub project_key: String,
+    pub profile: String,
+    #[serde(rename = "remoteHost")]
+    pub remote_host: String,
+    #[serde(rename = "remotePort")]
+    pub remote_port: u16,
+    #[serde(rename = "localPort", default)]
+    pub local_port: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConnectResult {
+    #[serde(rename = "connectionId")]
+    pub connection_id: String,
+    #[serde(rename = "connectionInfo")]
+    pub connection_info: ConnectionInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DisconnectParams {
+    #[serde(rename = "connectionId")]
+    pub connection_id: String,
+}
+
+/// Tunnel and SSO events, mirrored from [`TunnelEventHandler`] and
+/// [`SsoEventHandler`] callbacks inside the daemon.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum DaemonEvent {
+    Status {
+        message: String,
+        #[serde(rename = "connectionId")]
+        connection_id: Option<String>,
+    },
+    Notification {
+        title: String,
+        body: String,
+    },
+    ConnectionHealth {
+        #[serde(rename = "connectionId")]
+        connection_id: String,
+        status: String,
+        #[serde(rename = "lastCheck")]
+        last_check: u64,
+    },
+    ConnectionError {
+        #[serde(rename = "connectionId")]
+        connection_id: String,
+        message: String,
+    },
+    Disconnected {
+        #[serde(rename = "connectionId")]
+        connection_id: String,
+        reason: String,
+    },
+    Reconnecting {
+        #[serde(rename = "connectionId")]
+        connection_id: String,
+        attempt: u32,
+        #[serde(rename = "maxAttempts")]
+        max_attempts: u32,
+    },
+    Reconnected {
+        #[serde(rename = "connectionId")]
+        connection_id: String,
+    },
+    CredentialsRefreshed {
+        #[serde(rename = "connectionId")]
+        connection_id: String,
+        #[serde(rename = "connectionInfo")]
+        connection_info: ConnectionInfo,
+    },
+    SsoStatus {
+        message: String,
+        #[serde(rename = "connectionId")]
+        connection_id: Option<String>,
+    },
+    SsoOpenUrl {
+        url: String,
+        #[serde(rename = "connectionId")]
+        connection_id: Option<String>,
+    },
+}
+
+impl DaemonEvent {
+    /// The tunnel the event belongs to, if any.
+    pub fn connection_id(&self) -> Option<&str> {
+        match self {
+            DaemonEvent::Status { connection_id, .. }
+            | DaemonEvent::SsoStatus { connection_id, .. }
+            | DaemonEvent::SsoOpenUrl { connection_id, .. } => connection_id.as_deref(),
+            DaemonEvent::ConnectionHealth { connection_id, .. }
+            | DaemonEvent::ConnectionError { connection_id, .. }
+            | DaemonEvent::Disconnected { connection_id, .. }
+            | DaemonEvent::Reconnecting { connection_id, .. }
+            | DaemonEvent::Reconnected { connection_id }
+            | DaemonEvent::CredentialsRefreshed { connection_id, .. } => Some(connection_id),
+            DaemonEvent::Notification { .. } => None,
+        }
+    }
+
+    /// Replay the event on a local handler (e.g. Tauri or terminal output).
+    pub fn dispatch(&self, handler: &dyn TunnelEventHandler) {
+        match self {
+            DaemonEvent::Status { message, connection_id } => {
+                handler.on_status(message, connection_id.as_deref())
+            }
+            DaemonEvent::Notification { title, body } => handler.on_notification(title, body),
+            DaemonEvent::ConnectionHealth { connection_id, status, last_check } => {
+                handler.on_health(connection_id, status, *last_check)
+            }
+            DaemonEvent::ConnectionError { connection_id, message } => {
+                handler.on_error(connection_id, message)
+            }
+            DaemonEvent::Disconnected { connection_id, reason } => {
+                handler.on_disconnected(connection_id, reason)
+            }
+            DaemonEvent::Reconnecting { connection_id, attempt, max_attempts } => {
+                handler.on_reconnecting(connection_id, *attempt, *max_attempts)
+            }
+            DaemonEvent::Reconnected { connection_id } => handler.on_reconnected(connection_id),
+            DaemonEvent::CredentialsRefreshed { connection_id, connection_info } => {
+                handler.on_credentials_refreshed(connection_id, connection_info)
+            }
+            DaemonEvent::SsoStatus { message, connection_id } => {
+                handler.sso_handler().on_status(message, connection_id.as_deref())
+            }
+            DaemonEvent::SsoOpenUrl { url, connection_id } => {
+                handler.sso_handler().on_open_url(url, connection_id.as_deref())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_wire_format() {
+        let event = DaemonEvent::ConnectionHealth {
+            connection_id: "conn_1".to_string(),
+            status: "healthy".to_string(),
+            last_check: 42,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "event": "connection-health",
+                "connectionId": "conn_1",
+                "status": "healthy",
+                "lastCheck": 42
+            })
+        );
+        assert_eq!(serde_json::from_value::<DaemonEvent>(json).unwrap(), event);
+    }
+}
@@ -0,0 +1,518 @@
+use super::{
+    socket_path, ConnectParams, ConnectResult, DaemonEvent, DisconnectParams, ForwardParams,
+    RpcError, RpcNotification, RpcRequest, RpcResponse, INVALID_PARAMS, JSONRPC_VERSION,
+    METHOD_NOT_FOUND, PARSE_ERROR, SERVER_ERROR,
+};
+use crate::aws::sso::SsoEventHandler;
+use crate::error::AppError;
+use crate::tunnel::manager::{ConnectionInfo, TunnelEventHandler, TunnelManager};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+
+const EVENT_BUFFER: usize = 256;
+
+/// Forwards manager callbacks to subscribed clients.
+struct BroadcastHandler {
+    events: broadcast::Sender<DaemonEvent>,
+}
+
+impl BroadcastHandler {
+    fn send(&self, event: DaemonEvent) {
+        // Err only means nobody is subscribed right now
+        let _ = self.events.send(event);
+    }
+}
+
+impl TunnelEventHandler for BroadcastHandler {
+    fn on_status(&self, message: &str, connection_id: Option<&str>) {
+        self.send(DaemonEvent::Status {
+            message: message.to_string(),
+            connection_id: connection_id.map(str::to_string),
+        });
+    }
+
+    fn on_notification(&self, title: &str, body: &str) {
+        log::info!("{}: {}", title, body);
+        self.send(DaemonEvent::Notification {
+            title: title.to_string(),
+            body: body.to_string(),
+        });
+    }
+
+    fn on_health(&self, connection_id: &str, status: &str, last_check_ms: u64) {
+        self.send(DaemonEvent::ConnectionHealth {
+            connection_id: connection_id.to_string(),
+            status: status.to_string(),
+            last_check: last_check_ms,
+        });
+    }
+
+    fn on_error(&self, connection_id: &str, message: &str) {
+        log::warn!("{}: {}", connection_id, message);
+        self.send(DaemonEvent::ConnectionError {
+            connection_id: connection_id.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    fn on_disconnected(&self, connection_id: &str, reason: &str) {
+        self.send(DaemonEvent::Disconnected {
+            connection_id: connection_id.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    fn on_reconnecting(&self, connection_id: &str, attempt: u32, max_attempts: u32) {
+        self.send(DaemonEvent::Reconnecting {
+            connection_id: connection_id.to_string(),
+            attempt,
+            max_attempts,
+        });
+    }
+
+    fn on_reconnected(&self, connection_id: &str) {
+        self.send(DaemonEvent::Reconnected {
+            connection_id: connection_id.to_string(),
+        });
+    }
+
+    fn on_credentials_refreshed(&self, connection_id: &str, info: &ConnectionInfo) {
+        self.send(DaemonEvent::CredentialsRefreshed {
+            connection_id: connection_id.to_string(),
+            connection_info: info.clone(),
+        });
+    }
+
+    fn sso_handler(&self) -> &dyn SsoEventHandler {
+        self
+    }
+}
+
+impl SsoEventHandler for BroadcastHandler {
+    fn on_status(&self, message: &str, connection_id: Option<&str>) {
+        self.send(DaemonEvent::SsoStatus {
+            message: message.to_string(),
+            connection_id: connection_id.map(str::to_string),
+        });
+    }
+
+    /// The daemon has no UI of its own — subscribed clients open the browser.
+    fn on_open_url(&self, url: &str, connection_id: Option<&str>) {
+        log::info!("SSO authorization required: {}", url);
+        self.send(DaemonEvent::SsoOpenUrl {
+            url: url.to_string(),
+            connection_id: connection_id.map(str::to_string),
+        });
+    }
+}
+
+/// Run the daemon on the default socket until Ctrl+C / SIGTERM.
+/// All tunnels are disconnected on shutdown.
+pub async fn run() -> Result<(), AppError> {
+    let path = socket_path();
+    let listener = bind(&path).await?;
+    log::info!("Daemon listening on {}", path.display());
+
+    let (events, _) = broadcast::channel(EVENT_BUFFER);
+    let manager = Arc::new(TunnelManager::new(Arc::new(BroadcastHandler {
+        events: events.clone(),
+    })));
+
+    let cancel = CancellationToken::new();
+    let cancel_signal = cancel.clone();
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        cancel_signal.cancel();
+    });
+
+    serve(listener, manager.clone(), events, cancel).await;
+
+    manager.disconnect_all().await?;
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+async fn wait_for_shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    match signal(SignalKind::terminate()) {
+        Ok(mut term) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+            }
+        }
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+/// Bind the control socket, replacing a stale socket file from a previous
+/// run but refusing to start a second daemon.
+async fn bind(path: &Path) -> Result<UnixListener, AppError> {
+    if path.exists() {
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(AppError::General(format!(
+                "A daemon is already running on {}",
+                path.display()
+            )));
+        }
+        std::fs::remove_file(path)?;
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    // The socket can open tunnels with the user's AWS credentials — owner only.
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(listener)
+}
+
+/// Accept control connections until `cancel` fires.
+pub async fn serve(
+    listener: UnixListener,
+    manager: Arc<TunnelManager>,
+    events: broadcast::Sender<DaemonEvent>,
+    cancel: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_client(stream, manager.clone(), events.clone()));
+                }
+                Err(e) => log::warn!("Failed to accept control connection: {}", e),
+            },
+            _ = cancel.cancelled() => break,
+        }
+    }
+}
+
+async fn handle_client(
+    stream: UnixStream,
+    manager: Arc<TunnelManager>,
+    events: broadcast::Sender<DaemonEvent>,
+) {
+    let (read, mut write) = stream.into_split();
+
+    // Responses and event notifications share one writer so lines never interleave
+    let (out_tx, mut out_rx) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        while let Some(line) = out_rx.recv().await {
+            if write.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut subscription: Option<tokio::task::JoinHandle<()>> = None;
+    let mut lines = BufReader::new(read).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: RpcRequest = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                send_line(
+                    &out_tx,
+                    &error_response(None, PARSE_ERROR, format!("Invalid request: {}", e)),
+                );
+                continue;
+            }
+        };
+
+        if request.method == "subscribe" {
+            if subscription.is_none() {
+                subscription = Some(tokio::spawn(forward_events(
+                    events.subscribe(),
+                    out_tx.clone(),
+                )));
+            }
+            send_line(&out_tx, &ok_response(request.id, serde_json::json!(true)));
+            continue;
+        }
+
+        // Requests run concurrently: a slow `connect` must not block `list`
+        let manager = manager.clone();
+        let out_tx = out_tx.clone();
+        tokio::spawn(async move {
+            let response = match handle_request(&manager, &request).await {
+                Ok(result) => ok_response(request.id, result),
+                Err(e) => RpcResponse {
+                    jsonrpc: JSONRPC_VERSION.to_string(),
+                    id: Some(request.id),
+                    result: None,
+                    error: Some(e),
+                },
+            };
+            send_line(&out_tx, &response);
+        });
+    }
+
+    if let Some(subscription) = subscription {
+        subscription.abort();
+    }
+    drop(out_tx);
+    let _ = writer.await;
+}
+
+async fn forward_events(
+    mut events: broadcast::Receiver<DaemonEvent>,
+    out_tx: mpsc::UnboundedSender<String>,
+) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                let notification = RpcNotification {
+                    jsonrpc: JSONRPC_VERSION.to_string(),
+                    method: "event".to_string(),
+                    params: event,
+                };
+                if !send_line(&out_tx, &notification) {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                log::warn!("Control client lagging, dropped {} events", n);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+async fn handle_request(
+    manager: &TunnelManager,
+    request: &RpcRequest,
+) -> Result<serde_json::Value, RpcError> {
+    let result = match request.method.as_str() {
+        "connect" => {
+            let params: ConnectParams = parse_params(request)?;
+            let used_ports = manager.get_used_ports().await;
+            let (connection_id, connection_info) = manager
+                .connect(
+                    &params.project_key,
+                    &params.profile,
+                    params.local_port.as_deref(),
+                    params.database.as_deref(),
+                    &used_ports,
+                )
+                .await
+                .map_err(server_error)?;
+            serde_json::to_value(ConnectResult {
+                connection_id,
+                connection_info,
+            })
+        }
+        "forward" => {
+            let params: ForwardParams = parse_params(request)?;
+            let used_ports = manager.get_used_ports().await;
+            let (connection_id, connection_info) = manager
+                .forward(
+                    &params.project_key,
+                    &params.profile,
+                    &params.remote_host,
+                    params.remote_port,
+                    params.local_port.as_deref(),
+                    &used_ports,
+                )
+                .await
+                .map_err(server_error)?;
+            serde_json::to_value(ConnectResult {
+                connection_id,
+                connection_info,
+            })
+        }
+        "disconnect" => {
+            let params: DisconnectParams = parse_params(request)?;
+            manager.disconnect(&params.connection_id).await.map_err(server_error)?;
+            Ok(serde_json::Value::Null)
+        }
+        "disconnectAll" => {
+            manager.disconnect_all().await.map_err(server_error)?;
+            Ok(serde_json::Value::Null)
+        }
+        "list" => serde_json::to_value(manager.get_active_connections().await),
+        "usedPorts" => serde_json::to_value(manager.get_used_ports().await),
+        other => {
+            return Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Unknown method: {}", other),
+            });
+        }
+    };
+    result.map_err(|e| server_error(e.into()))
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(request: &RpcRequest) -> Result<T, RpcError> {
+    serde_json::from_value(request.params.clone()).map_err(|e| RpcError {
+        code: INVALID_PARAMS,
+        message: format!("Invalid params for {}: {}", request.method, e),
+    })
+}
+
+fn server_error(e: AppError) -> RpcError {
+    RpcError {
+        code: SERVER_ERROR,
+        message: e.to_string(),
+    }
+}
+
+fn ok_response(id: u64, result: serde_json::Value) -> RpcResponse {
+    RpcResponse {
+        jsonrpc: JSONRPC_VERSION.to_string(),
+        id: Some(id),
+        result: Some(result),
+        error: None,
+    }
+}
+
+fn error_response(id: Option<u64>, code: i64, message: String) -> RpcResponse {
+    RpcResponse {
+        jsonrpc: JSONRPC_VERSION.to_string(),
+        id,
+        result: None,
+        error: Some(RpcError { code, message }),
+    }
+}
+
+/// Queue one JSON line for the client. Returns false once the client is gone.
+fn send_line<T: serde::Serialize>(out_tx: &mpsc::UnboundedSender<String>, message: &T) -> bool {
+    match serde_json::to_string(message) {
+        Ok(mut line) => {
+            line.push('\n');
+            out_tx.send(line).is_ok()
+        }
+        Err(e) => {
+            log::warn!("Failed to serialize control message: {}", e);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::client::DaemonClient;
+
+    struct TestDaemon {
+        client: DaemonClient,
+        events: broadcast::Sender<DaemonEvent>,
+        cancel: CancellationToken,
+        dir: std::path::PathBuf,
+    }
+
+    impl Drop for TestDaemon {
+        fn drop(&mut self) {
+            self.cancel.cancel();
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    async fn start_daemon() -> TestDaemon {
+        let dir = std::env::temp_dir().join(format!(
+            "connection-app-daemon-{}",
+            &uuid::Uuid::new_v4().to_string()[..8]
+        ));
+        let path = dir.join("daemon.sock");
+        let listener = bind(&path).await.unwrap();
+
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        let manager = Arc::new(TunnelManager::new(Arc::new(BroadcastHandler {
+            events: events.clone(),
+        })));
+        let cancel = CancellationToken::new();
+        tokio::spawn(serve(listener, manager, events.clone(), cancel.clone()));
+
+        let client = DaemonClient::connect(&path).await.unwrap();
+        TestDaemon {
+            client,
+            events,
+            cancel,
+            dir,
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_connections_and_rejects_unknown_methods() {
+        let daemon = start_daemon().await;
+
+        assert!(daemon.client.list().await.unwrap().is_empty());
+        assert!(daemon.client.used_ports().await.unwrap().is_empty());
+        daemon.client.disconnect("conn_missing").await.unwrap();
+
+        let err = daemon
+            .client
+            .call::<serde_json::Value>("reboot", serde_json::Value::Null)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Unknown method: reboot"), "{err}");
+
+        let err = daemon
+            .client
+            .call::<serde_json::Value>("connect", serde_json::json!({ "profile": "dev" }))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("projectKey"), "{err}");
+
+        let err = daemon
+            .client
+            .forward_tunnel("demo", "dev", "db;reboot", 5432, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Invalid remote host"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_tunnel_events() {
+        let daemon = start_daemon().await;
+        let mut events = daemon.client.subscribe().await.unwrap();
+
+        let event = DaemonEvent::Disconnected {
+            connection_id: "conn_1".to_string(),
+            reason: "session_ended".to_string(),
+        };
+        daemon.events.send(event.clone()).unwrap();
+
+        let received = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+            .await
+            .expect("event not delivered")
+            .unwrap();
+        assert_eq!(received, event);
+    }
+
+    #[tokio::test]
+    async fn event_stream_keeps_its_subscription_open() {
+        let daemon = start_daemon().await;
+        let client = DaemonClient::connect(&daemon.dir.join("daemon.sock"))
+            .await
+            .unwrap();
+        let mut events = client.into_events().await.unwrap();
+
+        let event = DaemonEvent::Disconnected {
+            connection_id: "conn_1".to_string(),
+            reason: "session_ended".to_string(),
+        };
+        daemon.events.send(event.clone()).unwrap();
+
+        let received = tokio::time::timeout(std::time::Duration::from_secs(5), events.next())
+            .await
+            .expect("event not delivered");
+        assert_eq!(received, Some(event));
+    }
+
+    #[tokio::test]
+    async fn refuses_to_replace_a_running_daemon() {
+        let daemon = start_daemon().await;
+        let err = bind(&daemon.dir.join("daemon.sock")).await.unwrap_err();
+        assert!(err.to_string().contains("already running"), "{err}");
+    }
+}
@@ -2,6 +2,8 @@ pub mod aws;
 #[cfg(feature = "gui")]
 mod commands;
 pub mod config;
+#[cfg(unix)]
+pub mod daemon;
 pub mod error;
 pub mod history;
 #[cfg(feature = "gui")]
@@ -15,7 +17,7 @@ pub fn run() {
     use std::sync::Arc;
     use tauri::{Listener, Manager};
     use tokio::sync::Mutex;
-    use tunnel::manager::TunnelManager;
+    use tunnel::manager::{TauriTunnelHandler, TunnelManager};
 
     // Initialize logging so log::info!/warn!/error! produce output in dev mode
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
@@ -31,8 +33,11 @@ pub fn run() {
         .setup(|app| {
             // Migrate legacy ~/.rds-ssm-connect/ → ~/.connection-app/ on first launch
             config::projects::migrate_legacy_config();
+            // Move saved connections out of the plugin store into ~/.connection-app/
+            config::saved::migrate_plugin_store();
 
-            let tunnel_manager = TunnelManager::new(app.handle().clone());
+            let tunnel_manager =
+                TunnelManager::new(Arc::new(TauriTunnelHandler::new(app.handle().clone())));
             app.manage(Arc::new(Mutex::new(tunnel_manager)));
 
             // Activate AWS directory bookmark if sandboxed and bookmark exists
@@ -63,6 +68,12 @@ pub fn run() {
                 tray::refresh_tray(&app_handle);
             });
 
+            // Mirror tunnels owned by the background daemon, if one is running
+            #[cfg(unix)]
+            tauri::async_runtime::spawn(commands::connection::relay_daemon_events(
+                app.handle().clone(),
+            ));
+
             Ok(())
         })
         .on_window_event(|window, event| {
@@ -80,9 +91,14 @@ pub fn run() {
             commands::projects::delete_project_config,
             // Connection commands
             commands::connection::connect,
+            commands::connection::forward,
+            commands::connection::pick_workspace_file,
+            commands::connection::load_workspace,
+            commands::connection::open_workspace,
             commands::connection::disconnect,
             commands::connection::disconnect_all,
             commands::connection::get_active_connections_list,
+            commands::connection::export_connection,
             commands::connection::get_used_ports,
             commands::connection::sso_login,
             // Profile commands
@@ -102,6 +118,7 @@ pub fn run() {
             commands::saved::move_connection_to_group,
             commands::saved::rename_connection_group,
             commands::saved::delete_connection_group,
+            commands::saved::export_to_db_client,
             // Tray commands
             commands::tray::refresh_tray_menu,
             // System commands
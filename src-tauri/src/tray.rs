@@ -1,4 +1,5 @@
-use crate::commands::saved::SavedConnection;
+use crate::commands::connection::{disconnect_connection, list_connections};
+use crate::config::saved::SavedConnection;
 use crate::tunnel::manager::{ActiveConnectionInfo, TunnelManager};
 use std::sync::Arc;
 use tauri::image::Image;
@@ -149,8 +150,7 @@ fn handle_menu_event(app_handle: &AppHandle, id: &str) {
         let conn_id = conn_id.to_string();
         tauri::async_runtime::spawn(async move {
             if let Some(manager) = app.try_state::<Arc<Mutex<TunnelManager>>>() {
-                let mgr = manager.lock().await;
-                let _ = mgr.disconnect(&conn_id).await;
+                let _ = disconnect_connection(&manager, Some(&conn_id)).await;
             }
             // Tray will be refreshed by the disconnect event handler
         });
@@ -271,13 +271,14 @@ pub fn refresh_tray(app_handle: &AppHandle) {
     tauri::async_runtime::spawn(async move {
         let active_connections = if let Some(manager) = app.try_state::<Arc<Mutex<TunnelManager>>>()
         {
-            let mgr = manager.lock().await;
-            mgr.get_active_connections().await
+            list_connections(&manager).await.unwrap_or_default()
         } else {
             vec![]
         };
 
-        let saved_connections = load_saved_connections_for_tray(&app);
+        let saved_connections = crate::config::saved::load_saved_connections()
+            .await
+            .unwrap_or_default();
 
         let has_active = !active_connections.is_empty();
 
@@ -299,18 +300,3 @@ pub fn refresh_tray(app_handle: &AppHandle) {
             }
     });
 }
-
-/// Load saved connections from the store (non-async, for tray menu building).
-fn load_saved_connections_for_tray(app: &AppHandle) -> Vec<SavedConnection> {
-    use tauri_plugin_store::StoreExt;
-
-    let store = match app.store("connections.json") {
-        Ok(s) => s,
-        Err(_) => return vec![],
-    };
-
-    store
-        .get("savedConnections")
-        .and_then(|v| serde_json::from_value(v).ok())
-        .unwrap_or_default()
-}
@@ -0,0 +1,190 @@
+//! Auth-injecting local proxy (`authProxy: true`). Instead of relaying bytes
+//! blindly, each local connection is handed to a small protocol proxy that
+//! answers the client's login itself, logs in upstream with the real
+//! credentials (Secrets Manager secret or RDS IAM token) and then steps out
+//! of the way. Clients connect with no password and never see one.
+//!
+//! The same hook carries the HTTP reverse proxy (`serviceType: "http"`, see
+//! [`crate::tunnel::http_proxy`]), which has no login to answer but rewrites
+//! host names in each request and response (and, for `serviceType:
+//! "sigv4-http"`, signs it), and the Redis proxy (`connectionType: "redis"`,
+//! see [`crate::tunnel::redis_proxy`]), which speaks TLS upstream for
+//! encrypted groups and points cluster clients at local ports.
+//!
+//! The proxy sits in front of the tunnel relay: [`AuthProxy::intercept`]
+//! takes the accepted TCP connection and returns the stream the relay should
+//! carry instead — the proxy's upstream side.
+
+use crate::aws::iam_auth::Sigv4Signer;
+use crate::tunnel::read_only::ReadOnlyGuard;
+use crate::tunnel::redis_proxy::ClusterNodes;
+use crate::tunnel::{http_proxy, mysql_proxy, pg_proxy, redis_proxy};
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream};
+use tokio::net::TcpStream;
+
+/// Bytes buffered between a proxy task and the tunnel relay.
+const RELAY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Wire protocol the proxy speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyProtocol {
+    Postgres,
+    Mysql,
+    Http,
+    Redis,
+}
+
+/// Upstream login used by the proxy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DbCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// Shared by every connection through one tunnel. The IAM token refresher
+/// replaces the password in place; each new client login reads the current
+/// one.
+#[derive(Debug, Clone)]
+pub struct AuthProxy {
+    pub protocol: ProxyProtocol,
+    /// Upstream host name, sent as TLS SNI (and as `Host` for HTTP).
+    pub server_name: String,
+    /// Refuse to send credentials unless the server accepts TLS. IAM auth
+    /// needs this; RDS rejects IAM tokens over plaintext anyway. For HTTP:
+    /// speak HTTPS upstream.
+    pub require_tls: bool,
+    /// Check the upstream certificate against the system roots. On for HTTP
+    /// sites unless `httpTlsSkipVerify`; off for databases and Redis (see
+    /// [`connect_tls`]).
+    pub verify_tls: bool,
+    /// Keep the session read-only and refuse writes (`readOnlyEnvs`).
+    pub read_only: Option<ReadOnlyGuard>,
+    /// HTTP only: sign every request with SigV4 (`serviceType: "sigv4-http"`).
+    pub sigv4: Option<Arc<Sigv4Signer>>,
+    /// Redis only: the cluster's nodes, when replies carry node addresses to
+    /// rewrite (cluster mode).
+    pub cluster: Option<Arc<ClusterNodes>>,
+    credentials: Arc<RwLock<DbCredentials>>,
+}
+
+impl AuthProxy {
+    pub fn new(
+        protocol: ProxyProtocol,
+        server_name: &str,
+        require_tls: bool,
+        credentials: DbCredentials,
+    ) -> Self {
+        Self {
+            protocol,
+            server_name: server_name.to_string(),
+            require_tls,
+            verify_tls: false,
+            read_only: None,
+            sigv4: None,
+            cluster: None,
+            credentials: Arc::new(RwLock::new(credentials)),
+        }
+    }
+
+    /// HTTP reverse proxy for the site `server_name`, over HTTPS if `tls`.
+    /// The site's certificate is verified.
+    pub fn http(server_name: &str, tls: bool) -> Self {
+        Self {
+            verify_tls: true,
+            ..Self::new(
+                ProxyProtocol::Http,
+                server_name,
+                tls,
+                DbCredentials::default(),
+            )
+        }
+    }
+
+    /// Redis proxy for `server_name`, over TLS if `tls`.
+    pub fn redis(server_name: &str, tls: bool, cluster: Option<Arc<ClusterNodes>>) -> Self {
+        Self {
+            cluster,
+            ..Self::new(
+                ProxyProtocol::Redis,
+                server_name,
+                tls,
+                DbCredentials::default(),
+            )
+        }
+    }
+
+    /// The same proxy in front of another server (a cluster node).
+    pub(crate) fn for_server(&self, server_name: &str) -> Self {
+        Self {
+            server_name: server_name.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Credentials for the next upstream login.
+    pub fn credentials(&self) -> DbCredentials {
+        match self.credentials.read() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Swap in a refreshed password (IAM token). Open sessions are not
+    /// affected — a token is only checked at login.
+    pub fn set_password(&self, password: String) {
+        match self.credentials.write() {
+            Ok(mut guard) => guard.password = password,
+            Err(poisoned) => poisoned.into_inner().password = password,
+        }
+    }
+
+    /// Serve `client` through the proxy in the background. The returned
+    /// stream carries the proxy's upstream traffic; relay it over the tunnel
+    /// in place of the client connection.
+    pub fn intercept(&self, client: TcpStream) -> DuplexStream {
+        let (relay_side, upstream) = tokio::io::duplex(RELAY_BUFFER_SIZE);
+        let proxy = self.clone();
+        tokio::spawn(async move {
+            let result = match proxy.protocol {
+                ProxyProtocol::Postgres => pg_proxy::serve(client, upstream, &proxy).await,
+                ProxyProtocol::Mysql => mysql_proxy::serve(client, upstream, &proxy).await,
+                ProxyProtocol::Http => http_proxy::serve(client, upstream, &proxy).await,
+                ProxyProtocol::Redis => redis_proxy::serve(client, upstream, &proxy).await,
+            };
+            if let Err(e) = result {
+                log::warn!("{:?} proxy connection failed: {}", proxy.protocol, e);
+            }
+        });
+        relay_side
+    }
+}
+
+/// Either side of a proxied connection, plain or TLS.
+pub(crate) trait ProxyStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ProxyStream for T {}
+
+/// TLS to the upstream server, inside the tunnel. With `verify` the
+/// certificate must chain to the system roots and match `server_name`.
+/// Without it, like libpq's `sslmode=require`, it is not checked: the RDS CA
+/// is not in system trust stores, and the path to the database already runs
+/// through the authenticated SSM session.
+pub(crate) async fn connect_tls<S>(
+    stream: S,
+    server_name: &str,
+    verify: bool,
+) -> Result<tokio_native_tls::TlsStream<S>, String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let connector = tokio_native_tls::native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(!verify)
+        .danger_accept_invalid_hostnames(!verify)
+        .build()
+        .map_err(|e| format!("Failed to set up TLS: {}", e))?;
+    tokio_native_tls::TlsConnector::from(connector)
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| format!("TLS handshake with {} failed: {}", server_name, e))
+}
@@ -0,0 +1,233 @@
+//! KMS-backed session encryption for the SSM data channel.
+//!
+//! When the session document enables KMS encryption, the agent asks for a
+//! `KMSEncryption` client action during the handshake. The client generates a
+//! 64-byte data key bound to the session and target (encryption context),
+//! keeps the plaintext halves as AES-256-GCM keys, and returns the ciphertext
+//! blob so the agent can decrypt the same key material. The first half
+//! decrypts agent→client traffic, the second half encrypts client→agent
+//! traffic — the agent uses the mirror image.
+//!
+//! Ciphertexts are `nonce (12 bytes) || AES-GCM(ciphertext || tag)`, matching
+//! the Go session-manager-plugin.
+
+use crate::tunnel::protocol::{
+    AgentMessage, EncryptionChallenge, KmsEncryptionResponse, PAYLOAD_OUTPUT,
+};
+use aws_lc_rs::aead::{Aad, Nonce, RandomizedNonceKey, AES_256_GCM, NONCE_LEN};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use sha2::{Digest, Sha256};
+
+/// Data key size requested from KMS: two 32-byte AES-256 keys.
+const DATA_KEY_SIZE: i32 = 64;
+
+/// Everything needed to answer a KMSEncryption handshake action.
+#[derive(Clone)]
+pub struct KmsSessionContext {
+    pub kms: aws_sdk_kms::Client,
+    /// Session ID from StartSession (part of the KMS encryption context).
+    pub session_id: String,
+    /// Instance the session targets (part of the KMS encryption context).
+    pub target_id: String,
+}
+
+/// Negotiated encryption state for a data channel.
+pub struct SessionEncryption {
+    encryption_key: RandomizedNonceKey,
+    decryption_key: RandomizedNonceKey,
+    cipher_text_key: Vec<u8>,
+}
+
+impl SessionEncryption {
+    /// Generate a session data key under `kms_key_id` and derive the channel keys.
+    pub async fn negotiate(ctx: &KmsSessionContext, kms_key_id: &str) -> Result<Self, String> {
+        let response = ctx
+            .kms
+            .generate_data_key()
+            .key_id(kms_key_id)
+            .number_of_bytes(DATA_KEY_SIZE)
+            .encryption_context("aws:ssm:SessionId", &ctx.session_id)
+            .encryption_context("aws:ssm:TargetId", &ctx.target_id)
+            .send()
+            .await
+            .map_err(|e| format!("KMS GenerateDataKey failed: {}", e))?;
+
+        let plaintext = response
+            .plaintext()
+            .ok_or("KMS GenerateDataKey returned no plaintext key")?;
+        let cipher_text_key = response
+            .ciphertext_blob()
+            .ok_or("KMS GenerateDataKey returned no ciphertext blob")?;
+
+        Self::from_data_key(plaintext.as_ref(), cipher_text_key.as_ref().to_vec())
+    }
+
+    /// Build from a plaintext data key (decrypt half first, encrypt half second).
+    pub fn from_data_key(plaintext: &[u8], cipher_text_key: Vec<u8>) -> Result<Self, String> {
+        if plaintext.len() != DATA_KEY_SIZE as usize {
+            return Err(format!(
+                "KMS data key has {} bytes, expected {}",
+                plaintext.len(),
+                DATA_KEY_SIZE
+            ));
+        }
+        let half = plaintext.len() / 2;
+        let decryption_key = RandomizedNonceKey::new(&AES_256_GCM, &plaintext[..half])
+            .map_err(|_| "Invalid session decryption key".to_string())?;
+        let encryption_key = RandomizedNonceKey::new(&AES_256_GCM, &plaintext[half..])
+            .map_err(|_| "Invalid session encryption key".to_string())?;
+        Ok(Self {
+            encryption_key,
+            decryption_key,
+            cipher_text_key,
+        })
+    }
+
+    /// `ActionResult` for the KMSEncryption handshake action.
+    pub fn action_result(&self) -> KmsEncryptionResponse {
+        let hash = Sha256::digest(&self.cipher_text_key);
+        KmsEncryptionResponse {
+            kms_cipher_text_key: BASE64.encode(&self.cipher_text_key),
+            kms_cipher_text_hash: BASE64.encode(hash),
+        }
+    }
+
+    /// Encrypt client→agent payload bytes.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        seal(&self.encryption_key, plaintext)
+    }
+
+    /// Decrypt agent→client payload bytes.
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+        open(&self.decryption_key, ciphertext)
+    }
+
+    /// Answer an EncChallengeRequest: decrypt the challenge with our decrypt
+    /// key and re-encrypt it with our encrypt key, proving we hold both halves.
+    /// Returns the base64 challenge for the EncChallengeResponse payload.
+    pub fn answer_challenge(&self, request_payload: &[u8]) -> Result<String, String> {
+        let request: EncryptionChallenge = serde_json::from_slice(request_payload)
+            .map_err(|e| format!("Invalid encryption challenge: {}", e))?;
+        let challenge = BASE64
+            .decode(&request.challenge)
+            .map_err(|e| format!("Invalid encryption challenge encoding: {}", e))?;
+        let plaintext = self.decrypt(&challenge)?;
+        Ok(BASE64.encode(self.encrypt(&plaintext)?))
+    }
+}
+
+/// Encrypt an outgoing OUTPUT payload when the session negotiated KMS encryption.
+pub fn seal_output(encryption: Option<&SessionEncryption>, data: &[u8]) -> Result<Vec<u8>, String> {
+    match encryption {
+        Some(enc) => enc.encrypt(data),
+        None => Ok(data.to_vec()),
+    }
+}
+
+/// Decrypt an inbound OUTPUT payload in place when the session negotiated KMS
+/// encryption. Flags and other payload types are never encrypted.
+pub fn open_output(encryption: Option<&SessionEncryption>, msg: &mut AgentMessage) -> Result<(), String> {
+    if let Some(enc) = encryption
+        && msg.payload_type == PAYLOAD_OUTPUT
+    {
+        msg.payload = enc.decrypt(&msg.payload)?;
+    }
+    Ok(())
+}
+
+fn seal(key: &RandomizedNonceKey, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut in_out = plaintext.to_vec();
+    let nonce = key
+        .seal_in_place_append_tag(Aad::empty(), &mut in_out)
+        .map_err(|_| "Failed to encrypt payload".to_string())?;
+    let mut out = Vec::with_capacity(NONCE_LEN + in_out.len());
+    out.extend_from_slice(nonce.as_ref());
+    out.extend_from_slice(&in_out);
+    Ok(out)
+}
+
+fn open(key: &RandomizedNonceKey, ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+    if ciphertext.len() < NONCE_LEN {
+        return Err("Encrypted payload shorter than nonce".to_string());
+    }
+    let nonce = Nonce::try_assume_unique_for_key(&ciphertext[..NONCE_LEN])
+        .map_err(|_| "Invalid payload nonce".to_string())?;
+    let mut in_out = ciphertext[NONCE_LEN..].to_vec();
+    let plaintext_len = key
+        .open_in_place(nonce, Aad::empty(), &mut in_out)
+        .map_err(|_| "Failed to decrypt payload (authentication failed)".to_string())?
+        .len();
+    in_out.truncate(plaintext_len);
+    Ok(in_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_key() -> Vec<u8> {
+        (0u8..64).collect()
+    }
+
+    /// The agent holds the same data key with the halves swapped.
+    fn agent_side() -> SessionEncryption {
+        let key = data_key();
+        let mut swapped = key[32..].to_vec();
+        swapped.extend_from_slice(&key[..32]);
+        SessionEncryption::from_data_key(&swapped, vec![]).unwrap()
+    }
+
+    #[test]
+    fn client_and_agent_roundtrip() {
+        let client = SessionEncryption::from_data_key(&data_key(), b"blob".to_vec()).unwrap();
+        let agent = agent_side();
+
+        let sealed = client.encrypt(b"SELECT 1;").unwrap();
+        assert_eq!(sealed.len(), NONCE_LEN + 9 + 16);
+        assert_eq!(agent.decrypt(&sealed).unwrap(), b"SELECT 1;");
+
+        let reply = agent.encrypt(b"1 row").unwrap();
+        assert_eq!(client.decrypt(&reply).unwrap(), b"1 row");
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let client = SessionEncryption::from_data_key(&data_key(), vec![]).unwrap();
+        let agent = agent_side();
+        let mut sealed = agent.encrypt(b"payload").unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 0x01;
+        assert!(client.decrypt(&sealed).is_err());
+        assert!(client.decrypt(&[0u8; 4]).is_err());
+    }
+
+    #[test]
+    fn challenge_is_reencrypted_with_client_key() {
+        let client = SessionEncryption::from_data_key(&data_key(), vec![]).unwrap();
+        let agent = agent_side();
+
+        let challenge = BASE64.encode(agent.encrypt(b"nonce-challenge").unwrap());
+        let request = serde_json::to_vec(&EncryptionChallenge { challenge }).unwrap();
+        let answer = client.answer_challenge(&request).unwrap();
+
+        let answer_bytes = BASE64.decode(answer).unwrap();
+        assert_eq!(agent.decrypt(&answer_bytes).unwrap(), b"nonce-challenge");
+    }
+
+    #[test]
+    fn action_result_hashes_cipher_text_key() {
+        let client = SessionEncryption::from_data_key(&data_key(), b"ciphertext".to_vec()).unwrap();
+        let result = client.action_result();
+        assert_eq!(result.kms_cipher_text_key, BASE64.encode(b"ciphertext"));
+        assert_eq!(
+            result.kms_cipher_text_hash,
+            BASE64.encode(Sha256::digest(b"ciphertext"))
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_data_key_size() {
+        assert!(SessionEncryption::from_data_key(&[0u8; 32], vec![]).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+//! `onConnect` / `onDisconnect` launch hooks: a command template such as
+//! `psql {{uri}}` or `open vnc://localhost:{{port}}` run when a tunnel becomes
+//! ready or closes.
+//!
+//! Templates are split into arguments *before* substitution and run without a
+//! shell, so a value can never add arguments or commands. Shell syntax is
+//! rejected outright (see [`validate_template`]). The command gets a scrubbed
+//! environment and no stdin; its exit status and output go to history. A hook
+//! still running after [`HOOK_WAIT`] (e.g. one that opens a GUI client) is
+//! left running and recorded as such.
+
+use crate::history::{self, HistoryEntry};
+use crate::tunnel::export;
+use crate::tunnel::manager::ConnectionInfo;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Variables a template may use, as `{{name}}`.
+pub const TEMPLATE_VARIABLES: &[&str] = &[
+    "host",
+    "port",
+    "username",
+    "password",
+    "database",
+    "uri",
+    "remoteHost",
+    "instanceId",
+    "connectionId",
+    "project",
+    "profile",
+];
+
+/// Characters that would mean something to a shell. Nothing runs through a
+/// shell, but a template using them was written expecting one.
+const SHELL_METACHARACTERS: &[char] = &[
+    ';', '|', '&', '$', '`', '<', '>', '(', ')', '\'', '"', '\\', '\n', '\r',
+];
+
+/// Environment variables passed through to hook commands; everything else
+/// (AWS credentials, tokens, ...) is dropped.
+const PASSTHROUGH_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "LANG",
+    "LC_ALL",
+    "TERM",
+    "TMPDIR",
+    "DISPLAY",
+    "WAYLAND_DISPLAY",
+    "XAUTHORITY",
+    "XDG_RUNTIME_DIR",
+    "DBUS_SESSION_BUS_ADDRESS",
+    "SystemRoot",
+    "USERPROFILE",
+];
+
+/// Captured output kept in the history entry.
+const MAX_OUTPUT_CHARS: usize = 1000;
+
+/// How long to wait for a hook's exit status before recording it as running.
+pub const HOOK_WAIT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    Connect,
+    Disconnect,
+}
+
+impl HookEvent {
+    fn config_field(self) -> &'static str {
+        match self {
+            HookEvent::Connect => "onConnect",
+            HookEvent::Disconnect => "onDisconnect",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Variable(String),
+}
+
+/// Split a template into arguments of literal text and `{{variable}}` parts.
+fn parse_template(template: &str) -> Result<Vec<Vec<Part>>, String> {
+    if let Some(c) = template.chars().find(|c| SHELL_METACHARACTERS.contains(c)) {
+        return Err(format!(
+            "must be a plain command without shell syntax (found {:?})",
+            c
+        ));
+    }
+
+    let mut args = Vec::new();
+    for word in template.split_whitespace() {
+        let mut parts = Vec::new();
+        let mut rest = word;
+        while !rest.is_empty() {
+            match rest.find("{{") {
+                Some(0) => {
+                    let end = rest
+                        .find("}}")
+                        .ok_or_else(|| format!("unclosed {{{{ in {:?}", word))?;
+                    let name = &rest[2..end];
+                    if !TEMPLATE_VARIABLES.contains(&name) {
+                        return Err(format!(
+                            "unknown variable {{{{{}}}}} (available: {})",
+                            name,
+                            TEMPLATE_VARIABLES.join(", ")
+                        ));
+                    }
+                    parts.push(Part::Variable(name.to_string()));
+                    rest = &rest[end + 2..];
+                }
+                found => {
+                    let end = found.unwrap_or(rest.len());
+                    let literal = &rest[..end];
+                    if literal.contains('{') || literal.contains('}') {
+                        return Err(format!("stray brace in {:?}", word));
+                    }
+                    parts.push(Part::Literal(literal.to_string()));
+                    rest = &rest[end..];
+                }
+            }
+        }
+        args.push(parts);
+    }
+
+    match args.first() {
+        None => Err("must not be empty".to_string()),
+        Some(program) if program.iter().any(|p| matches!(p, Part::Variable(_))) => {
+            Err("the command itself cannot be a variable".to_string())
+        }
+        Some(_) => Ok(args),
+    }
+}
+
+/// Check a hook template; the error names what is wrong with it.
+pub fn validate_template(template: &str) -> Result<(), String> {
+    parse_template(template).map(|_| ())
+}
+
+/// Values for the template variables. Fields a connection doesn't have
+/// (e.g. `password` for a VNC tunnel) are empty.
+pub fn template_variables(
+    info: &ConnectionInfo,
+    connection_id: &str,
+    project_key: &str,
+    profile: &str,
+) -> HashMap<&'static str, String> {
+    let uri = export::connection_uri(info).unwrap_or_else(|_| {
+        // socks5h: clients resolve names through the proxy
+        let scheme = match info.service_type.as_deref() {
+            Some("socks") => "socks5h",
+            Some("sigv4-http") => "http",
+            other => other.unwrap_or("tcp"),
+        };
+        format!("{}://{}:{}", scheme, info.host, info.port)
+    });
+    HashMap::from([
+        ("host", info.host.clone()),
+        ("port", info.port.clone()),
+        ("username", info.username.clone().unwrap_or_default()),
+        ("password", info.password.clone().unwrap_or_default()),
+        ("database", info.database.clone().unwrap_or_default()),
+        ("uri", uri),
+        ("remoteHost", info.remote_host.clone().unwrap_or_default()),
+        ("instanceId", info.instance_id.clone().unwrap_or_default()),
+        ("connectionId", connection_id.to_string()),
+        ("project", project_key.to_string()),
+        ("profile", profile.to_string()),
+    ])
+}
+
+/// The argv for a template. Each variable stays inside its own argument.
+fn render_args(
+    template: &str,
+    variables: &HashMap<&'static str, String>,
+) -> Result<Vec<String>, String> {
+    Ok(parse_template(template)?
+        .into_iter()
+        .map(|parts| {
+            parts
+                .into_iter()
+                .map(|part| match part {
+                    Part::Literal(text) => text,
+                    Part::Variable(name) => {
+                        variables.get(name.as_str()).cloned().unwrap_or_default()
+                    }
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Run a hook in the background and record the outcome in history. The
+/// rendered command line is never logged — it may contain the password.
+pub fn spawn_hook(
+    event: HookEvent,
+    template: &str,
+    variables: HashMap<&'static str, String>,
+    connection_id: &str,
+    project_key: &str,
+    profile: &str,
+) {
+    let template = template.to_string();
+    let connection_id = connection_id.to_string();
+    let project_key = project_key.to_string();
+    let profile = profile.to_string();
+
+    tokio::spawn(async move {
+        let env: Vec<_> = std::env::vars_os().collect();
+        let outcome = run_hook(&template, &variables, env, HOOK_WAIT).await;
+        let details = match outcome {
+            Ok(summary) => format!("{} `{}`: {}", event.config_field(), template, summary),
+            Err(e) => format!("{} `{}` failed: {}", event.config_field(), template, e),
+        };
+        log::info!(
+            "{} hook for {}: {}",
+            event.config_field(),
+            connection_id,
+            details
+        );
+        history::log_event(HistoryEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            event_type: "hook".to_string(),
+            connection_id,
+            project_key,
+            profile,
+            details: Some(details),
+        })
+        .await;
+    });
+}
+
+/// Run a hook with only the [`PASSTHROUGH_ENV`] part of `env`. Returns the
+/// exit status and output tail, or a note that it is still running after
+/// `wait` — it keeps running, with its output drained and discarded.
+async fn run_hook<E>(
+    template: &str,
+    variables: &HashMap<&'static str, String>,
+    env: E,
+    wait: Duration,
+) -> Result<String, String>
+where
+    E: IntoIterator<Item = (OsString, OsString)>,
+{
+    let args = render_args(template, variables)?;
+    let mut command = tokio::process::Command::new(&args[0]);
+    command
+        .args(&args[1..])
+        .env_clear()
+        .envs(env.into_iter().filter(|(name, _)| {
+            name.to_str()
+                .is_some_and(|name| PASSTHROUGH_ENV.contains(&name))
+        }))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = command.spawn().map_err(|e| e.to_string())?;
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    let finish = async move { tokio::join!(read_tail(stdout), read_tail(stderr), child.wait()) };
+    // A task of its own, so it keeps draining the pipes (and reaps the child)
+    // after we stop waiting for it
+    let (stdout, stderr, status) = match tokio::time::timeout(wait, tokio::spawn(finish)).await {
+        Ok(Ok(finished)) => finished,
+        Ok(Err(e)) => return Err(e.to_string()),
+        Err(_) => return Ok(format!("still running after {:?}", wait)),
+    };
+    let status = status.map_err(|e| e.to_string())?;
+
+    let mut captured = String::from_utf8_lossy(&stdout).into_owned();
+    captured.push_str(&String::from_utf8_lossy(&stderr));
+    let captured = captured.trim();
+
+    let status = match status.code() {
+        Some(code) => format!("exit {}", code),
+        None => "killed by signal".to_string(),
+    };
+    if captured.is_empty() {
+        return Ok(status);
+    }
+    // Keep the tail — that's where errors end up
+    let skip = captured.chars().count().saturating_sub(MAX_OUTPUT_CHARS);
+    let tail: String = captured.chars().skip(skip).collect();
+    Ok(format!("{}: {}", status, tail))
+}
+
+/// Read a pipe to the end, keeping roughly the last [`MAX_OUTPUT_CHARS`].
+async fn read_tail<R: AsyncRead + Unpin>(pipe: Option<R>) -> Vec<u8> {
+    // Chars are up to 4 bytes
+    const KEEP: usize = MAX_OUTPUT_CHARS * 4;
+    let mut tail = Vec::new();
+    let Some(mut pipe) = pipe else {
+        return tail;
+    };
+    let mut buf = [0u8; 4096];
+    loop {
+        match pipe.read(&mut buf).await {
+            Ok(0) | Err(_) => return tail,
+            Ok(n) => {
+                tail.extend_from_slice(&buf[..n]);
+                if tail.len() > 2 * KEEP {
+                    tail.drain(..tail.len() - KEEP);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> HashMap<&'static str, String> {
+        HashMap::from([
+            ("port", "5433".to_string()),
+            ("password", "x; rm -rf ~".to_string()),
+            ("uri", "postgresql://app@localhost:5433/main".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_render_keeps_values_in_one_argument() {
+        assert_eq!(
+            render_args("psql {{uri}}", &variables()).unwrap(),
+            vec!["psql", "postgresql://app@localhost:5433/main"]
+        );
+        assert_eq!(
+            render_args("open vnc://localhost:{{port}}", &variables()).unwrap(),
+            vec!["open", "vnc://localhost:5433"]
+        );
+        assert_eq!(
+            render_args("client --password={{password}} -v", &variables()).unwrap(),
+            vec!["client", "--password=x; rm -rf ~", "-v"]
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_shell_syntax_and_unknown_variables() {
+        assert!(validate_template("xfreerdp /v:localhost:{{port}}").is_ok());
+        for (template, expected) in [
+            ("psql {{uri}}; rm -rf ~", "shell syntax"),
+            ("psql $(cat secret)", "shell syntax"),
+            ("open \"vnc://{{host}}\"", "shell syntax"),
+            ("psql {{url}}", "unknown variable"),
+            ("psql {{uri}", "unclosed"),
+            ("psql uri}}", "stray brace"),
+            ("{{host}} --help", "cannot be a variable"),
+            ("   ", "must not be empty"),
+        ] {
+            let err = validate_template(template).unwrap_err();
+            assert!(err.contains(expected), "{template}: {err}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_hook_scrubs_environment() {
+        let env = [
+            ("PATH", std::env::var("PATH").unwrap_or_default()),
+            ("HOME", "/home/hook".to_string()),
+            ("AWS_SECRET_ACCESS_KEY", "leak".to_string()),
+        ]
+        .map(|(name, value)| (OsString::from(name), OsString::from(value)));
+        let output = run_hook("env", &HashMap::new(), env, HOOK_WAIT)
+            .await
+            .unwrap();
+        assert!(output.starts_with("exit 0"), "{output}");
+        assert!(output.contains("HOME=/home/hook"), "{output}");
+        assert!(!output.contains("AWS_SECRET_ACCESS_KEY"), "{output}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_hook_leaves_long_running_commands_running() {
+        let env = std::env::vars_os();
+        let output = run_hook("sleep 5", &HashMap::new(), env, Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert_eq!(output, "still running after 100ms");
+    }
+}
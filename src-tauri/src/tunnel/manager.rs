@@ -1,20 +1,29 @@
 use crate::aws::credentials::{build_aws_config, create_aws_clients, AwsClients};
 use crate::aws::iam_auth;
+use crate::aws::instance_connect::{self, EphemeralSshKey};
 use crate::aws::operations;
-use crate::aws::sso::{ensure_sso_session, TauriSsoHandler};
+use crate::aws::sso::{ensure_sso_session, CliSsoHandler, SsoEventHandler};
 use crate::config::preferences;
 use crate::config::projects::{
-    get_default_port_for_engine, get_local_port, load_project_configs, ProjectConfig,
+    get_default_port_for_engine, get_local_port, is_read_only_env, load_project_configs,
+    ProjectConfig,
 };
 use crate::error::AppError;
 use crate::history::{self, HistoryEntry};
+use crate::tunnel::auth_proxy::{AuthProxy, DbCredentials, ProxyProtocol};
+use crate::tunnel::encryption::KmsSessionContext;
+use crate::tunnel::export;
+use crate::tunnel::hooks::{self, HookEvent};
 use crate::tunnel::native;
+use crate::tunnel::read_only::ReadOnlyGuard;
+use crate::tunnel::redis_proxy::{self, ClusterNodes};
+use crate::tunnel::socks::{self, SessionTokens, SocksAllowList};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter, Manager};
-use tauri_plugin_notification::NotificationExt;
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
 
@@ -36,7 +45,174 @@ static HOSTNAME_PATTERN: std::sync::LazyLock<Regex> =
 static IP_PATTERN: std::sync::LazyLock<Regex> =
     std::sync::LazyLock::new(|| Regex::new(r"^(\d{1,3}\.){3}\d{1,3}$").unwrap());
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Trait for tunnel lifecycle callbacks. Allows decoupling from Tauri so the
+/// GUI and the CLI run the same engine.
+pub trait TunnelEventHandler: Send + Sync {
+    /// Progress message during connect / reconnect.
+    fn on_status(&self, message: &str, connection_id: Option<&str>);
+    /// User-facing notification (connection lost, reconnected, failed).
+    fn on_notification(&self, title: &str, body: &str);
+    /// Result of a periodic health check: "healthy", "unhealthy" or "degraded".
+    fn on_health(&self, connection_id: &str, status: &str, last_check_ms: u64);
+    /// The tunnel failed permanently (after retries).
+    fn on_error(&self, connection_id: &str, message: &str);
+    /// The tunnel is gone; `reason` is "session_ended" or "error".
+    fn on_disconnected(&self, connection_id: &str, reason: &str);
+    /// Auto-reconnect is about to make attempt `attempt` of `max_attempts`.
+    fn on_reconnecting(&self, _connection_id: &str, _attempt: u32, _max_attempts: u32) {}
+    /// Auto-reconnect found the target again and is restarting port forwarding.
+    fn on_reconnected(&self, _connection_id: &str) {}
+    /// The connection's credentials changed (its IAM auth token was regenerated).
+    fn on_credentials_refreshed(&self, _connection_id: &str, _info: &ConnectionInfo) {}
+    /// Handler used for the SSO pre-flight.
+    fn sso_handler(&self) -> &dyn SsoEventHandler;
+}
+
+/// GUI event handler (Tauri).
+#[cfg(feature = "gui")]
+pub struct TauriTunnelHandler {
+    pub app_handle: tauri::AppHandle,
+    sso: crate::aws::sso::TauriSsoHandler,
+}
+
+#[cfg(feature = "gui")]
+impl TauriTunnelHandler {
+    pub fn new(app_handle: tauri::AppHandle) -> Self {
+        Self {
+            sso: crate::aws::sso::TauriSsoHandler {
+                app_handle: app_handle.clone(),
+            },
+            app_handle,
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl TunnelEventHandler for TauriTunnelHandler {
+    fn on_status(&self, message: &str, connection_id: Option<&str>) {
+        use tauri::Emitter;
+        let mut payload = serde_json::json!({ "message": message });
+        if let Some(id) = connection_id {
+            payload["connectionId"] = serde_json::json!(id);
+        }
+        let _ = self.app_handle.emit("status", &payload);
+    }
+
+    /// Send a desktop notification only when no app window is focused.
+    /// Failures are silently ignored so notifications are never fatal.
+    fn on_notification(&self, title: &str, body: &str) {
+        use tauri::Manager;
+        use tauri_plugin_notification::NotificationExt;
+        // Skip notification if any app window is currently focused
+        if self
+            .app_handle
+            .webview_windows()
+            .values()
+            .any(|w| w.is_focused().unwrap_or(false))
+        {
+            return;
+        }
+        let _ = self
+            .app_handle
+            .notification()
+            .builder()
+            .title(title)
+            .body(body)
+            .show();
+    }
+
+    fn on_health(&self, connection_id: &str, status: &str, last_check_ms: u64) {
+        use tauri::Emitter;
+        let _ = self.app_handle.emit(
+            "connection-health",
+            serde_json::json!({
+                "connectionId": connection_id,
+                "status": status,
+                "lastCheck": last_check_ms,
+            }),
+        );
+    }
+
+    fn on_error(&self, connection_id: &str, message: &str) {
+        use tauri::Emitter;
+        let _ = self.app_handle.emit(
+            "connection-error",
+            serde_json::json!({
+                "connectionId": connection_id,
+                "message": message
+            }),
+        );
+    }
+
+    fn on_disconnected(&self, connection_id: &str, reason: &str) {
+        use tauri::Emitter;
+        let _ = self.app_handle.emit(
+            "disconnected",
+            serde_json::json!({
+                "connectionId": connection_id,
+                "reason": reason
+            }),
+        );
+    }
+
+    fn on_credentials_refreshed(&self, connection_id: &str, info: &ConnectionInfo) {
+        use tauri::Emitter;
+        let _ = self.app_handle.emit(
+            "credentials-refreshed",
+            serde_json::json!({
+                "connectionId": connection_id,
+                "connectionInfo": info,
+            }),
+        );
+    }
+
+    fn sso_handler(&self) -> &dyn SsoEventHandler {
+        &self.sso
+    }
+}
+
+/// CLI event handler (prints to stderr). Keeps the last tunnel error so the
+/// caller can turn it into an exit status.
+#[derive(Default)]
+pub struct CliTunnelHandler {
+    last_error: std::sync::Mutex<Option<String>>,
+}
+
+impl CliTunnelHandler {
+    pub fn take_error(&self) -> Option<String> {
+        self.last_error.lock().ok().and_then(|mut e| e.take())
+    }
+}
+
+impl TunnelEventHandler for CliTunnelHandler {
+    fn on_status(&self, message: &str, _connection_id: Option<&str>) {
+        eprintln!("  \u{23F3} {}", message);
+    }
+
+    fn on_notification(&self, title: &str, body: &str) {
+        eprintln!("  \u{1F514} {}: {}", title, body);
+    }
+
+    fn on_health(&self, _connection_id: &str, status: &str, _last_check_ms: u64) {
+        if status != "healthy" {
+            eprintln!("  \u{26A0}\u{FE0F}  Tunnel health check: {}", status);
+        }
+    }
+
+    fn on_error(&self, _connection_id: &str, message: &str) {
+        if let Ok(mut e) = self.last_error.lock() {
+            *e = Some(message.to_string());
+        }
+    }
+
+    fn on_disconnected(&self, _connection_id: &str, _reason: &str) {}
+
+    fn sso_handler(&self) -> &dyn SsoEventHandler {
+        &CliSsoHandler
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ConnectionInfo {
     pub host: String,
     pub port: String,
@@ -65,6 +241,20 @@ pub struct ConnectionInfo {
     // SSH-specific: pre-built SSH command for copy-paste
     #[serde(rename = "sshCommand", skip_serializing_if = "Option::is_none")]
     pub ssh_command: Option<String>,
+    // EKS-specific: kubeconfig written for the tunnel (removed when it closes)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kubeconfig: Option<String>,
+    /// Logins go through the local auth proxy; no password is handed out.
+    #[serde(rename = "authProxy", default, skip_serializing_if = "std::ops::Not::not")]
+    pub auth_proxy: bool,
+}
+
+/// Ephemeral EC2 Instance Connect key for an `sshKeyMode: "instance-connect"`
+/// connection. Dropping it deletes the private key file.
+struct PushedSshKey {
+    key: EphemeralSshKey,
+    instance_id: String,
+    os_user: String,
 }
 
 #[derive(Debug, Clone)]
@@ -74,13 +264,81 @@ pub struct Connection {
     pub profile: String,
     pub local_port: String,
     pub connection_info: ConnectionInfo,
+    /// RFC 3339 timestamp of when the connection was opened.
+    pub connected_at: String,
     cancel_token: CancellationToken,
+    /// Cancelled once the tunnel task has finished and cleaned up.
+    closed: CancellationToken,
 }
 
-/// Manages all active tunnel connections.
+/// Manages all active tunnel connections. Clones are handles to the same
+/// connections.
+#[derive(Clone)]
 pub struct TunnelManager {
     connections: Arc<Mutex<HashMap<String, Connection>>>,
-    app_handle: AppHandle,
+    /// Addresses picked by connects that are still starting up.
+    reserved_ports: Arc<std::sync::Mutex<HashSet<SocketAddrV4>>>,
+    events: Arc<dyn TunnelEventHandler>,
+}
+
+/// A local port held for a connect in progress; released on drop.
+struct PortReservation {
+    port: u16,
+    address: Ipv4Addr,
+    reserved: Arc<std::sync::Mutex<HashSet<SocketAddrV4>>>,
+}
+
+impl Drop for PortReservation {
+    fn drop(&mut self) {
+        if let Ok(mut reserved) = self.reserved.lock() {
+            reserved.remove(&SocketAddrV4::new(self.address, self.port));
+        }
+    }
+}
+
+/// Host clients use for a tunnel listening on `address`.
+fn local_host(address: Ipv4Addr) -> String {
+    if address == Ipv4Addr::LOCALHOST {
+        "localhost".to_string()
+    } else {
+        address.to_string()
+    }
+}
+
+/// Address a connection's listener is bound to (see [`local_host`]).
+fn bind_address_of(info: &ConnectionInfo) -> Ipv4Addr {
+    info.host.parse().unwrap_or(Ipv4Addr::LOCALHOST)
+}
+
+/// Reject remote hosts that are neither a plain hostname nor an IPv4 address.
+fn validate_remote_host(host: &str) -> Result<(), AppError> {
+    let valid = if host.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        IP_PATTERN.is_match(host)
+            && host.split('.').all(|octet| octet.parse::<u8>().is_ok())
+    } else {
+        HOSTNAME_PATTERN.is_match(host)
+            && !host.starts_with(['.', '-'])
+            && !host.ends_with('-')
+    };
+    if !valid {
+        return Err(AppError::General(format!("Invalid remote host: {}", host)));
+    }
+    Ok(())
+}
+
+/// Parse an ad-hoc forward target such as `10.0.1.25:6379` or
+/// `internal-alb.example.com:443`.
+pub fn parse_forward_target(target: &str) -> Result<(String, u16), AppError> {
+    let (host, port) = target.rsplit_once(':').ok_or_else(|| {
+        AppError::General(format!("Expected host:port, got: {}", target))
+    })?;
+    validate_remote_host(host)?;
+    let port = port
+        .parse::<u16>()
+        .ok()
+        .filter(|p| *p != 0)
+        .ok_or_else(|| AppError::General(format!("Invalid remote port: {}", port)))?;
+    Ok((host.to_string(), port))
 }
 
 /// Attempt to identify which process is holding a given port.
@@ -175,18 +433,19 @@ fn get_port_holder(port: u16) -> Option<String> {
 }
 
 impl TunnelManager {
-    pub fn new(app_handle: AppHandle) -> Self {
+    pub fn new(events: Arc<dyn TunnelEventHandler>) -> Self {
         Self {
             connections: Arc::new(Mutex::new(HashMap::new())),
-            app_handle,
+            reserved_ports: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            events,
         }
     }
 
     /// Check if a port is available by attempting to bind with SO_REUSEADDR.
     /// Using SO_REUSEADDR is critical on Linux where TIME_WAIT sockets from
     /// previous connections can block a plain bind for up to 60 seconds.
-    fn is_port_available(port: u16) -> bool {
-        let addr = std::net::SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, port));
+    fn is_port_available(address: Ipv4Addr, port: u16) -> bool {
+        let addr = std::net::SocketAddr::from((address, port));
         let socket = match socket2::Socket::new(
             socket2::Domain::IPV4,
             socket2::Type::STREAM,
@@ -217,6 +476,7 @@ impl TunnelManager {
                 local_port: c.local_port.clone(),
                 connection_info: c.connection_info.clone(),
                 status: "connected".to_string(),
+                connected_at: c.connected_at.clone(),
             })
             .collect()
     }
@@ -244,34 +504,248 @@ impl TunnelManager {
             AppError::Config(format!("Unknown project: {}", project_key))
         })?;
 
-        // Determine port
+        // Determine address and port
+        let bind_address = Self::bind_address(project_key, profile, project_config).await;
         let requested_port = local_port
             .map(|p| p.to_string())
             .unwrap_or_else(|| get_local_port(profile, project_config));
+        // Held until the connection is registered (or has failed)
+        let reservation = self
+            .allocate_port(bind_address, &requested_port, local_port.is_some(), used_ports)
+            .await?;
+        let port_to_use = reservation.port.to_string();
+
+        // Generate connection ID
+        let connection_id = format!("conn_{}", &uuid::Uuid::new_v4().to_string()[..8]);
+
+        // SSO pre-flight
+        ensure_sso_session(profile, self.events.sso_handler(), Some(&connection_id)).await?;
+
+        // Create AWS clients
+        let clients = create_aws_clients(profile, &project_config.region).await;
+
+        // Dispatch based on connection type
+        let local_addr = SocketAddrV4::new(bind_address, reservation.port);
+        let (connection_info, tunnel_target, ssh_key) = match project_config.connection_type.as_str() {
+            "service" if project_config.service_type.as_deref() == Some("socks") => {
+                let (info, target) = self.resolve_socks_target(&clients, &connection_id, project_key, profile, project_config, local_addr).await?;
+                (info, target, None)
+            }
+            "service" => self.resolve_service_target(&clients, &connection_id, project_key, profile, project_config, local_addr).await?,
+            "eks" => {
+                let (info, target) = self.resolve_eks_target(&clients, &connection_id, project_key, profile, project_config, local_addr).await?;
+                (info, target, None)
+            }
+            "redis" => {
+                let (info, target) = self.resolve_redis_target(&clients, &connection_id, project_key, profile, project_config, local_addr).await?;
+                (info, target, None)
+            }
+            _ => {
+                let (info, target) = self.resolve_rds_target(&clients, &connection_id, project_key, profile, project_config, local_addr, database).await?;
+                (info, target, None)
+            }
+        };
+
+        self.launch(
+            connection_id,
+            project_key,
+            profile,
+            project_config,
+            clients,
+            port_to_use,
+            connection_info,
+            tunnel_target,
+            ssh_key,
+        )
+        .await
+    }
+
+    /// Forward a local port to an arbitrary `remote_host:remote_port` through
+    /// the project's bastion, reusing its region, SSO flow and bastion pattern.
+    /// Defaults to the remote port locally (the project's port for privileged ones).
+    pub async fn forward(
+        &self,
+        project_key: &str,
+        profile: &str,
+        remote_host: &str,
+        remote_port: u16,
+        local_port: Option<&str>,
+        used_ports: &[String],
+    ) -> Result<(String, ConnectionInfo), AppError> {
+        if !PROFILE_SAFE_PATTERN.is_match(profile) {
+            return Err(AppError::General(format!(
+                "Invalid profile name: {}",
+                profile
+            )));
+        }
+        validate_remote_host(remote_host)?;
+        if remote_port == 0 {
+            return Err(AppError::General("Invalid remote port: 0".to_string()));
+        }
+
+        let configs = load_project_configs().await?;
+        let project_config = configs.get(project_key).ok_or_else(|| {
+            AppError::Config(format!("Unknown project: {}", project_key))
+        })?;
+
+        let requested_port = match local_port {
+            Some(port) => port.to_string(),
+            None if remote_port >= 1024 => remote_port.to_string(),
+            None => get_local_port(profile, project_config),
+        };
+        let bind_address = Self::bind_address(project_key, profile, project_config).await;
+        // Held until the connection is registered (or has failed)
+        let reservation = self
+            .allocate_port(bind_address, &requested_port, local_port.is_some(), used_ports)
+            .await?;
+        let port_to_use = reservation.port.to_string();
+
+        let connection_id = format!("conn_{}", &uuid::Uuid::new_v4().to_string()[..8]);
 
+        ensure_sso_session(profile, self.events.sso_handler(), Some(&connection_id)).await?;
+        let clients = create_aws_clients(profile, &project_config.region).await;
+
+        self.emit_status("Finding bastion instance...", Some(&connection_id));
+        let prefs = preferences::load_preferences().await;
+        let preferred = preferences::get_preferred_bastion(&prefs, project_key, profile)
+            .map(|s| s.to_string());
+        let bastion_id = operations::find_bastion_instance(
+            &clients,
+            project_config.bastion_pattern(),
+            preferred.as_deref(),
+        )
+        .await?;
+
+        // Save bastion preference
+        preferences::update_preferences(|prefs| {
+            preferences::set_preferred_bastion(prefs, project_key, profile, &bastion_id)
+        })
+        .await;
+
+        if !INSTANCE_ID_PATTERN.is_match(&bastion_id) {
+            return Err(AppError::Aws(format!("Invalid bastion instance ID format: {}", bastion_id)));
+        }
+
+        let connection_info = ConnectionInfo {
+            host: local_host(bind_address),
+            port: port_to_use.clone(),
+            connection_type: "forward".to_string(),
+            username: None,
+            password: None,
+            database: None,
+            rds_endpoint: None,
+            instance_id: Some(bastion_id.clone()),
+            service_type: None,
+            remote_host: Some(format!("{}:{}", remote_host, remote_port)),
+            target_type: None,
+            engine: None,
+            ssh_command: None,
+            kubeconfig: None,
+            auth_proxy: false,
+        };
+
+        let tunnel_target = TunnelTarget::RemoteHost {
+            bastion_id,
+            remote_host: remote_host.to_string(),
+            remote_port: remote_port.to_string(),
+            multiplexed: project_config.multiplexed.unwrap_or(true),
+            fixed_host: true,
+            auth_proxy: None,
+        };
+
+        self.launch(
+            connection_id,
+            project_key,
+            profile,
+            project_config,
+            clients,
+            port_to_use,
+            connection_info,
+            tunnel_target,
+            None,
+        )
+        .await
+    }
+
+    /// The project's loopback alias for `profile` with `loopbackAlias`
+    /// (assigned on first use and kept in preferences), otherwise 127.0.0.1.
+    /// Falls back to 127.0.0.1 where the alias isn't usable: macOS only has
+    /// 127.0.0.1 until `ifconfig lo0 alias` adds more.
+    async fn bind_address(project_key: &str, profile: &str, project_config: &ProjectConfig) -> Ipv4Addr {
+        if project_config.loopback_alias != Some(true) {
+            return Ipv4Addr::LOCALHOST;
+        }
+        let assigned = preferences::update_preferences(|prefs| {
+            preferences::assign_loopback_alias(prefs, project_key, profile)
+        })
+        .await;
+        let Some(address) = assigned else {
+            log::warn!("No loopback aliases left; using 127.0.0.1");
+            return Ipv4Addr::LOCALHOST;
+        };
+        match std::net::TcpListener::bind((address, 0)) {
+            Ok(_) => address,
+            Err(e) => {
+                log::warn!(
+                    "Cannot listen on {} ({}); using 127.0.0.1. On macOS add it with `sudo ifconfig lo0 alias {} up`",
+                    address, e, address
+                );
+                Ipv4Addr::LOCALHOST
+            }
+        }
+    }
+
+    /// Pick and reserve the local port on `bind_address`: `requested_port` if
+    /// free, otherwise the next free one above it — unless the user asked for
+    /// that exact port. Tunnels on other loopback aliases don't compete.
+    async fn allocate_port(
+        &self,
+        bind_address: Ipv4Addr,
+        requested_port: &str,
+        user_specified_port: bool,
+        used_ports: &[String],
+    ) -> Result<PortReservation, AppError> {
         let mut port_num: u16 = requested_port
             .parse()
             .map_err(|_| AppError::General(format!("Invalid port number: {}", requested_port)))?;
 
         // Collect ports already used by our active connections
-        let all_used_ports: std::collections::HashSet<u16> = {
+        let active_ports: Vec<u16> = {
             let guard = self.connections.lock().await;
-            let active_ports: Vec<u16> = guard
+            guard
                 .values()
+                .filter(|c| bind_address_of(&c.connection_info) == bind_address)
                 .filter_map(|c| c.local_port.parse().ok())
-                .collect();
-
-            used_ports
-                .iter()
-                .filter_map(|p| p.parse().ok())
-                .chain(active_ports)
                 .collect()
         };
+        // The caller's list is ports on 127.0.0.1
+        let used_ports = if bind_address == Ipv4Addr::LOCALHOST {
+            used_ports
+        } else {
+            &[]
+        };
+
+        // Locked until the chosen port is reserved, so concurrent connects
+        // (e.g. a manifest of tunnels) never pick the same port.
+        let mut reserved = self
+            .reserved_ports
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let all_used_ports: HashSet<u16> = used_ports
+            .iter()
+            .filter_map(|p| p.parse().ok())
+            .chain(active_ports)
+            .chain(
+                reserved
+                    .iter()
+                    .filter(|addr| *addr.ip() == bind_address)
+                    .map(|addr| addr.port()),
+            )
+            .collect();
 
         // If our own app holds the port, auto-increment to find the next free one.
         // If an external process holds it, report the error so the user can decide.
-        let user_specified_port = local_port.is_some();
-        if all_used_ports.contains(&port_num) || !Self::is_port_available(port_num) {
+        if all_used_ports.contains(&port_num) || !Self::is_port_available(bind_address, port_num) {
             if user_specified_port {
                 // User explicitly requested this port — don't auto-increment
                 let holder = get_port_holder(port_num)
@@ -289,7 +763,9 @@ impl TunnelManager {
                 if candidate > 65534 {
                     break;
                 }
-                if !all_used_ports.contains(&candidate) && Self::is_port_available(candidate) {
+                if !all_used_ports.contains(&candidate)
+                    && Self::is_port_available(bind_address, candidate)
+                {
                     log::info!(
                         "Port {} in use, auto-assigned port {} instead",
                         port_num, candidate
@@ -309,27 +785,31 @@ impl TunnelManager {
             }
         }
 
-        let port_to_use = port_num.to_string();
-
-        // Generate connection ID
-        let connection_id = format!("conn_{}", &uuid::Uuid::new_v4().to_string()[..8]);
-
-        // SSO pre-flight
-        let sso_handler = TauriSsoHandler {
-            app_handle: self.app_handle.clone(),
-        };
-        ensure_sso_session(profile, &sso_handler, Some(&connection_id)).await?;
-
-        // Create AWS clients
-        let clients = create_aws_clients(profile, &project_config.region).await;
-
-        // Dispatch based on connection type
-        let (connection_info, tunnel_target) = match project_config.connection_type.as_str() {
-            "service" => self.resolve_service_target(&clients, &connection_id, project_key, profile, project_config, &port_to_use).await?,
-            _ => self.resolve_rds_target(&clients, &connection_id, project_key, profile, project_config, &port_to_use, database).await?,
-        };
+        reserved.insert(SocketAddrV4::new(bind_address, port_num));
+        Ok(PortReservation {
+            port: port_num,
+            address: bind_address,
+            reserved: self.reserved_ports.clone(),
+        })
+    }
 
+    /// Register the connection, run its tunnel lifecycle in the background and
+    /// wait until the local listener is bound.
+    #[allow(clippy::too_many_arguments)]
+    async fn launch(
+        &self,
+        connection_id: String,
+        project_key: &str,
+        profile: &str,
+        project_config: &ProjectConfig,
+        clients: AwsClients,
+        port_to_use: String,
+        connection_info: ConnectionInfo,
+        tunnel_target: TunnelTarget,
+        ssh_key: Option<PushedSshKey>,
+    ) -> Result<(String, ConnectionInfo), AppError> {
         let cancel_token = CancellationToken::new();
+        let closed = CancellationToken::new();
 
         let connection = Connection {
             id: connection_id.clone(),
@@ -337,7 +817,9 @@ impl TunnelManager {
             profile: profile.to_string(),
             local_port: port_to_use.clone(),
             connection_info: connection_info.clone(),
+            connected_at: chrono::Utc::now().to_rfc3339(),
             cancel_token: cancel_token.clone(),
+            closed: closed.clone(),
         };
 
         // Store connection
@@ -349,25 +831,78 @@ impl TunnelManager {
         // Channel to signal when the tunnel is actually ready
         let (ready_tx, ready_rx) = tokio::sync::oneshot::channel::<Result<(), String>>();
 
+        let local_addr = SocketAddrV4::new(
+            bind_address_of(&connection_info),
+            port_to_use.parse().unwrap_or_default(),
+        );
         // Clone values needed for health check (before they're moved into spawn)
-        let health_port = port_to_use.clone();
         let health_cancel = cancel_token.clone();
 
         // Spawn background task for port forwarding lifecycle
-        let app_handle = self.app_handle.clone();
+        let events = self.events.clone();
         let connections = self.connections.clone();
         let conn_id = connection_id.clone();
         let project_key_owned = project_key.to_string();
         let profile_owned = profile.to_string();
         let project_config = project_config.clone();
+        // Ad-hoc forwards only borrow the project's bastion; its hooks are for
+        // the project's own target.
+        let (on_connect, on_disconnect) = if connection_info.connection_type == "forward" {
+            (None, None)
+        } else {
+            (
+                project_config.on_connect.clone().filter(|t| !t.is_empty()),
+                project_config.on_disconnect.clone().filter(|t| !t.is_empty()),
+            )
+        };
+        let hook_info = connection_info.clone();
+        // IAM tokens expire after 15 minutes; keep the one we hand out fresh
+        let token_source = match tunnel_target {
+            TunnelTarget::RemoteHost {
+                ref remote_host,
+                ref remote_port,
+                ref auth_proxy,
+                ..
+            } if project_config.auth_type == "iam" && connection_info.connection_type == "rds" => {
+                match (&connection_info.username, remote_port.parse::<u16>()) {
+                    (Some(username), Ok(port)) => Some(IamTokenSource {
+                        profile: profile.to_string(),
+                        region: project_config.region.clone(),
+                        hostname: remote_host.clone(),
+                        port,
+                        username: username.clone(),
+                        auth_proxy: auth_proxy.clone(),
+                    }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        // onDisconnect only pairs with a tunnel that became ready
+        let became_ready = Arc::new(AtomicBool::new(false));
+        let hook_ready = became_ready.clone();
 
         tokio::spawn(async move {
             let conn_label = format!("{} {}", project_key_owned, profile_owned);
+
+            // Instance Connect keys expire after 60s — keep re-pushing (and
+            // keep the key file) for as long as the tunnel is up.
+            let key_cancel = cancel_token.child_token();
+            if let Some(ref pushed) = ssh_key {
+                tokio::spawn(instance_connect::keep_ssh_public_key_pushed(
+                    clients.ec2_instance_connect.clone(),
+                    pushed.instance_id.clone(),
+                    pushed.os_user.clone(),
+                    pushed.key.public_key.clone(),
+                    key_cancel.clone(),
+                ));
+            }
+
             let result = run_tunnel_lifecycle(
-                &app_handle,
+                events.as_ref(),
                 &clients,
                 &conn_id,
-                &port_to_use,
+                local_addr,
                 &project_config,
                 tunnel_target,
                 cancel_token.clone(),
@@ -378,6 +913,28 @@ impl TunnelManager {
             )
             .await;
 
+            key_cancel.cancel();
+            drop(ssh_key);
+            export::remove_exports(&conn_id);
+
+            if let Some(ref template) = on_disconnect
+                && hook_ready.load(Ordering::SeqCst)
+            {
+                hooks::spawn_hook(
+                    HookEvent::Disconnect,
+                    template,
+                    hooks::template_variables(
+                        &hook_info,
+                        &conn_id,
+                        &project_key_owned,
+                        &profile_owned,
+                    ),
+                    &conn_id,
+                    &project_key_owned,
+                    &profile_owned,
+                );
+            }
+
             // Clean up connection
             {
                 let mut guard = connections.lock().await;
@@ -397,19 +954,12 @@ impl TunnelManager {
                     .await;
                     // Only notify if this was not a user-initiated disconnect
                     if !cancel_token.is_cancelled() {
-                        send_notification(
-                            &app_handle,
+                        events.on_notification(
                             "Connection Lost",
                             &format!("{} disconnected", conn_label),
                         );
                     }
-                    let _ = app_handle.emit(
-                        "disconnected",
-                        serde_json::json!({
-                            "connectionId": conn_id,
-                            "reason": "session_ended"
-                        }),
-                    );
+                    events.on_disconnected(&conn_id, "session_ended");
                 }
                 Err(e) => {
                     history::log_event(HistoryEntry {
@@ -421,27 +971,15 @@ impl TunnelManager {
                         details: Some(e.to_string()),
                     })
                     .await;
-                    send_notification(
-                        &app_handle,
+                    events.on_notification(
                         "Connection Failed",
                         &format!("{}: {}", conn_label, e),
                     );
-                    let _ = app_handle.emit(
-                        "connection-error",
-                        serde_json::json!({
-                            "connectionId": conn_id,
-                            "message": e.to_string()
-                        }),
-                    );
-                    let _ = app_handle.emit(
-                        "disconnected",
-                        serde_json::json!({
-                            "connectionId": conn_id,
-                            "reason": "error"
-                        }),
-                    );
+                    events.on_error(&conn_id, &e.to_string());
+                    events.on_disconnected(&conn_id, "error");
                 }
             }
+            closed.cancel();
         });
 
         self.emit_status("Starting port forwarding...", Some(&connection_id));
@@ -460,11 +998,37 @@ impl TunnelManager {
                 .await;
                 // Tunnel is ready — start periodic health checks
                 Self::spawn_health_check(
-                    &self.app_handle,
+                    &self.events,
                     &connection_id,
-                    &health_port,
+                    local_addr,
                     &health_cancel,
                 );
+                if let Some(source) = token_source {
+                    Self::spawn_token_refresher(
+                        &self.events,
+                        &self.connections,
+                        &connection_id,
+                        export::service_name(project_key, profile),
+                        source,
+                        &health_cancel,
+                    );
+                }
+                if let Some(ref template) = on_connect {
+                    hooks::spawn_hook(
+                        HookEvent::Connect,
+                        template,
+                        hooks::template_variables(
+                            &connection_info,
+                            &connection_id,
+                            project_key,
+                            profile,
+                        ),
+                        &connection_id,
+                        project_key,
+                        profile,
+                    );
+                }
+                became_ready.store(true, Ordering::SeqCst);
                 Ok((connection_id, connection_info))
             }
             Ok(Ok(Err(e))) => {
@@ -498,7 +1062,7 @@ impl TunnelManager {
         project_key: &str,
         profile: &str,
         project_config: &ProjectConfig,
-        local_port: &str,
+        local_addr: SocketAddrV4,
         selected_database: Option<&str>,
     ) -> Result<(ConnectionInfo, TunnelTarget), AppError> {
         let effective_db = project_config.effective_database(selected_database);
@@ -515,11 +1079,10 @@ impl TunnelManager {
         .await?;
 
         // Save bastion preference for next time
-        {
-            let mut prefs = prefs;
-            preferences::set_preferred_bastion(&mut prefs, project_key, profile, &instance_id);
-            preferences::save_preferences(&prefs).await;
-        }
+        preferences::update_preferences(|prefs| {
+            preferences::set_preferred_bastion(prefs, project_key, profile, &instance_id)
+        })
+        .await;
 
         if !INSTANCE_ID_PATTERN.is_match(&instance_id) {
             return Err(AppError::Aws(format!(
@@ -602,12 +1165,34 @@ impl TunnelManager {
             }
         };
 
+        // With the auth proxy the password never leaves the app
+        let auth_proxy = (project_config.auth_proxy == Some(true)).then(|| {
+            let protocol = match project_config.engine.as_deref() {
+                Some("mysql") => ProxyProtocol::Mysql,
+                _ => ProxyProtocol::Postgres,
+            };
+            let mut proxy = AuthProxy::new(
+                protocol,
+                &rds_endpoint,
+                auth_type == "iam",
+                DbCredentials {
+                    username: username.clone(),
+                    password: password.clone(),
+                },
+            );
+            if is_read_only_env(profile, project_config) {
+                log::info!("Read-only guard enabled for {}", connection_id);
+                proxy.read_only = Some(ReadOnlyGuard::new(connection_id, project_key, profile));
+            }
+            proxy
+        });
+
         let connection_info = ConnectionInfo {
-            host: "localhost".to_string(),
-            port: local_port.to_string(),
+            host: local_host(*local_addr.ip()),
+            port: local_addr.port().to_string(),
             connection_type: "rds".to_string(),
             username: Some(username),
-            password: Some(password),
+            password: auth_proxy.is_none().then_some(password),
             database: Some(effective_db.to_string()),
             rds_endpoint: Some(rds_endpoint.clone()),
             instance_id: Some(instance_id.clone()),
@@ -616,6 +1201,8 @@ impl TunnelManager {
             target_type: None,
             engine: project_config.engine.clone(),
             ssh_command: None,
+            kubeconfig: None,
+            auth_proxy: auth_proxy.is_some(),
         };
 
         let target = TunnelTarget::RemoteHost {
@@ -623,6 +1210,8 @@ impl TunnelManager {
             remote_host: rds_endpoint,
             remote_port: rds_port,
             multiplexed: project_config.multiplexed.unwrap_or(true),
+            fixed_host: false,
+            auth_proxy,
         };
 
         Ok((connection_info, target))
@@ -636,12 +1225,9 @@ impl TunnelManager {
         project_key: &str,
         profile: &str,
         project_config: &ProjectConfig,
-        local_port: &str,
-    ) -> Result<(ConnectionInfo, TunnelTarget), AppError> {
-        let target_type = project_config
-            .target_type
-            .as_deref()
-            .ok_or_else(|| AppError::Config("Missing targetType for service connection".to_string()))?;
+        local_addr: SocketAddrV4,
+    ) -> Result<(ConnectionInfo, TunnelTarget, Option<PushedSshKey>), AppError> {
+        let target_type = project_config.target_type.as_deref().filter(|t| !t.is_empty());
         let remote_port = project_config
             .remote_port
             .ok_or_else(|| AppError::Config("Missing remotePort for service connection".to_string()))?;
@@ -650,8 +1236,35 @@ impl TunnelManager {
             .as_deref()
             .unwrap_or("*");
 
-        let (tunnel_target, instance_id, remote_host) = match target_type {
-            "ec2-direct" => {
+        // serviceType "http": a reverse proxy that talks to the site by name;
+        // "sigv4-http" also signs each request with the profile's credentials
+        let http_proxy = match project_config.service_type.as_deref() {
+            Some(service_type @ ("http" | "sigv4-http")) => {
+                let host = project_config
+                    .http_host
+                    .as_deref()
+                    .filter(|h| !h.is_empty())
+                    .ok_or_else(|| AppError::Config("Missing httpHost for http service".to_string()))?;
+                let tls = project_config.http_tls.unwrap_or(remote_port == 443);
+                let mut proxy = AuthProxy::http(host, tls);
+                proxy.verify_tls = project_config.http_tls_skip_verify != Some(true);
+                if service_type == "sigv4-http" {
+                    let signing_name = project_config
+                        .sigv4_service
+                        .as_deref()
+                        .filter(|s| !s.is_empty())
+                        .ok_or_else(|| AppError::Config("Missing sigv4Service for sigv4-http service".to_string()))?;
+                    let sdk_config = build_aws_config(profile, &project_config.region).await;
+                    proxy.sigv4 = Some(Arc::new(iam_auth::Sigv4Signer::new(&sdk_config, signing_name)?));
+                }
+                Some(proxy)
+            }
+            _ => None,
+        };
+
+        // ec2_target: the EC2 instance behind the tunnel, for Instance Connect
+        let (tunnel_target, instance_id, remote_host, ec2_target) = match target_type {
+            Some("ec2-direct") => {
                 self.emit_status("Finding target EC2 instance...", Some(connection_id));
                 let (id, _ip) = operations::find_ec2_instance(clients, target_pattern).await?;
 
@@ -673,10 +1286,11 @@ impl TunnelManager {
                     instance_id: id.clone(),
                     remote_port: remote_port.to_string(),
                     multiplexed: project_config.multiplexed.unwrap_or(true),
+                    auth_proxy: http_proxy,
                 };
-                (target, Some(id), None)
+                (target, Some(id.clone()), None, Some(id))
             }
-            "ec2-bastion" => {
+            Some("ec2-bastion") => {
                 self.emit_status("Finding bastion instance...", Some(connection_id));
                 let prefs = preferences::load_preferences().await;
                 let preferred = preferences::get_preferred_bastion(&prefs, project_key, profile)
@@ -689,18 +1303,17 @@ impl TunnelManager {
                 .await?;
 
                 // Save bastion preference
-                {
-                    let mut prefs = prefs;
-                    preferences::set_preferred_bastion(&mut prefs, project_key, profile, &bastion_id);
-                    preferences::save_preferences(&prefs).await;
-                }
+                preferences::update_preferences(|prefs| {
+                    preferences::set_preferred_bastion(prefs, project_key, profile, &bastion_id)
+                })
+                .await;
 
                 if !INSTANCE_ID_PATTERN.is_match(&bastion_id) {
                     return Err(AppError::Aws(format!("Invalid bastion instance ID format: {}", bastion_id)));
                 }
 
                 self.emit_status("Finding target EC2 instance...", Some(connection_id));
-                let (_id, ip) = operations::find_ec2_instance(clients, target_pattern).await?;
+                let (target_id, ip) = operations::find_ec2_instance(clients, target_pattern).await?;
 
                 if !IP_PATTERN.is_match(&ip) {
                     return Err(AppError::Aws(format!("Invalid EC2 private IP format: {}", ip)));
@@ -711,10 +1324,12 @@ impl TunnelManager {
                     remote_host: ip.clone(),
                     remote_port: remote_port.to_string(),
                     multiplexed: project_config.multiplexed.unwrap_or(true),
+                    fixed_host: false,
+                    auth_proxy: http_proxy,
                 };
-                (target, Some(bastion_id), Some(ip))
+                (target, Some(bastion_id), Some(ip), Some(target_id))
             }
-            "ecs-bastion" => {
+            Some("ecs-bastion") => {
                 let cluster = project_config
                     .ecs_cluster
                     .as_deref()
@@ -736,11 +1351,10 @@ impl TunnelManager {
                 .await?;
 
                 // Save bastion preference
-                {
-                    let mut prefs = prefs;
-                    preferences::set_preferred_bastion(&mut prefs, project_key, profile, &bastion_id);
-                    preferences::save_preferences(&prefs).await;
-                }
+                preferences::update_preferences(|prefs| {
+                    preferences::set_preferred_bastion(prefs, project_key, profile, &bastion_id)
+                })
+                .await;
 
                 if !INSTANCE_ID_PATTERN.is_match(&bastion_id) {
                     return Err(AppError::Aws(format!("Invalid bastion instance ID format: {}", bastion_id)));
@@ -758,24 +1372,98 @@ impl TunnelManager {
                     remote_host: task_ip.clone(),
                     remote_port: remote_port.to_string(),
                     multiplexed: project_config.multiplexed.unwrap_or(true),
+                    fixed_host: false,
+                    auth_proxy: http_proxy,
                 };
-                (target, Some(bastion_id), Some(task_ip))
+                (target, Some(bastion_id), Some(task_ip), None)
             }
-            _ => return Err(AppError::Config(format!("Unknown targetType: {}", target_type))),
-        };
+            // No target instance: the bastion reaches the HTTP site by its
+            // own name, e.g. a private ALB
+            None if http_proxy.is_some() => {
+                let site = http_proxy
+                    .as_ref()
+                    .map(|proxy| proxy.server_name.clone())
+                    .unwrap_or_default();
 
-        // Build SSH command if service type is SSH
-        let ssh_command = if project_config.service_type.as_deref() == Some("ssh") {
-            let ssh_user = project_config
-                .ssh_username
-                .as_deref()
-                .filter(|s| !s.is_empty())
-                .unwrap_or("ec2-user");
-            let mut cmd = format!(
-                "ssh -p {} {}@localhost -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null",
-                local_port, ssh_user
+                self.emit_status("Finding bastion instance...", Some(connection_id));
+                let prefs = preferences::load_preferences().await;
+                let preferred = preferences::get_preferred_bastion(&prefs, project_key, profile)
+                    .map(|s| s.to_string());
+                let bastion_id = operations::find_bastion_instance(
+                    clients,
+                    project_config.bastion_pattern(),
+                    preferred.as_deref(),
+                )
+                .await?;
+
+                // Save bastion preference
+                preferences::update_preferences(|prefs| {
+                    preferences::set_preferred_bastion(prefs, project_key, profile, &bastion_id)
+                })
+                .await;
+
+                if !INSTANCE_ID_PATTERN.is_match(&bastion_id) {
+                    return Err(AppError::Aws(format!("Invalid bastion instance ID format: {}", bastion_id)));
+                }
+
+                let target = TunnelTarget::RemoteHost {
+                    bastion_id: bastion_id.clone(),
+                    remote_host: site.clone(),
+                    remote_port: remote_port.to_string(),
+                    multiplexed: project_config.multiplexed.unwrap_or(true),
+                    fixed_host: true,
+                    auth_proxy: http_proxy,
+                };
+                (target, Some(bastion_id), Some(site), None)
+            }
+            Some(other) => return Err(AppError::Config(format!("Unknown targetType: {}", other))),
+            None => {
+                return Err(AppError::Config(
+                    "Missing targetType for service connection".to_string(),
+                ))
+            }
+        };
+
+        // Build SSH command if service type is SSH
+        let mut ssh_key = None;
+        let ssh_command = if project_config.service_type.as_deref() == Some("ssh") {
+            let ssh_user = project_config
+                .ssh_username
+                .as_deref()
+                .filter(|s| !s.is_empty())
+                .unwrap_or("ec2-user");
+            let mut cmd = format!(
+                "ssh -p {} {}@{} -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null",
+                local_addr.port(),
+                ssh_user,
+                local_host(*local_addr.ip())
             );
-            if let Some(ref key_path) = project_config.ssh_key_path
+            if project_config.ssh_key_mode.as_deref() == Some("instance-connect") {
+                let ec2_id = ec2_target.ok_or_else(|| {
+                    AppError::Config(format!(
+                        "sshKeyMode \"instance-connect\" is not supported for targetType {}",
+                        target_type.unwrap_or_default()
+                    ))
+                })?;
+                self.emit_status("Pushing SSH key via EC2 Instance Connect...", Some(connection_id));
+                let key = EphemeralSshKey::generate()?;
+                instance_connect::send_ssh_public_key(
+                    &clients.ec2_instance_connect,
+                    &ec2_id,
+                    ssh_user,
+                    &key.public_key,
+                )
+                .await?;
+                cmd.push_str(&format!(
+                    " -i {} -o IdentitiesOnly=yes",
+                    key.private_key_path().display()
+                ));
+                ssh_key = Some(PushedSshKey {
+                    key,
+                    instance_id: ec2_id,
+                    os_user: ssh_user.to_string(),
+                });
+            } else if let Some(ref key_path) = project_config.ssh_key_path
                 && !key_path.is_empty()
             {
                 cmd.push_str(&format!(" -i {}", key_path));
@@ -786,8 +1474,8 @@ impl TunnelManager {
         };
 
         let connection_info = ConnectionInfo {
-            host: "localhost".to_string(),
-            port: local_port.to_string(),
+            host: local_host(*local_addr.ip()),
+            port: local_addr.port().to_string(),
             connection_type: "service".to_string(),
             username: None,
             password: None,
@@ -796,12 +1484,282 @@ impl TunnelManager {
             instance_id,
             service_type: project_config.service_type.clone(),
             remote_host,
-            target_type: Some(target_type.to_string()),
+            target_type: target_type.map(str::to_string),
             engine: None,
             ssh_command,
+            kubeconfig: None,
+            auth_proxy: false,
+        };
+
+        Ok((connection_info, tunnel_target, ssh_key))
+    }
+
+    /// Resolve a SOCKS5 proxy: only the bastion is needed up front, sessions
+    /// to each destination are started as clients ask for them.
+    async fn resolve_socks_target(
+        &self,
+        clients: &AwsClients,
+        connection_id: &str,
+        project_key: &str,
+        profile: &str,
+        project_config: &ProjectConfig,
+        local_addr: SocketAddrV4,
+    ) -> Result<(ConnectionInfo, TunnelTarget), AppError> {
+        let allow = project_config
+            .socks_allow
+            .as_deref()
+            .map(SocksAllowList::parse)
+            .transpose()
+            .map_err(|e| AppError::Config(format!("Invalid socksAllow: {}", e)))?;
+
+        self.emit_status("Finding bastion instance...", Some(connection_id));
+        let prefs = preferences::load_preferences().await;
+        let preferred = preferences::get_preferred_bastion(&prefs, project_key, profile)
+            .map(|s| s.to_string());
+        let bastion_id = operations::find_bastion_instance(
+            clients,
+            project_config.bastion_pattern(),
+            preferred.as_deref(),
+        )
+        .await?;
+
+        // Save bastion preference
+        preferences::update_preferences(|prefs| {
+            preferences::set_preferred_bastion(prefs, project_key, profile, &bastion_id)
+        })
+        .await;
+
+        if !INSTANCE_ID_PATTERN.is_match(&bastion_id) {
+            return Err(AppError::Aws(format!("Invalid bastion instance ID format: {}", bastion_id)));
+        }
+
+        let connection_info = ConnectionInfo {
+            host: local_host(*local_addr.ip()),
+            port: local_addr.port().to_string(),
+            connection_type: "service".to_string(),
+            username: None,
+            password: None,
+            database: None,
+            rds_endpoint: None,
+            instance_id: Some(bastion_id.clone()),
+            service_type: Some("socks".to_string()),
+            remote_host: None,
+            target_type: None,
+            engine: None,
+            ssh_command: None,
+            kubeconfig: None,
+            auth_proxy: false,
+        };
+
+        let target = TunnelTarget::Socks {
+            bastion_id,
+            multiplexed: project_config.multiplexed.unwrap_or(true),
+            allow,
+        };
+
+        Ok((connection_info, target))
+    }
+
+    /// Resolve an EKS cluster: look up its API endpoint, tunnel to it through
+    /// the bastion and write a kubeconfig that points kubectl at the tunnel.
+    async fn resolve_eks_target(
+        &self,
+        clients: &AwsClients,
+        connection_id: &str,
+        project_key: &str,
+        profile: &str,
+        project_config: &ProjectConfig,
+        local_addr: SocketAddrV4,
+    ) -> Result<(ConnectionInfo, TunnelTarget), AppError> {
+        let cluster_name = project_config
+            .eks_cluster
+            .as_deref()
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| AppError::Config("Missing eksCluster for eks connection".to_string()))?;
+
+        self.emit_status("Getting EKS cluster endpoint...", Some(connection_id));
+        let cluster = operations::describe_eks_cluster(clients, cluster_name).await?;
+
+        if !HOSTNAME_PATTERN.is_match(&cluster.endpoint) {
+            return Err(AppError::Aws(format!("Invalid EKS endpoint format: {}", cluster.endpoint)));
+        }
+
+        self.emit_status("Finding bastion instance...", Some(connection_id));
+        let prefs = preferences::load_preferences().await;
+        let preferred = preferences::get_preferred_bastion(&prefs, project_key, profile)
+            .map(|s| s.to_string());
+        let bastion_id = operations::find_bastion_instance(
+            clients,
+            project_config.bastion_pattern(),
+            preferred.as_deref(),
+        )
+        .await?;
+
+        // Save bastion preference
+        preferences::update_preferences(|prefs| {
+            preferences::set_preferred_bastion(prefs, project_key, profile, &bastion_id)
+        })
+        .await;
+
+        if !INSTANCE_ID_PATTERN.is_match(&bastion_id) {
+            return Err(AppError::Aws(format!("Invalid bastion instance ID format: {}", bastion_id)));
+        }
+
+        // The API server certificate is issued for the real endpoint, so
+        // kubectl verifies it against tls-server-name rather than localhost
+        let host = local_host(*local_addr.ip());
+        let kubeconfig = export::write_kubeconfig(
+            connection_id,
+            &export::EksKubeconfig {
+                name: &export::service_name(project_key, profile),
+                server: format!("https://{}:{}", host, local_addr.port()),
+                tls_server_name: &cluster.endpoint,
+                certificate_authority_data: &cluster.certificate_authority,
+                profile,
+                region: &project_config.region,
+                cluster: cluster_name,
+            },
+        )?;
+
+        let connection_info = ConnectionInfo {
+            host,
+            port: local_addr.port().to_string(),
+            connection_type: "eks".to_string(),
+            username: None,
+            password: None,
+            database: None,
+            rds_endpoint: None,
+            instance_id: Some(bastion_id.clone()),
+            service_type: None,
+            remote_host: Some(cluster.endpoint.clone()),
+            target_type: None,
+            engine: None,
+            ssh_command: None,
+            kubeconfig: Some(kubeconfig),
+            auth_proxy: false,
+        };
+
+        let target = TunnelTarget::RemoteHost {
+            bastion_id,
+            remote_host: cluster.endpoint,
+            remote_port: "443".to_string(),
+            multiplexed: project_config.multiplexed.unwrap_or(true),
+            fixed_host: true,
+            auth_proxy: None,
+        };
+
+        Ok((connection_info, target))
+    }
+
+    /// Resolve an ElastiCache replication group or MemoryDB cluster: look up
+    /// its endpoint and tunnel to it through the bastion. Cluster-mode groups
+    /// get the Redis proxy with a local port per node.
+    async fn resolve_redis_target(
+        &self,
+        clients: &AwsClients,
+        connection_id: &str,
+        project_key: &str,
+        profile: &str,
+        project_config: &ProjectConfig,
+        local_addr: SocketAddrV4,
+    ) -> Result<(ConnectionInfo, TunnelTarget), AppError> {
+        let cluster_name = project_config
+            .redis_cluster
+            .as_deref()
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| AppError::Config("Missing redisCluster for redis connection".to_string()))?;
+
+        self.emit_status("Getting Redis endpoint...", Some(connection_id));
+        let endpoint = if project_config.redis_service.as_deref() == Some("memorydb") {
+            operations::describe_memorydb_endpoint(clients, cluster_name).await?
+        } else {
+            operations::describe_elasticache_endpoint(clients, cluster_name).await?
+        };
+
+        if !HOSTNAME_PATTERN.is_match(&endpoint.host) {
+            return Err(AppError::Aws(format!("Invalid Redis endpoint format: {}", endpoint.host)));
+        }
+
+        // AUTH token (or ACL user) from Secrets Manager, if configured
+        let (username, password) =
+            if project_config.secret_path.is_some() || !project_config.secret_prefix.is_empty() {
+                self.emit_status("Getting credentials...", Some(connection_id));
+                let (username, password) = operations::get_redis_credentials(
+                    clients,
+                    &project_config.secret_prefix,
+                    project_config.secret_path.as_deref(),
+                    project_config.secret_username_field.as_deref(),
+                    project_config.secret_password_field.as_deref(),
+                )
+                .await?;
+                (username, Some(password))
+            } else {
+                (None, None)
+            };
+
+        self.emit_status("Finding bastion instance...", Some(connection_id));
+        let prefs = preferences::load_preferences().await;
+        let preferred = preferences::get_preferred_bastion(&prefs, project_key, profile)
+            .map(|s| s.to_string());
+        let bastion_id = operations::find_bastion_instance(
+            clients,
+            project_config.bastion_pattern(),
+            preferred.as_deref(),
+        )
+        .await?;
+
+        // Save bastion preference
+        preferences::update_preferences(|prefs| {
+            preferences::set_preferred_bastion(prefs, project_key, profile, &bastion_id)
+        })
+        .await;
+
+        if !INSTANCE_ID_PATTERN.is_match(&bastion_id) {
+            return Err(AppError::Aws(format!("Invalid bastion instance ID format: {}", bastion_id)));
+        }
+
+        let connection_info = ConnectionInfo {
+            host: local_host(*local_addr.ip()),
+            port: local_addr.port().to_string(),
+            connection_type: "redis".to_string(),
+            username,
+            password,
+            database: None,
+            rds_endpoint: None,
+            instance_id: Some(bastion_id.clone()),
+            service_type: None,
+            remote_host: Some(endpoint.host.clone()),
+            target_type: None,
+            engine: None,
+            ssh_command: None,
+            kubeconfig: None,
+            auth_proxy: false,
         };
 
-        Ok((connection_info, tunnel_target))
+        let multiplexed = project_config.multiplexed.unwrap_or(true);
+        let target = if endpoint.cluster_mode {
+            let nodes = ClusterNodes::new(*local_addr.ip());
+            TunnelTarget::RedisCluster {
+                bastion_id,
+                proxy: AuthProxy::redis(&endpoint.host, endpoint.tls, Some(Arc::new(nodes))),
+                remote_host: endpoint.host,
+                remote_port: endpoint.port,
+                multiplexed,
+            }
+        } else {
+            TunnelTarget::RemoteHost {
+                bastion_id,
+                auth_proxy: endpoint
+                    .tls
+                    .then(|| AuthProxy::redis(&endpoint.host, true, None)),
+                remote_host: endpoint.host,
+                remote_port: endpoint.port.to_string(),
+                multiplexed,
+                fixed_host: true,
+            }
+        };
+
+        Ok((connection_info, target))
     }
 
     /// Disconnect a specific connection.
@@ -813,6 +1771,13 @@ impl TunnelManager {
         Ok(())
     }
 
+    /// Token that is cancelled once the connection's tunnel task has finished
+    /// and cleaned up (user disconnect, session end, or failed reconnects).
+    pub async fn closed_token(&self, connection_id: &str) -> Option<CancellationToken> {
+        let guard = self.connections.lock().await;
+        guard.get(connection_id).map(|c| c.closed.clone())
+    }
+
     /// Disconnect all connections.
     pub async fn disconnect_all(&self) -> Result<(), AppError> {
         let mut guard = self.connections.lock().await;
@@ -826,14 +1791,13 @@ impl TunnelManager {
     /// Periodically verifies the tunnel's local listener is still bound (via a
     /// bind-conflict probe — never by connecting, which would create tunnel traffic).
     fn spawn_health_check(
-        app_handle: &AppHandle,
+        events: &Arc<dyn TunnelEventHandler>,
         connection_id: &str,
-        local_port: &str,
+        local_addr: SocketAddrV4,
         cancel_token: &CancellationToken,
     ) {
-        let app_handle = app_handle.clone();
+        let events = events.clone();
         let conn_id = connection_id.to_string();
-        let port: u16 = local_port.parse().unwrap_or(0);
         let cancel = cancel_token.clone();
 
         tokio::spawn(async move {
@@ -856,8 +1820,7 @@ impl TunnelManager {
                 // lifetime of every connection. A bind attempt gives the same
                 // signal (listener present) with zero tunnel traffic: if the
                 // port is held by our listener, bind fails with AddrInUse.
-                let addr = std::net::SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, port));
-                let status = match std::net::TcpListener::bind(addr) {
+                let status = match std::net::TcpListener::bind(local_addr) {
                     // Bind succeeded — nothing is listening on the port anymore.
                     Ok(sock) => {
                         drop(sock);
@@ -873,14 +1836,7 @@ impl TunnelManager {
                     .unwrap_or_default()
                     .as_millis() as u64;
 
-                let _ = app_handle.emit(
-                    "connection-health",
-                    serde_json::json!({
-                        "connectionId": conn_id,
-                        "status": status,
-                        "lastCheck": now_ms,
-                    }),
-                );
+                events.on_health(&conn_id, status, now_ms);
 
                 // Wait for next interval or cancellation
                 tokio::select! {
@@ -891,15 +1847,89 @@ impl TunnelManager {
         });
     }
 
+    /// Regenerate the connection's IAM auth token before the current one
+    /// expires: update the stored `ConnectionInfo`, rewrite any written
+    /// exports (e.g. `.pgpass`) and tell listeners the password changed.
+    /// With the auth proxy only the proxy's copy is replaced.
+    fn spawn_token_refresher(
+        events: &Arc<dyn TunnelEventHandler>,
+        connections: &Arc<Mutex<HashMap<String, Connection>>>,
+        connection_id: &str,
+        service_name: String,
+        source: IamTokenSource,
+        cancel_token: &CancellationToken,
+    ) {
+        let events = events.clone();
+        let connections = connections.clone();
+        let conn_id = connection_id.to_string();
+        let cancel = cancel_token.clone();
+
+        tokio::spawn(async move {
+            let mut delay = iam_auth::TOKEN_REFRESH_INTERVAL;
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = cancel.cancelled() => { break; }
+                }
+
+                // Rebuilt each time so rotated SSO credentials are picked up
+                let sdk_config = build_aws_config(&source.profile, &source.region).await;
+                let token = match iam_auth::generate_rds_auth_token(
+                    &sdk_config,
+                    &source.hostname,
+                    source.port,
+                    &source.username,
+                )
+                .await
+                {
+                    Ok(token) => token,
+                    Err(e) => {
+                        log::warn!("IAM token refresh for {} failed: {}", conn_id, e);
+                        delay = iam_auth::TOKEN_REFRESH_RETRY;
+                        continue;
+                    }
+                };
+                delay = iam_auth::TOKEN_REFRESH_INTERVAL;
+
+                if let Some(ref proxy) = source.auth_proxy {
+                    proxy.set_password(token);
+                    log::debug!("Refreshed IAM auth token for the {} auth proxy", conn_id);
+                    continue;
+                }
+
+                let info = {
+                    let mut guard = connections.lock().await;
+                    let Some(connection) = guard.get_mut(&conn_id) else {
+                        break;
+                    };
+                    connection.connection_info.password = Some(token);
+                    connection.connection_info.clone()
+                };
+                if let Err(e) = export::rewrite_exports(&conn_id, &info, &service_name) {
+                    log::warn!("Failed to rewrite exports for {}: {}", conn_id, e);
+                }
+                log::debug!("Refreshed IAM auth token for {}", conn_id);
+                events.on_credentials_refreshed(&conn_id, &info);
+            }
+        });
+    }
+
     fn emit_status(&self, message: &str, connection_id: Option<&str>) {
-        let mut payload = serde_json::json!({ "message": message });
-        if let Some(id) = connection_id {
-            payload["connectionId"] = serde_json::json!(id);
-        }
-        let _ = self.app_handle.emit("status", &payload);
+        self.events.on_status(message, connection_id);
     }
 }
 
+/// What's needed to regenerate a connection's RDS IAM auth token.
+struct IamTokenSource {
+    profile: String,
+    region: String,
+    hostname: String,
+    port: u16,
+    username: String,
+    /// Proxy holding the token, when `authProxy` is on.
+    auth_proxy: Option<AuthProxy>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ActiveConnectionInfo {
     pub id: String,
@@ -911,6 +1941,8 @@ pub struct ActiveConnectionInfo {
     #[serde(rename = "connectionInfo")]
     pub connection_info: ConnectionInfo,
     pub status: String,
+    #[serde(rename = "connectedAt", default)]
+    pub connected_at: String,
 }
 
 /// Describes what the tunnel connects to.
@@ -922,12 +1954,34 @@ enum TunnelTarget {
         remote_host: String,
         remote_port: String,
         multiplexed: bool,
+        /// Ad-hoc forward: keep `remote_host` on reconnect, only re-find the bastion.
+        fixed_host: bool,
+        /// Put the database auth proxy (or the HTTP or Redis proxy) in front
+        /// of each local connection.
+        auth_proxy: Option<AuthProxy>,
     },
     /// Direct port forwarding to an EC2 instance (SSM agent on the instance itself).
     DirectInstance {
         instance_id: String,
         remote_port: String,
         multiplexed: bool,
+        /// The HTTP proxy, for `serviceType: "http"` and `"sigv4-http"`.
+        auth_proxy: Option<AuthProxy>,
+    },
+    /// SOCKS5 proxy through a bastion; one session per requested destination.
+    Socks {
+        bastion_id: String,
+        multiplexed: bool,
+        allow: Option<SocksAllowList>,
+    },
+    /// Cluster-mode Redis through a bastion: the configuration endpoint plus
+    /// one session per node the proxy hands out.
+    RedisCluster {
+        bastion_id: String,
+        remote_host: String,
+        remote_port: u16,
+        multiplexed: bool,
+        proxy: AuthProxy,
     },
 }
 
@@ -936,6 +1990,18 @@ impl TunnelTarget {
         match self {
             TunnelTarget::RemoteHost { multiplexed, .. } => *multiplexed,
             TunnelTarget::DirectInstance { multiplexed, .. } => *multiplexed,
+            TunnelTarget::Socks { multiplexed, .. } => *multiplexed,
+            TunnelTarget::RedisCluster { multiplexed, .. } => *multiplexed,
+        }
+    }
+
+    /// The SSM target instance the session is started against.
+    fn ssm_target(&self) -> &str {
+        match self {
+            TunnelTarget::RemoteHost { bastion_id, .. } => bastion_id,
+            TunnelTarget::DirectInstance { instance_id, .. } => instance_id,
+            TunnelTarget::Socks { bastion_id, .. } => bastion_id,
+            TunnelTarget::RedisCluster { bastion_id, .. } => bastion_id,
         }
     }
 }
@@ -943,10 +2009,10 @@ impl TunnelTarget {
 /// Run the tunnel lifecycle: start port forwarding, keepalive, auto-reconnect.
 #[allow(clippy::too_many_arguments)]
 async fn run_tunnel_lifecycle(
-    app_handle: &AppHandle,
+    events: &dyn TunnelEventHandler,
     clients: &AwsClients,
     connection_id: &str,
-    local_port: &str,
+    local_addr: SocketAddrV4,
     project_config: &ProjectConfig,
     mut target: TunnelTarget,
     cancel_token: CancellationToken,
@@ -965,7 +2031,7 @@ async fn run_tunnel_lifecycle(
 
         let result = start_port_forwarding_with_retry(
             clients,
-            local_port,
+            local_addr,
             &target,
             project_config.bastion_pattern(),
             &cancel_token,
@@ -979,8 +2045,7 @@ async fn run_tunnel_lifecycle(
 
         // Notify about connection drop on first failure in this cycle
         if reconnect_count == 0 {
-            send_notification(
-                app_handle,
+            events.on_notification(
                 "Connection Lost",
                 &format!("{} — attempting to reconnect...", conn_label),
             );
@@ -1004,8 +2069,8 @@ async fn run_tunnel_lifecycle(
                     details: Some(format!("attempt {}/{}", reconnect_count, AUTO_RECONNECT_MAX_RETRIES)),
                 })
                 .await;
-                emit_status_event(
-                    app_handle,
+                events.on_reconnecting(connection_id, reconnect_count, AUTO_RECONNECT_MAX_RETRIES);
+                events.on_status(
                     &format!("Session ended. Reconnecting... ({})", reconnect_count),
                     Some(connection_id),
                 );
@@ -1029,8 +2094,8 @@ async fn run_tunnel_lifecycle(
                     details: Some(format!("error retry {}/{}: {}", reconnect_count, AUTO_RECONNECT_MAX_RETRIES, e)),
                 })
                 .await;
-                emit_status_event(
-                    app_handle,
+                events.on_reconnecting(connection_id, reconnect_count, AUTO_RECONNECT_MAX_RETRIES);
+                events.on_status(
                     &format!(
                         "Connection error. Retrying... ({}/{})",
                         reconnect_count, AUTO_RECONNECT_MAX_RETRIES
@@ -1047,11 +2112,10 @@ async fn run_tunnel_lifecycle(
         }
 
         // Verify credentials
-        emit_status_event(app_handle, "Checking credentials...", Some(connection_id));
+        events.on_status("Checking credentials...", Some(connection_id));
         let cred_check = operations::check_credentials_valid(clients).await;
         if !cred_check.valid {
-            emit_status_event(
-                app_handle,
+            events.on_status(
                 "AWS credentials expired. Please re-authenticate and reconnect.",
                 Some(connection_id),
             );
@@ -1059,11 +2123,11 @@ async fn run_tunnel_lifecycle(
         }
 
         // Re-discover infrastructure based on target type
-        target = rediscover_target(app_handle, clients, connection_id, project_config, &target).await?;
+        target = rediscover_target(events, clients, connection_id, project_config, &target).await?;
 
         // Notify that auto-reconnect succeeded
-        send_notification(
-            app_handle,
+        events.on_reconnected(connection_id);
+        events.on_notification(
             "Reconnected",
             &format!("{} tunnel restored", conn_label),
         );
@@ -1071,8 +2135,7 @@ async fn run_tunnel_lifecycle(
         // Reset reconnect counter for the next cycle
         reconnect_count = 0;
 
-        emit_status_event(
-            app_handle,
+        events.on_status(
             "Reconnecting port forwarding...",
             Some(connection_id),
         );
@@ -1083,22 +2146,24 @@ async fn run_tunnel_lifecycle(
 
 /// Re-discover infrastructure for reconnection.
 async fn rediscover_target(
-    app_handle: &AppHandle,
+    events: &dyn TunnelEventHandler,
     clients: &AwsClients,
     connection_id: &str,
     project_config: &ProjectConfig,
     current: &TunnelTarget,
 ) -> Result<TunnelTarget, AppError> {
     match current {
-        TunnelTarget::RemoteHost { remote_port, multiplexed, .. } => {
+        TunnelTarget::RemoteHost { remote_host, remote_port, multiplexed, fixed_host, auth_proxy, .. } => {
             // Re-find bastion (no preference on reconnect — use whatever is available)
-            emit_status_event(app_handle, "Finding bastion instance...", Some(connection_id));
+            events.on_status("Finding bastion instance...", Some(connection_id));
             let bastion_id =
                 operations::find_bastion_instance(clients, project_config.bastion_pattern(), None).await?;
 
             // Re-discover remote host based on connection type
-            let remote_host = if project_config.connection_type == "rds" {
-                emit_status_event(app_handle, "Getting RDS endpoint...", Some(connection_id));
+            let remote_host = if *fixed_host {
+                remote_host.clone()
+            } else if project_config.connection_type == "rds" {
+                events.on_status("Getting RDS endpoint...", Some(connection_id));
                 operations::get_rds_endpoint(
                     clients,
                     &project_config.rds_type,
@@ -1109,7 +2174,7 @@ async fn rediscover_target(
                     AppError::Aws("Failed to find the RDS endpoint during reconnection.".to_string())
                 })?
             } else if project_config.target_type.as_deref() == Some("ecs-bastion") {
-                emit_status_event(app_handle, "Finding ECS task IP...", Some(connection_id));
+                events.on_status("Finding ECS task IP...", Some(connection_id));
                 operations::find_ecs_task_ip(
                     clients,
                     project_config.ecs_cluster.as_deref().unwrap_or(""),
@@ -1118,7 +2183,7 @@ async fn rediscover_target(
                 .await?
             } else {
                 // ec2-bastion: re-find the EC2 instance IP
-                emit_status_event(app_handle, "Finding target instance...", Some(connection_id));
+                events.on_status("Finding target instance...", Some(connection_id));
                 let (_id, ip) = operations::find_ec2_instance(
                     clients,
                     project_config.target_pattern.as_deref().unwrap_or("*"),
@@ -1132,11 +2197,13 @@ async fn rediscover_target(
                 remote_host,
                 remote_port: remote_port.clone(),
                 multiplexed: *multiplexed,
+                fixed_host: *fixed_host,
+                auth_proxy: auth_proxy.clone(),
             })
         }
-        TunnelTarget::DirectInstance { remote_port, multiplexed, .. } => {
+        TunnelTarget::DirectInstance { remote_port, multiplexed, auth_proxy, .. } => {
             // Re-find the direct instance
-            emit_status_event(app_handle, "Finding target instance...", Some(connection_id));
+            events.on_status("Finding target instance...", Some(connection_id));
             let (instance_id, _ip) = operations::find_ec2_instance(
                 clients,
                 project_config.target_pattern.as_deref().unwrap_or("*"),
@@ -1146,6 +2213,30 @@ async fn rediscover_target(
                 instance_id,
                 remote_port: remote_port.clone(),
                 multiplexed: *multiplexed,
+                auth_proxy: auth_proxy.clone(),
+            })
+        }
+        TunnelTarget::Socks { multiplexed, allow, .. } => {
+            events.on_status("Finding bastion instance...", Some(connection_id));
+            let bastion_id =
+                operations::find_bastion_instance(clients, project_config.bastion_pattern(), None).await?;
+            Ok(TunnelTarget::Socks {
+                bastion_id,
+                multiplexed: *multiplexed,
+                allow: allow.clone(),
+            })
+        }
+        TunnelTarget::RedisCluster { remote_host, remote_port, multiplexed, proxy, .. } => {
+            // The endpoint is fixed; keep the node ports clients already know
+            events.on_status("Finding bastion instance...", Some(connection_id));
+            let bastion_id =
+                operations::find_bastion_instance(clients, project_config.bastion_pattern(), None).await?;
+            Ok(TunnelTarget::RedisCluster {
+                bastion_id,
+                remote_host: remote_host.clone(),
+                remote_port: *remote_port,
+                multiplexed: *multiplexed,
+                proxy: proxy.clone(),
             })
         }
     }
@@ -1154,7 +2245,7 @@ async fn rediscover_target(
 /// Start port forwarding with TargetNotConnected retry.
 async fn start_port_forwarding_with_retry(
     clients: &AwsClients,
-    local_port: &str,
+    local_addr: SocketAddrV4,
     target: &TunnelTarget,
     bastion_pattern: &str,
     cancel_token: &CancellationToken,
@@ -1167,7 +2258,7 @@ async fn start_port_forwarding_with_retry(
     loop {
         let result = execute_port_forwarding(
             clients,
-            local_port,
+            local_addr,
             &current_target,
             cancel_token,
             ready_tx.take(),
@@ -1177,7 +2268,7 @@ async fn start_port_forwarding_with_retry(
         match result {
             Ok(()) => return Ok(()),
             Err(PortForwardError::TargetNotConnected) if retry_count < PORT_FORWARDING_MAX_RETRIES => {
-                if let TunnelTarget::RemoteHost { ref remote_host, ref remote_port, multiplexed, .. } = current_target {
+                if let TunnelTarget::RemoteHost { ref remote_host, ref remote_port, multiplexed, fixed_host, ref auth_proxy, .. } = current_target {
                     // TargetNotConnected means the SSM target isn't currently reachable —
                     // typically a stale/cached bastion ID pointing at a replaced instance, or
                     // the SSM agent briefly not connected. Re-discover the current running
@@ -1196,6 +2287,28 @@ async fn start_port_forwarding_with_retry(
                         remote_host: remote_host.clone(),
                         remote_port: remote_port.clone(),
                         multiplexed,
+                        fixed_host,
+                        auth_proxy: auth_proxy.clone(),
+                    };
+                } else if let TunnelTarget::Socks { multiplexed, ref allow, .. } = current_target {
+                    // Same as above: the bastion was replaced, never terminate it
+                    let new_id =
+                        operations::find_bastion_instance(clients, bastion_pattern, None).await?;
+                    current_target = TunnelTarget::Socks {
+                        bastion_id: new_id,
+                        multiplexed,
+                        allow: allow.clone(),
+                    };
+                } else if let TunnelTarget::RedisCluster { ref remote_host, remote_port, multiplexed, ref proxy, .. } = current_target {
+                    // Same as above: the bastion was replaced, never terminate it
+                    let new_id =
+                        operations::find_bastion_instance(clients, bastion_pattern, None).await?;
+                    current_target = TunnelTarget::RedisCluster {
+                        bastion_id: new_id,
+                        remote_host: remote_host.clone(),
+                        remote_port,
+                        multiplexed,
+                        proxy: proxy.clone(),
                     };
                 }
 
@@ -1222,25 +2335,66 @@ enum PortForwardError {
 /// Execute a single port forwarding session via native WebSocket.
 async fn execute_port_forwarding(
     clients: &AwsClients,
-    local_port: &str,
+    local_addr: SocketAddrV4,
     target: &TunnelTarget,
     cancel_token: &CancellationToken,
     ready_tx: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
 ) -> Result<(), PortForwardError> {
+    let local_port = local_addr.port().to_string();
+    if let TunnelTarget::Socks { bastion_id, multiplexed, allow } = target {
+        let sessions = BastionSessions {
+            clients: clients.clone(),
+            bastion_id: bastion_id.clone(),
+            local_port,
+        };
+        let result = socks::run_socks_server(
+            sessions,
+            allow.clone(),
+            *multiplexed,
+            local_addr,
+            cancel_token.child_token(),
+            ready_tx,
+        )
+        .await;
+        return port_forward_outcome(result, cancel_token);
+    }
+    if let TunnelTarget::RedisCluster { bastion_id, remote_host, remote_port, multiplexed, proxy } = target {
+        let sessions = BastionSessions {
+            clients: clients.clone(),
+            bastion_id: bastion_id.clone(),
+            local_port,
+        };
+        let result = redis_proxy::run_cluster_proxy(
+            sessions,
+            proxy.clone(),
+            remote_host,
+            *remote_port,
+            *multiplexed,
+            local_addr,
+            cancel_token.child_token(),
+            ready_tx,
+        )
+        .await;
+        return port_forward_outcome(result, cancel_token);
+    }
+
     // Start SSM session based on target type
     let session_response = match target {
         TunnelTarget::RemoteHost { bastion_id, remote_host, remote_port, .. } => {
             operations::start_remote_port_forwarding_session(
-                clients, bastion_id, remote_host, remote_port, local_port,
+                clients, bastion_id, remote_host, remote_port, &local_port,
             )
             .await
         }
         TunnelTarget::DirectInstance { instance_id, remote_port, .. } => {
             operations::start_direct_port_forwarding_session(
-                clients, instance_id, remote_port, local_port,
+                clients, instance_id, remote_port, &local_port,
             )
             .await
         }
+        TunnelTarget::Socks { .. } | TunnelTarget::RedisCluster { .. } => {
+            unreachable!("SOCKS and Redis cluster targets start sessions per destination")
+        }
     }
     .map_err(|e| PortForwardError::Failed(e.to_string()))?;
 
@@ -1253,20 +2407,32 @@ async fn execute_port_forwarding(
         .ok_or_else(|| PortForwardError::Failed("No TokenValue in session response".to_string()))?
         .to_string();
 
-    let port_num: u16 = local_port
-        .parse()
-        .map_err(|_| PortForwardError::Failed(format!("Invalid port: {}", local_port)))?;
+    if local_addr.port() == 0 {
+        return Err(PortForwardError::Failed("Invalid port: 0".to_string()));
+    }
 
     let multiplexed = target.is_multiplexed();
+    let auth_proxy = match target {
+        TunnelTarget::RemoteHost { auth_proxy, .. }
+        | TunnelTarget::DirectInstance { auth_proxy, .. } => auth_proxy.clone(),
+        TunnelTarget::Socks { .. } | TunnelTarget::RedisCluster { .. } => None,
+    };
+    let kms = KmsSessionContext {
+        kms: clients.kms.clone(),
+        session_id: session_response.session_id().unwrap_or_default().to_string(),
+        target_id: target.ssm_target().to_string(),
+    };
 
     // Run port forwarding — multiplexed or basic mode
     let cancel_child = cancel_token.child_token();
     let result = if multiplexed {
-        log::info!("Starting multiplexed port forwarding on port {}", port_num);
+        log::info!("Starting multiplexed port forwarding on {}", local_addr);
         native::start_multiplexed_port_forwarding(
             stream_url,
             token_value,
-            port_num,
+            Some(kms),
+            local_addr,
+            auth_proxy,
             cancel_child,
             ready_tx,
         )
@@ -1275,13 +2441,23 @@ async fn execute_port_forwarding(
         native::start_native_port_forwarding(
             stream_url,
             token_value,
-            port_num,
+            Some(kms),
+            local_addr,
+            auth_proxy,
             cancel_child,
             ready_tx,
         )
         .await
     };
 
+    port_forward_outcome(result, cancel_token)
+}
+
+/// Classify how a port forwarding run ended.
+fn port_forward_outcome(
+    result: Result<(), String>,
+    cancel_token: &CancellationToken,
+) -> Result<(), PortForwardError> {
     if cancel_token.is_cancelled() {
         return Err(PortForwardError::Cancelled);
     }
@@ -1295,25 +2471,121 @@ async fn execute_port_forwarding(
     }
 }
 
-/// Send a desktop notification only when no app window is focused.
-/// Failures are silently ignored so notifications are never fatal.
-fn send_notification(app_handle: &AppHandle, title: &str, body: &str) {
-    // Skip notification if any app window is currently focused
-    if app_handle.webview_windows().values().any(|w| w.is_focused().unwrap_or(false)) {
-        return;
+/// Starts per-destination sessions on the bastion, for the SOCKS proxy and
+/// Redis cluster nodes.
+struct BastionSessions {
+    clients: AwsClients,
+    bastion_id: String,
+    local_port: String,
+}
+
+impl socks::SessionStarter for BastionSessions {
+    async fn start(&self, host: &str, port: u16) -> Result<SessionTokens, String> {
+        let response = operations::start_remote_port_forwarding_session(
+            &self.clients,
+            &self.bastion_id,
+            host,
+            &port.to_string(),
+            &self.local_port,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let stream_url = response
+            .stream_url()
+            .ok_or_else(|| "No StreamUrl in session response".to_string())?
+            .to_string();
+        let token_value = response
+            .token_value()
+            .ok_or_else(|| "No TokenValue in session response".to_string())?
+            .to_string();
+        let kms = KmsSessionContext {
+            kms: self.clients.kms.clone(),
+            session_id: response.session_id().unwrap_or_default().to_string(),
+            target_id: self.bastion_id.clone(),
+        };
+
+        Ok(SessionTokens {
+            stream_url,
+            token_value,
+            kms: Some(kms),
+        })
     }
-    let _ = app_handle
-        .notification()
-        .builder()
-        .title(title)
-        .body(body)
-        .show();
 }
 
-fn emit_status_event(app_handle: &AppHandle, message: &str, connection_id: Option<&str>) {
-    let mut payload = serde_json::json!({ "message": message });
-    if let Some(id) = connection_id {
-        payload["connectionId"] = serde_json::json!(id);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_forward_targets() {
+        assert_eq!(
+            parse_forward_target("10.0.1.25:6379").unwrap(),
+            ("10.0.1.25".to_string(), 6379)
+        );
+        assert_eq!(
+            parse_forward_target("internal-api.corp.example.com:443").unwrap(),
+            ("internal-api.corp.example.com".to_string(), 443)
+        );
+
+        for bad in [
+            "10.0.1.25",
+            "10.0.1.256:80",
+            "host:0",
+            "host:70000",
+            "-oProxyCommand=x:22",
+            "host name:80",
+            "host;rm:80",
+            ":80",
+        ] {
+            assert!(parse_forward_target(bad).is_err(), "accepted {bad}");
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_connects_reserve_distinct_ports() {
+        let manager = TunnelManager::new(Arc::new(CliTunnelHandler::default()));
+        let free = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+            .to_string();
+
+        let localhost = Ipv4Addr::LOCALHOST;
+        let first = manager.allocate_port(localhost, &free, false, &[]).await.unwrap();
+        let second = manager.allocate_port(localhost, &free, false, &[]).await.unwrap();
+        assert_eq!(first.port.to_string(), free);
+        assert_ne!(second.port, first.port);
+
+        let err = manager.allocate_port(localhost, &free, true, &[]).await.err().unwrap();
+        assert!(err.to_string().contains("already in use"), "{err}");
+
+        drop(first);
+        let again = manager.allocate_port(localhost, &free, true, &[]).await.unwrap();
+        assert_eq!(again.port.to_string(), free);
+    }
+
+    /// The whole of 127/8 is local on Linux; elsewhere aliases need setup.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn loopback_aliases_share_a_port() {
+        let manager = TunnelManager::new(Arc::new(CliTunnelHandler::default()));
+        let free = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+            .to_string();
+
+        let first = manager
+            .allocate_port(Ipv4Addr::LOCALHOST, &free, true, &[])
+            .await
+            .unwrap();
+        let alias = manager
+            .allocate_port(Ipv4Addr::new(127, 0, 0, 2), &free, true, &[free.clone()])
+            .await
+            .unwrap();
+        assert_eq!(first.port, alias.port);
     }
-    let _ = app_handle.emit("status", &payload);
 }
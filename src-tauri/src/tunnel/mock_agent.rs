@@ -0,0 +1,667 @@
+//! In-process stand-in for the SSM relay and agent, for end-to-end tunnel tests.
+//!
+//! `MockAgent` accepts one data channel on a local WebSocket and speaks the
+//! protocol the way the real agent does: `OpenDataChannelInput`, the handshake
+//! payloads, acknowledges, per-direction sequence numbers with retransmission,
+//! `channel_closed`, and smux framing when both sides negotiate it. Forwarded
+//! bytes are relayed to a local TCP target (usually `spawn_echo_target`).
+//!
+//! `Faults` injects packet loss, reordering and duplicate delivery, so the
+//! client's reliability layer can be exercised in `cargo test` without an AWS
+//! account.
+
+use crate::tunnel::protocol::{
+    build_acknowledge, AgentMessage, HandshakeResponsePayload, ProcessedClientAction,
+    ACKNOWLEDGE, ACTION_KMS_ENCRYPTION, ACTION_SESSION_TYPE, ACTION_STATUS_SUCCESS,
+    CHANNEL_CLOSED, FLAG_DATA, FLAG_DISCONNECT_TO_PORT, FLAG_SYN, FLAG_TERMINATE_SESSION,
+    INPUT_STREAM_DATA, OUTPUT_STREAM_DATA, PAYLOAD_FLAG, PAYLOAD_HANDSHAKE_COMPLETE,
+    PAYLOAD_HANDSHAKE_REQUEST, PAYLOAD_HANDSHAKE_RESPONSE, PAYLOAD_OUTPUT, PAYLOAD_SIZE,
+    TerminalSize,
+};
+use crate::tunnel::smux::{self, SmuxFrame, CMD_FIN, CMD_PSH, CMD_SYN};
+use byteorder::{BigEndian, ByteOrder};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use tokio_util::sync::CancellationToken;
+
+/// Token the mock expects in `OpenDataChannelInput`.
+const MOCK_TOKEN: &str = "mock-session-token";
+
+/// Agent version reported by default — new enough for smux.
+const MOCK_AGENT_VERSION: &str = "3.3.40.0";
+
+/// How long an agent→client message may stay unacknowledged before resending.
+const RETRANSMIT_AFTER: Duration = Duration::from_millis(200);
+
+/// How often the retransmission scheduler runs.
+const RETRANSMIT_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Lowest client version for which the agent enables smux (1.1.70.0).
+const SMUX_MIN_CLIENT_VERSION: [u32; 3] = [1, 1, 70];
+
+/// Deterministic fault injection. Each `*_every: Some(n)` hits every nth
+/// message in that direction, counting from 1.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Faults {
+    /// Drop the first transmission of every nth agent→client message; the
+    /// mock's retransmission delivers it later.
+    pub drop_every: Option<u64>,
+    /// Send every nth agent→client message twice.
+    pub duplicate_every: Option<u64>,
+    /// Hold back every nth agent→client message until after the next one.
+    pub reorder_every: Option<u64>,
+    /// Ignore every nth client→agent message (no ack, no processing), as if
+    /// it was lost on the wire, so the client has to retransmit it.
+    pub drop_inbound_every: Option<u64>,
+}
+
+impl Faults {
+    fn hits(every: Option<u64>, count: u64) -> bool {
+        matches!(every, Some(n) if n > 0 && count.is_multiple_of(n))
+    }
+}
+
+/// Mock agent configuration.
+#[derive(Debug, Clone)]
+pub(crate) struct MockAgentConfig {
+    /// Where forwarded connections are relayed to.
+    pub target: SocketAddr,
+    /// `AgentVersion` sent in the HandshakeRequest; controls smux negotiation.
+    pub agent_version: String,
+    /// When set, the HandshakeRequest also asks for KMSEncryption with this key.
+    /// The mock cannot decrypt, so it closes the channel if the client accepts
+    /// or rejects it — tests use it to check what the client reported.
+    pub kms_key_id: Option<String>,
+    /// Offer a `Standard_Stream` shell session instead of port forwarding.
+    /// Input is relayed to `target`, which plays the remote shell.
+    pub shell: bool,
+    pub faults: Faults,
+}
+
+impl MockAgentConfig {
+    pub fn new(target: SocketAddr) -> Self {
+        Self {
+            target,
+            agent_version: MOCK_AGENT_VERSION.to_string(),
+            kms_key_id: None,
+            shell: false,
+            faults: Faults::default(),
+        }
+    }
+}
+
+/// What the mock observed during the session, for test assertions.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MockReport {
+    /// `ClientVersion` from `OpenDataChannelInput`.
+    pub client_version: String,
+    /// Whether smux framing was negotiated.
+    pub multiplexed: bool,
+    /// Client actions from the HandshakeResponse.
+    pub processed_actions: Vec<ProcessedClientAction>,
+    /// SYN flags received (one per basic-mode TCP connection).
+    pub syn_count: u32,
+    /// Whether the client sent TerminateSession.
+    pub terminated: bool,
+    /// Terminal sizes from `PAYLOAD_SIZE` messages, in order.
+    pub terminal_sizes: Vec<TerminalSize>,
+}
+
+/// Handle to a running mock agent. Accepts a single data channel, like a real
+/// session token.
+pub(crate) struct MockAgent {
+    url: String,
+    report: Arc<Mutex<MockReport>>,
+    /// Notified whenever the session records something in the report.
+    changed: Arc<Notify>,
+    close: CancellationToken,
+    kill: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+impl MockAgent {
+    pub async fn start(config: MockAgentConfig) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock agent listener");
+        let url = format!("ws://{}/", listener.local_addr().expect("local addr"));
+        let report = Arc::new(Mutex::new(MockReport::default()));
+        let changed = Arc::new(Notify::new());
+        let close = CancellationToken::new();
+        let kill = CancellationToken::new();
+
+        let report_task = report.clone();
+        let changed_task = changed.clone();
+        let close_task = close.clone();
+        let kill_task = kill.clone();
+        let handle = tokio::spawn(async move {
+            let (tcp, _) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(_) => return,
+            };
+            let ws = match tokio_tungstenite::accept_async(tcp).await {
+                Ok(ws) => ws,
+                Err(_) => return,
+            };
+            run_session(ws, config, report_task, changed_task, close_task, kill_task).await;
+        });
+
+        Self {
+            url,
+            report,
+            changed,
+            close,
+            kill,
+            handle,
+        }
+    }
+
+    pub fn stream_url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn token(&self) -> String {
+        MOCK_TOKEN.to_string()
+    }
+
+    /// Send `channel_closed` and close the WebSocket, as the relay does when
+    /// the session is terminated server-side.
+    pub fn close_channel(&self) {
+        self.close.cancel();
+    }
+
+    /// Drop the connection without a close frame or `channel_closed`.
+    pub fn drop_connection(&self) {
+        self.kill.cancel();
+    }
+
+    pub fn report(&self) -> MockReport {
+        self.report.lock().expect("mock report lock").clone()
+    }
+
+    /// Wait until the report satisfies `done` (e.g. a message has arrived).
+    pub async fn wait_until(&self, done: impl Fn(&MockReport) -> bool) {
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            // Registered before checking, so a change in between isn't missed
+            changed.as_mut().enable();
+            if done(&self.report()) {
+                return;
+            }
+            changed.await;
+        }
+    }
+
+    /// Wait for the session to end and return the final report.
+    pub async fn finished(self) -> MockReport {
+        let Self { report, handle, .. } = self;
+        let _ = handle.await;
+        report.lock().expect("mock report lock").clone()
+    }
+}
+
+/// Reported by the reader task of a target connection.
+enum TargetEvent {
+    Data(u32, Vec<u8>),
+    Closed(u32),
+}
+
+/// Server-side state of one data channel.
+struct MockSession {
+    ws: WebSocketStream<TcpStream>,
+    config: MockAgentConfig,
+    report: Arc<Mutex<MockReport>>,
+    changed: Arc<Notify>,
+    multiplexed: bool,
+    // agent → client
+    next_seq: i64,
+    unacked: BTreeMap<i64, (Vec<u8>, Instant)>,
+    held_back: Option<Vec<u8>>,
+    sent_count: u64,
+    // client → agent
+    expected_seq: i64,
+    inbound: BTreeMap<i64, AgentMessage>,
+    received_count: u64,
+    // target side: smux stream ID, or a per-connection ID in basic mode
+    targets: HashMap<u32, OwnedWriteHalf>,
+    target_tx: mpsc::UnboundedSender<TargetEvent>,
+    basic_conn: u32,
+    smux_buf: Vec<u8>,
+}
+
+async fn run_session(
+    mut ws: WebSocketStream<TcpStream>,
+    config: MockAgentConfig,
+    report: Arc<Mutex<MockReport>>,
+    changed: Arc<Notify>,
+    close: CancellationToken,
+    kill: CancellationToken,
+) {
+    // The client authenticates with OpenDataChannelInput as a text frame.
+    let client_version = match ws.next().await {
+        Some(Ok(Message::Text(text))) => {
+            let open: serde_json::Value = match serde_json::from_str(text.as_str()) {
+                Ok(v) => v,
+                Err(_) => return,
+            };
+            if open["TokenValue"] != MOCK_TOKEN {
+                let _ = ws.close(None).await;
+                return;
+            }
+            open["ClientVersion"].as_str().unwrap_or_default().to_string()
+        }
+        _ => return,
+    };
+    let multiplexed = !config.shell
+        && smux::agent_supports_smux(&config.agent_version)
+        && version_at_least(&client_version, &SMUX_MIN_CLIENT_VERSION);
+    {
+        let mut r = report.lock().expect("mock report lock");
+        r.client_version = client_version;
+        r.multiplexed = multiplexed;
+    }
+
+    let (target_tx, mut target_rx) = mpsc::unbounded_channel();
+    let mut session = MockSession {
+        ws,
+        config,
+        report,
+        changed,
+        multiplexed,
+        next_seq: 0,
+        unacked: BTreeMap::new(),
+        held_back: None,
+        sent_count: 0,
+        expected_seq: 0,
+        inbound: BTreeMap::new(),
+        received_count: 0,
+        targets: HashMap::new(),
+        target_tx,
+        basic_conn: 0,
+        smux_buf: Vec::new(),
+    };
+
+    if session.send_handshake_request().await.is_err() {
+        return;
+    }
+
+    let mut retransmit = tokio::time::interval(RETRANSMIT_CHECK_INTERVAL);
+    loop {
+        let result = tokio::select! {
+            // Abrupt close: drop the socket without a close frame.
+            _ = kill.cancelled() => return,
+            _ = close.cancelled() => {
+                session.close_channel("Session terminated by the mock agent").await;
+                return;
+            }
+            msg = session.ws.next() => match msg {
+                Some(Ok(Message::Binary(data))) => session.on_client_message(&data).await,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => Ok(()),
+            },
+            event = target_rx.recv() => match event {
+                Some(event) => session.on_target_event(event).await,
+                None => Ok(()),
+            },
+            _ = retransmit.tick() => session.retransmit().await,
+        };
+        if result.is_err() {
+            return;
+        }
+    }
+}
+
+impl MockSession {
+    async fn send_handshake_request(&mut self) -> Result<(), String> {
+        let session_type = if self.config.shell {
+            serde_json::json!({ "SessionType": "Standard_Stream" })
+        } else {
+            serde_json::json!({
+                "SessionType": "Port",
+                "Properties": {
+                    "portNumber": self.config.target.port().to_string(),
+                    "type": "LocalPortForwarding",
+                },
+            })
+        };
+        let mut actions = vec![serde_json::json!({
+            "ActionType": ACTION_SESSION_TYPE,
+            "ActionParameters": session_type,
+        })];
+        if let Some(key) = &self.config.kms_key_id {
+            actions.push(serde_json::json!({
+                "ActionType": ACTION_KMS_ENCRYPTION,
+                "ActionParameters": { "KMSKeyId": key },
+            }));
+        }
+        let request = serde_json::json!({
+            "AgentVersion": self.config.agent_version,
+            "RequestedClientActions": actions,
+        });
+        let payload = serde_json::to_vec(&request).map_err(|e| e.to_string())?;
+        self.send_output(PAYLOAD_HANDSHAKE_REQUEST, payload).await
+    }
+
+    /// Send an output_stream_data message reliably, applying the outbound faults
+    /// to its first transmission.
+    async fn send_output(&mut self, payload_type: u32, payload: Vec<u8>) -> Result<(), String> {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let bytes =
+            AgentMessage::new(OUTPUT_STREAM_DATA, seq, FLAG_DATA, payload_type, payload).serialize();
+        self.unacked.insert(seq, (bytes.clone(), Instant::now()));
+
+        self.sent_count += 1;
+        let faults = self.config.faults;
+        if Faults::hits(faults.drop_every, self.sent_count) {
+            return Ok(());
+        }
+        if Faults::hits(faults.reorder_every, self.sent_count) && self.held_back.is_none() {
+            self.held_back = Some(bytes);
+            return Ok(());
+        }
+        self.send_raw(bytes.clone()).await?;
+        if Faults::hits(faults.duplicate_every, self.sent_count) {
+            self.send_raw(bytes).await?;
+        }
+        if let Some(held) = self.held_back.take() {
+            self.send_raw(held).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_raw(&mut self, bytes: Vec<u8>) -> Result<(), String> {
+        self.ws
+            .send(Message::Binary(bytes.into()))
+            .await
+            .map_err(|e| format!("Mock agent send failed: {}", e))
+    }
+
+    async fn retransmit(&mut self) -> Result<(), String> {
+        if let Some(held) = self.held_back.take() {
+            self.send_raw(held).await?;
+        }
+        let due: Vec<Vec<u8>> = self
+            .unacked
+            .values_mut()
+            .filter(|(_, sent_at)| sent_at.elapsed() > RETRANSMIT_AFTER)
+            .map(|(bytes, sent_at)| {
+                *sent_at = Instant::now();
+                bytes.clone()
+            })
+            .collect();
+        for bytes in due {
+            self.send_raw(bytes).await?;
+        }
+        Ok(())
+    }
+
+    async fn on_client_message(&mut self, data: &[u8]) -> Result<(), String> {
+        let msg = AgentMessage::deserialize(data)?;
+        match msg.message_type.as_str() {
+            ACKNOWLEDGE => {
+                let content: serde_json::Value =
+                    serde_json::from_slice(&msg.payload).map_err(|e| e.to_string())?;
+                if let Some(seq) = content["AcknowledgedMessageSequenceNumber"].as_i64() {
+                    self.unacked.remove(&seq);
+                }
+                Ok(())
+            }
+            INPUT_STREAM_DATA => {
+                self.received_count += 1;
+                if Faults::hits(self.config.faults.drop_inbound_every, self.received_count) {
+                    return Ok(());
+                }
+                if msg.payload_type == PAYLOAD_FLAG
+                    && msg.payload.len() >= 4
+                    && BigEndian::read_u32(&msg.payload[..4]) == FLAG_TERMINATE_SESSION
+                {
+                    // Sent right before the client closes the socket — record it
+                    // even if an earlier message is still missing, and before
+                    // the ack (which may find the socket already closed).
+                    self.record(|r| r.terminated = true);
+                }
+                self.send_raw(build_acknowledge(&msg).serialize()).await?;
+
+                // Duplicate of an already processed message: acked, not replayed
+                if msg.sequence_number < self.expected_seq {
+                    return Ok(());
+                }
+                self.inbound.insert(msg.sequence_number, msg);
+                while let Some(next) = self.inbound.remove(&self.expected_seq) {
+                    self.expected_seq += 1;
+                    self.process(next).await?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Handle an in-order client message.
+    async fn process(&mut self, msg: AgentMessage) -> Result<(), String> {
+        match msg.payload_type {
+            PAYLOAD_HANDSHAKE_RESPONSE => {
+                let response: HandshakeResponsePayload =
+                    serde_json::from_slice(&msg.payload).map_err(|e| e.to_string())?;
+                let kms_requested = response
+                    .processed_client_actions
+                    .iter()
+                    .any(|a| a.action_type == ACTION_KMS_ENCRYPTION);
+                let failed = response
+                    .processed_client_actions
+                    .iter()
+                    .find(|a| a.action_status != ACTION_STATUS_SUCCESS)
+                    .map(|a| a.error.clone());
+                self.record(|r| r.processed_actions = response.processed_client_actions);
+
+                // The real agent terminates the session when a client action
+                // fails; the mock also stops after a KMS negotiation it cannot
+                // take part in.
+                if let Some(error) = failed {
+                    self.close_channel(&error).await;
+                    return Err(error);
+                }
+                if kms_requested {
+                    self.close_channel("KMS encryption is not supported by the mock agent")
+                        .await;
+                    return Err("KMS encryption requested".to_string());
+                }
+
+                let complete = serde_json::json!({
+                    "HandshakeTimeToComplete": 1_000_000,
+                    "CustomerMessage": "",
+                });
+                let payload = serde_json::to_vec(&complete).map_err(|e| e.to_string())?;
+                self.send_output(PAYLOAD_HANDSHAKE_COMPLETE, payload).await
+            }
+            PAYLOAD_FLAG if msg.flags == FLAG_SYN => {
+                self.record(|r| r.syn_count += 1);
+                if !self.multiplexed {
+                    self.connect_basic().await?;
+                }
+                Ok(())
+            }
+            PAYLOAD_FLAG if msg.payload.len() >= 4 => {
+                if BigEndian::read_u32(&msg.payload[..4]) == FLAG_DISCONNECT_TO_PORT {
+                    self.targets.remove(&self.basic_conn);
+                }
+                // TerminateSession is recorded on arrival; anything else
+                // (keepalive flag 0) is a no-op, as on the real agent.
+                Ok(())
+            }
+            PAYLOAD_SIZE => {
+                let size: TerminalSize =
+                    serde_json::from_slice(&msg.payload).map_err(|e| e.to_string())?;
+                self.record(|r| r.terminal_sizes.push(size));
+                Ok(())
+            }
+            PAYLOAD_OUTPUT if self.multiplexed => self.on_smux_data(&msg.payload).await,
+            PAYLOAD_OUTPUT => {
+                if !self.targets.contains_key(&self.basic_conn) {
+                    self.connect_basic().await?;
+                }
+                self.write_target(self.basic_conn, &msg.payload).await;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn on_smux_data(&mut self, data: &[u8]) -> Result<(), String> {
+        self.smux_buf.extend_from_slice(data);
+        while let Some((frame, consumed)) = SmuxFrame::deserialize(&self.smux_buf) {
+            self.smux_buf.drain(..consumed);
+            match frame.cmd {
+                CMD_SYN => self.connect_target(frame.stream_id).await?,
+                CMD_PSH => self.write_target(frame.stream_id, &frame.payload).await,
+                CMD_FIN => {
+                    self.targets.remove(&frame.stream_id);
+                }
+                _ => {} // NOP keepalive
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_target_event(&mut self, event: TargetEvent) -> Result<(), String> {
+        match event {
+            TargetEvent::Data(id, data) => {
+                if !self.targets.contains_key(&id) {
+                    return Ok(());
+                }
+                let payload = if self.multiplexed {
+                    SmuxFrame::psh(id, data).serialize()
+                } else if id == self.basic_conn {
+                    data
+                } else {
+                    return Ok(());
+                };
+                self.send_output(PAYLOAD_OUTPUT, payload).await
+            }
+            TargetEvent::Closed(id) => {
+                if self.targets.remove(&id).is_some() && self.multiplexed {
+                    return self
+                        .send_output(PAYLOAD_OUTPUT, SmuxFrame::fin(id).serialize())
+                        .await;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Basic mode: (re)connect the single target connection.
+    async fn connect_basic(&mut self) -> Result<(), String> {
+        self.targets.remove(&self.basic_conn);
+        self.basic_conn += 1;
+        self.connect_target(self.basic_conn).await
+    }
+
+    async fn connect_target(&mut self, id: u32) -> Result<(), String> {
+        let stream = TcpStream::connect(self.config.target)
+            .await
+            .map_err(|e| format!("Mock agent failed to reach target: {}", e))?;
+        let (mut read, write) = stream.into_split();
+        self.targets.insert(id, write);
+
+        let tx = self.target_tx.clone();
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 4096];
+            loop {
+                match read.read(&mut buf).await {
+                    Ok(0) | Err(_) => {
+                        let _ = tx.send(TargetEvent::Closed(id));
+                        break;
+                    }
+                    Ok(n) => {
+                        if tx.send(TargetEvent::Data(id, buf[..n].to_vec())).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn write_target(&mut self, id: u32, data: &[u8]) {
+        if let Some(write) = self.targets.get_mut(&id)
+            && write.write_all(data).await.is_err()
+        {
+            self.targets.remove(&id);
+        }
+    }
+
+    async fn close_channel(&mut self, reason: &str) {
+        let payload = serde_json::json!({
+            "MessageType": CHANNEL_CLOSED,
+            "SchemaVersion": 1,
+            "Output": reason,
+        });
+        let msg = AgentMessage::new(
+            CHANNEL_CLOSED,
+            0,
+            FLAG_DATA,
+            0,
+            serde_json::to_vec(&payload).unwrap_or_default(),
+        );
+        let _ = self.send_raw(msg.serialize()).await;
+        let _ = self.ws.close(None).await;
+    }
+
+    fn report(&self) -> std::sync::MutexGuard<'_, MockReport> {
+        self.report.lock().expect("mock report lock")
+    }
+
+    /// Update the report and wake [`MockAgent::wait_until`].
+    fn record(&self, update: impl FnOnce(&mut MockReport)) {
+        update(&mut self.report());
+        self.changed.notify_waiters();
+    }
+}
+
+/// Compare a dotted version against a `[major, minor, build]` floor.
+fn version_at_least(version: &str, floor: &[u32; 3]) -> bool {
+    let parts: Vec<u32> = version
+        .split('.')
+        .take(3)
+        .map(|s| s.parse().unwrap_or(0))
+        .collect();
+    parts.as_slice() >= floor.as_slice()
+}
+
+/// Start a TCP echo server on an ephemeral port.
+pub(crate) async fn spawn_echo_target() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind echo target");
+    let addr = listener.local_addr().expect("echo target addr");
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let (mut read, mut write) = stream.split();
+                let _ = tokio::io::copy(&mut read, &mut write).await;
+            });
+        }
+    });
+    addr
+}
+
+/// Pick a free local port for the tunnel listener.
+pub(crate) fn free_local_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .map(|addr| addr.port())
+        .expect("free local port")
+}
@@ -1,6 +1,18 @@
-#[cfg(feature = "gui")]
+pub mod auth_proxy;
+pub mod encryption;
+pub mod export;
+pub mod hooks;
+pub mod http_proxy;
 pub mod manager;
+#[cfg(test)]
+pub(crate) mod mock_agent;
+pub mod mysql_proxy;
 pub mod native;
+pub mod pg_proxy;
 pub mod protocol;
+pub mod read_only;
+pub mod redis_proxy;
+pub mod shell;
 pub mod smux;
+pub mod socks;
 pub mod websocket;
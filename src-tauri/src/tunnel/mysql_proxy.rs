@@ -0,0 +1,875 @@
+//! MySQL side of the auth proxy (see [`crate::tunnel::auth_proxy`]).
+//!
+//! The server speaks first in MySQL, so the proxy reads the upstream greeting
+//! before greeting the client with the same server version and connection ID
+//! (`KILL` keeps working). Whatever password the client sends — including
+//! none — is accepted. Upstream the proxy switches to TLS, logs in with the
+//! real user name and answers `mysql_native_password`,
+//! `caching_sha2_password` or `mysql_clear_password` (RDS IAM auth). The
+//! server's OK packet is passed on to the client and from then on the session
+//! is relayed untouched — or, with the read-only guard, made read-only first
+//! and relayed packet by packet with each query checked.
+
+use crate::tunnel::auth_proxy::{self, AuthProxy, DbCredentials, ProxyStream};
+use crate::tunnel::read_only::{self, ReadOnlyGuard, SqlDialect};
+use aws_lc_rs::digest;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+// Capability flags
+const CLIENT_CONNECT_WITH_DB: u32 = 0x0000_0008;
+const CLIENT_COMPRESS: u32 = 0x0000_0020;
+const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+const CLIENT_SSL: u32 = 0x0000_0800;
+const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+const CLIENT_CONNECT_ATTRS: u32 = 0x0010_0000;
+const CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA: u32 = 0x0020_0000;
+const CLIENT_ZSTD_COMPRESSION_ALGORITHM: u32 = 0x0400_0000;
+const CLIENT_QUERY_ATTRIBUTES: u32 = 0x0800_0000;
+
+/// Capabilities never offered to the local client: it talks plain TCP, and
+/// compression would start before the proxy is out of the way.
+const LOCAL_CAPABILITIES_MASK: u32 =
+    !(CLIENT_SSL | CLIENT_COMPRESS | CLIENT_ZSTD_COMPRESSION_ALGORITHM);
+
+const NATIVE_PASSWORD: &str = "mysql_native_password";
+const CACHING_SHA2_PASSWORD: &str = "caching_sha2_password";
+const SHA256_PASSWORD: &str = "sha256_password";
+const CLEAR_PASSWORD: &str = "mysql_clear_password";
+
+const OK_PACKET: u8 = 0x00;
+const AUTH_MORE_DATA: u8 = 0x01;
+const AUTH_SWITCH_REQUEST: u8 = 0xfe;
+const ERR_PACKET: u8 = 0xff;
+
+/// `caching_sha2_password` AuthMoreData payloads.
+const FAST_AUTH_SUCCESS: u8 = 0x03;
+const PERFORM_FULL_AUTHENTICATION: u8 = 0x04;
+
+/// Largest packet accepted before login completes.
+const MAX_AUTH_PACKET_LENGTH: usize = 64 * 1024;
+
+/// Commands the read-only guard looks at.
+const COM_QUERY: u8 = 0x03;
+const COM_CHANGE_USER: u8 = 0x11;
+const COM_STMT_PREPARE: u8 = 0x16;
+const COM_RESET_CONNECTION: u8 = 0x1f;
+
+/// A payload this long continues in the next packet.
+const MAX_PACKET_PAYLOAD: usize = 0xff_ffff;
+
+const ER_HANDSHAKE_ERROR: u16 = 1043;
+const ER_ACCESS_DENIED_ERROR: u16 = 1045;
+const ER_CANT_EXECUTE_IN_READ_ONLY_TRANSACTION: u16 = 1792;
+
+/// The server greeting (Protocol::HandshakeV10) fields the proxy uses.
+struct ServerGreeting {
+    server_version: String,
+    connection_id: u32,
+    scramble: Vec<u8>,
+    capabilities: u32,
+    character_set: u8,
+    status_flags: u16,
+    auth_plugin: String,
+}
+
+/// The client's Protocol::HandshakeResponse41, minus its password.
+#[derive(Debug, PartialEq)]
+struct ClientLogin {
+    capabilities: u32,
+    max_packet_size: u32,
+    character_set: u8,
+    database: Option<String>,
+    auth_plugin: String,
+    auth_response: Vec<u8>,
+    /// Raw `CLIENT_CONNECT_ATTRS` block (length prefix included).
+    attributes: Option<Vec<u8>>,
+}
+
+/// Why an upstream login failed.
+enum LoginError {
+    /// The server sent this ERR packet; pass it on to the client.
+    Rejected(Vec<u8>),
+    /// Anything else; the client still needs to be told.
+    Failed(String),
+}
+
+/// Serve one client connection: upstream greeting, local handshake, upstream
+/// login, then relay.
+pub async fn serve<C, U>(mut client: C, mut upstream: U, proxy: &AuthProxy) -> Result<(), String>
+where
+    C: AsyncRead + AsyncWrite + Unpin + Send,
+    U: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let greeting = match read_packet(&mut upstream).await {
+        Ok((_, payload)) if payload.first() == Some(&ERR_PACKET) => {
+            // e.g. "Host is blocked" — the client should see it as is
+            let _ = write_packet(&mut client, 0, &payload).await;
+            return Err(format!("Server refused the connection: {}", error_message(&payload)));
+        }
+        Ok((_, payload)) => parse_greeting(&payload),
+        Err(e) => Err(e),
+    };
+    let greeting = match greeting {
+        Ok(greeting) => greeting,
+        Err(e) => {
+            send_error(&mut client, 0, ER_HANDSHAKE_ERROR, "08S01", &e).await;
+            return Err(e);
+        }
+    };
+
+    // Query attributes would sit in front of the SQL the guard checks
+    let local_mask = match proxy.read_only {
+        Some(_) => LOCAL_CAPABILITIES_MASK & !CLIENT_QUERY_ATTRIBUTES,
+        None => LOCAL_CAPABILITIES_MASK,
+    };
+    let local_scramble = random_scramble();
+    write_packet(&mut client, 0, &local_greeting(&greeting, &local_scramble, local_mask))
+        .await
+        .map_err(|e| e.to_string())?;
+    let (_, response) = read_packet(&mut client).await?;
+    let login = match parse_client_login(&response) {
+        Ok(login) => login,
+        Err(e) => {
+            send_error(&mut client, 2, ER_HANDSHAKE_ERROR, "08S01", &e).await;
+            return Err(e);
+        }
+    };
+
+    // A real server confirms a caching_sha2 scramble with "fast auth
+    // success" before the OK; clients that picked that plugin expect it.
+    let mut ok_sequence = 2;
+    if login.auth_plugin == CACHING_SHA2_PASSWORD && !login.auth_response.is_empty() {
+        write_packet(&mut client, 2, &[AUTH_MORE_DATA, FAST_AUTH_SUCCESS])
+            .await
+            .map_err(|e| e.to_string())?;
+        ok_sequence = 3;
+    }
+
+    let credentials = proxy.credentials();
+    let result = login_upstream(upstream, proxy, &greeting, &login, &credentials).await;
+    let mut upstream = match result {
+        Ok((upstream, ok)) => {
+            write_packet(&mut client, ok_sequence, &ok)
+                .await
+                .map_err(|e| e.to_string())?;
+            upstream
+        }
+        Err(LoginError::Rejected(error)) => {
+            let _ = write_packet(&mut client, ok_sequence, &error).await;
+            return Err(format!("Server rejected login: {}", error_message(&error)));
+        }
+        Err(LoginError::Failed(e)) => {
+            send_error(&mut client, ok_sequence, ER_ACCESS_DENIED_ERROR, "28000", &e).await;
+            return Err(e);
+        }
+    };
+
+    if let Some(ref guard) = proxy.read_only {
+        return relay_read_only(client, upstream, guard).await;
+    }
+    match tokio::io::copy_bidirectional(&mut client, &mut upstream).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Switch to TLS if possible, send the login and answer auth requests until
+/// the server sends OK. Returns the stream and the OK payload.
+async fn login_upstream<U>(
+    mut upstream: U,
+    proxy: &AuthProxy,
+    greeting: &ServerGreeting,
+    login: &ClientLogin,
+    credentials: &DbCredentials,
+) -> Result<(Box<dyn ProxyStream>, Vec<u8>), LoginError>
+where
+    U: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let failed = LoginError::Failed;
+    if greeting.capabilities & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA == 0 {
+        // Needed for IAM tokens (~1 KB); every supported server has it
+        return Err(failed("The database server is too old for the auth proxy".to_string()));
+    }
+
+    let mut capabilities = (login.capabilities & greeting.capabilities & LOCAL_CAPABILITIES_MASK)
+        | CLIENT_PROTOCOL_41
+        | CLIENT_SECURE_CONNECTION
+        | CLIENT_PLUGIN_AUTH
+        | CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA;
+    if login.database.is_none() {
+        capabilities &= !CLIENT_CONNECT_WITH_DB;
+    }
+    let mut sequence = 1u8;
+
+    let tls = greeting.capabilities & CLIENT_SSL != 0;
+    let mut stream: Box<dyn ProxyStream> = if tls {
+        capabilities |= CLIENT_SSL;
+        let request = login_header(capabilities, login);
+        write_packet(&mut upstream, sequence, &request)
+            .await
+            .map_err(|e| failed(e.to_string()))?;
+        sequence += 1;
+        Box::new(
+            auth_proxy::connect_tls(upstream, &proxy.server_name, proxy.verify_tls)
+                .await
+                .map_err(failed)?,
+        )
+    } else if proxy.require_tls {
+        return Err(failed(
+            "The database server does not accept TLS, required for IAM auth".to_string(),
+        ));
+    } else {
+        Box::new(upstream)
+    };
+
+    let mut plugin = greeting.auth_plugin.clone();
+    let mut scramble = greeting.scramble.clone();
+    // IAM tokens only work with mysql_clear_password; start with it rather
+    // than waiting for the server to ask
+    if proxy.require_tls {
+        plugin = CLEAR_PASSWORD.to_string();
+    }
+    let auth_data = auth_response(&plugin, &credentials.password, &scramble, tls).map_err(failed)?;
+
+    let mut response = login_header(capabilities, login);
+    response.extend_from_slice(&cstring(&credentials.username));
+    response.extend_from_slice(&lenenc_int(auth_data.len() as u64));
+    response.extend_from_slice(&auth_data);
+    if let Some(ref database) = login.database
+        && capabilities & CLIENT_CONNECT_WITH_DB != 0
+    {
+        response.extend_from_slice(&cstring(database));
+    }
+    response.extend_from_slice(&cstring(&plugin));
+    if let Some(ref attributes) = login.attributes
+        && capabilities & CLIENT_CONNECT_ATTRS != 0
+    {
+        response.extend_from_slice(attributes);
+    }
+    write_packet(&mut stream, sequence, &response)
+        .await
+        .map_err(|e| failed(e.to_string()))?;
+
+    loop {
+        let (received, payload) = read_packet(&mut stream).await.map_err(failed)?;
+        sequence = received.wrapping_add(1);
+        match payload.first() {
+            Some(&OK_PACKET) => {
+                if proxy.read_only.is_some() {
+                    start_read_only_session(&mut stream).await?;
+                }
+                return Ok((stream, payload));
+            }
+            Some(&ERR_PACKET) => return Err(LoginError::Rejected(payload)),
+            Some(&AUTH_SWITCH_REQUEST) => {
+                let (name, data) = split_cstring(&payload[1..]);
+                plugin = name.to_string();
+                scramble = data.strip_suffix(&[0]).unwrap_or(data).to_vec();
+                let answer =
+                    auth_response(&plugin, &credentials.password, &scramble, tls).map_err(failed)?;
+                write_packet(&mut stream, sequence, &answer)
+                    .await
+                    .map_err(|e| failed(e.to_string()))?;
+            }
+            Some(&AUTH_MORE_DATA) if plugin == CACHING_SHA2_PASSWORD => {
+                match payload.get(1) {
+                    Some(&FAST_AUTH_SUCCESS) => {}
+                    Some(&PERFORM_FULL_AUTHENTICATION) if tls => {
+                        write_packet(&mut stream, sequence, &cstring(&credentials.password))
+                            .await
+                            .map_err(|e| failed(e.to_string()))?;
+                    }
+                    Some(&PERFORM_FULL_AUTHENTICATION) => {
+                        return Err(failed(
+                            "caching_sha2_password full authentication needs TLS".to_string(),
+                        ));
+                    }
+                    _ => return Err(failed("Unexpected caching_sha2_password data".to_string())),
+                }
+            }
+            _ => {
+                return Err(failed(format!(
+                    "Unexpected packet during {} login",
+                    plugin
+                )));
+            }
+        }
+    }
+}
+
+/// Make every later transaction read-only, before the client gets in.
+async fn start_read_only_session<S>(stream: &mut S) -> Result<(), LoginError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut query = vec![COM_QUERY];
+    query.extend_from_slice(b"SET SESSION TRANSACTION READ ONLY");
+    write_packet(stream, 0, &query)
+        .await
+        .map_err(|e| LoginError::Failed(e.to_string()))?;
+    let (_, response) = read_packet(stream).await.map_err(LoginError::Failed)?;
+    match response.first() {
+        Some(&OK_PACKET) => Ok(()),
+        Some(&ERR_PACKET) => Err(LoginError::Rejected(response)),
+        _ => Err(LoginError::Failed(
+            "Unexpected answer to SET SESSION TRANSACTION READ ONLY".to_string(),
+        )),
+    }
+}
+
+/// Relay a logged-in session packet by packet. Refused commands never reach
+/// the server; the client gets an ERR packet in place of the server's answer.
+async fn relay_read_only<C, U>(client: C, upstream: U, guard: &ReadOnlyGuard) -> Result<(), String>
+where
+    C: AsyncRead + AsyncWrite + Unpin + Send,
+    U: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let (mut client_read, client_write) = tokio::io::split(client);
+    let (mut upstream_read, mut upstream_write) = tokio::io::split(upstream);
+    // Both directions answer the client; whole packets keep the framing
+    let client_write = Arc::new(Mutex::new(client_write));
+
+    let commands = async {
+        while let Some(packet) = read_client_packet(&mut client_read).await? {
+            // Commands start at sequence 0; anything else belongs to one
+            let command = match packet.first_sequence {
+                0 => packet.payload.first().copied(),
+                _ => None,
+            };
+            let refusal = match command {
+                Some(COM_QUERY) | Some(COM_STMT_PREPARE) => guard
+                    .check(&String::from_utf8_lossy(&packet.payload[1..]), SqlDialect::Mysql)
+                    .map(read_only::blocked_message),
+                // Both start a fresh session without SET ... READ ONLY
+                Some(COM_CHANGE_USER) | Some(COM_RESET_CONNECTION) => Some(
+                    "connection-app read-only guard: resetting the session is blocked on this connection"
+                        .to_string(),
+                ),
+                _ => None,
+            };
+            match refusal {
+                Some(message) => {
+                    let mut error = vec![ERR_PACKET];
+                    error.extend_from_slice(&ER_CANT_EXECUTE_IN_READ_ONLY_TRANSACTION.to_le_bytes());
+                    error.extend_from_slice(b"#25006");
+                    error.extend_from_slice(message.as_bytes());
+                    let mut writer = client_write.lock().await;
+                    write_packet(&mut *writer, packet.last_sequence.wrapping_add(1), &error)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+                None => upstream_write
+                    .write_all(&packet.raw)
+                    .await
+                    .map_err(|e| e.to_string())?,
+            }
+        }
+        let _ = upstream_write.shutdown().await;
+        Ok(())
+    };
+    let results = async {
+        loop {
+            let mut header = [0u8; 4];
+            match upstream_read.read_exact(&mut header).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.to_string()),
+            }
+            let length = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+            let mut packet = header.to_vec();
+            packet.resize(4 + length, 0);
+            upstream_read
+                .read_exact(&mut packet[4..])
+                .await
+                .map_err(|e| e.to_string())?;
+            client_write
+                .lock()
+                .await
+                .write_all(&packet)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        let _ = client_write.lock().await.shutdown().await;
+        Ok(())
+    };
+    tokio::try_join!(commands, results).map(|_| ())
+}
+
+/// A client packet with its continuations (payloads of exactly
+/// [`MAX_PACKET_PAYLOAD`] bytes continue in the next one).
+struct ClientPacket {
+    first_sequence: u8,
+    last_sequence: u8,
+    payload: Vec<u8>,
+    /// As received, headers included, to forward unchanged.
+    raw: Vec<u8>,
+}
+
+/// Next packet from the client; `None` once it disconnects.
+async fn read_client_packet<R: AsyncRead + Unpin>(
+    stream: &mut R,
+) -> Result<Option<ClientPacket>, String> {
+    let mut packet = ClientPacket {
+        first_sequence: 0,
+        last_sequence: 0,
+        payload: Vec::new(),
+        raw: Vec::new(),
+    };
+    loop {
+        let mut header = [0u8; 4];
+        match stream.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && packet.raw.is_empty() => {
+                return Ok(None);
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+        let length = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        let mut payload = vec![0u8; length];
+        stream
+            .read_exact(&mut payload)
+            .await
+            .map_err(|e| e.to_string())?;
+        if packet.raw.is_empty() {
+            packet.first_sequence = header[3];
+        }
+        packet.last_sequence = header[3];
+        packet.raw.extend_from_slice(&header);
+        packet.raw.extend_from_slice(&payload);
+        packet.payload.extend_from_slice(&payload);
+        if length < MAX_PACKET_PAYLOAD {
+            return Ok(Some(packet));
+        }
+    }
+}
+
+/// Password response for `plugin`. Plaintext plugins are only answered over
+/// TLS.
+fn auth_response(plugin: &str, password: &str, scramble: &[u8], tls: bool) -> Result<Vec<u8>, String> {
+    let scramble = &scramble[..scramble.len().min(20)];
+    match plugin {
+        NATIVE_PASSWORD => Ok(native_password(password, scramble)),
+        CACHING_SHA2_PASSWORD => Ok(caching_sha2_password(password, scramble)),
+        CLEAR_PASSWORD | SHA256_PASSWORD if tls => Ok(cstring(password)),
+        CLEAR_PASSWORD | SHA256_PASSWORD => Err(format!(
+            "The server asked for {}, which sends the password in clear; refusing without TLS",
+            plugin
+        )),
+        other => Err(format!("Unsupported authentication plugin {}", other)),
+    }
+}
+
+/// `SHA1(password) XOR SHA1(scramble + SHA1(SHA1(password)))`
+fn native_password(password: &str, scramble: &[u8]) -> Vec<u8> {
+    if password.is_empty() {
+        return Vec::new();
+    }
+    let sha1 = |data: &[u8]| digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, data);
+    let stage1 = sha1(password.as_bytes());
+    let stage2 = sha1(stage1.as_ref());
+    let mix = sha1(&[scramble, stage2.as_ref()].concat());
+    xor(stage1.as_ref(), mix.as_ref())
+}
+
+/// `SHA256(password) XOR SHA256(SHA256(SHA256(password)) + scramble)`
+fn caching_sha2_password(password: &str, scramble: &[u8]) -> Vec<u8> {
+    if password.is_empty() {
+        return Vec::new();
+    }
+    let sha256 = |data: &[u8]| digest::digest(&digest::SHA256, data);
+    let stage1 = sha256(password.as_bytes());
+    let stage2 = sha256(stage1.as_ref());
+    let mix = sha256(&[stage2.as_ref(), scramble].concat());
+    xor(stage1.as_ref(), mix.as_ref())
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(x, y)| x ^ y).collect()
+}
+
+fn parse_greeting(payload: &[u8]) -> Result<ServerGreeting, String> {
+    let malformed = || "Malformed server greeting".to_string();
+    if payload.first() != Some(&10) {
+        return Err(format!(
+            "Unsupported MySQL protocol version {}",
+            payload.first().copied().unwrap_or_default()
+        ));
+    }
+    let (server_version, rest) = split_cstring(&payload[1..]);
+    if rest.len() < 4 + 8 + 1 + 2 {
+        return Err(malformed());
+    }
+    let connection_id = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]);
+    let mut scramble = rest[4..12].to_vec();
+    let mut capabilities = u16::from_le_bytes([rest[13], rest[14]]) as u32;
+    let rest = &rest[15..];
+    if rest.len() < 1 + 2 + 2 + 1 + 10 {
+        return Err(malformed());
+    }
+    let character_set = rest[0];
+    let status_flags = u16::from_le_bytes([rest[1], rest[2]]);
+    capabilities |= (u16::from_le_bytes([rest[3], rest[4]]) as u32) << 16;
+    let auth_data_length = rest[5] as usize;
+    let rest = &rest[16..];
+
+    let mut auth_plugin = NATIVE_PASSWORD.to_string();
+    if capabilities & CLIENT_SECURE_CONNECTION != 0 {
+        let part2_length = auth_data_length.saturating_sub(8).max(13).min(rest.len());
+        let part2 = &rest[..part2_length];
+        scramble.extend_from_slice(part2.strip_suffix(&[0]).unwrap_or(part2));
+        if capabilities & CLIENT_PLUGIN_AUTH != 0 {
+            // Some servers leave the plugin name unterminated
+            auth_plugin = split_cstring(&rest[part2_length..]).0.to_string();
+        }
+    }
+
+    Ok(ServerGreeting {
+        server_version: server_version.to_string(),
+        connection_id,
+        scramble,
+        capabilities,
+        character_set,
+        status_flags,
+        auth_plugin,
+    })
+}
+
+/// The greeting the local client sees: the server's, with our own scramble
+/// and capabilities the proxy can pass through.
+fn local_greeting(greeting: &ServerGreeting, scramble: &[u8; 20], mask: u32) -> Vec<u8> {
+    let capabilities = greeting.capabilities & mask;
+    let mut packet = vec![10];
+    packet.extend_from_slice(&cstring(&greeting.server_version));
+    packet.extend_from_slice(&greeting.connection_id.to_le_bytes());
+    packet.extend_from_slice(&scramble[..8]);
+    packet.push(0);
+    packet.extend_from_slice(&(capabilities as u16).to_le_bytes());
+    packet.push(greeting.character_set);
+    packet.extend_from_slice(&greeting.status_flags.to_le_bytes());
+    packet.extend_from_slice(&((capabilities >> 16) as u16).to_le_bytes());
+    packet.push(21);
+    packet.extend_from_slice(&[0; 10]);
+    packet.extend_from_slice(&scramble[8..]);
+    packet.push(0);
+    packet.extend_from_slice(&cstring(CACHING_SHA2_PASSWORD));
+    packet
+}
+
+fn parse_client_login(payload: &[u8]) -> Result<ClientLogin, String> {
+    let malformed = || "Malformed login packet".to_string();
+    if payload.len() < 32 {
+        return Err(malformed());
+    }
+    let capabilities = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+    if capabilities & CLIENT_PROTOCOL_41 == 0 {
+        return Err("Clients older than MySQL 4.1 are not supported".to_string());
+    }
+    if capabilities & CLIENT_SSL != 0 {
+        return Err("The local connection does not support SSL; disable it in the client".to_string());
+    }
+    let max_packet_size = u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
+    let character_set = payload[8];
+
+    let (_username, mut rest) = split_cstring(&payload[32..]);
+    let auth_response = if capabilities & CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA != 0 {
+        let (length, after) = read_lenenc_int(rest).ok_or_else(malformed)?;
+        let length = usize::try_from(length).map_err(|_| malformed())?;
+        let data = after.get(..length).ok_or_else(malformed)?;
+        rest = &after[length..];
+        data.to_vec()
+    } else if capabilities & CLIENT_SECURE_CONNECTION != 0 {
+        let length = *rest.first().ok_or_else(malformed)? as usize;
+        let data = rest.get(1..1 + length).ok_or_else(malformed)?;
+        rest = &rest[1 + length..];
+        data.to_vec()
+    } else {
+        let (data, after) = split_cstring(rest);
+        rest = after;
+        data.as_bytes().to_vec()
+    };
+
+    let mut database = None;
+    if capabilities & CLIENT_CONNECT_WITH_DB != 0 {
+        let (name, after) = split_cstring(rest);
+        database = Some(name.to_string()).filter(|name| !name.is_empty());
+        rest = after;
+    }
+    let mut auth_plugin = NATIVE_PASSWORD.to_string();
+    if capabilities & CLIENT_PLUGIN_AUTH != 0 {
+        let (name, after) = split_cstring(rest);
+        auth_plugin = name.to_string();
+        rest = after;
+    }
+    let attributes = (capabilities & CLIENT_CONNECT_ATTRS != 0 && !rest.is_empty())
+        .then(|| rest.to_vec());
+
+    Ok(ClientLogin {
+        capabilities,
+        max_packet_size,
+        character_set,
+        database,
+        auth_plugin,
+        auth_response,
+        attributes,
+    })
+}
+
+/// Capabilities, max packet size, character set and filler: the start of
+/// both SSLRequest and HandshakeResponse41.
+fn login_header(capabilities: u32, login: &ClientLogin) -> Vec<u8> {
+    let mut header = capabilities.to_le_bytes().to_vec();
+    header.extend_from_slice(&login.max_packet_size.to_le_bytes());
+    header.push(login.character_set);
+    header.extend_from_slice(&[0; 23]);
+    header
+}
+
+async fn read_packet<R: AsyncRead + Unpin>(stream: &mut R) -> Result<(u8, Vec<u8>), String> {
+    let mut header = [0u8; 4];
+    stream
+        .read_exact(&mut header)
+        .await
+        .map_err(|e| format!("Connection closed during login: {}", e))?;
+    let length = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    if length > MAX_AUTH_PACKET_LENGTH {
+        return Err(format!("Login packet too large ({} bytes)", length));
+    }
+    let mut payload = vec![0u8; length];
+    stream
+        .read_exact(&mut payload)
+        .await
+        .map_err(|e| format!("Truncated packet during login: {}", e))?;
+    Ok((header[3], payload))
+}
+
+async fn write_packet<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    sequence: u8,
+    payload: &[u8],
+) -> std::io::Result<()> {
+    let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+    packet.push(sequence);
+    packet.extend_from_slice(payload);
+    stream.write_all(&packet).await
+}
+
+/// Send an ERR packet; the client reports it and closes the connection.
+async fn send_error<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    sequence: u8,
+    code: u16,
+    sql_state: &str,
+    message: &str,
+) {
+    let mut payload = vec![ERR_PACKET];
+    payload.extend_from_slice(&code.to_le_bytes());
+    payload.push(b'#');
+    payload.extend_from_slice(sql_state.as_bytes());
+    payload.extend_from_slice(format!("connection-app auth proxy: {}", message).as_bytes());
+    let _ = write_packet(stream, sequence, &payload).await;
+}
+
+/// Message of an ERR packet.
+fn error_message(payload: &[u8]) -> String {
+    // 0xff, code (2), then "#" + SQL state (5) with CLIENT_PROTOCOL_41
+    let message = match payload.get(3) {
+        Some(b'#') => payload.get(9..),
+        _ => payload.get(3..),
+    };
+    String::from_utf8_lossy(message.unwrap_or_default()).into_owned()
+}
+
+/// Scramble for the local greeting. Clients never prove anything with it,
+/// but it looks like a server's: printable, no NUL.
+fn random_scramble() -> [u8; 20] {
+    let mut scramble = [0u8; 20];
+    let _ = aws_lc_rs::rand::fill(&mut scramble);
+    for byte in scramble.iter_mut() {
+        *byte = b'!' + *byte % 94;
+    }
+    scramble
+}
+
+/// Split at the first NUL; without one the whole input is the string.
+fn split_cstring(data: &[u8]) -> (&str, &[u8]) {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    let value = std::str::from_utf8(&data[..end]).unwrap_or_default();
+    (value, data.get(end + 1..).unwrap_or_default())
+}
+
+fn cstring(value: &str) -> Vec<u8> {
+    let mut bytes = value.as_bytes().to_vec();
+    bytes.push(0);
+    bytes
+}
+
+fn lenenc_int(value: u64) -> Vec<u8> {
+    match value {
+        0..=250 => vec![value as u8],
+        251..=0xffff => [&[0xfc][..], &(value as u16).to_le_bytes()].concat(),
+        0x1_0000..=0xff_ffff => [&[0xfd][..], &(value as u32).to_le_bytes()[..3]].concat(),
+        _ => [&[0xfe][..], &value.to_le_bytes()].concat(),
+    }
+}
+
+fn read_lenenc_int(data: &[u8]) -> Option<(u64, &[u8])> {
+    let (&first, rest) = data.split_first()?;
+    let width = match first {
+        0..=250 => return Some((first as u64, rest)),
+        0xfc => 2,
+        0xfd => 3,
+        0xfe => 8,
+        _ => return None,
+    };
+    let bytes = rest.get(..width)?;
+    let mut value = [0u8; 8];
+    value[..width].copy_from_slice(bytes);
+    Some((u64::from_le_bytes(value), &rest[width..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tunnel::auth_proxy::ProxyProtocol;
+
+    const SCRAMBLE: &[u8; 20] = b"0123456789abcdefghij";
+
+    #[test]
+    fn test_password_scrambles() {
+        assert_eq!(
+            hex(&native_password("secret", SCRAMBLE)),
+            "f5cac3f3b3df2133feb57db682c71e6ed433a988"
+        );
+        assert_eq!(
+            hex(&caching_sha2_password("secret", SCRAMBLE)),
+            "7e4adda2f2d5ef09b4147518becb0cee2939376203650a8c2bb2815959364bd0"
+        );
+        assert!(native_password("", SCRAMBLE).is_empty());
+        assert!(auth_response(CLEAR_PASSWORD, "token", SCRAMBLE, false).is_err());
+        assert_eq!(
+            auth_response(CLEAR_PASSWORD, "token", SCRAMBLE, true).unwrap(),
+            b"token\0"
+        );
+    }
+
+    #[test]
+    fn test_lenenc_round_trip() {
+        for value in [0, 250, 251, 1200, 0x1_0000, 0x100_0000] {
+            let encoded = lenenc_int(value);
+            assert_eq!(read_lenenc_int(&encoded), Some((value, &[][..])));
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn server_greeting() -> Vec<u8> {
+        let greeting = ServerGreeting {
+            server_version: "8.0.35".to_string(),
+            connection_id: 42,
+            scramble: SCRAMBLE.to_vec(),
+            capabilities: 0xffff_f7ff & !CLIENT_SSL,
+            character_set: 255,
+            status_flags: 2,
+            auth_plugin: NATIVE_PASSWORD.to_string(),
+        };
+        let mut packet = local_greeting(&greeting, SCRAMBLE, LOCAL_CAPABILITIES_MASK);
+        // local_greeting masks compression and names caching_sha2; fix up
+        // the plugin so the fake server asks for native passwords
+        packet.truncate(packet.len() - CACHING_SHA2_PASSWORD.len() - 1);
+        packet.extend_from_slice(&cstring(NATIVE_PASSWORD));
+        packet
+    }
+
+    #[tokio::test]
+    async fn test_serve_logs_in_upstream_with_injected_password() {
+        let proxy = AuthProxy::new(
+            ProxyProtocol::Mysql,
+            "db.example.com",
+            false,
+            DbCredentials {
+                username: "app".to_string(),
+                password: "secret".to_string(),
+            },
+        );
+        let (mut client, client_side) = tokio::io::duplex(4096);
+        let (upstream_side, mut server) = tokio::io::duplex(4096);
+        let serve_proxy = proxy.clone();
+        let proxy_task =
+            tokio::spawn(async move { serve(client_side, upstream_side, &serve_proxy).await });
+
+        let server_task = tokio::spawn(async move {
+            write_packet(&mut server, 0, &server_greeting()).await.unwrap();
+            let (sequence, response) = read_packet(&mut server).await.unwrap();
+            assert_eq!(sequence, 1);
+            let (user, rest) = split_cstring(&response[32..]);
+            assert_eq!(user, "app");
+            let (length, rest) = read_lenenc_int(rest).unwrap();
+            assert_eq!(&rest[..length as usize], native_password("secret", SCRAMBLE));
+            let (database, _) = split_cstring(&rest[length as usize..]);
+            assert_eq!(database, "main");
+            write_packet(&mut server, 2, &[OK_PACKET, 0, 0, 2, 0, 0, 0])
+                .await
+                .unwrap();
+        });
+
+        let (sequence, greeting) = read_packet(&mut client).await.unwrap();
+        assert_eq!(sequence, 0);
+        let greeting = parse_greeting(&greeting).unwrap();
+        assert_eq!(greeting.connection_id, 42);
+        assert_eq!(greeting.auth_plugin, CACHING_SHA2_PASSWORD);
+        assert_eq!(greeting.capabilities & (CLIENT_SSL | CLIENT_COMPRESS), 0);
+
+        // Client: any user, some caching_sha2 scramble of a wrong password
+        let capabilities = CLIENT_PROTOCOL_41
+            | CLIENT_SECURE_CONNECTION
+            | CLIENT_PLUGIN_AUTH
+            | CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA
+            | CLIENT_CONNECT_WITH_DB;
+        let mut response = capabilities.to_le_bytes().to_vec();
+        response.extend_from_slice(&(16 * 1024 * 1024u32).to_le_bytes());
+        response.push(255);
+        response.extend_from_slice(&[0; 23]);
+        response.extend_from_slice(b"whoever\0");
+        response.push(32);
+        response.extend_from_slice(&[7; 32]);
+        response.extend_from_slice(b"main\0caching_sha2_password\0");
+        write_packet(&mut client, 1, &response).await.unwrap();
+
+        assert_eq!(
+            read_packet(&mut client).await.unwrap(),
+            (2, vec![AUTH_MORE_DATA, FAST_AUTH_SUCCESS])
+        );
+        let (sequence, ok) = read_packet(&mut client).await.unwrap();
+        assert_eq!((sequence, ok[0]), (3, OK_PACKET));
+
+        server_task.await.unwrap();
+        drop(client);
+        assert!(proxy_task.await.unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_parse_client_login_keeps_attributes() {
+        let capabilities = CLIENT_PROTOCOL_41
+            | CLIENT_SECURE_CONNECTION
+            | CLIENT_PLUGIN_AUTH
+            | CLIENT_CONNECT_ATTRS;
+        let mut payload = capabilities.to_le_bytes().to_vec();
+        payload.extend_from_slice(&1024u32.to_le_bytes());
+        payload.push(33);
+        payload.extend_from_slice(&[0; 23]);
+        payload.extend_from_slice(b"root\0\x00mysql_native_password\0");
+        payload.extend_from_slice(b"\x0a\x04_pid\x041234");
+
+        let login = parse_client_login(&payload).unwrap();
+        assert_eq!(login.database, None);
+        assert_eq!(login.auth_plugin, NATIVE_PASSWORD);
+        assert!(login.auth_response.is_empty());
+        assert_eq!(login.attributes.as_deref(), Some(&b"\x0a\x04_pid\x041234"[..]));
+    }
+}
@@ -4,14 +4,21 @@ use crate::tunnel::protocol::{
     FLAG_TERMINATE_SESSION, INPUT_STREAM_DATA, OUTPUT_STREAM_DATA, PAYLOAD_FLAG, PAYLOAD_OUTPUT,
     STREAM_DATA_PAYLOAD_SIZE,
 };
+use crate::tunnel::auth_proxy::AuthProxy;
+use crate::tunnel::encryption::{open_output, seal_output, KmsSessionContext};
 use crate::tunnel::smux::{self, SmuxSession};
-use crate::tunnel::websocket::{open_data_channel, open_data_channel_with_version, SsmDataChannel};
+use crate::tunnel::websocket::{open_data_channel_with_encryption, SsmDataChannel};
 use byteorder::{BigEndian, ByteOrder};
 use futures_util::{SinkExt, StreamExt};
 use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_util::sync::CancellationToken;
 
@@ -100,47 +107,239 @@ impl RttEstimator {
 }
 
 /// Native port forwarding session — replaces session-manager-plugin.
+///
+/// `kms` lets the handshake answer a KMSEncryption request when the session
+/// document enables encryption; it is only used if the agent asks for it.
+/// With `auth_proxy`, each local connection goes through the auth proxy.
 pub async fn start_native_port_forwarding(
     stream_url: String,
     token_value: String,
-    local_port: u16,
+    kms: Option<KmsSessionContext>,
+    local_addr: SocketAddrV4,
+    auth_proxy: Option<AuthProxy>,
     cancel: tokio_util::sync::CancellationToken,
     ready_tx: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
 ) -> Result<(), String> {
     // Open data channel (WebSocket + handshake)
-    let channel = open_data_channel(&stream_url, &token_value).await?;
+    let channel = open_data_channel_with_encryption(
+        &stream_url,
+        &token_value,
+        smux::BASIC_CLIENT_VERSION,
+        kms.as_ref(),
+    )
+    .await?;
     log::info!(
         "SSM data channel open, agent version: {}",
         channel.agent_version
     );
-    start_basic_port_forwarding(channel, local_port, cancel, ready_tx).await
+    start_basic_port_forwarding(channel, local_addr, auth_proxy, cancel, ready_tx).await
+}
+
+/// Port forwarding over a single caller-provided stream instead of a local
+/// TCP listener — e.g. stdin/stdout when running as an ssh `ProxyCommand`.
+///
+/// Always uses basic mode (one stream, no smux). The session ends when
+/// `reader` reaches EOF, the agent disconnects from the remote port, or
+/// `cancel` fires.
+pub async fn start_stream_port_forwarding<R, W>(
+    stream_url: String,
+    token_value: String,
+    kms: Option<KmsSessionContext>,
+    reader: R,
+    writer: W,
+    cancel: CancellationToken,
+) -> Result<(), String>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let channel = open_data_channel_with_encryption(
+        &stream_url,
+        &token_value,
+        smux::BASIC_CLIENT_VERSION,
+        kms.as_ref(),
+    )
+    .await?;
+    log::info!(
+        "SSM data channel open (single stream), agent version: {}",
+        channel.agent_version
+    );
+    let source = ClientSource::Stream(Some((Box::new(reader), Box::new(writer))));
+    run_basic_session(channel, source, cancel).await
+}
+
+pub(crate) type ClientReader = Box<dyn AsyncRead + Unpin + Send>;
+pub(crate) type ClientWriter = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// A connection handed to [`start_queued_port_forwarding`]. The session
+/// holds `open` until the connection is done, so the sender can count its
+/// open connections with `Arc::strong_count`.
+pub struct QueuedConnection {
+    pub stream: TcpStream,
+    pub open: Arc<()>,
+}
+
+/// One half of a queued connection, keeping its `open` marker alive.
+struct HoldOpen<T> {
+    inner: T,
+    _open: Arc<()>,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for HoldOpen<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for HoldOpen<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Where client connections come from.
+pub(crate) enum ClientSource {
+    /// Local TCP listeners — each accepted connection is relayed in turn.
+    Tcp {
+        v4: tokio::net::TcpListener,
+        v6: Option<tokio::net::TcpListener>,
+        auth_proxy: Option<AuthProxy>,
+    },
+    /// Connections accepted elsewhere (e.g. by the SOCKS server) and handed
+    /// over; closing the sender ends the session.
+    Queue(mpsc::Receiver<QueuedConnection>),
+    /// One caller-provided stream; taken by the first (and only) connection.
+    Stream(Option<(ClientReader, ClientWriter)>),
+}
+
+impl ClientSource {
+    /// A single stream has nowhere to reconnect to: when the agent drops the
+    /// remote connection, the session is over.
+    fn is_single_stream(&self) -> bool {
+        matches!(self, ClientSource::Stream(_))
+    }
+
+    /// Wait for the next client connection. `Ok(None)` means there will be
+    /// no more (the single stream was already used, or the queue closed).
+    pub(crate) async fn next_client(
+        &mut self,
+    ) -> Result<Option<(ClientReader, ClientWriter)>, String> {
+        let mut open = None;
+        let tcp_stream = match self {
+            ClientSource::Stream(stream) => return Ok(stream.take()),
+            ClientSource::Queue(incoming) => match incoming.recv().await {
+                Some(queued) => {
+                    open = Some(queued.open);
+                    queued.stream
+                }
+                None => return Ok(None),
+            },
+            ClientSource::Tcp { v4, v6, .. } => tokio::select! {
+                result = v4.accept() => result
+                    .map(|(stream, _addr)| stream)
+                    .map_err(|e| format!("TCP accept error (IPv4): {}", e))?,
+                result = async {
+                    match v6 {
+                        Some(l) => l.accept().await,
+                        None => std::future::pending().await,
+                    }
+                } => result
+                    .map(|(stream, _addr)| stream)
+                    .map_err(|e| format!("TCP accept error (IPv6): {}", e))?,
+            },
+        };
+        if let Ok(addr) = tcp_stream.peer_addr() {
+            log::info!("Client connection from {}", addr);
+        }
+
+        // Disable Nagle's algorithm — critical for database protocols that
+        // rely on prompt delivery of small packets (e.g. PostgreSQL 1-byte SSL response).
+        let _ = tcp_stream.set_nodelay(true);
+
+        // Enable TCP keepalive so the kernel detects dead connections.
+        // Without this, a silently dropped connection (e.g. laptop sleep, network change)
+        // leaves the tunnel stuck forever on Linux.
+        let sock_ref = socket2::SockRef::from(&tcp_stream);
+        let keepalive = socket2::TcpKeepalive::new()
+            .with_time(std::time::Duration::from_secs(60))
+            .with_interval(std::time::Duration::from_secs(10));
+        let _ = sock_ref.set_tcp_keepalive(&keepalive);
+
+        if let ClientSource::Tcp { auth_proxy: Some(proxy), .. } = self {
+            let (read, write) = tokio::io::split(proxy.intercept(tcp_stream));
+            return Ok(Some((Box::new(read), Box::new(write))));
+        }
+        let (read, write) = tcp_stream.into_split();
+        if let Some(open) = open {
+            let read = HoldOpen {
+                inner: read,
+                _open: open.clone(),
+            };
+            let write = HoldOpen {
+                inner: write,
+                _open: open,
+            };
+            return Ok(Some((Box::new(read), Box::new(write))));
+        }
+        Ok(Some((Box::new(read), Box::new(write))))
+    }
 }
 
 /// Basic (non-multiplexed) port forwarding using a pre-opened SSM data channel.
 /// Handles one TCP connection at a time serially.
 async fn start_basic_port_forwarding(
     channel: SsmDataChannel,
-    local_port: u16,
+    local_addr: SocketAddrV4,
+    auth_proxy: Option<AuthProxy>,
     cancel: tokio_util::sync::CancellationToken,
     ready_tx: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
 ) -> Result<(), String> {
+    let (listener_v4, listener_v6) = bind_local_listeners(local_addr)?;
+    if listener_v6.is_some() {
+        log::info!("Listening on {} and [::1]:{}", local_addr, local_addr.port());
+    } else {
+        log::info!("Listening on {} (no IPv6 listener)", local_addr);
+    }
 
-    // Extract sequence numbers before moving ws
-    let initial_outgoing_seq = channel.outgoing_seq;
-    let initial_incoming_seq = channel.expected_incoming_seq;
-
-    // Split the WebSocket into read/write halves
-    let (ws_write_half, ws_read_half) = channel.ws.split();
+    // Signal that the tunnel is ready for connections
+    if let Some(tx) = ready_tx {
+        let _ = tx.send(Ok(()));
+    }
 
-    // Wrap write half in Arc<Mutex> for shared access
-    let ws_write = Arc::new(tokio::sync::Mutex::new(ws_write_half));
+    let source = ClientSource::Tcp {
+        v4: listener_v4,
+        v6: listener_v6,
+        auth_proxy,
+    };
+    run_basic_session(channel, source, cancel).await
+}
 
-    // Bind local TCP listeners with SO_REUSEADDR — critical on Linux where
-    // TIME_WAIT lasts 60s (vs ~15s on macOS), blocking reconnections.
-    // Listen on both IPv4 (127.0.0.1) and IPv6 (::1) so clients that resolve
-    // "localhost" to either address can connect (DataGrip on Linux uses IPv6).
+/// Bind the local listeners with SO_REUSEADDR — critical on Linux where
+/// TIME_WAIT lasts 60s (vs ~15s on macOS), blocking reconnections. On
+/// 127.0.0.1 there is also a best-effort IPv6 listener on ::1 so clients that
+/// resolve "localhost" to either address can connect (DataGrip on Linux uses
+/// IPv6); loopback aliases (127.0.0.N) are IPv4 only, as ::1 is shared.
+pub(crate) fn bind_local_listeners(
+    local_addr: SocketAddrV4,
+) -> Result<(tokio::net::TcpListener, Option<tokio::net::TcpListener>), String> {
+    let local_port = local_addr.port();
     let listener_v4 = {
-        let addr = std::net::SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, local_port));
         let socket = socket2::Socket::new(
             socket2::Domain::IPV4,
             socket2::Type::STREAM,
@@ -154,8 +353,8 @@ async fn start_basic_port_forwarding(
             .set_nonblocking(true)
             .map_err(|e| format!("Failed to set nonblocking: {}", e))?;
         socket
-            .bind(&addr.into())
-            .map_err(|e| format!("Failed to bind port {}: {}", local_port, e))?;
+            .bind(&SocketAddr::V4(local_addr).into())
+            .map_err(|e| format!("Failed to bind {}: {}", local_addr, e))?;
         socket
             .listen(128)
             .map_err(|e| format!("Failed to listen on port {}: {}", local_port, e))?;
@@ -164,9 +363,9 @@ async fn start_basic_port_forwarding(
             .map_err(|e| format!("Failed to create async listener: {}", e))?
     };
 
-    // IPv6 listener — best-effort (may fail if IPv6 is disabled on the system)
-    let listener_v6: Option<tokio::net::TcpListener> = {
-        let addr = std::net::SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, local_port));
+    // Best-effort (may fail if IPv6 is disabled on the system)
+    let listener_v6 = if *local_addr.ip() == Ipv4Addr::LOCALHOST {
+        let addr = SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, local_port));
         socket2::Socket::new(
             socket2::Domain::IPV6,
             socket2::Type::STREAM,
@@ -182,25 +381,31 @@ async fn start_basic_port_forwarding(
             let std_listener: std::net::TcpListener = socket.into();
             tokio::net::TcpListener::from_std(std_listener).ok()
         })
+    } else {
+        None
     };
 
-    if listener_v6.is_some() {
-        log::info!(
-            "Listening on 127.0.0.1:{} and [::1]:{}",
-            local_port,
-            local_port
-        );
-    } else {
-        log::info!(
-            "Listening on 127.0.0.1:{} (IPv6 not available)",
-            local_port
-        );
-    }
+    Ok((listener_v4, listener_v6))
+}
 
-    // Signal that the tunnel is ready for connections
-    if let Some(tx) = ready_tx {
-        let _ = tx.send(Ok(()));
-    }
+/// Relay client connections from `source` over a basic-mode data channel,
+/// one at a time.
+async fn run_basic_session(
+    channel: SsmDataChannel,
+    mut source: ClientSource,
+    cancel: CancellationToken,
+) -> Result<(), String> {
+    // Extract sequence numbers before moving ws
+    let initial_outgoing_seq = channel.outgoing_seq;
+    let initial_incoming_seq = channel.expected_incoming_seq;
+    let encryption = channel.encryption.clone();
+    let single_stream = source.is_single_stream();
+
+    // Split the WebSocket into read/write halves
+    let (ws_write_half, ws_read_half) = channel.ws.split();
+
+    // Wrap write half in Arc<Mutex> for shared access
+    let ws_write = Arc::new(tokio::sync::Mutex::new(ws_write_half));
 
     // Shared state — sequence numbers continue from where the handshake left off.
     // The handshake consumed some sequence numbers on both sides:
@@ -423,6 +628,7 @@ async fn start_basic_port_forwarding(
     let tcp_connected_ws = tcp_connected.clone();
     let liveness_ws = ws_liveness.clone();
     let last_ssm_ws = last_ssm_activity.clone();
+    let encryption_ws = encryption.clone();
 
     let ws_read_handle = tokio::spawn(async move {
         let mut ws = ws_read_task.lock().await;
@@ -458,7 +664,7 @@ async fn start_basic_port_forwarding(
                     liveness_ws.record(epoch_secs());
                     last_ssm_ws.store(epoch_secs(), Ordering::Relaxed);
 
-                    let agent_msg = match AgentMessage::deserialize(&data) {
+                    let mut agent_msg = match AgentMessage::deserialize(&data) {
                         Ok(m) => m,
                         Err(e) => {
                             log::warn!("Failed to deserialize message: {}", e);
@@ -491,6 +697,14 @@ async fn start_basic_port_forwarding(
                             let _ = ws_w.send(Message::Binary(ack.serialize().into())).await;
                             drop(ws_w);
 
+                            if let Err(e) =
+                                open_output(encryption_ws.as_deref(), &mut agent_msg)
+                            {
+                                log::error!("{}, cancelling session", e);
+                                session_cancel_ws.cancel();
+                                break;
+                            }
+
                             // Track sequence numbers for ALL payload types (the agent
                             // uses a single counter for output, flag, etc.)
                             let seq = agent_msg.sequence_number;
@@ -508,6 +722,9 @@ async fn start_basic_port_forwarding(
                                             FLAG_DISCONNECT_TO_PORT => {
                                                 log::info!("Agent disconnected from remote port");
                                                 tcp_connected_ws.store(false, Ordering::Relaxed);
+                                                if single_stream {
+                                                    session_cancel_ws.cancel();
+                                                }
                                             }
                                             FLAG_CONNECT_TO_PORT_ERROR => {
                                                 log::error!(
@@ -536,6 +753,9 @@ async fn start_basic_port_forwarding(
                                                 FLAG_DISCONNECT_TO_PORT => {
                                                     log::info!("Agent disconnected from remote port");
                                                     tcp_connected_ws.store(false, Ordering::Relaxed);
+                                                    if single_stream {
+                                                        session_cancel_ws.cancel();
+                                                    }
                                                 }
                                                 FLAG_CONNECT_TO_PORT_ERROR => {
                                                     log::error!("Agent failed to connect to remote port");
@@ -602,53 +822,26 @@ async fn start_basic_port_forwarding(
         }
     });
 
-    // --- Main loop: accept TCP connections ---
+    // --- Main loop: accept client connections ---
     let mut is_first_connection = true;
     loop {
         if cancel.is_cancelled() || session_cancel.is_cancelled() {
             break;
         }
 
-        let tcp_stream = tokio::select! {
-            result = listener_v4.accept() => {
-                match result {
-                    Ok((stream, _addr)) => stream,
-                    Err(e) => {
-                        log::error!("TCP accept error (IPv4): {}", e);
-                        continue;
-                    }
-                }
-            }
-            result = async {
-                match &listener_v6 {
-                    Some(l) => l.accept().await,
-                    None => std::future::pending().await,
-                }
-            } => {
-                match result {
-                    Ok((stream, _addr)) => stream,
-                    Err(e) => {
-                        log::error!("TCP accept error (IPv6): {}", e);
-                        continue;
-                    }
-                }
-            }
+        let client = tokio::select! {
+            result = source.next_client() => result,
             _ = cancel.cancelled() => break,
             _ = session_cancel.cancelled() => break,
         };
-
-        // Disable Nagle's algorithm — critical for database protocols that
-        // rely on prompt delivery of small packets (e.g. PostgreSQL 1-byte SSL response).
-        let _ = tcp_stream.set_nodelay(true);
-
-        // Enable TCP keepalive so the kernel detects dead connections.
-        // Without this, a silently dropped connection (e.g. laptop sleep, network change)
-        // leaves the tunnel stuck forever on Linux.
-        let sock_ref = socket2::SockRef::from(&tcp_stream);
-        let keepalive = socket2::TcpKeepalive::new()
-            .with_time(std::time::Duration::from_secs(60))
-            .with_interval(std::time::Duration::from_secs(10));
-        let _ = sock_ref.set_tcp_keepalive(&keepalive);
+        let (mut tcp_read, mut tcp_write) = match client {
+            Ok(Some(client)) => client,
+            Ok(None) => break,
+            Err(e) => {
+                log::error!("{}", e);
+                continue;
+            }
+        };
 
         // Drain any stale data left in the channel from the previous TCP connection
         // (response data in-flight when the previous client disconnected).
@@ -688,8 +881,6 @@ async fn start_basic_port_forwarding(
 
         tcp_connected.store(true, Ordering::Relaxed);
 
-        let (mut tcp_read, mut tcp_write) = tcp_stream.into_split();
-
         // Per-connection cancel token — used to stop the write task when the read side disconnects
         let tcp_conn_cancel = tokio_util::sync::CancellationToken::new();
 
@@ -707,7 +898,10 @@ async fn start_basic_port_forwarding(
                     data = tcp_data_rx.recv() => {
                         match data {
                             Some(bytes) => {
-                                if tcp_write.write_all(&bytes).await.is_err() {
+                                // Flush: stdout-backed writers buffer, TCP is a no-op
+                                if tcp_write.write_all(&bytes).await.is_err()
+                                    || tcp_write.flush().await.is_err()
+                                {
                                     tcp_connected_tw.store(false, Ordering::Relaxed);
                                     break;
                                 }
@@ -719,6 +913,16 @@ async fn start_basic_port_forwarding(
                     _ = tcp_conn_cancel_tw.cancelled() => break,
                 }
             }
+            // A single stream gets no next connection: hand it whatever the
+            // agent sent before the session ended.
+            if single_stream {
+                while let Ok(bytes) = tcp_data_rx.try_recv() {
+                    if tcp_write.write_all(&bytes).await.is_err() {
+                        break;
+                    }
+                }
+                let _ = tcp_write.flush().await;
+            }
             // Return the receiver so we can reuse it for the next TCP connection
             tcp_data_rx
         });
@@ -752,10 +956,19 @@ async fn start_basic_port_forwarding(
                     }
                 }
                 _ = cancel_tr.cancelled() => break,
+                _ = session_cancel_tr.cancelled() => break,
             };
 
+            let payload = match seal_output(encryption.as_deref(), &buf[..n]) {
+                Ok(p) => p,
+                Err(e) => {
+                    log::error!("{}, cancelling session", e);
+                    session_cancel_tr.cancel();
+                    break;
+                }
+            };
             let seq = outgoing_seq_tcp.fetch_add(1, Ordering::Relaxed);
-            let msg = build_data_message(&buf[..n], seq);
+            let msg = build_data_message(&payload, seq);
             let serialized = msg.serialize();
 
             // Add to outgoing buffer for retransmission tracking
@@ -825,8 +1038,7 @@ async fn start_basic_port_forwarding(
 
     // 1. Drop TCP listeners immediately to release ports.
     //    On Linux, delayed drop can block rebinding for up to 60s (TIME_WAIT).
-    drop(listener_v4);
-    drop(listener_v6);
+    drop(source);
 
     // 2. Signal all spawned tasks to stop, then abort to ensure they release
     //    their Arc<Mutex<ws_write>> clones before we try to send terminate.
@@ -862,14 +1074,20 @@ async fn start_basic_port_forwarding(
 pub async fn start_multiplexed_port_forwarding(
     stream_url: String,
     token_value: String,
-    local_port: u16,
+    kms: Option<KmsSessionContext>,
+    local_addr: SocketAddrV4,
+    auth_proxy: Option<AuthProxy>,
     cancel: CancellationToken,
     ready_tx: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
 ) -> Result<(), String> {
     // Open data channel with smux-capable client version
-    let channel =
-        open_data_channel_with_version(&stream_url, &token_value, smux::SMUX_CLIENT_VERSION)
-            .await?;
+    let channel = open_data_channel_with_encryption(
+        &stream_url,
+        &token_value,
+        smux::SMUX_CLIENT_VERSION,
+        kms.as_ref(),
+    )
+    .await?;
     log::info!(
         "SSM data channel open (multiplexed), agent version: {}",
         channel.agent_version
@@ -884,73 +1102,80 @@ pub async fn start_multiplexed_port_forwarding(
         // Reuse the existing channel — session tokens are single-use so we can't
         // open a new connection. The agent didn't enable smux framing (version too old),
         // so basic mode works directly on this channel.
-        return start_basic_port_forwarding(channel, local_port, cancel, ready_tx).await;
+        return start_basic_port_forwarding(channel, local_addr, auth_proxy, cancel, ready_tx).await;
     }
 
-    let initial_outgoing_seq = channel.outgoing_seq;
-    let initial_incoming_seq = channel.expected_incoming_seq;
-
-    // Split WebSocket
-    let (ws_write_half, ws_read_half) = channel.ws.split();
-    let ws_write = Arc::new(tokio::sync::Mutex::new(ws_write_half));
+    let (listener_v4, listener_v6) = bind_local_listeners(local_addr)?;
+    if listener_v6.is_some() {
+        log::info!(
+            "Multiplexed: listening on {} and [::1]:{}",
+            local_addr,
+            local_addr.port()
+        );
+    } else {
+        log::info!("Multiplexed: listening on {} (no IPv6 listener)", local_addr);
+    }
 
-    // Bind TCP listeners (same as basic mode)
-    let listener_v4 = {
-        let addr = std::net::SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, local_port));
-        let socket = socket2::Socket::new(
-            socket2::Domain::IPV4,
-            socket2::Type::STREAM,
-            Some(socket2::Protocol::TCP),
-        )
-        .map_err(|e| format!("Failed to create socket: {}", e))?;
-        socket
-            .set_reuse_address(true)
-            .map_err(|e| format!("Failed to set SO_REUSEADDR: {}", e))?;
-        socket
-            .set_nonblocking(true)
-            .map_err(|e| format!("Failed to set nonblocking: {}", e))?;
-        socket
-            .bind(&addr.into())
-            .map_err(|e| format!("Failed to bind port {}: {}", local_port, e))?;
-        socket
-            .listen(128)
-            .map_err(|e| format!("Failed to listen on port {}: {}", local_port, e))?;
-        let std_listener: std::net::TcpListener = socket.into();
-        tokio::net::TcpListener::from_std(std_listener)
-            .map_err(|e| format!("Failed to create async listener: {}", e))?
+    let source = ClientSource::Tcp {
+        v4: listener_v4,
+        v6: listener_v6,
+        auth_proxy,
     };
+    run_multiplexed_session(channel, source, cancel, ready_tx).await
+}
 
-    let listener_v6: Option<tokio::net::TcpListener> = {
-        let addr = std::net::SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, local_port));
-        socket2::Socket::new(
-            socket2::Domain::IPV6,
-            socket2::Type::STREAM,
-            Some(socket2::Protocol::TCP),
-        )
-        .ok()
-        .and_then(|socket| {
-            let _ = socket.set_only_v6(true);
-            let _ = socket.set_reuse_address(true);
-            let _ = socket.set_nonblocking(true);
-            socket.bind(&addr.into()).ok()?;
-            socket.listen(128).ok()?;
-            let std_listener: std::net::TcpListener = socket.into();
-            tokio::net::TcpListener::from_std(std_listener).ok()
-        })
+/// Port forwarding for connections accepted elsewhere and handed over on
+/// `incoming` (the SOCKS server does this once a CONNECT is negotiated),
+/// instead of a local listener. Multiplexed when asked for and the agent
+/// supports it. The session ends when `incoming` is closed and the last
+/// connection is done, the agent disconnects, or `cancel` fires.
+pub async fn start_queued_port_forwarding(
+    stream_url: String,
+    token_value: String,
+    kms: Option<KmsSessionContext>,
+    multiplexed: bool,
+    incoming: mpsc::Receiver<QueuedConnection>,
+    cancel: CancellationToken,
+    ready_tx: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
+) -> Result<(), String> {
+    let client_version = if multiplexed {
+        smux::SMUX_CLIENT_VERSION
+    } else {
+        smux::BASIC_CLIENT_VERSION
     };
+    let channel =
+        open_data_channel_with_encryption(&stream_url, &token_value, client_version, kms.as_ref())
+            .await?;
+    log::info!(
+        "SSM data channel open (queued), agent version: {}",
+        channel.agent_version
+    );
 
-    if listener_v6.is_some() {
-        log::info!(
-            "Multiplexed: listening on 127.0.0.1:{} and [::1]:{}",
-            local_port,
-            local_port
-        );
-    } else {
-        log::info!(
-            "Multiplexed: listening on 127.0.0.1:{} (IPv6 not available)",
-            local_port
-        );
+    let source = ClientSource::Queue(incoming);
+    if multiplexed && smux::agent_supports_smux(&channel.agent_version) {
+        return run_multiplexed_session(channel, source, cancel, ready_tx).await;
+    }
+    if let Some(tx) = ready_tx {
+        let _ = tx.send(Ok(()));
     }
+    run_basic_session(channel, source, cancel).await
+}
+
+/// Relay client connections from `source` over a smux-enabled data channel,
+/// one smux stream each.
+async fn run_multiplexed_session(
+    channel: SsmDataChannel,
+    source: ClientSource,
+    cancel: CancellationToken,
+    ready_tx: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
+) -> Result<(), String> {
+    let initial_outgoing_seq = channel.outgoing_seq;
+    let initial_incoming_seq = channel.expected_incoming_seq;
+    let encryption = channel.encryption.clone();
+
+    // Split WebSocket
+    let (ws_write_half, ws_read_half) = channel.ws.split();
+    let ws_write = Arc::new(tokio::sync::Mutex::new(ws_write_half));
 
     // Signal tunnel ready
     if let Some(tx) = ready_tx {
@@ -1162,6 +1387,7 @@ pub async fn start_multiplexed_port_forwarding(
     let rtt_estimator_ack = rtt_estimator.clone();
     let liveness_ws = ws_liveness.clone();
     let last_ssm_ws = last_ssm_activity.clone();
+    let encryption_ws = encryption.clone();
 
     let ws_read_handle = tokio::spawn(async move {
         let mut ws = ws_read_task.lock().await;
@@ -1197,7 +1423,7 @@ pub async fn start_multiplexed_port_forwarding(
                     liveness_ws.record(epoch_secs());
                     last_ssm_ws.store(epoch_secs(), Ordering::Relaxed);
 
-                    let agent_msg = match AgentMessage::deserialize(&data) {
+                    let mut agent_msg = match AgentMessage::deserialize(&data) {
                         Ok(m) => m,
                         Err(e) => {
                             log::warn!("Failed to deserialize message: {}", e);
@@ -1229,6 +1455,14 @@ pub async fn start_multiplexed_port_forwarding(
                             let _ = ws_w.send(Message::Binary(ack.serialize().into())).await;
                             drop(ws_w);
 
+                            if let Err(e) =
+                                open_output(encryption_ws.as_deref(), &mut agent_msg)
+                            {
+                                log::error!("{}, cancelling session", e);
+                                session_cancel_ws.cancel();
+                                break;
+                            }
+
                             let seq = agent_msg.sequence_number;
                             let expected = expected_seq.load(Ordering::Relaxed);
                             if seq == expected {
@@ -1321,6 +1555,7 @@ pub async fn start_multiplexed_port_forwarding(
     let outgoing_seq_smux = outgoing_seq.clone();
     let cancel_smux_write = cancel.clone();
     let session_cancel_smux_write = session_cancel.clone();
+    let encryption_smux = encryption.clone();
     let smux_write_handle = tokio::spawn(async move {
         loop {
             let frame_data = tokio::select! {
@@ -1335,8 +1570,16 @@ pub async fn start_multiplexed_port_forwarding(
             };
 
             // Wrap smux frame bytes in an SSM input_stream_data message
+            let payload = match seal_output(encryption_smux.as_deref(), &frame_data) {
+                Ok(p) => p,
+                Err(e) => {
+                    log::error!("{}, cancelling session", e);
+                    session_cancel_smux_write.cancel();
+                    break;
+                }
+            };
             let seq = outgoing_seq_smux.fetch_add(1, Ordering::Relaxed);
-            let msg = build_data_message(&frame_data, seq);
+            let msg = build_data_message(&payload, seq);
             let serialized = msg.serialize();
             log::debug!(
                 "Smux frame -> SSM data message: seq={}, frame_cmd={}, frame_len={}",
@@ -1402,7 +1645,7 @@ pub async fn start_multiplexed_port_forwarding(
         smux_session_keepalive.run_keepalive().await;
     });
 
-    // --- Task 7: Multiplexed TCP listener ---
+    // --- Task 7: Multiplexed client listener ---
     let smux_session_listener = smux_session.clone();
     let cancel_listener = cancel.clone();
     let session_cancel_listener = session_cancel.clone();
@@ -1423,13 +1666,7 @@ pub async fn start_multiplexed_port_forwarding(
             cc2.cancel();
         });
 
-        smux::run_multiplexed_listener(
-            smux_session_listener,
-            listener_v4,
-            listener_v6,
-            combined_cancel,
-        )
-        .await;
+        smux::run_multiplexed_listener(smux_session_listener, source, combined_cancel).await;
 
         listener_cancel_inner.cancel();
         monitor1.abort();
@@ -1521,6 +1758,25 @@ impl WsLiveness {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tunnel::mock_agent::{
+        free_local_port, spawn_echo_target, Faults, MockAgent, MockAgentConfig,
+    };
+    use crate::tunnel::protocol::{ACTION_KMS_ENCRYPTION, ACTION_STATUS_FAILED};
+    use std::time::Duration;
+    use tokio::net::TcpStream;
+
+    /// Upper bound for any single end-to-end step against the mock agent.
+    const E2E_TIMEOUT: Duration = Duration::from_secs(20);
+
+    type TunnelHandle = tokio::task::JoinHandle<Result<(), String>>;
+
+    /// Loss, reordering and duplicates in both directions at once.
+    const LOSSY_LINK: Faults = Faults {
+        drop_every: Some(5),
+        duplicate_every: Some(3),
+        reorder_every: Some(4),
+        drop_inbound_every: Some(6),
+    };
 
     /// Regression test: a socket that keeps delivering frames — of any kind,
     /// not just pongs — must never trip the liveness watchdog, while a truly
@@ -1551,4 +1807,256 @@ mod tests {
         // Frames stop entirely: dead one window later
         assert!(liveness.expired(now + window + 1));
     }
+
+    /// Start a tunnel against `mock` and wait until its listener is up.
+    async fn start_tunnel(mock: &MockAgent, multiplexed: bool) -> (u16, CancellationToken, TunnelHandle) {
+        let port = free_local_port();
+        let cancel = CancellationToken::new();
+        let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+        let (url, token, child) = (mock.stream_url(), mock.token(), cancel.clone());
+        let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, port);
+        let handle = if multiplexed {
+            tokio::spawn(start_multiplexed_port_forwarding(url, token, None, addr, None, child, Some(ready_tx)))
+        } else {
+            tokio::spawn(start_native_port_forwarding(url, token, None, addr, None, child, Some(ready_tx)))
+        };
+        tokio::time::timeout(E2E_TIMEOUT, ready_rx)
+            .await
+            .expect("tunnel not ready in time")
+            .expect("tunnel exited before signalling ready")
+            .expect("tunnel failed to start");
+        (port, cancel, handle)
+    }
+
+    /// Cancel the tunnel and check it shuts down cleanly.
+    async fn stop_tunnel(cancel: CancellationToken, handle: TunnelHandle) {
+        cancel.cancel();
+        let result = tokio::time::timeout(E2E_TIMEOUT, handle)
+            .await
+            .expect("tunnel did not stop after cancel")
+            .expect("tunnel task panicked");
+        assert_eq!(result, Ok(()));
+    }
+
+    /// Send `payload` through the tunnel on a fresh TCP connection and return
+    /// what the echo target sent back.
+    async fn echo_through(port: u16, payload: &[u8]) -> Vec<u8> {
+        let transfer = async {
+            let stream = TcpStream::connect(("127.0.0.1", port))
+                .await
+                .expect("connect to tunnel");
+            let (mut read, mut write) = stream.into_split();
+            let mut echoed = vec![0u8; payload.len()];
+            let (written, received) =
+                tokio::join!(write.write_all(payload), read.read_exact(&mut echoed));
+            written.expect("write through tunnel");
+            received.expect("read echo through tunnel");
+            echoed
+        };
+        tokio::time::timeout(E2E_TIMEOUT, transfer)
+            .await
+            .expect("echo through tunnel timed out")
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn basic_mode_relays_data_and_reconnects_per_client() {
+        let target = spawn_echo_target().await;
+        let mock = MockAgent::start(MockAgentConfig::new(target)).await;
+        let (port, cancel, handle) = start_tunnel(&mock, false).await;
+
+        // Spans many STREAM_DATA_PAYLOAD_SIZE messages
+        let payload = pattern(10 * STREAM_DATA_PAYLOAD_SIZE + 17);
+        assert_eq!(echo_through(port, &payload).await, payload);
+        // A second client must re-send SYN so the agent reconnects the target
+        assert_eq!(echo_through(port, b"second client").await, b"second client");
+
+        stop_tunnel(cancel, handle).await;
+        let report = mock.finished().await;
+        assert_eq!(report.client_version, smux::BASIC_CLIENT_VERSION);
+        assert!(!report.multiplexed);
+        assert_eq!(report.syn_count, 2);
+        assert!(report.terminated, "client did not send TerminateSession");
+    }
+
+    #[tokio::test]
+    async fn basic_mode_survives_loss_reordering_and_duplicates() {
+        let target = spawn_echo_target().await;
+        let mut config = MockAgentConfig::new(target);
+        config.faults = LOSSY_LINK;
+        let mock = MockAgent::start(config).await;
+        let (port, cancel, handle) = start_tunnel(&mock, false).await;
+
+        let payload = pattern(32 * 1024);
+        assert_eq!(echo_through(port, &payload).await, payload);
+
+        stop_tunnel(cancel, handle).await;
+    }
+
+    #[tokio::test]
+    async fn multiplexed_mode_carries_concurrent_streams() {
+        let target = spawn_echo_target().await;
+        let mock = MockAgent::start(MockAgentConfig::new(target)).await;
+        let (port, cancel, handle) = start_tunnel(&mock, true).await;
+
+        let transfers = (0..4usize).map(|i| {
+            let payload = pattern(100_000 + i * 1000);
+            async move { assert_eq!(echo_through(port, &payload).await, payload) }
+        });
+        futures_util::future::join_all(transfers).await;
+
+        stop_tunnel(cancel, handle).await;
+        let report = mock.finished().await;
+        assert_eq!(report.client_version, smux::SMUX_CLIENT_VERSION);
+        assert!(report.multiplexed);
+        assert!(report.terminated, "client did not send TerminateSession");
+    }
+
+    #[tokio::test]
+    async fn multiplexed_mode_survives_loss_reordering_and_duplicates() {
+        let target = spawn_echo_target().await;
+        let mut config = MockAgentConfig::new(target);
+        config.faults = LOSSY_LINK;
+        let mock = MockAgent::start(config).await;
+        let (port, cancel, handle) = start_tunnel(&mock, true).await;
+
+        let transfers = (0..3usize).map(|i| {
+            let payload = pattern(24 * 1024 + i);
+            async move { assert_eq!(echo_through(port, &payload).await, payload) }
+        });
+        futures_util::future::join_all(transfers).await;
+
+        stop_tunnel(cancel, handle).await;
+    }
+
+    #[tokio::test]
+    async fn multiplexed_falls_back_to_basic_for_old_agents() {
+        let target = spawn_echo_target().await;
+        let mut config = MockAgentConfig::new(target);
+        config.agent_version = "3.0.161.0".to_string();
+        let mock = MockAgent::start(config).await;
+        let (port, cancel, handle) = start_tunnel(&mock, true).await;
+
+        assert_eq!(echo_through(port, b"fallback").await, b"fallback");
+
+        stop_tunnel(cancel, handle).await;
+        let report = mock.finished().await;
+        assert_eq!(report.client_version, smux::SMUX_CLIENT_VERSION);
+        assert!(!report.multiplexed);
+    }
+
+    #[tokio::test]
+    async fn stream_mode_relays_one_stream_and_ends_at_eof() {
+        let target = spawn_echo_target().await;
+        let mock = MockAgent::start(MockAgentConfig::new(target)).await;
+
+        let (client, tunnel_side) = tokio::io::duplex(64 * 1024);
+        let (tunnel_read, tunnel_write) = tokio::io::split(tunnel_side);
+        let handle = tokio::spawn(start_stream_port_forwarding(
+            mock.stream_url(),
+            mock.token(),
+            None,
+            tunnel_read,
+            tunnel_write,
+            CancellationToken::new(),
+        ));
+
+        let (mut read, mut write) = tokio::io::split(client);
+        let payload = pattern(8 * STREAM_DATA_PAYLOAD_SIZE + 3);
+        let mut echoed = vec![0u8; payload.len()];
+        let transfer = async {
+            let (written, received) =
+                tokio::join!(write.write_all(&payload), read.read_exact(&mut echoed));
+            written.expect("write through tunnel");
+            received.expect("read echo through tunnel");
+        };
+        tokio::time::timeout(E2E_TIMEOUT, transfer)
+            .await
+            .expect("echo through stream timed out");
+        assert_eq!(echoed, payload);
+
+        // EOF on the input side (ssh exiting) ends the whole session
+        write.shutdown().await.expect("close stream input");
+        let result = tokio::time::timeout(E2E_TIMEOUT, handle)
+            .await
+            .expect("session outlived its only stream")
+            .expect("tunnel task panicked");
+        assert_eq!(result, Ok(()));
+
+        let report = mock.finished().await;
+        assert!(!report.multiplexed);
+        assert_eq!(report.syn_count, 1);
+        assert!(report.terminated, "client did not send TerminateSession");
+    }
+
+    #[tokio::test]
+    async fn channel_closed_by_agent_ends_session() {
+        for multiplexed in [false, true] {
+            let target = spawn_echo_target().await;
+            let mock = MockAgent::start(MockAgentConfig::new(target)).await;
+            let (port, _cancel, handle) = start_tunnel(&mock, multiplexed).await;
+            assert_eq!(echo_through(port, b"before close").await, b"before close");
+            assert_eq!(mock.report().multiplexed, multiplexed);
+
+            mock.close_channel();
+            let result = tokio::time::timeout(E2E_TIMEOUT, handle)
+                .await
+                .expect("session outlived channel_closed")
+                .expect("tunnel task panicked");
+            assert_eq!(result, Ok(()));
+        }
+    }
+
+    #[tokio::test]
+    async fn abrupt_websocket_close_ends_session() {
+        for multiplexed in [false, true] {
+            let target = spawn_echo_target().await;
+            let mock = MockAgent::start(MockAgentConfig::new(target)).await;
+            let (_port, _cancel, handle) = start_tunnel(&mock, multiplexed).await;
+
+            mock.drop_connection();
+            let result = tokio::time::timeout(E2E_TIMEOUT, handle)
+                .await
+                .expect("session outlived a dropped WebSocket")
+                .expect("tunnel task panicked");
+            assert_eq!(result, Ok(()));
+        }
+    }
+
+    /// Without a KMS context the client must report KMSEncryption as failed
+    /// instead of claiming success, and the agent then closes the channel.
+    #[tokio::test]
+    async fn kms_request_without_context_is_reported_as_failed() {
+        let target = spawn_echo_target().await;
+        let mut config = MockAgentConfig::new(target);
+        config.kms_key_id = Some("alias/session-manager".to_string());
+        let mock = MockAgent::start(config).await;
+
+        let result = tokio::time::timeout(
+            E2E_TIMEOUT,
+            start_native_port_forwarding(
+                mock.stream_url(),
+                mock.token(),
+                None,
+                SocketAddrV4::new(Ipv4Addr::LOCALHOST, free_local_port()),
+                None,
+                CancellationToken::new(),
+                None,
+            ),
+        )
+        .await
+        .expect("handshake did not finish");
+        assert!(result.is_err());
+
+        let report = mock.finished().await;
+        let kms = report
+            .processed_actions
+            .iter()
+            .find(|a| a.action_type == ACTION_KMS_ENCRYPTION)
+            .expect("KMSEncryption action answered");
+        assert_eq!(kms.action_status, ACTION_STATUS_FAILED);
+    }
 }
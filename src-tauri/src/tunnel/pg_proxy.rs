@@ -0,0 +1,688 @@
+//! PostgreSQL side of the auth proxy (see [`crate::tunnel::auth_proxy`]).
+//!
+//! Locally the proxy declines SSL and lets the client in without asking for a
+//! password (trust auth). Upstream it negotiates TLS, sends the client's
+//! startup parameters with the real user name and answers whichever password
+//! challenge the server picks: cleartext, MD5 or SCRAM-SHA-256. Once the
+//! server says `AuthenticationOk` the two sides are joined and the rest of
+//! the session is relayed untouched — or, with the read-only guard, with each
+//! query checked on its way up.
+
+use crate::tunnel::auth_proxy::{self, AuthProxy, DbCredentials, ProxyStream};
+use crate::tunnel::read_only::{self, ReadOnlyGuard, SqlDialect};
+use aws_lc_rs::{digest, hmac, pbkdf2};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use md5::{Digest, Md5};
+use std::num::NonZeroU32;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const SSL_REQUEST_CODE: i32 = 80877103;
+const GSSENC_REQUEST_CODE: i32 = 80877104;
+const CANCEL_REQUEST_CODE: i32 = 80877102;
+const PROTOCOL_VERSION_3: i32 = 196608;
+
+/// Largest startup packet accepted from a client (libpq sends a few hundred
+/// bytes).
+const MAX_STARTUP_LENGTH: usize = 10_000;
+
+/// Largest message accepted from the server before login completes.
+const MAX_AUTH_MESSAGE_LENGTH: usize = 1024 * 1024;
+
+const AUTH_OK: i32 = 0;
+const AUTH_CLEARTEXT_PASSWORD: i32 = 3;
+const AUTH_MD5_PASSWORD: i32 = 5;
+const AUTH_SASL: i32 = 10;
+const AUTH_SASL_CONTINUE: i32 = 11;
+const AUTH_SASL_FINAL: i32 = 12;
+
+const SCRAM_SHA_256: &str = "SCRAM-SHA-256";
+
+/// Why an upstream login failed.
+enum LoginError {
+    /// The server sent an `ErrorResponse`; it has been passed to the client.
+    Rejected(String),
+    /// Anything else; the client still needs to be told.
+    Failed(String),
+}
+
+/// Serve one client connection: local handshake, upstream login, then relay.
+pub async fn serve<C, U>(mut client: C, upstream: U, proxy: &AuthProxy) -> Result<(), String>
+where
+    C: AsyncRead + AsyncWrite + Unpin + Send,
+    U: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let parameters = loop {
+        let packet = read_startup_packet(&mut client).await?;
+        match be_i32(&packet) {
+            SSL_REQUEST_CODE | GSSENC_REQUEST_CODE => {
+                // The client side is plain TCP on localhost
+                client.write_all(b"N").await.map_err(|e| e.to_string())?;
+            }
+            CANCEL_REQUEST_CODE => {
+                // Carries the server's own key; pass it through as is
+                let mut upstream = open_upstream(upstream, proxy).await?;
+                write_startup_packet(&mut upstream, &packet).await?;
+                let _ = upstream.shutdown().await;
+                return Ok(());
+            }
+            PROTOCOL_VERSION_3 => break parse_startup_parameters(&packet[4..])?,
+            version => {
+                let message = format!(
+                    "unsupported frontend protocol {}.{}",
+                    version >> 16,
+                    version & 0xffff
+                );
+                send_error(&mut client, "0A000", &message).await;
+                return Err(message);
+            }
+        }
+    };
+
+    let mut upstream = match open_upstream(upstream, proxy).await {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            send_error(&mut client, "08006", &e).await;
+            return Err(e);
+        }
+    };
+
+    let credentials = proxy.credentials();
+    let startup = startup_packet(&parameters, &credentials.username, proxy.read_only.is_some());
+    write_startup_packet(&mut upstream, &startup).await?;
+
+    match login(&mut client, &mut upstream, &credentials).await {
+        Ok(()) => {}
+        Err(LoginError::Rejected(e)) => return Err(e),
+        Err(LoginError::Failed(e)) => {
+            send_error(&mut client, "28000", &e).await;
+            return Err(e);
+        }
+    }
+
+    if let Some(ref guard) = proxy.read_only {
+        return relay_read_only(client, upstream, guard).await;
+    }
+    match tokio::io::copy_bidirectional(&mut client, &mut upstream).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Relay a logged-in session, replacing refused queries on the way up.
+async fn relay_read_only<C, U>(client: C, upstream: U, guard: &ReadOnlyGuard) -> Result<(), String>
+where
+    C: AsyncRead + AsyncWrite + Unpin + Send,
+    U: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let (mut client_read, mut client_write) = tokio::io::split(client);
+    let (mut upstream_read, mut upstream_write) = tokio::io::split(upstream);
+
+    let queries = async {
+        loop {
+            let mut header = [0u8; 5];
+            match client_read.read_exact(&mut header).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.to_string()),
+            }
+            let length = usize::try_from(be_i32(&header[1..]))
+                .ok()
+                .filter(|&length| length >= 4)
+                .ok_or_else(|| "Invalid message length from client".to_string())?;
+            let mut body = vec![0u8; length - 4];
+            client_read
+                .read_exact(&mut body)
+                .await
+                .map_err(|e| e.to_string())?;
+            let replacement = match header[0] {
+                b'Q' => guard_query(&body, guard),
+                b'P' => guard_parse(&body, guard),
+                _ => None,
+            };
+            write_message(&mut upstream_write, header[0], &replacement.unwrap_or(body)).await?;
+        }
+        let _ = upstream_write.shutdown().await;
+        Ok(())
+    };
+    let results = async {
+        match tokio::io::copy(&mut upstream_read, &mut client_write).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {}
+            Err(e) => return Err(e.to_string()),
+        }
+        let _ = client_write.shutdown().await;
+        Ok(())
+    };
+    tokio::try_join!(queries, results).map(|_| ())
+}
+
+/// Simple query: a refused one is swapped for a statement that fails with
+/// the guard's message, so the server answers with the usual `ErrorResponse`
+/// and `ReadyForQuery` (and aborts the transaction, as a real error would).
+fn guard_query(body: &[u8], guard: &ReadOnlyGuard) -> Option<Vec<u8>> {
+    let sql = split_cstrings(body).first().copied().unwrap_or_default();
+    let keyword = guard.check(sql, SqlDialect::Postgres)?;
+    Some(cstring(&format!(
+        "DO $read_only_guard$ BEGIN RAISE EXCEPTION USING ERRCODE = '25006', MESSAGE = '{}'; END $read_only_guard$",
+        read_only::blocked_message(keyword)
+    )))
+}
+
+/// Extended protocol `Parse`: the replacement has to fail at parse time —
+/// the server then skips the client's Bind/Execute up to its Sync. Casting
+/// the message to an integer does that.
+fn guard_parse(body: &[u8], guard: &ReadOnlyGuard) -> Option<Vec<u8>> {
+    let fields = split_cstrings(body);
+    let (name, sql) = match fields.as_slice() {
+        [sql] => ("", *sql),
+        [name, sql, ..] => (*name, *sql),
+        [] => return None,
+    };
+    let keyword = guard.check(sql, SqlDialect::Postgres)?;
+    let mut replacement = cstring(name);
+    replacement.extend_from_slice(&cstring(&format!(
+        "SELECT '{}'::pg_catalog.int4",
+        read_only::blocked_message(keyword)
+    )));
+    replacement.extend_from_slice(&0i16.to_be_bytes());
+    Some(replacement)
+}
+
+/// Ask the server for TLS and set it up if accepted.
+async fn open_upstream<U>(mut upstream: U, proxy: &AuthProxy) -> Result<Box<dyn ProxyStream>, String>
+where
+    U: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    write_startup_packet(&mut upstream, &SSL_REQUEST_CODE.to_be_bytes()).await?;
+    let mut answer = [0u8; 1];
+    upstream
+        .read_exact(&mut answer)
+        .await
+        .map_err(|e| format!("No answer to SSLRequest: {}", e))?;
+    match answer[0] {
+        b'S' => Ok(Box::new(
+            auth_proxy::connect_tls(upstream, &proxy.server_name, proxy.verify_tls).await?,
+        )),
+        b'N' if !proxy.require_tls => Ok(Box::new(upstream)),
+        b'N' => Err("The database server does not accept TLS, required for IAM auth".to_string()),
+        other => Err(format!("Unexpected answer to SSLRequest: {:?}", other as char)),
+    }
+}
+
+/// Relay the upstream login, answering password challenges, until the
+/// server accepts it. `AuthenticationOk` is passed on to the client, which
+/// never saw a challenge — to it the login looks like trust auth.
+async fn login<C, U>(
+    client: &mut C,
+    upstream: &mut U,
+    credentials: &DbCredentials,
+) -> Result<(), LoginError>
+where
+    C: AsyncWrite + Unpin,
+    U: AsyncRead + AsyncWrite + Unpin,
+{
+    let failed = LoginError::Failed;
+    let mut scram: Option<ScramSha256> = None;
+    loop {
+        let (tag, body) = read_message(upstream).await.map_err(failed)?;
+        match tag {
+            b'R' => {
+                if body.len() < 4 {
+                    return Err(failed("Malformed authentication request".to_string()));
+                }
+                match be_i32(&body) {
+                    AUTH_OK => {
+                        write_message(client, b'R', &body).await.map_err(failed)?;
+                        return Ok(());
+                    }
+                    AUTH_CLEARTEXT_PASSWORD => {
+                        write_message(upstream, b'p', &cstring(&credentials.password))
+                            .await
+                            .map_err(failed)?;
+                    }
+                    AUTH_MD5_PASSWORD => {
+                        let salt = body.get(4..8).ok_or_else(|| {
+                            failed("Malformed MD5 password request".to_string())
+                        })?;
+                        let response =
+                            md5_password(&credentials.username, &credentials.password, salt);
+                        write_message(upstream, b'p', &cstring(&response))
+                            .await
+                            .map_err(failed)?;
+                    }
+                    AUTH_SASL => {
+                        let mechanisms = split_cstrings(&body[4..]);
+                        if !mechanisms.contains(&SCRAM_SHA_256) {
+                            return Err(failed(format!(
+                                "Unsupported SASL mechanisms: {}",
+                                mechanisms.join(", ")
+                            )));
+                        }
+                        let client_scram = ScramSha256::new(&credentials.password);
+                        let first = client_scram.client_first();
+                        let mut response = cstring(SCRAM_SHA_256);
+                        response.extend_from_slice(&(first.len() as i32).to_be_bytes());
+                        response.extend_from_slice(first.as_bytes());
+                        write_message(upstream, b'p', &response).await.map_err(failed)?;
+                        scram = Some(client_scram);
+                    }
+                    AUTH_SASL_CONTINUE => {
+                        let client_scram = scram
+                            .as_mut()
+                            .ok_or_else(|| failed("Unexpected SASL continue".to_string()))?;
+                        let last = client_scram.client_final(&body[4..]).map_err(failed)?;
+                        write_message(upstream, b'p', last.as_bytes())
+                            .await
+                            .map_err(failed)?;
+                    }
+                    AUTH_SASL_FINAL => {
+                        scram
+                            .as_ref()
+                            .ok_or_else(|| failed("Unexpected SASL final".to_string()))?
+                            .verify_server_final(&body[4..])
+                            .map_err(failed)?;
+                    }
+                    code => {
+                        return Err(failed(format!(
+                            "Unsupported authentication method requested by the server ({})",
+                            code
+                        )));
+                    }
+                }
+            }
+            b'E' => {
+                let _ = write_message(client, b'E', &body).await;
+                return Err(LoginError::Rejected(format!(
+                    "Server rejected login: {}",
+                    error_message(&body)
+                )));
+            }
+            // NegotiateProtocolVersion answers the client's own startup
+            b'v' => write_message(client, b'v', &body).await.map_err(failed)?,
+            other => {
+                return Err(failed(format!(
+                    "Unexpected message {:?} during login",
+                    other as char
+                )));
+            }
+        }
+    }
+}
+
+/// Startup-phase packet: a length, then the body (no type byte).
+async fn read_startup_packet<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Vec<u8>, String> {
+    let mut header = [0u8; 4];
+    stream
+        .read_exact(&mut header)
+        .await
+        .map_err(|e| format!("Client closed before startup: {}", e))?;
+    let length = be_i32(&header) as usize;
+    if !(8..=MAX_STARTUP_LENGTH).contains(&length) {
+        return Err(format!("Invalid startup packet length {}", length));
+    }
+    let mut body = vec![0u8; length - 4];
+    stream
+        .read_exact(&mut body)
+        .await
+        .map_err(|e| format!("Truncated startup packet: {}", e))?;
+    Ok(body)
+}
+
+async fn write_startup_packet<W: AsyncWrite + Unpin>(stream: &mut W, body: &[u8]) -> Result<(), String> {
+    let mut packet = ((body.len() + 4) as i32).to_be_bytes().to_vec();
+    packet.extend_from_slice(body);
+    stream.write_all(&packet).await.map_err(|e| e.to_string())
+}
+
+/// Regular message: type byte, length, body.
+async fn read_message<R: AsyncRead + Unpin>(stream: &mut R) -> Result<(u8, Vec<u8>), String> {
+    let mut header = [0u8; 5];
+    stream
+        .read_exact(&mut header)
+        .await
+        .map_err(|e| format!("Server closed the connection during login: {}", e))?;
+    let length = be_i32(&header[1..]) as usize;
+    if !(4..=MAX_AUTH_MESSAGE_LENGTH).contains(&length) {
+        return Err(format!("Invalid message length {}", length));
+    }
+    let mut body = vec![0u8; length - 4];
+    stream
+        .read_exact(&mut body)
+        .await
+        .map_err(|e| format!("Truncated message from server: {}", e))?;
+    Ok((header[0], body))
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(stream: &mut W, tag: u8, body: &[u8]) -> Result<(), String> {
+    let mut message = vec![tag];
+    message.extend_from_slice(&((body.len() + 4) as i32).to_be_bytes());
+    message.extend_from_slice(body);
+    stream.write_all(&message).await.map_err(|e| e.to_string())
+}
+
+/// Send a FATAL `ErrorResponse` to the client; it closes the connection.
+async fn send_error<W: AsyncWrite + Unpin>(stream: &mut W, code: &str, message: &str) {
+    let mut body = Vec::new();
+    for (field, value) in [(b'S', "FATAL"), (b'V', "FATAL"), (b'C', code)] {
+        body.push(field);
+        body.extend_from_slice(&cstring(value));
+    }
+    body.push(b'M');
+    body.extend_from_slice(&cstring(&format!("connection-app auth proxy: {}", message)));
+    body.push(0);
+    let _ = write_message(stream, b'E', &body).await;
+}
+
+/// Name/value pairs from a StartupMessage body (after the version).
+fn parse_startup_parameters(body: &[u8]) -> Result<Vec<(String, String)>, String> {
+    let fields = split_cstrings(body);
+    if !fields.len().is_multiple_of(2) {
+        return Err("Malformed startup parameters".to_string());
+    }
+    Ok(fields
+        .chunks(2)
+        .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+        .collect())
+}
+
+/// The upstream StartupMessage: the client's parameters with `user`
+/// replaced. libpq defaults `database` to the user name, so a client that
+/// left it out gets its own (local) user name as the database, as it asked.
+/// `read_only` adds `default_transaction_read_only=on`, last so it wins.
+fn startup_packet(parameters: &[(String, String)], username: &str, read_only: bool) -> Vec<u8> {
+    let client_user = parameters
+        .iter()
+        .find(|(name, _)| name == "user")
+        .map(|(_, value)| value.as_str());
+    let mut packet = PROTOCOL_VERSION_3.to_be_bytes().to_vec();
+    packet.extend_from_slice(&cstring("user"));
+    packet.extend_from_slice(&cstring(username));
+    if !parameters.iter().any(|(name, _)| name == "database")
+        && let Some(database) = client_user
+    {
+        packet.extend_from_slice(&cstring("database"));
+        packet.extend_from_slice(&cstring(database));
+    }
+    for (name, value) in parameters.iter().filter(|(name, _)| name != "user") {
+        packet.extend_from_slice(&cstring(name));
+        packet.extend_from_slice(&cstring(value));
+    }
+    if read_only {
+        packet.extend_from_slice(&cstring("default_transaction_read_only"));
+        packet.extend_from_slice(&cstring("on"));
+    }
+    packet.push(0);
+    packet
+}
+
+/// `M` field of an `ErrorResponse`.
+fn error_message(body: &[u8]) -> String {
+    body.split(|&b| b == 0)
+        .find(|field| field.first() == Some(&b'M'))
+        .map(|field| String::from_utf8_lossy(&field[1..]).into_owned())
+        .unwrap_or_else(|| "unknown error".to_string())
+}
+
+fn split_cstrings(body: &[u8]) -> Vec<&str> {
+    body.split(|&b| b == 0)
+        .take_while(|field| !field.is_empty())
+        .map(|field| std::str::from_utf8(field).unwrap_or_default())
+        .collect()
+}
+
+fn cstring(value: &str) -> Vec<u8> {
+    let mut bytes = value.as_bytes().to_vec();
+    bytes.push(0);
+    bytes
+}
+
+fn be_i32(bytes: &[u8]) -> i32 {
+    i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// `md5` password response: `"md5" + md5(md5(password + user) + salt)`.
+fn md5_password(username: &str, password: &str, salt: &[u8]) -> String {
+    let inner = md5_hex(&[password.as_bytes(), username.as_bytes()].concat());
+    let outer = md5_hex(&[inner.as_bytes(), salt].concat());
+    format!("md5{}", outer)
+}
+
+fn md5_hex(input: &[u8]) -> String {
+    Md5::digest(input)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Client side of SCRAM-SHA-256 (RFC 5802 / RFC 7677) without channel
+/// binding. Postgres takes the user name from the startup packet, so the
+/// SCRAM user name is left empty.
+struct ScramSha256 {
+    password: String,
+    client_first_bare: String,
+    client_nonce: String,
+    server_signature: Option<Vec<u8>>,
+}
+
+impl ScramSha256 {
+    fn new(password: &str) -> Self {
+        let mut nonce = [0u8; 18];
+        let _ = aws_lc_rs::rand::fill(&mut nonce);
+        Self::with_nonce("", password, &BASE64.encode(nonce))
+    }
+
+    fn with_nonce(username: &str, password: &str, nonce: &str) -> Self {
+        Self {
+            password: password.to_string(),
+            client_first_bare: format!("n={},r={}", username, nonce),
+            client_nonce: nonce.to_string(),
+            server_signature: None,
+        }
+    }
+
+    fn client_first(&self) -> String {
+        format!("n,,{}", self.client_first_bare)
+    }
+
+    /// Answer the server-first message with the client proof.
+    fn client_final(&mut self, server_first: &[u8]) -> Result<String, String> {
+        let server_first = std::str::from_utf8(server_first)
+            .map_err(|_| "Malformed SCRAM server-first message".to_string())?;
+        let attribute = |name: char| {
+            server_first
+                .split(',')
+                .find_map(|part| part.strip_prefix(name)?.strip_prefix('='))
+                .ok_or_else(|| format!("SCRAM server-first message has no {}=", name))
+        };
+        let nonce = attribute('r')?;
+        if !nonce.starts_with(&self.client_nonce) {
+            return Err("SCRAM server nonce does not extend the client nonce".to_string());
+        }
+        let salt = BASE64
+            .decode(attribute('s')?)
+            .map_err(|_| "Malformed SCRAM salt".to_string())?;
+        let iterations = attribute('i')?
+            .parse::<u32>()
+            .ok()
+            .and_then(NonZeroU32::new)
+            .ok_or_else(|| "Malformed SCRAM iteration count".to_string())?;
+
+        let mut salted_password = [0u8; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            &salt,
+            self.password.as_bytes(),
+            &mut salted_password,
+        );
+        let client_key = hmac_sha256(&salted_password, b"Client Key");
+        let stored_key = digest::digest(&digest::SHA256, &client_key);
+        let server_key = hmac_sha256(&salted_password, b"Server Key");
+
+        // "biws" is base64("n,,"): no channel binding
+        let client_final_bare = format!("c=biws,r={}", nonce);
+        let auth_message = format!(
+            "{},{},{}",
+            self.client_first_bare, server_first, client_final_bare
+        );
+        let client_signature = hmac_sha256(stored_key.as_ref(), auth_message.as_bytes());
+        let proof: Vec<u8> = client_key
+            .iter()
+            .zip(&client_signature)
+            .map(|(key, signature)| key ^ signature)
+            .collect();
+        self.server_signature = Some(hmac_sha256(&server_key, auth_message.as_bytes()));
+
+        Ok(format!("{},p={}", client_final_bare, BASE64.encode(proof)))
+    }
+
+    /// Check the server's signature, proving it knows the password too.
+    fn verify_server_final(&self, server_final: &[u8]) -> Result<(), String> {
+        let server_final = std::str::from_utf8(server_final)
+            .map_err(|_| "Malformed SCRAM server-final message".to_string())?;
+        if let Some(error) = server_final.strip_prefix("e=") {
+            return Err(format!("SCRAM authentication failed: {}", error));
+        }
+        let signature = server_final
+            .strip_prefix("v=")
+            .and_then(|v| BASE64.decode(v.split(',').next().unwrap_or_default()).ok())
+            .ok_or_else(|| "Malformed SCRAM server-final message".to_string())?;
+        if self.server_signature.as_deref() != Some(signature.as_slice()) {
+            return Err("SCRAM server signature does not match".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data)
+        .as_ref()
+        .to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tunnel::auth_proxy::ProxyProtocol;
+
+    #[test]
+    fn test_md5_password() {
+        // RFC 1321 test suite
+        for (input, digest) in [
+            ("", "d41d8cd98f00b204e9800998ecf8427e"),
+            ("a", "0cc175b9c0f1b6a831c399e269772661"),
+            ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+            ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+        ] {
+            assert_eq!(md5_hex(input.as_bytes()), digest, "{input:?}");
+        }
+        assert_eq!(
+            md5_password("postgres", "secret", &[1, 2, 3, 4]),
+            "md5bb41a296aab6baccb36ff243a562abff"
+        );
+    }
+
+    #[test]
+    fn test_scram_sha_256_rfc7677_vector() {
+        let mut scram = ScramSha256::with_nonce("user", "pencil", "rOprNGfwEbeRWgbNEkqO");
+        assert_eq!(scram.client_first(), "n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+        let last = scram
+            .client_final(
+                b"r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+                  s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+            )
+            .unwrap();
+        assert_eq!(
+            last,
+            "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+             p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ="
+        );
+        assert!(scram
+            .verify_server_final(b"v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=")
+            .is_ok());
+        assert!(scram.verify_server_final(b"v=AAAA").is_err());
+    }
+
+    #[test]
+    fn test_startup_packet_replaces_user() {
+        let parameters = parse_startup_parameters(
+            b"user\0alice\0application_name\0psql\0\0",
+        )
+        .unwrap();
+        let packet = startup_packet(&parameters, "app_iam", false);
+        assert_eq!(be_i32(&packet), PROTOCOL_VERSION_3);
+        assert_eq!(
+            split_cstrings(&packet[4..]),
+            vec!["user", "app_iam", "database", "alice", "application_name", "psql"]
+        );
+
+        let packet = startup_packet(&parameters, "app_iam", true);
+        assert_eq!(
+            split_cstrings(&packet[4..])[6..],
+            ["default_transaction_read_only", "on"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_logs_in_upstream_with_injected_password() {
+        let proxy = AuthProxy::new(
+            ProxyProtocol::Postgres,
+            "db.example.com",
+            false,
+            DbCredentials {
+                username: "app".to_string(),
+                password: "s3cret".to_string(),
+            },
+        );
+        let (mut client, client_side) = tokio::io::duplex(4096);
+        let (upstream_side, mut server) = tokio::io::duplex(4096);
+        let serve_proxy = proxy.clone();
+        let proxy_task =
+            tokio::spawn(async move { serve(client_side, upstream_side, &serve_proxy).await });
+
+        let server_task = tokio::spawn(async move {
+            assert_eq!(
+                be_i32(&read_startup_packet(&mut server).await.unwrap()),
+                SSL_REQUEST_CODE
+            );
+            server.write_all(b"N").await.unwrap();
+            let startup = read_startup_packet(&mut server).await.unwrap();
+            let parameters = parse_startup_parameters(&startup[4..]).unwrap();
+            assert!(parameters.contains(&("user".to_string(), "app".to_string())));
+            assert!(parameters.contains(&("database".to_string(), "main".to_string())));
+
+            write_message(&mut server, b'R', &AUTH_CLEARTEXT_PASSWORD.to_be_bytes())
+                .await
+                .unwrap();
+            let (tag, body) = read_message(&mut server).await.unwrap();
+            assert_eq!((tag, body), (b'p', cstring("s3cret")));
+            write_message(&mut server, b'R', &AUTH_OK.to_be_bytes())
+                .await
+                .unwrap();
+            write_message(&mut server, b'Z', b"I").await.unwrap();
+        });
+
+        // Client: SSLRequest is declined, then a password-less startup
+        write_startup_packet(&mut client, &SSL_REQUEST_CODE.to_be_bytes())
+            .await
+            .unwrap();
+        let mut answer = [0u8; 1];
+        client.read_exact(&mut answer).await.unwrap();
+        assert_eq!(&answer, b"N");
+        let mut startup = PROTOCOL_VERSION_3.to_be_bytes().to_vec();
+        startup.extend_from_slice(b"user\0whoever\0database\0main\0\0");
+        write_startup_packet(&mut client, &startup).await.unwrap();
+
+        assert_eq!(
+            read_message(&mut client).await.unwrap(),
+            (b'R', AUTH_OK.to_be_bytes().to_vec())
+        );
+        assert_eq!(read_message(&mut client).await.unwrap(), (b'Z', b"I".to_vec()));
+
+        server_task.await.unwrap();
+        drop(client);
+        assert!(proxy_task.await.unwrap().is_ok());
+    }
+}
@@ -40,11 +40,23 @@ pub const START_PUBLICATION: &str = "start_publication";
 pub const PAYLOAD_OUTPUT: u32 = 1;
 #[allow(dead_code)]
 pub const PAYLOAD_ERROR: u32 = 2;
+pub const PAYLOAD_SIZE: u32 = 3;
 pub const PAYLOAD_HANDSHAKE_REQUEST: u32 = 5;
 pub const PAYLOAD_HANDSHAKE_RESPONSE: u32 = 6;
 pub const PAYLOAD_HANDSHAKE_COMPLETE: u32 = 7;
+pub const PAYLOAD_ENC_CHALLENGE_REQUEST: u32 = 8;
+pub const PAYLOAD_ENC_CHALLENGE_RESPONSE: u32 = 9;
 pub const PAYLOAD_FLAG: u32 = 10;
 
+// --- Handshake client actions ---
+pub const ACTION_SESSION_TYPE: &str = "SessionType";
+pub const ACTION_KMS_ENCRYPTION: &str = "KMSEncryption";
+
+// --- ActionStatus values reported back in the HandshakeResponse ---
+pub const ACTION_STATUS_SUCCESS: u32 = 1;
+pub const ACTION_STATUS_FAILED: u32 = 2;
+pub const ACTION_STATUS_UNSUPPORTED: u32 = 3;
+
 // --- Flag values ---
 pub const FLAG_DATA: u64 = 0;
 pub const FLAG_SYN: u64 = 1;
@@ -315,6 +327,20 @@ pub fn build_flag_message(flag_value: u32, sequence_number: i64, fin: bool) -> A
     AgentMessage::new(INPUT_STREAM_DATA, sequence_number, flags, PAYLOAD_FLAG, payload)
 }
 
+/// Payload of a PAYLOAD_SIZE message: the client terminal's dimensions, sent
+/// on shell sessions whenever they change.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalSize {
+    pub cols: u32,
+    pub rows: u32,
+}
+
+/// Build an input_stream_data message carrying a terminal size update.
+pub fn build_size_message(size: TerminalSize, sequence_number: i64) -> AgentMessage {
+    let payload = serde_json::to_vec(&size).unwrap_or_default();
+    AgentMessage::new(INPUT_STREAM_DATA, sequence_number, FLAG_DATA, PAYLOAD_SIZE, payload)
+}
+
 // --- Handshake JSON structures ---
 
 #[derive(serde::Deserialize, Debug)]
@@ -328,7 +354,6 @@ pub struct HandshakeRequestPayload {
 #[serde(rename_all = "PascalCase")]
 pub struct RequestedClientAction {
     pub action_type: String,
-    #[allow(dead_code)]
     pub action_parameters: serde_json::Value,
 }
 
@@ -340,7 +365,7 @@ pub struct HandshakeResponsePayload {
     pub errors: Vec<String>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct ProcessedClientAction {
     pub action_type: String,
@@ -349,6 +374,35 @@ pub struct ProcessedClientAction {
     pub error: String,
 }
 
+impl ProcessedClientAction {
+    pub fn success(action_type: &str, action_result: Option<serde_json::Value>) -> Self {
+        Self {
+            action_type: action_type.to_string(),
+            action_status: ACTION_STATUS_SUCCESS,
+            action_result,
+            error: String::new(),
+        }
+    }
+
+    pub fn failed(action_type: &str, error: String) -> Self {
+        Self {
+            action_type: action_type.to_string(),
+            action_status: ACTION_STATUS_FAILED,
+            action_result: None,
+            error,
+        }
+    }
+
+    pub fn unsupported(action_type: &str) -> Self {
+        Self {
+            action_type: action_type.to_string(),
+            action_status: ACTION_STATUS_UNSUPPORTED,
+            action_result: None,
+            error: format!("Unsupported action {}", action_type),
+        }
+    }
+}
+
 #[derive(serde::Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct HandshakeCompletePayload {
@@ -357,6 +411,31 @@ pub struct HandshakeCompletePayload {
     pub customer_message: String,
 }
 
+/// `ActionParameters` of a KMSEncryption action.
+#[derive(serde::Deserialize, Debug)]
+pub struct KmsEncryptionRequest {
+    #[serde(rename = "KMSKeyId")]
+    pub kms_key_id: String,
+}
+
+/// `ActionResult` of a successful KMSEncryption action. Byte fields are
+/// base64 strings on the wire (Go `[]byte` JSON encoding).
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct KmsEncryptionResponse {
+    #[serde(rename = "KMSCipherTextKey")]
+    pub kms_cipher_text_key: String,
+    #[serde(rename = "KMSCipherTextHash")]
+    pub kms_cipher_text_hash: String,
+}
+
+/// Payload of EncChallengeRequest / EncChallengeResponse messages.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct EncryptionChallenge {
+    /// Base64-encoded challenge ciphertext.
+    pub challenge: String,
+}
+
 /// Build the HandshakeResponse message.
 ///
 /// `client_version` controls whether the SSM agent enables smux multiplexing:
@@ -369,7 +448,9 @@ pub fn build_handshake_response(
     build_handshake_response_with_version(request, sequence_number, "1.0.0.0")
 }
 
-/// Build the HandshakeResponse message with a specific client version.
+/// Build the HandshakeResponse message with a specific client version, for a
+/// client that has no encryption context: SessionType succeeds, KMSEncryption
+/// fails, and anything else is reported as unsupported.
 pub fn build_handshake_response_with_version(
     request: &HandshakeRequestPayload,
     sequence_number: i64,
@@ -378,18 +459,36 @@ pub fn build_handshake_response_with_version(
     let processed = request
         .requested_client_actions
         .iter()
-        .map(|action| ProcessedClientAction {
-            action_type: action.action_type.clone(),
-            action_status: 1, // Success
-            action_result: None,
-            error: String::new(),
+        .map(|action| match action.action_type.as_str() {
+            ACTION_SESSION_TYPE => ProcessedClientAction::success(ACTION_SESSION_TYPE, None),
+            ACTION_KMS_ENCRYPTION => ProcessedClientAction::failed(
+                ACTION_KMS_ENCRYPTION,
+                "KMS encryption is not available for this session".to_string(),
+            ),
+            other => ProcessedClientAction::unsupported(other),
         })
         .collect();
 
+    build_handshake_response_with_actions(processed, sequence_number, client_version)
+}
+
+/// Build the HandshakeResponse message from already-processed client actions.
+/// Errors of non-successful actions are also listed in the top-level `Errors`.
+pub fn build_handshake_response_with_actions(
+    processed: Vec<ProcessedClientAction>,
+    sequence_number: i64,
+    client_version: &str,
+) -> AgentMessage {
+    let errors = processed
+        .iter()
+        .filter(|action| action.action_status != ACTION_STATUS_SUCCESS)
+        .map(|action| action.error.clone())
+        .collect();
+
     let response = HandshakeResponsePayload {
         client_version: client_version.to_string(),
         processed_client_actions: processed,
-        errors: vec![],
+        errors,
     };
 
     let payload = serde_json::to_vec(&response).unwrap_or_default();
@@ -402,6 +501,21 @@ pub fn build_handshake_response_with_version(
     )
 }
 
+/// Build the EncChallengeResponse message carrying the re-encrypted challenge.
+pub fn build_encryption_challenge_response(challenge_b64: String, sequence_number: i64) -> AgentMessage {
+    let payload = serde_json::to_vec(&EncryptionChallenge {
+        challenge: challenge_b64,
+    })
+    .unwrap_or_default();
+    AgentMessage::new(
+        INPUT_STREAM_DATA,
+        sequence_number,
+        FLAG_DATA,
+        PAYLOAD_ENC_CHALLENGE_RESPONSE,
+        payload,
+    )
+}
+
 /// JSON message sent as WebSocket text to authenticate the data channel.
 #[derive(serde::Serialize)]
 #[serde(rename_all = "PascalCase")]
@@ -577,6 +691,68 @@ mod tests {
         assert_eq!(response.client_version, "1.0.0.0");
         assert_eq!(response.processed_client_actions.len(), 1);
         assert_eq!(response.processed_client_actions[0].action_status, 1);
+        assert!(response.errors.is_empty());
+    }
+
+    #[test]
+    fn handshake_response_reports_real_action_failures() {
+        let request = HandshakeRequestPayload {
+            agent_version: "3.3.40.0".to_string(),
+            requested_client_actions: vec![
+                RequestedClientAction {
+                    action_type: ACTION_SESSION_TYPE.to_string(),
+                    action_parameters: serde_json::json!({"SessionType": "Port"}),
+                },
+                RequestedClientAction {
+                    action_type: ACTION_KMS_ENCRYPTION.to_string(),
+                    action_parameters: serde_json::json!({"KMSKeyId": "alias/ssm"}),
+                },
+                RequestedClientAction {
+                    action_type: "SomethingNew".to_string(),
+                    action_parameters: serde_json::Value::Null,
+                },
+            ],
+        };
+
+        let msg = build_handshake_response_with_version(&request, 0, "1.2.0.0");
+        let response: HandshakeResponsePayload =
+            serde_json::from_slice(&msg.payload).unwrap();
+        let statuses: Vec<u32> = response
+            .processed_client_actions
+            .iter()
+            .map(|a| a.action_status)
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![ACTION_STATUS_SUCCESS, ACTION_STATUS_FAILED, ACTION_STATUS_UNSUPPORTED]
+        );
+        assert_eq!(response.errors.len(), 2);
+        assert!(response.processed_client_actions[1].error.contains("KMS"));
+    }
+
+    #[test]
+    fn kms_encryption_response_uses_agent_field_names() {
+        let result = serde_json::to_value(KmsEncryptionResponse {
+            kms_cipher_text_key: "a2V5".to_string(),
+            kms_cipher_text_hash: "aGFzaA==".to_string(),
+        })
+        .unwrap();
+        assert_eq!(result["KMSCipherTextKey"], "a2V5");
+        assert_eq!(result["KMSCipherTextHash"], "aGFzaA==");
+
+        let params: KmsEncryptionRequest =
+            serde_json::from_value(serde_json::json!({"KMSKeyId": "arn:aws:kms:key/1"})).unwrap();
+        assert_eq!(params.kms_key_id, "arn:aws:kms:key/1");
+    }
+
+    #[test]
+    fn size_message_uses_lowercase_json_fields() {
+        let msg = build_size_message(TerminalSize { cols: 120, rows: 40 }, 7);
+        assert_eq!(msg.message_type, INPUT_STREAM_DATA);
+        assert_eq!(msg.payload_type, PAYLOAD_SIZE);
+        assert_eq!(msg.sequence_number, 7);
+        let size: serde_json::Value = serde_json::from_slice(&msg.payload).unwrap();
+        assert_eq!(size, serde_json::json!({"cols": 120, "rows": 40}));
     }
 
     #[test]
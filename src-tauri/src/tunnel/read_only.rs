@@ -0,0 +1,337 @@
+//! Read-only guard for production tunnels (`readOnlyEnvs`). Runs inside the
+//! auth proxy, which already parses the wire protocol:
+//!
+//! - the session is made read-only on the server (Postgres:
+//!   `default_transaction_read_only=on` in the startup packet; MySQL:
+//!   `SET SESSION TRANSACTION READ ONLY` after login);
+//! - each query is checked here before it is sent, and statements that write,
+//!   change the schema or turn read-only off are refused and recorded in
+//!   connection history.
+//!
+//! The keyword check is a safety net against mistakes, not a sandbox: the
+//! server-side read-only session does the real work, the check catches what
+//! it doesn't (DDL on MySQL, `SET ... READ WRITE`).
+
+use crate::history::{self, HistoryEntry};
+
+/// Statements refused when they come first.
+const BLOCKED_COMMANDS: &[&str] = &[
+    "INSERT", "UPDATE", "DELETE", "MERGE", "REPLACE", "UPSERT", "CREATE", "ALTER", "DROP",
+    "TRUNCATE", "RENAME", "GRANT", "REVOKE", "COMMENT", "REINDEX", "VACUUM", "CLUSTER", "LOCK",
+    "CALL", "DO", "REFRESH", "IMPORT", "LOAD", "REASSIGN", "SECURITY", "OPTIMIZE", "REPAIR",
+];
+
+/// Refused anywhere in a statement: data-modifying CTEs, `EXPLAIN ANALYZE
+/// DELETE ...`, `PREPARE ... AS INSERT ...`, `SELECT ... FOR UPDATE`.
+const BLOCKED_ANYWHERE: &[&str] = &["INSERT", "UPDATE", "DELETE", "MERGE", "TRUNCATE"];
+
+/// History details are cut to this many characters of the statement.
+const MAX_LOGGED_STATEMENT: usize = 500;
+
+/// Quoting and comment rules of the SQL being checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlDialect {
+    Postgres,
+    Mysql,
+}
+
+/// Enabled on an [`AuthProxy`](crate::tunnel::auth_proxy::AuthProxy) for
+/// profiles matching `readOnlyEnvs`. Carries what a history entry needs.
+#[derive(Debug, Clone)]
+pub struct ReadOnlyGuard {
+    connection_id: String,
+    project_key: String,
+    profile: String,
+}
+
+impl ReadOnlyGuard {
+    pub fn new(connection_id: &str, project_key: &str, profile: &str) -> Self {
+        Self {
+            connection_id: connection_id.to_string(),
+            project_key: project_key.to_string(),
+            profile: profile.to_string(),
+        }
+    }
+
+    /// Check a query about to be sent. Returns the offending keyword if it
+    /// must be refused, after recording it in connection history.
+    pub fn check(&self, sql: &str, dialect: SqlDialect) -> Option<&'static str> {
+        let keyword = blocked_keyword(sql, dialect)?;
+        let statement: String = sql.trim().chars().take(MAX_LOGGED_STATEMENT).collect();
+        log::warn!(
+            "Read-only guard blocked {} on {}: {}",
+            keyword,
+            self.connection_id,
+            statement
+        );
+        let entry = HistoryEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            event_type: "blocked".to_string(),
+            connection_id: self.connection_id.clone(),
+            project_key: self.project_key.clone(),
+            profile: self.profile.clone(),
+            details: Some(format!("{}: {}", keyword, statement)),
+        };
+        tokio::spawn(history::log_event(entry));
+        Some(keyword)
+    }
+}
+
+/// Error shown to the client for a refused statement.
+pub fn blocked_message(keyword: &str) -> String {
+    format!(
+        "connection-app read-only guard: {} statements are blocked on this connection",
+        keyword
+    )
+}
+
+/// Keyword that makes `sql` a write, if any. Comments, string literals and
+/// quoted identifiers are skipped; each `;`-separated statement is checked.
+pub fn blocked_keyword(sql: &str, dialect: SqlDialect) -> Option<&'static str> {
+    split_statements(&tokenize(sql, dialect))
+        .into_iter()
+        .find_map(check_statement)
+}
+
+fn check_statement(words: Vec<&Word>) -> Option<&'static str> {
+    let first = words.first()?;
+    if let Some(&command) = BLOCKED_COMMANDS.iter().find(|&&c| c == first.text) {
+        return Some(command);
+    }
+    if let Some(&keyword) = words
+        .iter()
+        .find_map(|word| BLOCKED_ANYWHERE.iter().find(|&&k| k == word.text))
+    {
+        return Some(keyword);
+    }
+    // COPY ... FROM loads data; COPY ... TO (or COPY (SELECT ...) TO) reads
+    if first.text == "COPY" && words.iter().any(|w| w.depth == 0 && w.text == "FROM") {
+        return Some("COPY");
+    }
+    // Turning the read-only session back off
+    if words.windows(2).any(|pair| pair[0].text == "READ" && pair[1].text == "WRITE") {
+        return Some("READ WRITE");
+    }
+    if (first.text == "SET" || first.text == "RESET")
+        && words.iter().any(|w| w.text.contains("READ_ONLY"))
+    {
+        return Some("SET");
+    }
+    if words.iter().any(|w| w.text == "SET_CONFIG") {
+        return Some("SET_CONFIG");
+    }
+    None
+}
+
+/// A bare word, upper-cased, with its parenthesis depth.
+#[derive(Debug, PartialEq)]
+struct Word {
+    text: String,
+    depth: usize,
+}
+
+enum Token {
+    Word(Word),
+    Semicolon,
+}
+
+fn split_statements(tokens: &[Token]) -> Vec<Vec<&Word>> {
+    let mut statements = vec![Vec::new()];
+    for token in tokens {
+        match token {
+            Token::Word(word) => statements.last_mut().unwrap().push(word),
+            Token::Semicolon => statements.push(Vec::new()),
+        }
+    }
+    statements
+}
+
+fn tokenize(sql: &str, dialect: SqlDialect) -> Vec<Token> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mysql = dialect == SqlDialect::Mysql;
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            // MySQL needs whitespace after "--": "1--1" is arithmetic
+            '-' if next == Some('-')
+                && (!mysql || chars.get(i + 2).is_none_or(|c| c.is_whitespace())) =>
+            {
+                i = skip_line(&chars, i);
+            }
+            '#' if mysql => i = skip_line(&chars, i),
+            '/' if next == Some('*') => {
+                if mysql && chars.get(i + 2) == Some(&'!') {
+                    // MySQL runs /*!NNNNN ... */ as code: drop the marker only
+                    i += 3;
+                    while chars.get(i).is_some_and(|c| c.is_ascii_digit()) {
+                        i += 1;
+                    }
+                } else {
+                    i = skip_block_comment(&chars, i, !mysql);
+                }
+            }
+            '*' if mysql && next == Some('/') => i += 2,
+            '\'' => i = skip_quoted(&chars, i, '\'', mysql),
+            '"' => i = skip_quoted(&chars, i, '"', mysql),
+            '`' if mysql => i = skip_quoted(&chars, i, '`', false),
+            '$' if !mysql => i = skip_dollar_quoted(&chars, i),
+            '(' => {
+                depth += 1;
+                i += 1;
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+                i += 1;
+            }
+            ';' => {
+                tokens.push(Token::Semicolon);
+                depth = 0;
+                i += 1;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while chars
+                    .get(i)
+                    .is_some_and(|&c| c.is_alphanumeric() || c == '_' || c == '$')
+                {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                // Postgres E'...' strings take backslash escapes
+                if !mysql && text.eq_ignore_ascii_case("e") && chars.get(i) == Some(&'\'') {
+                    i = skip_quoted(&chars, i, '\'', true);
+                    continue;
+                }
+                tokens.push(Token::Word(Word {
+                    text: text.to_uppercase(),
+                    depth,
+                }));
+            }
+            _ => i += 1,
+        }
+    }
+    tokens
+}
+
+fn skip_line(chars: &[char], start: usize) -> usize {
+    chars[start..]
+        .iter()
+        .position(|&c| c == '\n')
+        .map_or(chars.len(), |offset| start + offset + 1)
+}
+
+/// Skip `/* ... */`; Postgres comments nest.
+fn skip_block_comment(chars: &[char], start: usize, nested: bool) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i < chars.len() {
+        if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+            if depth == 0 || nested {
+                depth += 1;
+            }
+            i += 2;
+        } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    chars.len()
+}
+
+/// Skip a quoted string or identifier; doubling the quote escapes it, and
+/// so does a backslash where `backslash` is set.
+fn skip_quoted(chars: &[char], start: usize, quote: char, backslash: bool) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if backslash => i += 2,
+            c if c == quote => {
+                if chars.get(i + 1) == Some(&quote) {
+                    i += 2;
+                } else {
+                    return i + 1;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// Skip a Postgres `$tag$ ... $tag$` string. A `$` that doesn't open one
+/// (`$1` parameters) is skipped alone.
+fn skip_dollar_quoted(chars: &[char], start: usize) -> usize {
+    let mut end = start + 1;
+    while chars
+        .get(end)
+        .is_some_and(|&c| c.is_alphanumeric() || c == '_')
+    {
+        end += 1;
+    }
+    let tag_is_valid = chars.get(end) == Some(&'$')
+        && !chars.get(start + 1).is_some_and(|c| c.is_ascii_digit());
+    if !tag_is_valid {
+        return start + 1;
+    }
+    let tag = &chars[start..=end];
+    let body = end + 1;
+    (body..chars.len())
+        .find(|&i| chars[i..].starts_with(tag))
+        .map_or(chars.len(), |i| i + tag.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pg(sql: &str) -> Option<&'static str> {
+        blocked_keyword(sql, SqlDialect::Postgres)
+    }
+
+    fn mysql(sql: &str) -> Option<&'static str> {
+        blocked_keyword(sql, SqlDialect::Mysql)
+    }
+
+    #[test]
+    fn test_allows_reads() {
+        assert_eq!(pg("SELECT * FROM orders WHERE status = 'DELETE me'"), None);
+        assert_eq!(pg("select created_at, updated_by from \"update\""), None);
+        assert_eq!(pg("EXPLAIN SELECT 1; SHOW search_path"), None);
+        assert_eq!(pg("SELECT $body$ DROP TABLE x $body$, $1"), None);
+        assert_eq!(pg("/* drop /* nested */ table */ SELECT 1 -- delete"), None);
+        assert_eq!(pg("COPY (SELECT a FROM t) TO STDOUT"), None);
+        assert_eq!(pg("BEGIN READ ONLY; SET search_path = app"), None);
+        assert_eq!(mysql("SELECT `delete` FROM t # drop table"), None);
+        assert_eq!(mysql("SELECT 'it\\'s; DROP TABLE t'"), None);
+    }
+
+    #[test]
+    fn test_blocks_writes() {
+        assert_eq!(pg("delete from orders"), Some("DELETE"));
+        assert_eq!(pg("SELECT 1; DROP TABLE orders"), Some("DROP"));
+        assert_eq!(pg("WITH gone AS (DELETE FROM t RETURNING *) SELECT * FROM gone"), Some("DELETE"));
+        assert_eq!(pg("EXPLAIN ANALYZE UPDATE t SET a = 1"), Some("UPDATE"));
+        assert_eq!(pg("COPY orders FROM STDIN"), Some("COPY"));
+        assert_eq!(pg("SET default_transaction_read_only = off"), Some("SET"));
+        assert_eq!(pg("BEGIN READ WRITE"), Some("READ WRITE"));
+        assert_eq!(
+            pg("SELECT set_config('default_transaction_read_only', 'off', false)"),
+            Some("SET_CONFIG")
+        );
+        assert_eq!(pg("SELECT E'\\'' ; truncate t"), Some("TRUNCATE"));
+        assert_eq!(mysql("SELECT 1--1; DELETE FROM t"), Some("DELETE"));
+        assert_eq!(mysql("/*!40101 DROP TABLE t */"), Some("DROP"));
+        assert_eq!(mysql("SET SESSION TRANSACTION READ WRITE"), Some("READ WRITE"));
+        assert_eq!(mysql("SET @@session.transaction_read_only = 0"), Some("SET"));
+        assert_eq!(mysql("REPLACE INTO t VALUES (1)"), Some("REPLACE"));
+    }
+}
@@ -0,0 +1,901 @@
+//! Redis side of the protocol proxy (`connectionType: "redis"`, see
+//! [`crate::tunnel::auth_proxy`]).
+//!
+//! A cluster-mode replication group hands out its nodes' own addresses — in
+//! `CLUSTER SLOTS` replies and in `MOVED` / `ASK` redirections — and clients
+//! then connect to those directly, which from a laptop goes nowhere. The
+//! proxy relays RESP between the client and the tunnel and swaps each node
+//! address for a local port that forwards to that node ([`ClusterNodes`]).
+//! A node's port gets its own session through the bastion the first time
+//! the node is handed out ([`run_cluster_proxy`]).
+//!
+//! Upstream the proxy speaks TLS when the group has in-transit encryption,
+//! so clients always connect to the local port in plain text.
+//!
+//! Replies are matched to commands in order. Once a connection subscribes
+//! (RESP2 pub/sub), runs `MONITOR` or turns replies off they no longer line
+//! up, and only `MOVED` / `ASK` are rewritten from then on — clients ask for
+//! the topology on a connection of its own anyway.
+
+use crate::tunnel::auth_proxy::{self, AuthProxy, ProxyStream};
+use crate::tunnel::native;
+use crate::tunnel::socks::{self, SessionStarter};
+use std::collections::{HashMap, HashSet};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+    BufWriter,
+};
+use tokio::sync::{mpsc, watch};
+use tokio_util::sync::CancellationToken;
+
+/// Longest simple string, error or length line accepted, either way.
+const MAX_LINE_LENGTH: u64 = 64 * 1024;
+
+/// Command arguments read to tell what a command is (`CLUSTER SLOTS`,
+/// `SUBSCRIBE`, ...); longer ones are relayed without being looked at.
+const MAX_KEYWORD_LENGTH: i64 = 32;
+
+/// Nodes a cluster proxy hands out ports for. Clusters have up to 500, but
+/// each node here is an SSM session of its own.
+const MAX_NODES: usize = 128;
+
+/// Time a node's session gets to come up before the reply naming it goes
+/// out anyway.
+const NODE_START_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a reply answers, as far as the proxy cares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    /// Node addresses in the reply are rewritten.
+    ClusterSlots,
+    /// Replies stop lining up with commands after this one.
+    Untracked,
+    Other,
+}
+
+impl Command {
+    /// Classify a command by its first two words (upper case).
+    fn classify(words: &[Vec<u8>]) -> Self {
+        match (
+            words.first().map(Vec::as_slice),
+            words.get(1).map(Vec::as_slice),
+        ) {
+            (Some(b"CLUSTER"), Some(b"SLOTS")) => Command::ClusterSlots,
+            (Some(b"SUBSCRIBE" | b"PSUBSCRIBE" | b"SSUBSCRIBE" | b"MONITOR"), _)
+            | (Some(b"CLIENT"), Some(b"REPLY")) => Command::Untracked,
+            _ => Command::Other,
+        }
+    }
+}
+
+/// How far a node's session has got, for replies waiting to name it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeState {
+    Starting,
+    Up,
+    Down,
+}
+
+struct Node {
+    port: u16,
+    state: Arc<watch::Sender<NodeState>>,
+}
+
+/// A node whose session should be (re)started on `local_port`.
+struct NodeRequest {
+    host: String,
+    port: u16,
+    local_port: u16,
+    state: Arc<watch::Sender<NodeState>>,
+}
+
+/// Local stand-ins for a cluster's nodes: one port each on the tunnel's bind
+/// address, kept across reconnects so clients' cached topology stays valid.
+pub struct ClusterNodes {
+    bind: Ipv4Addr,
+    nodes: std::sync::Mutex<HashMap<(String, u16), Node>>,
+    requests: mpsc::UnboundedSender<NodeRequest>,
+    /// Taken by the running [`run_cluster_proxy`].
+    pending: tokio::sync::Mutex<mpsc::UnboundedReceiver<NodeRequest>>,
+}
+
+impl std::fmt::Debug for ClusterNodes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClusterNodes")
+            .field("bind", &self.bind)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ClusterNodes {
+    pub fn new(bind: Ipv4Addr) -> Self {
+        let (requests, pending) = mpsc::unbounded_channel();
+        Self {
+            bind,
+            nodes: std::sync::Mutex::new(HashMap::new()),
+            requests,
+            pending: tokio::sync::Mutex::new(pending),
+        }
+    }
+
+    /// The local port standing in for `host:port`, assigned (and its
+    /// session asked for) on first use. Waits for the session to come up,
+    /// so the client's first connection to the port isn't refused.
+    async fn local_port(&self, host: &str, port: u16) -> Result<u16, String> {
+        let (local_port, mut state) = {
+            let mut nodes = self
+                .nodes
+                .lock()
+                .map_err(|_| "Redis node table poisoned".to_string())?;
+            let key = (host.to_string(), port);
+            if !nodes.contains_key(&key) && nodes.len() >= MAX_NODES {
+                return Err(format!("more than {} cluster nodes", MAX_NODES));
+            }
+            let node = match nodes.entry(key) {
+                std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    let local_port = std::net::TcpListener::bind((self.bind, 0))
+                        .and_then(|listener| listener.local_addr())
+                        .map_err(|e| format!("no free local port: {}", e))?
+                        .port();
+                    log::info!(
+                        "Redis: node {}:{} is on local port {}",
+                        host,
+                        port,
+                        local_port
+                    );
+                    entry.insert(Node {
+                        port: local_port,
+                        state: Arc::new(watch::channel(NodeState::Down).0),
+                    })
+                }
+            };
+            if *node.state.borrow() == NodeState::Down {
+                node.state.send_replace(NodeState::Starting);
+                let _ = self.requests.send(NodeRequest {
+                    host: host.to_string(),
+                    port,
+                    local_port: node.port,
+                    state: node.state.clone(),
+                });
+            }
+            (node.port, node.state.subscribe())
+        };
+
+        let started = tokio::time::timeout(
+            NODE_START_TIMEOUT,
+            state.wait_for(|state| *state != NodeState::Starting),
+        )
+        .await
+        .ok()
+        .and_then(|result| result.ok().map(|state| *state));
+        if started != Some(NodeState::Up) {
+            log::warn!("Redis: node {}:{} is not reachable yet", host, port);
+        }
+        Ok(local_port)
+    }
+
+    /// Ask for every known node's session again (after a reconnect).
+    fn restart_all(&self) {
+        let Ok(nodes) = self.nodes.lock() else {
+            return;
+        };
+        for ((host, port), node) in nodes.iter() {
+            node.state.send_replace(NodeState::Starting);
+            let _ = self.requests.send(NodeRequest {
+                host: host.clone(),
+                port: *port,
+                local_port: node.port,
+                state: node.state.clone(),
+            });
+        }
+    }
+}
+
+/// Serve a cluster-mode group: the configuration endpoint on `local_addr`,
+/// and every node the proxy hands out on a port of its own, each over its
+/// own session through the bastion (smux-multiplexed if `multiplexed`).
+/// Fails if the endpoint's session does, or once the bastion stops
+/// accepting sessions (e.g. it was replaced), so the tunnel lifecycle can
+/// find the new one.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_cluster_proxy<S: SessionStarter>(
+    starter: S,
+    proxy: AuthProxy,
+    host: &str,
+    port: u16,
+    multiplexed: bool,
+    local_addr: SocketAddrV4,
+    cancel: CancellationToken,
+    ready_tx: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
+) -> Result<(), String> {
+    let nodes = proxy
+        .cluster
+        .clone()
+        .ok_or_else(|| "Redis cluster proxy has no node table".to_string())?;
+    let mut requests = nodes.pending.lock().await;
+    let starter = Arc::new(starter);
+    let node_cancel = cancel.child_token();
+
+    let endpoint = forward(
+        starter.clone(),
+        host.to_string(),
+        port,
+        proxy.clone(),
+        multiplexed,
+        local_addr,
+        cancel.child_token(),
+        ready_tx,
+    );
+    tokio::pin!(endpoint);
+    // Ports handed out before a reconnect are still in clients' topology
+    nodes.restart_all();
+
+    let mut running = HashSet::new();
+    let mut sessions = tokio::task::JoinSet::new();
+    let result = loop {
+        tokio::select! {
+            result = &mut endpoint => break result,
+            Some(request) = requests.recv() => {
+                // Left over from before a reconnect
+                if !running.insert((request.host.clone(), request.port)) {
+                    continue;
+                }
+                let (node_ready_tx, node_ready_rx) = tokio::sync::oneshot::channel();
+                let session = forward(
+                    starter.clone(),
+                    request.host.clone(),
+                    request.port,
+                    proxy.for_server(&request.host),
+                    multiplexed,
+                    SocketAddrV4::new(*local_addr.ip(), request.local_port),
+                    node_cancel.clone(),
+                    Some(node_ready_tx),
+                );
+                sessions.spawn(async move {
+                    let up = async {
+                        if let Ok(Ok(())) = node_ready_rx.await {
+                            request.state.send_replace(NodeState::Up);
+                        }
+                    };
+                    let (result, ()) = tokio::join!(session, up);
+                    (request, result)
+                });
+            }
+            Some(joined) = sessions.join_next() => {
+                let Ok((request, result)) = joined else {
+                    continue;
+                };
+                running.remove(&(request.host.clone(), request.port));
+                request.state.send_replace(NodeState::Down);
+                match result {
+                    Err(e) if socks::is_bastion_lost(&e) => break Err(e),
+                    Err(e) => log::warn!(
+                        "Redis: session to node {}:{} failed: {}",
+                        request.host,
+                        request.port,
+                        e
+                    ),
+                    Ok(()) => log::info!(
+                        "Redis: session to node {}:{} ended",
+                        request.host,
+                        request.port
+                    ),
+                }
+            }
+            _ = cancel.cancelled() => break Ok(()),
+        }
+    };
+
+    node_cancel.cancel();
+    while let Some(joined) = sessions.join_next().await {
+        if let Ok((request, _)) = joined {
+            request.state.send_replace(NodeState::Down);
+        }
+    }
+    result
+}
+
+/// One port forwarding session to `host:port` on `local_addr`.
+#[allow(clippy::too_many_arguments)]
+async fn forward<S: SessionStarter>(
+    starter: Arc<S>,
+    host: String,
+    port: u16,
+    proxy: AuthProxy,
+    multiplexed: bool,
+    local_addr: SocketAddrV4,
+    cancel: CancellationToken,
+    ready_tx: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
+) -> Result<(), String> {
+    let tokens = match starter.start(&host, port).await {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            if let Some(tx) = ready_tx {
+                let _ = tx.send(Err(e.clone()));
+            }
+            return Err(e);
+        }
+    };
+    if multiplexed {
+        native::start_multiplexed_port_forwarding(
+            tokens.stream_url,
+            tokens.token_value,
+            tokens.kms,
+            local_addr,
+            Some(proxy),
+            cancel,
+            ready_tx,
+        )
+        .await
+    } else {
+        native::start_native_port_forwarding(
+            tokens.stream_url,
+            tokens.token_value,
+            tokens.kms,
+            local_addr,
+            Some(proxy),
+            cancel,
+            ready_tx,
+        )
+        .await
+    }
+}
+
+/// Relay one client connection to the server through the proxy, rewriting
+/// node addresses in replies when `proxy.cluster` is set.
+pub async fn serve<C, U>(client: C, upstream: U, proxy: &AuthProxy) -> Result<(), String>
+where
+    C: AsyncRead + AsyncWrite + Unpin + Send,
+    U: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (client_read, client_write) = tokio::io::split(client);
+    let mut client_read = BufReader::new(client_read);
+    let mut client_write = BufWriter::new(client_write);
+
+    // Nothing is opened upstream for connections that never send a command
+    if client_read.fill_buf().await.map_err(io_error)?.is_empty() {
+        return Ok(());
+    }
+
+    let upstream: Box<dyn ProxyStream> = if proxy.require_tls {
+        match auth_proxy::connect_tls(upstream, &proxy.server_name, proxy.verify_tls).await {
+            Ok(tls) => Box::new(tls),
+            Err(e) => {
+                let reply = format!("-ERR {}\r\n", e.replace(['\r', '\n'], " "));
+                let _ = client_write.write_all(reply.as_bytes()).await;
+                let _ = client_write.flush().await;
+                return Err(e);
+            }
+        }
+    } else {
+        Box::new(upstream)
+    };
+    let (upstream_read, upstream_write) = tokio::io::split(upstream);
+    let mut upstream_read = BufReader::new(upstream_read);
+    let mut upstream_write = BufWriter::new(upstream_write);
+
+    let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+    let replies = relay_replies(&mut upstream_read, &mut client_write, commands_rx, proxy);
+    tokio::pin!(replies);
+    tokio::select! {
+        result = relay_commands(&mut client_read, &mut upstream_write, commands_tx) => {
+            result?;
+            // The client is done sending; let the remaining replies through
+            replies.await
+        }
+        result = &mut replies => result,
+    }
+}
+
+fn io_error(e: std::io::Error) -> String {
+    format!("Redis proxy I/O error: {}", e)
+}
+
+/// One line, terminator included. Inline commands may end in a bare LF.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Vec<u8>, String> {
+    let mut line = Vec::new();
+    reader
+        .take(MAX_LINE_LENGTH)
+        .read_until(b'\n', &mut line)
+        .await
+        .map_err(io_error)?;
+    if !line.ends_with(b"\n") {
+        return Err(if line.len() as u64 >= MAX_LINE_LENGTH {
+            "RESP line too long".to_string()
+        } else {
+            "connection closed mid-message".to_string()
+        });
+    }
+    Ok(line)
+}
+
+/// The number after a RESP type byte (`*3\r\n`, `$-1\r\n`, ...).
+fn length(line: &[u8]) -> Result<i64, String> {
+    std::str::from_utf8(line[1..].trim_ascii_end())
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| {
+            format!(
+                "bad RESP length: {}",
+                String::from_utf8_lossy(line).trim_end()
+            )
+        })
+}
+
+/// Copy exactly `len` bytes.
+async fn copy_exact<R, W>(reader: &mut R, writer: &mut W, len: u64) -> Result<(), String>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let copied = tokio::io::copy_buf(&mut reader.take(len), writer)
+        .await
+        .map_err(io_error)?;
+    if copied < len {
+        return Err("connection closed mid-message".to_string());
+    }
+    Ok(())
+}
+
+/// Client to server: pass commands through unchanged, queueing what each
+/// one is for [`relay_replies`]. Shuts down the server side at end of input.
+async fn relay_commands<R, W>(
+    client: &mut BufReader<R>,
+    upstream: &mut W,
+    commands: mpsc::UnboundedSender<Command>,
+) -> Result<(), String>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        if client.buffer().is_empty() {
+            upstream.flush().await.map_err(io_error)?;
+        }
+        if client.fill_buf().await.map_err(io_error)?.is_empty() {
+            upstream.shutdown().await.map_err(io_error)?;
+            return Ok(());
+        }
+
+        let line = read_line(client).await?;
+        upstream.write_all(&line).await.map_err(io_error)?;
+        let mut words = Vec::new();
+        if line[0] == b'*' {
+            let count = length(&line)?;
+            if count <= 0 {
+                // Ignored by the server, no reply
+                continue;
+            }
+            for i in 0..count {
+                let header = read_line(client).await?;
+                if header[0] != b'$' {
+                    return Err("unexpected RESP from client".to_string());
+                }
+                upstream.write_all(&header).await.map_err(io_error)?;
+                let len = length(&header)?.max(0);
+                if i < 2 && len <= MAX_KEYWORD_LENGTH {
+                    let mut arg = vec![0u8; len as usize + 2];
+                    client.read_exact(&mut arg).await.map_err(io_error)?;
+                    upstream.write_all(&arg).await.map_err(io_error)?;
+                    arg.truncate(len as usize);
+                    arg.make_ascii_uppercase();
+                    words.push(arg);
+                } else {
+                    copy_exact(client, upstream, len as u64 + 2).await?;
+                }
+            }
+        } else {
+            // Inline command (`redis-cli` over telnet, `PING\r\n`)
+            words = line
+                .split(|b| b.is_ascii_whitespace())
+                .filter(|word| !word.is_empty())
+                .take(2)
+                .map(|word| word.to_ascii_uppercase())
+                .collect();
+            if words.is_empty() {
+                continue;
+            }
+        }
+        let _ = commands.send(Command::classify(&words));
+    }
+}
+
+/// Server to client: pass replies through, rewriting node addresses.
+async fn relay_replies<R, W>(
+    upstream: &mut BufReader<R>,
+    client: &mut W,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    proxy: &AuthProxy,
+) -> Result<(), String>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let nodes = proxy.cluster.as_deref();
+    let mut tracking = true;
+    loop {
+        if upstream.buffer().is_empty() {
+            client.flush().await.map_err(io_error)?;
+        }
+        let buffered = upstream.fill_buf().await.map_err(io_error)?;
+        let Some(&kind) = buffered.first() else {
+            client.shutdown().await.map_err(io_error)?;
+            return Ok(());
+        };
+
+        // RESP3 pushes (`>`) answer no command
+        let command = if tracking && kind != b'>' {
+            commands.recv().await.unwrap_or(Command::Other)
+        } else {
+            Command::Other
+        };
+        match (command, nodes) {
+            (Command::ClusterSlots, Some(nodes)) => {
+                let mut reply = Vec::new();
+                relay_value(upstream, &mut reply, None, &proxy.server_name).await?;
+                let value = parse_value(&mut reply.as_slice())?;
+                let value = rewrite_cluster_slots(value, nodes, &proxy.server_name).await;
+                client.write_all(&value.encode()).await.map_err(io_error)?;
+            }
+            (command, nodes) => {
+                tracking &= command != Command::Untracked;
+                relay_value(upstream, client, nodes, &proxy.server_name).await?;
+            }
+        }
+    }
+}
+
+/// Copy one complete reply, rewriting `MOVED` / `ASK` errors if `nodes`.
+async fn relay_value<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    nodes: Option<&ClusterNodes>,
+    server_name: &str,
+) -> Result<(), String>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    // Values still to read, nested ones included
+    let mut pending: i64 = 1;
+    while pending > 0 {
+        pending -= 1;
+        let mut line = read_line(reader).await?;
+        match line[0] {
+            b'*' | b'~' | b'>' => pending += length(&line)?.max(0),
+            b'%' => pending += 2 * length(&line)?.max(0),
+            // Attributes come before the value they describe
+            b'|' => pending += 2 * length(&line)?.max(0) + 1,
+            b'$' | b'=' | b'!' => {
+                writer.write_all(&line).await.map_err(io_error)?;
+                let len = length(&line)?;
+                if len >= 0 {
+                    copy_exact(reader, writer, len as u64 + 2).await?;
+                }
+                continue;
+            }
+            b'-' => {
+                if let Some(nodes) = nodes {
+                    line = rewrite_redirect(line, nodes, server_name).await;
+                }
+            }
+            b'+' | b':' | b'_' | b',' | b'#' | b'(' => {}
+            _ => return Err("unexpected RESP from server".to_string()),
+        }
+        writer.write_all(&line).await.map_err(io_error)?;
+    }
+    Ok(())
+}
+
+/// A parsed reply, kept close enough to the wire to write back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    /// Array, set, map or push, by type byte. Maps hold keys and values
+    /// alternately.
+    Aggregate(u8, Vec<Value>),
+    /// Blob string, error or verbatim string, by type byte.
+    Bulk(u8, Vec<u8>),
+    /// Anything on one line (type byte included, CRLF not), nulls too.
+    Line(Vec<u8>),
+}
+
+impl Value {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Aggregate(kind, items) => {
+                let count = if *kind == b'%' {
+                    items.len() / 2
+                } else {
+                    items.len()
+                };
+                out.push(*kind);
+                out.extend_from_slice(format!("{}\r\n", count).as_bytes());
+                for item in items {
+                    item.encode_into(out);
+                }
+            }
+            Value::Bulk(kind, data) => {
+                out.push(*kind);
+                out.extend_from_slice(format!("{}\r\n", data.len()).as_bytes());
+                out.extend_from_slice(data);
+                out.extend_from_slice(b"\r\n");
+            }
+            Value::Line(line) => {
+                out.extend_from_slice(line);
+                out.extend_from_slice(b"\r\n");
+            }
+        }
+    }
+
+    /// A string value (blob or simple).
+    fn text(&self) -> Option<&str> {
+        match self {
+            Value::Bulk(b'$', data) => std::str::from_utf8(data).ok(),
+            Value::Line(line) if line.first() == Some(&b'+') => {
+                std::str::from_utf8(&line[1..]).ok()
+            }
+            _ => None,
+        }
+    }
+
+    fn integer(&self) -> Option<i64> {
+        match self {
+            Value::Line(line) if line.first() == Some(&b':') => {
+                std::str::from_utf8(&line[1..]).ok()?.parse().ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parse one complete reply off the front of `input`. Attributes are
+/// dropped.
+fn parse_value(input: &mut &[u8]) -> Result<Value, String> {
+    let end = input
+        .windows(2)
+        .position(|w| w == b"\r\n")
+        .filter(|&end| end > 0)
+        .ok_or_else(|| "truncated RESP reply".to_string())?;
+    let line = input[..end].to_vec();
+    *input = &input[end + 2..];
+
+    match line[0] {
+        b'*' | b'~' | b'>' | b'%' | b'|' => {
+            let count = length(&line)?;
+            if count < 0 {
+                return Ok(Value::Line(line));
+            }
+            let count = if matches!(line[0], b'%' | b'|') {
+                2 * count
+            } else {
+                count
+            };
+            let items = (0..count)
+                .map(|_| parse_value(input))
+                .collect::<Result<Vec<_>, _>>()?;
+            if line[0] == b'|' {
+                return parse_value(input);
+            }
+            Ok(Value::Aggregate(line[0], items))
+        }
+        b'$' | b'=' | b'!' => {
+            let len = length(&line)?;
+            if len < 0 {
+                return Ok(Value::Line(line));
+            }
+            let len = len as usize;
+            if input.len() < len + 2 {
+                return Err("truncated RESP reply".to_string());
+            }
+            let data = input[..len].to_vec();
+            *input = &input[len + 2..];
+            Ok(Value::Bulk(line[0], data))
+        }
+        _ => Ok(Value::Line(line)),
+    }
+}
+
+/// Point every node in a `CLUSTER SLOTS` reply at its local port. Each slot
+/// range is `[start, end, primary, replicas...]`, each node
+/// `[host, port, id, ...]`; an empty host is the server being asked.
+async fn rewrite_cluster_slots(value: Value, nodes: &ClusterNodes, server_name: &str) -> Value {
+    let Value::Aggregate(kind, mut ranges) = value else {
+        return value;
+    };
+    for range in ranges.iter_mut() {
+        let Value::Aggregate(_, fields) = range else {
+            continue;
+        };
+        for node in fields.iter_mut().skip(2) {
+            let Value::Aggregate(_, node) = node else {
+                continue;
+            };
+            let (Some(host), Some(port)) = (
+                node.first().and_then(Value::text),
+                node.get(1).and_then(Value::integer),
+            ) else {
+                continue;
+            };
+            let host = if host.is_empty() { server_name } else { host }.to_string();
+            let Ok(port) = u16::try_from(port) else {
+                continue;
+            };
+            match nodes.local_port(&host, port).await {
+                Ok(local_port) => {
+                    node[0] = Value::Bulk(b'$', nodes.bind.to_string().into_bytes());
+                    node[1] = Value::Line(format!(":{}", local_port).into_bytes());
+                }
+                Err(e) => log::warn!("Redis: not rewriting node {}:{}: {}", host, port, e),
+            }
+        }
+    }
+    Value::Aggregate(kind, ranges)
+}
+
+/// Point a `-MOVED <slot> <host>:<port>` or `-ASK ...` error at the node's
+/// local port; other errors are returned unchanged.
+async fn rewrite_redirect(line: Vec<u8>, nodes: &ClusterNodes, server_name: &str) -> Vec<u8> {
+    let Ok(text) = std::str::from_utf8(&line) else {
+        return line;
+    };
+    let mut words = text.trim_end().splitn(3, ' ');
+    let (Some(kind @ ("-MOVED" | "-ASK")), Some(slot), Some(address)) =
+        (words.next(), words.next(), words.next())
+    else {
+        return line;
+    };
+    let Some((host, Ok(port))) = address
+        .rsplit_once(':')
+        .map(|(host, port)| (host, port.parse::<u16>()))
+    else {
+        return line;
+    };
+    let host = if host.is_empty() { server_name } else { host };
+    match nodes.local_port(host, port).await {
+        Ok(local_port) => {
+            format!("{} {} {}:{}\r\n", kind, slot, nodes.bind, local_port).into_bytes()
+        }
+        Err(e) => {
+            log::warn!("Redis: not rewriting redirect to {}:{}: {}", host, port, e);
+            line
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for [`run_cluster_proxy`]: every node comes up at once.
+    fn start_nodes(nodes: &Arc<ClusterNodes>) {
+        let nodes = nodes.clone();
+        tokio::spawn(async move {
+            let mut requests = nodes.pending.lock().await;
+            while let Some(request) = requests.recv().await {
+                request.state.send_replace(NodeState::Up);
+            }
+        });
+    }
+
+    fn node(host: &str, port: u16, id: &str) -> Value {
+        Value::Aggregate(
+            b'*',
+            vec![
+                Value::Bulk(b'$', host.as_bytes().to_vec()),
+                Value::Line(format!(":{}", port).into_bytes()),
+                Value::Bulk(b'$', id.as_bytes().to_vec()),
+            ],
+        )
+    }
+
+    fn slots(ranges: &[(i64, i64, Vec<Value>)]) -> Value {
+        Value::Aggregate(
+            b'*',
+            ranges
+                .iter()
+                .map(|(start, end, nodes)| {
+                    let mut fields = vec![
+                        Value::Line(format!(":{}", start).into_bytes()),
+                        Value::Line(format!(":{}", end).into_bytes()),
+                    ];
+                    fields.extend(nodes.iter().cloned());
+                    Value::Aggregate(b'*', fields)
+                })
+                .collect(),
+        )
+    }
+
+    #[tokio::test]
+    async fn cluster_slots_point_at_local_ports() {
+        let nodes = Arc::new(ClusterNodes::new(Ipv4Addr::LOCALHOST));
+        start_nodes(&nodes);
+
+        let reply = slots(&[
+            (
+                0,
+                8191,
+                vec![node("10.0.1.5", 6379, "a"), node("10.0.2.7", 6379, "b")],
+            ),
+            (
+                8192,
+                16383,
+                vec![node("", 6380, "c"), node("10.0.1.5", 6379, "a")],
+            ),
+        ]);
+        let encoded = reply.encode();
+        assert_eq!(parse_value(&mut encoded.as_slice()).unwrap(), reply);
+
+        let rewritten = rewrite_cluster_slots(reply, &nodes, "cfg.example.com").await;
+        let a = nodes.local_port("10.0.1.5", 6379).await.unwrap();
+        let b = nodes.local_port("10.0.2.7", 6379).await.unwrap();
+        let c = nodes.local_port("cfg.example.com", 6380).await.unwrap();
+        assert!(a != b && b != c && a != c);
+        assert_eq!(
+            rewritten,
+            slots(&[
+                (
+                    0,
+                    8191,
+                    vec![node("127.0.0.1", a, "a"), node("127.0.0.1", b, "b")]
+                ),
+                (
+                    8192,
+                    16383,
+                    vec![node("127.0.0.1", c, "c"), node("127.0.0.1", a, "a")]
+                ),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn serve_rewrites_replies_in_order() {
+        let nodes = Arc::new(ClusterNodes::new(Ipv4Addr::LOCALHOST));
+        start_nodes(&nodes);
+        let proxy = AuthProxy::redis("cfg.example.com", false, Some(nodes.clone()));
+
+        let (client, proxy_side) = tokio::io::duplex(4096);
+        let (upstream, mut server) = tokio::io::duplex(4096);
+        let serving = tokio::spawn(async move { serve(proxy_side, upstream, &proxy).await });
+
+        let commands: &[u8] =
+            b"*2\r\n$7\r\ncluster\r\n$5\r\nslots\r\nPING\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n";
+        let (mut client_read, mut client_write) = tokio::io::split(client);
+        client_write.write_all(commands).await.unwrap();
+        client_write.shutdown().await.unwrap();
+
+        let mut received = vec![0u8; commands.len()];
+        server.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, commands);
+        let reply = slots(&[(0, 16383, vec![node("10.0.1.5", 6379, "a")])]);
+        server.write_all(&reply.encode()).await.unwrap();
+        server
+            .write_all(b"+PONG\r\n-MOVED 3999 10.0.2.7:6379\r\n")
+            .await
+            .unwrap();
+        drop(server);
+
+        let mut replies = Vec::new();
+        client_read.read_to_end(&mut replies).await.unwrap();
+        serving.await.unwrap().unwrap();
+
+        let a = nodes.local_port("10.0.1.5", 6379).await.unwrap();
+        let b = nodes.local_port("10.0.2.7", 6379).await.unwrap();
+        let mut expected = slots(&[(0, 16383, vec![node("127.0.0.1", a, "a")])]).encode();
+        expected.extend_from_slice(b"+PONG\r\n");
+        expected.extend_from_slice(format!("-MOVED 3999 127.0.0.1:{}\r\n", b).as_bytes());
+        assert_eq!(
+            String::from_utf8_lossy(&replies),
+            String::from_utf8_lossy(&expected)
+        );
+    }
+}
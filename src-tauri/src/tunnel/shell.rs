@@ -0,0 +1,455 @@
+//! Interactive shell sessions (`Standard_Stream`) over the SSM data channel.
+//!
+//! Same transport as port forwarding — handshake, acknowledges, sequence
+//! numbers, retransmission — but the payload is a terminal: keystrokes go out
+//! as `input_stream_data` OUTPUT payloads, agent output is written to the local
+//! terminal, and terminal size changes are sent as `PAYLOAD_SIZE` messages.
+//! Shell traffic is tiny and interactive, so there is no smux, no SYN, and a
+//! fixed retransmission timeout instead of RTT estimation.
+
+use crate::tunnel::encryption::{open_output, seal_output, KmsSessionContext, SessionEncryption};
+use crate::tunnel::protocol::{
+    build_acknowledge, build_data_message, build_flag_message, build_size_message, AgentMessage,
+    TerminalSize, ACKNOWLEDGE, CHANNEL_CLOSED, FLAG_TERMINATE_SESSION, OUTPUT_STREAM_DATA,
+    PAYLOAD_OUTPUT,
+};
+use crate::tunnel::smux::BASIC_CLIENT_VERSION;
+use crate::tunnel::websocket::{open_data_channel_with_encryption, WsStream};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+
+/// Resend unacknowledged input after this long.
+const RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// How often to check for retransmissions.
+const RETRANSMIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// WebSocket ping interval — keeps an idle shell alive through NAT (see native.rs).
+const WS_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Max out-of-order messages buffered before giving up on the session.
+const MAX_BUFFER_SIZE: usize = 10_000;
+
+/// Run an interactive shell session until the agent closes the channel, the
+/// WebSocket drops, or `cancel` fires. A dropped or failing WebSocket is
+/// returned as an error.
+///
+/// `input` carries raw terminal bytes (already in raw mode on the caller's
+/// side); `sizes` carries terminal size updates, the first one right away.
+/// Agent output is written to `output` as it arrives.
+pub async fn run_shell_session<W>(
+    stream_url: String,
+    token_value: String,
+    kms: Option<KmsSessionContext>,
+    mut input: mpsc::Receiver<Vec<u8>>,
+    mut output: W,
+    mut sizes: mpsc::Receiver<TerminalSize>,
+    cancel: CancellationToken,
+) -> Result<(), String>
+where
+    W: AsyncWrite + Unpin,
+{
+    let channel = open_data_channel_with_encryption(
+        &stream_url,
+        &token_value,
+        BASIC_CLIENT_VERSION,
+        kms.as_ref(),
+    )
+    .await?;
+    log::info!(
+        "SSM shell channel open, agent version: {}",
+        channel.agent_version
+    );
+
+    let mut shell = ShellChannel {
+        ws: channel.ws,
+        encryption: channel.encryption,
+        outgoing_seq: channel.outgoing_seq,
+        expected_seq: channel.expected_incoming_seq,
+        unacked: BTreeMap::new(),
+        incoming: BTreeMap::new(),
+    };
+
+    let mut retransmit = tokio::time::interval(RETRANSMIT_CHECK_INTERVAL);
+    let mut ping = tokio::time::interval(WS_PING_INTERVAL);
+    let mut input_open = true;
+    let mut sizes_open = true;
+    let mut closed_by_agent = false;
+    let mut error = None;
+
+    loop {
+        let result = tokio::select! {
+            _ = cancel.cancelled() => break,
+            msg = shell.ws.next() => match msg {
+                Some(Ok(Message::Binary(data))) => {
+                    match shell.on_agent_message(&data, &mut output).await {
+                        Ok(true) => {
+                            closed_by_agent = true;
+                            break;
+                        }
+                        Ok(false) => Ok(()),
+                        Err(e) => Err(e),
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    closed_by_agent = true;
+                    break;
+                }
+                Some(Err(e)) => Err(format!("WebSocket read error: {}", e)),
+                Some(Ok(_)) => Ok(()),
+            },
+            data = input.recv(), if input_open => match data {
+                Some(bytes) => shell.send_input(&bytes).await,
+                None => {
+                    input_open = false;
+                    Ok(())
+                }
+            },
+            size = sizes.recv(), if sizes_open => match size {
+                Some(size) => {
+                    let seq = shell.next_seq();
+                    shell.send_reliable(build_size_message(size, seq)).await
+                }
+                None => {
+                    sizes_open = false;
+                    Ok(())
+                }
+            },
+            _ = retransmit.tick() => shell.retransmit().await,
+            _ = ping.tick() => shell
+                .ws
+                .send(Message::Ping(b"keepalive".to_vec().into()))
+                .await
+                .map_err(|e| format!("WebSocket ping failed: {}", e)),
+        };
+        if let Err(e) = result {
+            log::error!("{}, ending shell session", e);
+            error = Some(e);
+            break;
+        }
+    }
+
+    // Best-effort: tell the agent to end the session, then close the socket.
+    if !closed_by_agent {
+        let seq = shell.next_seq();
+        let term_msg = build_flag_message(FLAG_TERMINATE_SESSION, seq, true);
+        let _ = shell
+            .ws
+            .send(Message::Binary(term_msg.serialize().into()))
+            .await;
+    }
+    let _ = shell.ws.close(None).await;
+    let _ = output.flush().await;
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Client side of an open shell data channel.
+struct ShellChannel {
+    ws: WsStream,
+    encryption: Option<Arc<SessionEncryption>>,
+    outgoing_seq: i64,
+    expected_seq: i64,
+    /// Sent input awaiting an acknowledge: seq → (serialized message, last sent).
+    unacked: BTreeMap<i64, (Vec<u8>, Instant)>,
+    /// Agent messages that arrived ahead of `expected_seq`.
+    incoming: BTreeMap<i64, AgentMessage>,
+}
+
+impl ShellChannel {
+    fn next_seq(&mut self) -> i64 {
+        let seq = self.outgoing_seq;
+        self.outgoing_seq += 1;
+        seq
+    }
+
+    async fn send_input(&mut self, data: &[u8]) -> Result<(), String> {
+        let payload = seal_output(self.encryption.as_deref(), data)?;
+        let seq = self.next_seq();
+        self.send_reliable(build_data_message(&payload, seq)).await
+    }
+
+    /// Send a message and keep it for retransmission until acknowledged.
+    async fn send_reliable(&mut self, msg: AgentMessage) -> Result<(), String> {
+        let serialized = msg.serialize();
+        self.unacked
+            .insert(msg.sequence_number, (serialized.clone(), Instant::now()));
+        self.ws
+            .send(Message::Binary(serialized.into()))
+            .await
+            .map_err(|e| format!("Failed to send shell input: {}", e))
+    }
+
+    async fn retransmit(&mut self) -> Result<(), String> {
+        let due: Vec<Vec<u8>> = self
+            .unacked
+            .values_mut()
+            .filter(|(_, sent_at)| sent_at.elapsed() > RETRANSMIT_TIMEOUT)
+            .map(|(message, sent_at)| {
+                *sent_at = Instant::now();
+                message.clone()
+            })
+            .collect();
+        for message in due {
+            self.ws
+                .send(Message::Binary(message.into()))
+                .await
+                .map_err(|e| format!("Failed to retransmit shell input: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Handle one agent message. Returns `Ok(true)` when the agent closed the channel.
+    async fn on_agent_message<W>(&mut self, data: &[u8], output: &mut W) -> Result<bool, String>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let msg = match AgentMessage::deserialize(data) {
+            Ok(m) => m,
+            Err(e) => {
+                log::warn!("Failed to deserialize message: {}", e);
+                return Ok(false);
+            }
+        };
+        if !msg.validate_digest(data) {
+            log::warn!(
+                "Payload digest mismatch, dropping message seq={}",
+                msg.sequence_number
+            );
+            return Ok(false);
+        }
+
+        match msg.message_type.as_str() {
+            OUTPUT_STREAM_DATA => {
+                let ack = build_acknowledge(&msg);
+                self.ws
+                    .send(Message::Binary(ack.serialize().into()))
+                    .await
+                    .map_err(|e| format!("Failed to send ack: {}", e))?;
+
+                // seq < expected: duplicate, already written — drop
+                if msg.sequence_number >= self.expected_seq {
+                    if self.incoming.len() >= MAX_BUFFER_SIZE {
+                        return Err(format!(
+                            "Incoming buffer overflow ({} entries)",
+                            MAX_BUFFER_SIZE
+                        ));
+                    }
+                    self.incoming.insert(msg.sequence_number, msg);
+                }
+                while let Some(mut next) = self.incoming.remove(&self.expected_seq) {
+                    self.expected_seq += 1;
+                    open_output(self.encryption.as_deref(), &mut next)?;
+                    if next.payload_type == PAYLOAD_OUTPUT {
+                        output
+                            .write_all(&next.payload)
+                            .await
+                            .map_err(|e| format!("Failed to write shell output: {}", e))?;
+                    }
+                }
+                output
+                    .flush()
+                    .await
+                    .map_err(|e| format!("Failed to write shell output: {}", e))?;
+                Ok(false)
+            }
+            ACKNOWLEDGE => {
+                if let Ok(content) = serde_json::from_slice::<serde_json::Value>(&msg.payload)
+                    && let Some(seq) = content
+                        .get("AcknowledgedMessageSequenceNumber")
+                        .and_then(|v| v.as_i64())
+                {
+                    self.unacked.remove(&seq);
+                }
+                Ok(false)
+            }
+            CHANNEL_CLOSED => {
+                let reason = if msg.payload.is_empty() {
+                    "(no payload)".to_string()
+                } else {
+                    String::from_utf8_lossy(&msg.payload).to_string()
+                };
+                log::info!("Shell channel closed by agent: {}", reason);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tunnel::mock_agent::{spawn_echo_target, Faults, MockAgent, MockAgentConfig};
+    use tokio::io::AsyncReadExt;
+
+    const E2E_TIMEOUT: Duration = Duration::from_secs(20);
+
+    /// Keystrokes reach the remote side, its output reaches the terminal, and
+    /// size updates arrive as PAYLOAD_SIZE — over a lossy, reordering link.
+    #[tokio::test]
+    async fn shell_relays_keystrokes_output_and_size_updates() {
+        let target = spawn_echo_target().await;
+        let mut config = MockAgentConfig::new(target);
+        config.shell = true;
+        config.faults = Faults {
+            drop_every: Some(4),
+            duplicate_every: Some(3),
+            reorder_every: Some(5),
+            drop_inbound_every: Some(4),
+        };
+        let mock = MockAgent::start(config).await;
+
+        let (input_tx, input_rx) = mpsc::channel(16);
+        let (size_tx, size_rx) = mpsc::channel(4);
+        let (terminal, mut screen) = tokio::io::duplex(64 * 1024);
+        let cancel = CancellationToken::new();
+        let session = tokio::spawn(run_shell_session(
+            mock.stream_url(),
+            mock.token(),
+            None,
+            input_rx,
+            terminal,
+            size_rx,
+            cancel.clone(),
+        ));
+
+        size_tx
+            .send(TerminalSize { cols: 80, rows: 24 })
+            .await
+            .unwrap();
+        for key in [b"l", b"s", b" ", b"-", b"l", b"a"] {
+            input_tx.send(key.to_vec()).await.unwrap();
+        }
+        size_tx
+            .send(TerminalSize {
+                cols: 132,
+                rows: 50,
+            })
+            .await
+            .unwrap();
+        tokio::time::timeout(
+            E2E_TIMEOUT,
+            mock.wait_until(|r| r.terminal_sizes.len() == 2),
+        )
+        .await
+        .expect("size updates timed out");
+        input_tx.send(b"\r".to_vec()).await.unwrap();
+
+        // The echo target plays the remote shell: everything typed comes back
+        let mut echoed = vec![0u8; b"ls -la\r".len()];
+        tokio::time::timeout(E2E_TIMEOUT, screen.read_exact(&mut echoed))
+            .await
+            .expect("shell output timed out")
+            .expect("read shell output");
+        assert_eq!(echoed, b"ls -la\r");
+
+        mock.close_channel();
+        let result = tokio::time::timeout(E2E_TIMEOUT, session)
+            .await
+            .expect("shell session outlived channel_closed")
+            .expect("shell task panicked");
+        assert_eq!(result, Ok(()));
+
+        let report = mock.finished().await;
+        assert!(!report.multiplexed);
+        assert_eq!(
+            report.terminal_sizes,
+            vec![
+                TerminalSize { cols: 80, rows: 24 },
+                TerminalSize {
+                    cols: 132,
+                    rows: 50
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_terminates_the_session() {
+        let target = spawn_echo_target().await;
+        let mut config = MockAgentConfig::new(target);
+        config.shell = true;
+        let mock = MockAgent::start(config).await;
+
+        let (_input_tx, input_rx) = mpsc::channel(1);
+        let (size_tx, size_rx) = mpsc::channel(1);
+        let cancel = CancellationToken::new();
+        let session = tokio::spawn(run_shell_session(
+            mock.stream_url(),
+            mock.token(),
+            None,
+            input_rx,
+            tokio::io::sink(),
+            size_rx,
+            cancel.clone(),
+        ));
+
+        // Cancel once the channel is up, i.e. the first size has arrived
+        size_tx
+            .send(TerminalSize { cols: 80, rows: 24 })
+            .await
+            .unwrap();
+        tokio::time::timeout(
+            E2E_TIMEOUT,
+            mock.wait_until(|r| !r.terminal_sizes.is_empty()),
+        )
+        .await
+        .expect("handshake timed out");
+        cancel.cancel();
+        let result = tokio::time::timeout(E2E_TIMEOUT, session)
+            .await
+            .expect("shell session ignored cancel")
+            .expect("shell task panicked");
+        assert_eq!(result, Ok(()));
+        assert!(mock.finished().await.terminated);
+    }
+
+    /// A connection that drops without a close frame fails the session, so
+    /// the CLI exits non-zero instead of reporting a clean disconnect.
+    #[tokio::test]
+    async fn dropped_connection_is_an_error() {
+        let target = spawn_echo_target().await;
+        let mut config = MockAgentConfig::new(target);
+        config.shell = true;
+        let mock = MockAgent::start(config).await;
+
+        let (_input_tx, input_rx) = mpsc::channel(1);
+        let (size_tx, size_rx) = mpsc::channel(1);
+        let session = tokio::spawn(run_shell_session(
+            mock.stream_url(),
+            mock.token(),
+            None,
+            input_rx,
+            tokio::io::sink(),
+            size_rx,
+            CancellationToken::new(),
+        ));
+
+        size_tx
+            .send(TerminalSize { cols: 80, rows: 24 })
+            .await
+            .unwrap();
+        tokio::time::timeout(
+            E2E_TIMEOUT,
+            mock.wait_until(|r| !r.terminal_sizes.is_empty()),
+        )
+        .await
+        .expect("handshake timed out");
+        mock.drop_connection();
+        let result = tokio::time::timeout(E2E_TIMEOUT, session)
+            .await
+            .expect("shell session outlived the connection")
+            .expect("shell task panicked");
+        assert!(result.is_err(), "expected an error, got {:?}", result);
+    }
+}
@@ -7,6 +7,7 @@
 //!
 //! Protocol reference: AWS session-manager-plugin smux implementation.
 
+use crate::tunnel::native::ClientSource;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
@@ -40,13 +41,13 @@ const KEEPALIVE_INTERVAL_SECS: u64 = 10;
 // --- Smux commands ---
 
 /// SYN: open a new stream.
-const CMD_SYN: u8 = 0;
+pub(crate) const CMD_SYN: u8 = 0;
 /// FIN: close a stream.
-const CMD_FIN: u8 = 1;
+pub(crate) const CMD_FIN: u8 = 1;
 /// PSH: push data on a stream.
-const CMD_PSH: u8 = 2;
+pub(crate) const CMD_PSH: u8 = 2;
 /// NOP: keepalive (no-op).
-const CMD_NOP: u8 = 3;
+pub(crate) const CMD_NOP: u8 = 3;
 /// UPD: update receive window — smux protocol v2 ONLY. The SSM agent and the
 /// official plugin both run smux v1 (xtaci/smux DefaultConfig); xtaci's
 /// recvLoop returns ErrInvalidProtocol and closes the entire mux session if a
@@ -65,7 +66,7 @@ pub struct SmuxFrame {
 
 impl SmuxFrame {
     /// Create a new smux frame.
-    fn new(cmd: u8, stream_id: u32, payload: Vec<u8>) -> Self {
+    pub(crate) fn new(cmd: u8, stream_id: u32, payload: Vec<u8>) -> Self {
         Self {
             version: SMUX_VERSION,
             cmd,
@@ -125,12 +126,12 @@ impl SmuxFrame {
     }
 
     /// Create a FIN frame to close a stream.
-    fn fin(stream_id: u32) -> Self {
+    pub(crate) fn fin(stream_id: u32) -> Self {
         Self::new(CMD_FIN, stream_id, vec![])
     }
 
     /// Create a PSH (data push) frame.
-    fn psh(stream_id: u32, data: Vec<u8>) -> Self {
+    pub(crate) fn psh(stream_id: u32, data: Vec<u8>) -> Self {
         Self::new(CMD_PSH, stream_id, data)
     }
 }
@@ -361,18 +362,16 @@ impl SmuxSession {
 /// Run a multiplexed port forwarding session.
 ///
 /// This replaces the single-connection TCP relay loop in `native.rs` for multiplexed mode.
-/// It accepts multiple TCP connections on the local port and maps each to a smux stream,
-/// all sharing the same SSM WebSocket tunnel.
+/// It takes every client connection from `source` (local listeners or a queue)
+/// and maps each to a smux stream, all sharing the same SSM WebSocket tunnel.
 ///
 /// # Arguments
 /// * `smux_session` - The smux session multiplexer
-/// * `listener_v4` - IPv4 TCP listener
-/// * `listener_v6` - Optional IPv6 TCP listener
+/// * `source` - Where client connections come from (auth proxy already applied)
 /// * `cancel` - Cancellation token
-pub async fn run_multiplexed_listener(
+pub(crate) async fn run_multiplexed_listener(
     smux_session: Arc<SmuxSession>,
-    listener_v4: tokio::net::TcpListener,
-    listener_v6: Option<tokio::net::TcpListener>,
+    mut source: ClientSource,
     cancel: CancellationToken,
 ) {
     // Client-initiated stream IDs are odd numbers (1, 3, 5, ...).
@@ -384,59 +383,27 @@ pub async fn run_multiplexed_listener(
             break;
         }
 
-        let tcp_stream = tokio::select! {
-            result = listener_v4.accept() => {
-                match result {
-                    Ok((stream, addr)) => {
-                        log::info!("Multiplexed: TCP connection from {} (IPv4)", addr);
-                        stream
-                    }
-                    Err(e) => {
-                        log::error!("TCP accept error (IPv4): {}", e);
-                        continue;
-                    }
-                }
-            }
-            result = async {
-                match &listener_v6 {
-                    Some(l) => l.accept().await,
-                    None => std::future::pending().await,
-                }
-            } => {
-                match result {
-                    Ok((stream, addr)) => {
-                        log::info!("Multiplexed: TCP connection from {} (IPv6)", addr);
-                        stream
-                    }
-                    Err(e) => {
-                        log::error!("TCP accept error (IPv6): {}", e);
-                        continue;
-                    }
-                }
-            }
+        let client = tokio::select! {
+            result = source.next_client() => result,
             _ = cancel.cancelled() => break,
         };
+        let (mut tcp_read, mut tcp_write) = match client {
+            Ok(Some(client)) => client,
+            Ok(None) => break,
+            Err(e) => {
+                log::error!("{}", e);
+                continue;
+            }
+        };
 
-        // Disable Nagle's algorithm
-        let _ = tcp_stream.set_nodelay(true);
-
-        // Enable TCP keepalive
-        let sock_ref = socket2::SockRef::from(&tcp_stream);
-        let keepalive = socket2::TcpKeepalive::new()
-            .with_time(std::time::Duration::from_secs(60))
-            .with_interval(std::time::Duration::from_secs(10));
-        let _ = sock_ref.set_tcp_keepalive(&keepalive);
-
-        // Open a new smux stream for this TCP connection
+        // Open a new smux stream for this client connection
         let session = smux_session.clone();
         let cancel_child = cancel.clone();
         let counter = stream_id_counter.clone();
 
         tokio::spawn(async move {
             let (stream_id, mut data_rx) = session.open_stream(&counter).await;
-            log::info!("Opened smux stream {} for TCP connection", stream_id);
-
-            let (mut tcp_read, mut tcp_write) = tcp_stream.into_split();
+            log::info!("Opened smux stream {} for client connection", stream_id);
 
             // Task: remote -> TCP (write data from smux stream to TCP)
             let session_write = session.clone();
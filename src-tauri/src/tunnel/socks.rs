@@ -0,0 +1,664 @@
+//! SOCKS5 dynamic forwarding (`serviceType: "socks"`). The tunnel's local
+//! port is a SOCKS5 server (no authentication, CONNECT only) instead of a
+//! plain relay:
+//!
+//! - each destination `host:port` gets its own SSM port-forwarding session
+//!   through the project's bastion, started by the first CONNECT to it and
+//!   reused by later ones;
+//! - the negotiated client connection is handed to that session (see
+//!   [`native::start_queued_port_forwarding`]);
+//! - host names are resolved on the bastion, so VPC-private DNS names work.
+//!
+//! `socksAllow` limits destinations to IPv4 CIDRs (for addresses) and domain
+//! suffixes (for names).
+
+use crate::tunnel::encryption::KmsSessionContext;
+use crate::tunnel::native::{self, QueuedConnection};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+const SOCKS_VERSION: u8 = 5;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_NONE_ACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+const REPLY_SUCCEEDED: u8 = 0x00;
+const REPLY_NOT_ALLOWED: u8 = 0x02;
+const REPLY_HOST_UNREACHABLE: u8 = 0x04;
+const REPLY_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const REPLY_ADDRESS_NOT_SUPPORTED: u8 = 0x08;
+
+/// Destinations with a session open at once; the least recently used one
+/// without open connections is closed to make room.
+const MAX_SESSIONS: usize = 32;
+
+/// A session with no open connections for this long is closed.
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often to look for idle sessions.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time a client gets to finish the SOCKS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time a new destination's session gets to come up.
+const SESSION_START_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connections waiting to be picked up by a session.
+const QUEUED_CONNECTIONS: usize = 16;
+
+/// Destinations a SOCKS tunnel may reach (`socksAllow`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SocksAllowList {
+    /// (network, mask) pairs.
+    networks: Vec<(u32, u32)>,
+    /// Lowercase suffixes without a leading dot.
+    domains: Vec<String>,
+}
+
+impl SocksAllowList {
+    /// Parse `socksAllow` entries: IPv4 CIDRs (`10.0.0.0/8`; a bare address
+    /// is a /32) and domain suffixes (`internal.example.com`, optionally
+    /// written `.internal.example.com` or `*.internal.example.com`), which
+    /// match the domain and everything below it.
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        let mut list = Self::default();
+        for entry in entries {
+            let entry = entry.trim();
+            let looks_numeric = entry
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.' || c == '/');
+            if looks_numeric {
+                let (address, prefix) = entry.split_once('/').unwrap_or((entry, "32"));
+                let address: Ipv4Addr = address
+                    .parse()
+                    .map_err(|_| format!("invalid CIDR: {}", entry))?;
+                let prefix: u32 = prefix
+                    .parse()
+                    .ok()
+                    .filter(|p| *p <= 32)
+                    .ok_or_else(|| format!("invalid CIDR: {}", entry))?;
+                let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                list.networks.push((u32::from(address) & mask, mask));
+            } else {
+                let domain = entry
+                    .trim_start_matches("*.")
+                    .trim_start_matches('.')
+                    .to_ascii_lowercase();
+                if !is_valid_hostname(&domain) {
+                    return Err(format!("invalid domain suffix: {}", entry));
+                }
+                list.domains.push(domain);
+            }
+        }
+        Ok(list)
+    }
+
+    /// Whether `host` (an IPv4 address or a name) may be reached.
+    pub fn allows(&self, host: &str) -> bool {
+        if let Ok(address) = host.parse::<Ipv4Addr>() {
+            let address = u32::from(address);
+            return self
+                .networks
+                .iter()
+                .any(|(network, mask)| address & mask == *network);
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.domains.iter().any(|domain| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|rest| rest.ends_with('.'))
+        })
+    }
+}
+
+/// Hostnames are passed to SSM as the session's `host` parameter.
+fn is_valid_hostname(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// What a destination's SSM session needs to open its data channel.
+pub struct SessionTokens {
+    pub stream_url: String,
+    pub token_value: String,
+    pub kms: Option<KmsSessionContext>,
+}
+
+/// Starts the SSM session that carries connections to one destination.
+pub trait SessionStarter: Send + Sync + 'static {
+    fn start(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> impl Future<Output = Result<SessionTokens, String>> + Send;
+}
+
+/// One destination's session slot. `incoming` is `None` until the session
+/// is up, and closed once it has ended; dropping the slot closes it too.
+struct Slot {
+    incoming: Arc<tokio::sync::Mutex<Option<mpsc::Sender<QueuedConnection>>>>,
+    cancel: CancellationToken,
+    last_used: Instant,
+    /// Cloned into every connection handed to the session (see
+    /// [`QueuedConnection`]).
+    open: Arc<()>,
+}
+
+impl Slot {
+    fn open_connections(&self) -> usize {
+        Arc::strong_count(&self.open) - 1
+    }
+}
+
+struct SocksServer<S> {
+    starter: S,
+    allow: Option<SocksAllowList>,
+    multiplexed: bool,
+    slots: std::sync::Mutex<HashMap<(String, u16), Slot>>,
+    cancel: CancellationToken,
+    /// Reports that the bastion is gone; ends the server.
+    bastion_lost: mpsc::Sender<String>,
+}
+
+/// Serve SOCKS5 on `local_addr` until `cancel` fires. Fails if the listener
+/// can't be bound, or once the bastion stops accepting sessions (e.g. it was
+/// replaced), so the tunnel lifecycle can find the new one.
+pub async fn run_socks_server<S: SessionStarter>(
+    starter: S,
+    allow: Option<SocksAllowList>,
+    multiplexed: bool,
+    local_addr: SocketAddrV4,
+    cancel: CancellationToken,
+    ready_tx: Option<tokio::sync::oneshot::Sender<Result<(), String>>>,
+) -> Result<(), String> {
+    let (listener_v4, listener_v6) = native::bind_local_listeners(local_addr)?;
+    log::info!("SOCKS5 proxy listening on {}", local_addr);
+    if let Some(tx) = ready_tx {
+        let _ = tx.send(Ok(()));
+    }
+
+    let (bastion_lost, mut bastion_lost_rx) = mpsc::channel(1);
+    let server = Arc::new(SocksServer {
+        starter,
+        allow,
+        multiplexed,
+        slots: std::sync::Mutex::new(HashMap::new()),
+        cancel: cancel.child_token(),
+        bastion_lost,
+    });
+
+    let mut idle_check = tokio::time::interval(IDLE_CHECK_INTERVAL);
+    let result = loop {
+        let accepted = tokio::select! {
+            result = listener_v4.accept() => result,
+            result = async {
+                match &listener_v6 {
+                    Some(l) => l.accept().await,
+                    None => std::future::pending().await,
+                }
+            } => result,
+            Some(e) = bastion_lost_rx.recv() => break Err(e),
+            _ = idle_check.tick() => {
+                server.close_idle_sessions();
+                continue;
+            }
+            _ = cancel.cancelled() => break Ok(()),
+        };
+        match accepted {
+            Ok((stream, _addr)) => {
+                tokio::spawn(serve_client(server.clone(), stream));
+            }
+            Err(e) => log::error!("SOCKS accept error: {}", e),
+        }
+    };
+
+    server.cancel.cancel();
+    result
+}
+
+/// Negotiate one client and hand it to its destination's session.
+async fn serve_client<S: SessionStarter>(server: Arc<SocksServer<S>>, mut stream: TcpStream) {
+    let (host, port) = match tokio::time::timeout(HANDSHAKE_TIMEOUT, negotiate(&mut stream)).await {
+        Ok(Ok(destination)) => destination,
+        Ok(Err(e)) => {
+            log::warn!("SOCKS: {}", e);
+            return;
+        }
+        Err(_) => {
+            log::warn!("SOCKS: handshake timed out");
+            return;
+        }
+    };
+
+    if let Some(ref allow) = server.allow
+        && !allow.allows(&host)
+    {
+        log::warn!("SOCKS: {}:{} is not in socksAllow", host, port);
+        let _ = reply(&mut stream, REPLY_NOT_ALLOWED).await;
+        return;
+    }
+
+    let (incoming, open) = match server.session_for(&host, port).await {
+        Ok(session) => session,
+        Err(e) => {
+            log::warn!("SOCKS: no session to {}:{}: {}", host, port, e);
+            let _ = reply(&mut stream, REPLY_HOST_UNREACHABLE).await;
+            if is_bastion_lost(&e) {
+                let _ = server.bastion_lost.try_send(e);
+            }
+            return;
+        }
+    };
+
+    if reply(&mut stream, REPLY_SUCCEEDED).await.is_err() {
+        return;
+    }
+    log::info!("SOCKS: CONNECT {}:{}", host, port);
+    if incoming
+        .send(QueuedConnection { stream, open })
+        .await
+        .is_err()
+    {
+        log::warn!(
+            "SOCKS: session to {}:{} ended before taking the connection",
+            host,
+            port
+        );
+    }
+}
+
+/// The SSM target itself is unreachable — every destination would fail the
+/// same way (see `execute_port_forwarding`).
+pub(crate) fn is_bastion_lost(error: &str) -> bool {
+    error.contains("TargetNotConnected") || error.contains("is not connected")
+}
+
+impl<S: SessionStarter> SocksServer<S> {
+    /// The running session for `host:port`, starting one if needed, and the
+    /// marker to hand over with the connection. The connection counts as
+    /// open from here on, so the session can't be closed under it.
+    async fn session_for(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Result<(mpsc::Sender<QueuedConnection>, Arc<()>), String> {
+        let (slot, cancel, open) = {
+            let mut slots = self
+                .slots
+                .lock()
+                .map_err(|_| "SOCKS session table poisoned".to_string())?;
+            let key = (host.to_string(), port);
+            if !slots.contains_key(&key) {
+                make_room(&mut slots, MAX_SESSIONS)?;
+            }
+            let slot = slots.entry(key).or_insert_with(|| Slot {
+                incoming: Arc::default(),
+                cancel: self.cancel.child_token(),
+                last_used: Instant::now(),
+                open: Arc::default(),
+            });
+            slot.last_used = Instant::now();
+            (
+                slot.incoming.clone(),
+                slot.cancel.clone(),
+                slot.open.clone(),
+            )
+        };
+
+        // Held while starting, so concurrent CONNECTs share one new session
+        let mut incoming = slot.lock().await;
+        if let Some(ref running) = *incoming
+            && !running.is_closed()
+        {
+            return Ok((running.clone(), open));
+        }
+        let started = self.start_session(host, port, cancel).await?;
+        *incoming = Some(started.clone());
+        Ok((started, open))
+    }
+
+    fn close_idle_sessions(&self) {
+        if let Ok(mut slots) = self.slots.lock() {
+            close_idle(&mut slots, Instant::now());
+        }
+    }
+
+    async fn start_session(
+        &self,
+        host: &str,
+        port: u16,
+        cancel: CancellationToken,
+    ) -> Result<mpsc::Sender<QueuedConnection>, String> {
+        let tokens = self.starter.start(host, port).await?;
+        let (incoming_tx, incoming_rx) = mpsc::channel(QUEUED_CONNECTIONS);
+        let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+        let handle = tokio::spawn(native::start_queued_port_forwarding(
+            tokens.stream_url,
+            tokens.token_value,
+            tokens.kms,
+            self.multiplexed,
+            incoming_rx,
+            cancel,
+            Some(ready_tx),
+        ));
+
+        match tokio::time::timeout(SESSION_START_TIMEOUT, ready_rx).await {
+            Ok(Ok(Ok(()))) => {}
+            Ok(Ok(Err(e))) => return Err(e),
+            Ok(Err(_)) => {
+                return Err(match handle.await {
+                    Ok(Err(e)) => e,
+                    _ => "Session ended before it was ready".to_string(),
+                });
+            }
+            Err(_) => {
+                handle.abort();
+                return Err("Session start timed out".to_string());
+            }
+        }
+
+        let destination = format!("{}:{}", host, port);
+        log::info!("SOCKS: session to {} is up", destination);
+        tokio::spawn(async move {
+            match handle.await {
+                Ok(Err(e)) => log::warn!("SOCKS: session to {} failed: {}", destination, e),
+                _ => log::info!("SOCKS: session to {} ended", destination),
+            }
+        });
+        Ok(incoming_tx)
+    }
+}
+
+/// Close the least recently used session without open connections if
+/// `slots` is full. Fails when every session is in use.
+fn make_room(slots: &mut HashMap<(String, u16), Slot>, max: usize) -> Result<(), String> {
+    if slots.len() < max {
+        return Ok(());
+    }
+    let oldest = slots
+        .iter()
+        .filter(|(_, slot)| slot.open_connections() == 0)
+        .min_by_key(|(_, slot)| slot.last_used)
+        .map(|(key, _)| key.clone())
+        .ok_or_else(|| format!("all {} SOCKS sessions have open connections", max))?;
+    if let Some(((host, port), slot)) = slots.remove_entry(&oldest) {
+        log::info!(
+            "SOCKS: closing session to {}:{} (least recently used)",
+            host,
+            port
+        );
+        slot.cancel.cancel();
+    }
+    Ok(())
+}
+
+/// Drop the slots of sessions without open connections for
+/// [`SESSION_IDLE_TIMEOUT`], which closes their senders and ends them.
+fn close_idle(slots: &mut HashMap<(String, u16), Slot>, now: Instant) {
+    slots.retain(|(host, port), slot| {
+        if slot.open_connections() > 0 {
+            // Idle time counts from the last connection
+            slot.last_used = now;
+            return true;
+        }
+        if now.saturating_duration_since(slot.last_used) < SESSION_IDLE_TIMEOUT {
+            return true;
+        }
+        log::info!("SOCKS: closing idle session to {}:{}", host, port);
+        false
+    });
+}
+
+/// Read the greeting and the request. Requests we can't serve are answered
+/// here and come back as `Err`.
+async fn negotiate<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+) -> Result<(String, u16), String> {
+    let io_error = |e: std::io::Error| format!("handshake failed: {}", e);
+
+    let mut greeting = [0u8; 2];
+    stream.read_exact(&mut greeting).await.map_err(io_error)?;
+    if greeting[0] != SOCKS_VERSION {
+        return Err(format!("not a SOCKS5 client (version {})", greeting[0]));
+    }
+    let mut methods = vec![0u8; greeting[1] as usize];
+    stream.read_exact(&mut methods).await.map_err(io_error)?;
+    if !methods.contains(&METHOD_NO_AUTH) {
+        let _ = stream
+            .write_all(&[SOCKS_VERSION, METHOD_NONE_ACCEPTABLE])
+            .await;
+        return Err("client insists on authentication".to_string());
+    }
+    stream
+        .write_all(&[SOCKS_VERSION, METHOD_NO_AUTH])
+        .await
+        .map_err(io_error)?;
+
+    // VER CMD RSV ATYP DST.ADDR DST.PORT
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await.map_err(io_error)?;
+    if request[0] != SOCKS_VERSION {
+        return Err(format!("bad request version {}", request[0]));
+    }
+    let host = match request[3] {
+        ATYP_IPV4 => {
+            let mut address = [0u8; 4];
+            stream.read_exact(&mut address).await.map_err(io_error)?;
+            Some(Ipv4Addr::from(address).to_string())
+        }
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await.map_err(io_error)?;
+            let mut name = vec![0u8; len[0] as usize];
+            stream.read_exact(&mut name).await.map_err(io_error)?;
+            String::from_utf8(name)
+                .ok()
+                .map(|name| name.trim_end_matches('.').to_string())
+                .filter(|name| is_valid_hostname(name))
+        }
+        ATYP_IPV6 => {
+            let mut address = [0u8; 16];
+            stream.read_exact(&mut address).await.map_err(io_error)?;
+            None
+        }
+        other => {
+            let _ = reply(stream, REPLY_ADDRESS_NOT_SUPPORTED).await;
+            return Err(format!("unknown address type {}", other));
+        }
+    };
+    let mut port = [0u8; 2];
+    stream.read_exact(&mut port).await.map_err(io_error)?;
+    let port = u16::from_be_bytes(port);
+
+    if request[1] != CMD_CONNECT {
+        let _ = reply(stream, REPLY_COMMAND_NOT_SUPPORTED).await;
+        return Err(format!(
+            "command {} is not supported (CONNECT only)",
+            request[1]
+        ));
+    }
+    let Some(host) = host else {
+        let _ = reply(stream, REPLY_ADDRESS_NOT_SUPPORTED).await;
+        return Err("only IPv4 addresses and host names are supported".to_string());
+    };
+    Ok((host, port))
+}
+
+/// Send a reply; the bound address is not meaningful here and left zero.
+async fn reply<S: AsyncWrite + Unpin>(stream: &mut S, code: u8) -> std::io::Result<()> {
+    stream
+        .write_all(&[SOCKS_VERSION, code, 0, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tunnel::mock_agent::{MockAgent, MockAgentConfig, spawn_echo_target};
+
+    /// Hands out one mock agent session, like a single bastion destination.
+    struct MockStarter {
+        stream_url: String,
+        token: String,
+    }
+
+    impl SessionStarter for MockStarter {
+        async fn start(&self, _host: &str, _port: u16) -> Result<SessionTokens, String> {
+            Ok(SessionTokens {
+                stream_url: self.stream_url.clone(),
+                token_value: self.token.clone(),
+                kms: None,
+            })
+        }
+    }
+
+    /// SOCKS5 CONNECT to `host:port` by name; returns the reply code.
+    async fn connect(proxy: u16, host: &str, port: u16) -> (TcpStream, u8) {
+        let mut stream = TcpStream::connect(("127.0.0.1", proxy)).await.unwrap();
+        stream
+            .write_all(&[SOCKS_VERSION, 1, METHOD_NO_AUTH])
+            .await
+            .unwrap();
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice).await.unwrap();
+        assert_eq!(choice, [SOCKS_VERSION, METHOD_NO_AUTH]);
+
+        let mut request = vec![SOCKS_VERSION, CMD_CONNECT, 0, ATYP_DOMAIN, host.len() as u8];
+        request.extend_from_slice(host.as_bytes());
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await.unwrap();
+        let mut reply = [0u8; 10];
+        stream.read_exact(&mut reply).await.unwrap();
+        (stream, reply[1])
+    }
+
+    #[test]
+    fn allow_list_matches_cidrs_and_domain_suffixes() {
+        let entries: Vec<String> = ["10.20.0.0/16", "192.168.1.7", "*.internal.example.com"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let allow = SocksAllowList::parse(&entries).unwrap();
+        assert!(allow.allows("10.20.3.4"));
+        assert!(!allow.allows("10.21.0.1"));
+        assert!(allow.allows("192.168.1.7"));
+        assert!(!allow.allows("192.168.1.8"));
+        assert!(allow.allows("internal.example.com"));
+        assert!(allow.allows("Grafana.Internal.Example.com."));
+        assert!(!allow.allows("notinternal.example.com"));
+        assert!(!allow.allows("example.com"));
+
+        for bad in ["10.0.0.0/33", "10.0.0/8", "bad_name.example", ""] {
+            assert!(SocksAllowList::parse(&[bad.to_string()]).is_err(), "{bad}");
+        }
+    }
+
+    fn slot(last_used: Instant) -> Slot {
+        Slot {
+            incoming: Arc::default(),
+            cancel: CancellationToken::new(),
+            last_used,
+            open: Arc::default(),
+        }
+    }
+
+    #[test]
+    fn sessions_with_open_connections_are_kept() {
+        let start = Instant::now();
+        let mut slots = HashMap::new();
+        for (i, host) in ["a", "b", "c"].into_iter().enumerate() {
+            slots.insert(
+                (host.to_string(), 80),
+                slot(start + Duration::from_secs(i as u64)),
+            );
+        }
+        let connection = slots[&("a".to_string(), 80)].open.clone();
+
+        // "a" is least recently used but has a connection: "b" makes room
+        make_room(&mut slots, 3).unwrap();
+        assert!(slots.contains_key(&("a".to_string(), 80)));
+        assert!(!slots.contains_key(&("b".to_string(), 80)));
+
+        let later = start + SESSION_IDLE_TIMEOUT + Duration::from_secs(10);
+        close_idle(&mut slots, later);
+        let kept: Vec<&str> = slots.keys().map(|(host, _)| host.as_str()).collect();
+        assert_eq!(kept, vec!["a"]);
+
+        // Once its connection closes, "a" is idle from the last check on
+        drop(connection);
+        close_idle(&mut slots, later + Duration::from_secs(1));
+        assert_eq!(slots.len(), 1);
+        close_idle(&mut slots, later + SESSION_IDLE_TIMEOUT);
+        assert!(slots.is_empty());
+    }
+
+    #[test]
+    fn make_room_fails_when_every_session_is_in_use() {
+        let mut slots = HashMap::new();
+        slots.insert(("a".to_string(), 80), slot(Instant::now()));
+        let _connection = slots[&("a".to_string(), 80)].open.clone();
+        assert!(make_room(&mut slots, 1).is_err());
+        assert!(make_room(&mut slots, 2).is_ok());
+    }
+
+    #[tokio::test]
+    async fn connects_through_a_shared_session_and_enforces_the_allow_list() {
+        let target = spawn_echo_target().await;
+        let mock = MockAgent::start(MockAgentConfig::new(target)).await;
+        let starter = MockStarter {
+            stream_url: mock.stream_url(),
+            token: mock.token(),
+        };
+        let allow = SocksAllowList::parse(&["internal.example.com".to_string()]).unwrap();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let cancel = CancellationToken::new();
+        let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+        let server = tokio::spawn(run_socks_server(
+            starter,
+            Some(allow),
+            true,
+            SocketAddrV4::new(Ipv4Addr::LOCALHOST, port),
+            cancel.clone(),
+            Some(ready_tx),
+        ));
+        ready_rx.await.unwrap().unwrap();
+
+        let (_, code) = connect(port, "db.other.example.com", 443).await;
+        assert_eq!(code, REPLY_NOT_ALLOWED);
+
+        // Both connections ride the one session the mock agent accepts
+        for payload in [&b"first"[..], &b"second"[..]] {
+            let (mut stream, code) = connect(port, "grafana.internal.example.com", 443).await;
+            assert_eq!(code, REPLY_SUCCEEDED);
+            stream.write_all(payload).await.unwrap();
+            let mut echoed = vec![0u8; payload.len()];
+            tokio::time::timeout(Duration::from_secs(10), stream.read_exact(&mut echoed))
+                .await
+                .expect("echo through SOCKS timed out")
+                .unwrap();
+            assert_eq!(echoed, payload);
+        }
+
+        cancel.cancel();
+        assert_eq!(server.await.unwrap(), Ok(()));
+        assert!(mock.finished().await.multiplexed);
+    }
+}
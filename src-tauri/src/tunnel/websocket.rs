@@ -1,9 +1,13 @@
+use crate::tunnel::encryption::{KmsSessionContext, SessionEncryption};
 use crate::tunnel::protocol::{
-    build_acknowledge, build_handshake_response_with_version, AgentMessage,
-    HandshakeCompletePayload, HandshakeRequestPayload, OpenDataChannelInput, CHANNEL_CLOSED,
-    OUTPUT_STREAM_DATA, PAYLOAD_HANDSHAKE_COMPLETE, PAYLOAD_HANDSHAKE_REQUEST,
+    build_acknowledge, build_encryption_challenge_response, build_handshake_response_with_actions,
+    AgentMessage, HandshakeCompletePayload, HandshakeRequestPayload, KmsEncryptionRequest,
+    OpenDataChannelInput, ProcessedClientAction, ACTION_KMS_ENCRYPTION, ACTION_SESSION_TYPE,
+    CHANNEL_CLOSED, OUTPUT_STREAM_DATA, PAYLOAD_ENC_CHALLENGE_REQUEST, PAYLOAD_HANDSHAKE_COMPLETE,
+    PAYLOAD_HANDSHAKE_REQUEST,
 };
 use futures_util::{SinkExt, StreamExt};
+use std::sync::Arc;
 use tokio::net::TcpStream;
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
@@ -20,6 +24,9 @@ pub struct SsmDataChannel {
     pub outgoing_seq: i64,
     /// Next expected incoming sequence number (after handshake consumed some).
     pub expected_incoming_seq: i64,
+    /// Set when the agent requested KMS encryption: OUTPUT payloads in both
+    /// directions are AES-GCM encrypted from here on.
+    pub encryption: Option<Arc<SessionEncryption>>,
 }
 
 /// Connect to SSM WebSocket, authenticate, and complete the handshake.
@@ -42,6 +49,19 @@ pub async fn open_data_channel_with_version(
     stream_url: &str,
     token_value: &str,
     client_version: &str,
+) -> Result<SsmDataChannel, String> {
+    open_data_channel_with_encryption(stream_url, token_value, client_version, None).await
+}
+
+/// Connect to SSM WebSocket, authenticate, and complete the handshake,
+/// answering a KMSEncryption client action with `kms` when the session
+/// document requires it. Without `kms`, such an action is reported as failed
+/// and the agent terminates the session.
+pub async fn open_data_channel_with_encryption(
+    stream_url: &str,
+    token_value: &str,
+    client_version: &str,
+    kms: Option<&KmsSessionContext>,
 ) -> Result<SsmDataChannel, String> {
     // Step 1: Connect WebSocket
     let (mut ws, _response) = connect_async(stream_url)
@@ -77,6 +97,10 @@ pub async fn open_data_channel_with_version(
     // machine: it stops acking everything we send, retransmits handshake_complete
     // every 200ms for ~30s, then closes the channel ("channel_closed" ~32s in).
     let mut cached_response: Option<Vec<u8>> = None;
+    // Same idempotency rule for the encryption challenge: the response carries a
+    // random nonce, so a retransmitted challenge must get the cached bytes back.
+    let mut cached_challenge_response: Option<Vec<u8>> = None;
+    let mut encryption: Option<Arc<SessionEncryption>> = None;
 
     while !handshake_complete {
         let ws_msg = ws
@@ -109,18 +133,22 @@ pub async fn open_data_channel_with_version(
                                 msg.sequence_number,
                                 expected_server_seq,
                                 msg.payload_type,
-                                if msg.payload_type == PAYLOAD_HANDSHAKE_REQUEST {
-                                    "resending cached response"
-                                } else {
-                                    "dropping"
+                                match msg.payload_type {
+                                    PAYLOAD_HANDSHAKE_REQUEST | PAYLOAD_ENC_CHALLENGE_REQUEST => {
+                                        "resending cached response"
+                                    }
+                                    _ => "dropping",
                                 }
                             );
-                            // If the agent re-sent the HandshakeRequest, our response may
-                            // have been lost — resend the exact same response bytes
-                            // (idempotent: same sequence number, same content).
-                            if msg.payload_type == PAYLOAD_HANDSHAKE_REQUEST
-                                && let Some(ref resp) = cached_response
-                            {
+                            // If the agent re-sent the HandshakeRequest (or challenge), our
+                            // response may have been lost — resend the exact same response
+                            // bytes (idempotent: same sequence number, same content).
+                            let cached = match msg.payload_type {
+                                PAYLOAD_HANDSHAKE_REQUEST => cached_response.as_ref(),
+                                PAYLOAD_ENC_CHALLENGE_REQUEST => cached_challenge_response.as_ref(),
+                                _ => None,
+                            };
+                            if let Some(resp) = cached {
                                 ws.send(Message::Binary(resp.clone().into()))
                                     .await
                                     .map_err(|e| {
@@ -139,9 +167,13 @@ pub async fn open_data_channel_with_version(
                                     })?;
                                 agent_version = request.agent_version.clone();
 
+                                let (processed, negotiated) =
+                                    process_client_actions(&request, kms).await;
+                                encryption = negotiated.map(Arc::new);
+
                                 // Send HandshakeResponse with the requested client version
-                                let response = build_handshake_response_with_version(
-                                    &request,
+                                let response = build_handshake_response_with_actions(
+                                    processed,
                                     client_seq,
                                     client_version,
                                 );
@@ -154,6 +186,23 @@ pub async fn open_data_channel_with_version(
                                     })?;
                                 client_seq += 1;
                             }
+                            PAYLOAD_ENC_CHALLENGE_REQUEST => {
+                                let enc = encryption.as_ref().ok_or_else(|| {
+                                    "Encryption challenge received but KMS encryption was not negotiated"
+                                        .to_string()
+                                })?;
+                                let answer = enc.answer_challenge(&msg.payload)?;
+                                let serialized =
+                                    build_encryption_challenge_response(answer, client_seq)
+                                        .serialize();
+                                cached_challenge_response = Some(serialized.clone());
+                                ws.send(Message::Binary(serialized.into()))
+                                    .await
+                                    .map_err(|e| {
+                                        format!("Failed to send EncChallengeResponse: {}", e)
+                                    })?;
+                                client_seq += 1;
+                            }
                             PAYLOAD_HANDSHAKE_COMPLETE => {
                                 if let Ok(complete) =
                                     serde_json::from_slice::<HandshakeCompletePayload>(
@@ -198,5 +247,60 @@ pub async fn open_data_channel_with_version(
         agent_version,
         outgoing_seq: client_seq,
         expected_incoming_seq: expected_server_seq,
+        encryption,
     })
 }
+
+/// Process the client actions of a HandshakeRequest. Returns the per-action
+/// results and, when a KMSEncryption action succeeded, the negotiated keys.
+async fn process_client_actions(
+    request: &HandshakeRequestPayload,
+    kms: Option<&KmsSessionContext>,
+) -> (Vec<ProcessedClientAction>, Option<SessionEncryption>) {
+    let mut processed = Vec::with_capacity(request.requested_client_actions.len());
+    let mut encryption = None;
+
+    for action in &request.requested_client_actions {
+        match action.action_type.as_str() {
+            ACTION_SESSION_TYPE => {
+                processed.push(ProcessedClientAction::success(ACTION_SESSION_TYPE, None));
+            }
+            ACTION_KMS_ENCRYPTION => {
+                let result = match kms {
+                    Some(ctx) => negotiate_kms_encryption(ctx, &action.action_parameters).await,
+                    None => Err("KMS encryption is not available for this session".to_string()),
+                };
+                match result {
+                    Ok(enc) => {
+                        log::info!("KMS session encryption enabled");
+                        let action_result = serde_json::to_value(enc.action_result()).ok();
+                        processed.push(ProcessedClientAction::success(
+                            ACTION_KMS_ENCRYPTION,
+                            action_result,
+                        ));
+                        encryption = Some(enc);
+                    }
+                    Err(e) => {
+                        log::error!("KMS encryption handshake failed: {}", e);
+                        processed.push(ProcessedClientAction::failed(ACTION_KMS_ENCRYPTION, e));
+                    }
+                }
+            }
+            other => {
+                log::warn!("Unsupported handshake action: {}", other);
+                processed.push(ProcessedClientAction::unsupported(other));
+            }
+        }
+    }
+
+    (processed, encryption)
+}
+
+async fn negotiate_kms_encryption(
+    ctx: &KmsSessionContext,
+    parameters: &serde_json::Value,
+) -> Result<SessionEncryption, String> {
+    let request: KmsEncryptionRequest = serde_json::from_value(parameters.clone())
+        .map_err(|e| format!("Invalid KMSEncryption parameters: {}", e))?;
+    SessionEncryption::negotiate(ctx, &request.kms_key_id).await
+}
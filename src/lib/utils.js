@@ -111,12 +111,13 @@ function shellEscape(str) {
 /**
  * Build a connection string for a database connection.
  * Shell command formats (psql, mysql) use proper quoting to prevent injection.
- * @param {{ username: string, password: string, database: string, localPort: string|number, engine?: string }} info
+ * @param {{ username: string, password: string, database: string, localPort: string|number, host?: string, engine?: string }} info
  * @param {'psql'|'mysql'|'jdbc'|'uri'} format
  * @returns {string}
  */
 export function buildConnectionString(info, format) {
-  const host = 'localhost'
+  // Tunnels on a loopback alias (127.0.0.N) carry their own host
+  const host = info.host || 'localhost'
   const port = info.localPort
 
   switch (format) {